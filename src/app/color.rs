@@ -1,19 +1,143 @@
 //! Colors used for the UI.
+//!
+//! Colors come from the current [Theme], which [super::StateUI] keeps in egui's memory every frame
+//! so switching themes re-colors everything on the next frame.  Get it with [theme].
 
+use crate::prefs::ThemeKind;
 use egui::Color32;
 
-pub const BASE: Color32 = Color32::from_rgb(25, 23, 36);
-pub const SURFACE: Color32 = Color32::from_rgb(31, 29, 46);
-pub const OVERLAY: Color32 = Color32::from_rgb(38, 35, 58);
-pub const MUTED: Color32 = Color32::from_rgb(110, 106, 134);
-pub const SUBTLE: Color32 = Color32::from_rgb(144, 140, 170);
-pub const TEXT: Color32 = Color32::from_rgb(224, 222, 244);
-pub const LOVE: Color32 = Color32::from_rgb(235, 111, 146);
-pub const GOLD: Color32 = Color32::from_rgb(246, 193, 119);
-pub const ROSE: Color32 = Color32::from_rgb(235, 188, 186);
-pub const PINE: Color32 = Color32::from_rgb(49, 116, 143);
-pub const FOAM: Color32 = Color32::from_rgb(156, 207, 216);
-pub const IRIS: Color32 = Color32::from_rgb(196, 167, 231);
-pub const HIGHLIGHT_LOW: Color32 = Color32::from_rgb(33, 32, 46);
-pub const HIGHLIGHT_MED: Color32 = Color32::from_rgb(64, 61, 82);
-pub const HIGHLIGHT_HIGH: Color32 = Color32::from_rgb(82, 79, 103);
+/// Named color roles, from the [Rosé Pine](https://rosepinetheme.com) palettes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Window background
+    pub base: Color32,
+    /// Table stripes and panels
+    pub surface: Color32,
+    /// Widget background
+    pub overlay: Color32,
+    pub muted: Color32,
+    pub subtle: Color32,
+    pub text: Color32,
+    /// Errors and flagged logins
+    pub love: Color32,
+    /// Warnings and headings
+    pub gold: Color32,
+    pub rose: Color32,
+    pub pine: Color32,
+    pub foam: Color32,
+    pub iris: Color32,
+    pub highlight_low: Color32,
+    pub highlight_med: Color32,
+    pub highlight_high: Color32,
+    /// Whether egui's dark or light visuals fill in what isn't set from the palette
+    pub dark: bool,
+}
+
+impl Theme {
+    /// Rosé Pine
+    pub const DARK: Self = Self {
+        base: Color32::from_rgb(25, 23, 36),
+        surface: Color32::from_rgb(31, 29, 46),
+        overlay: Color32::from_rgb(38, 35, 58),
+        muted: Color32::from_rgb(110, 106, 134),
+        subtle: Color32::from_rgb(144, 140, 170),
+        text: Color32::from_rgb(224, 222, 244),
+        love: Color32::from_rgb(235, 111, 146),
+        gold: Color32::from_rgb(246, 193, 119),
+        rose: Color32::from_rgb(235, 188, 186),
+        pine: Color32::from_rgb(49, 116, 143),
+        foam: Color32::from_rgb(156, 207, 216),
+        iris: Color32::from_rgb(196, 167, 231),
+        highlight_low: Color32::from_rgb(33, 32, 46),
+        highlight_med: Color32::from_rgb(64, 61, 82),
+        highlight_high: Color32::from_rgb(82, 79, 103),
+        dark: true,
+    };
+
+    /// Rosé Pine Dawn, for bright rooms
+    pub const LIGHT: Self = Self {
+        base: Color32::from_rgb(250, 244, 237),
+        surface: Color32::from_rgb(255, 250, 243),
+        overlay: Color32::from_rgb(242, 233, 225),
+        muted: Color32::from_rgb(152, 147, 165),
+        subtle: Color32::from_rgb(121, 117, 147),
+        text: Color32::from_rgb(87, 82, 121),
+        love: Color32::from_rgb(180, 99, 122),
+        gold: Color32::from_rgb(234, 157, 52),
+        rose: Color32::from_rgb(215, 130, 126),
+        pine: Color32::from_rgb(40, 105, 131),
+        foam: Color32::from_rgb(86, 148, 159),
+        iris: Color32::from_rgb(144, 122, 169),
+        highlight_low: Color32::from_rgb(244, 237, 232),
+        highlight_med: Color32::from_rgb(223, 218, 217),
+        highlight_high: Color32::from_rgb(206, 202, 205),
+        dark: false,
+    };
+
+    /// egui's visuals colored from the palette
+    pub fn visuals(&self) -> egui::Visuals {
+        let base = if self.dark {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        let widget = |bg_fill: Color32, stroke: Color32| egui::style::WidgetVisuals {
+            bg_fill,
+            weak_bg_fill: bg_fill,
+            bg_stroke: egui::Stroke::new(1.0, stroke),
+            rounding: egui::Rounding::same(4.0),
+            fg_stroke: egui::Stroke::new(1.0, self.text),
+            expansion: 1.0,
+        };
+        egui::Visuals {
+            override_text_color: None,
+            hyperlink_color: self.iris,
+            faint_bg_color: self.surface, // Table stripes
+            extreme_bg_color: self.highlight_low,
+            code_bg_color: self.highlight_med,
+            warn_fg_color: self.gold,
+            error_fg_color: self.love,
+            window_fill: self.overlay, // Widget background
+            panel_fill: self.base,     // Background background
+            widgets: egui::style::Widgets {
+                noninteractive: widget(self.surface, self.highlight_med), // Separator color
+                inactive: widget(self.muted, self.overlay),
+                hovered: widget(self.muted, self.muted),
+                active: widget(self.subtle, self.subtle),
+                open: widget(self.subtle, self.muted),
+            },
+            selection: egui::style::Selection {
+                bg_fill: self.pine,
+                stroke: egui::Stroke::new(1.0, self.text),
+            },
+            ..base
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DARK
+    }
+}
+
+impl ThemeKind {
+    pub fn theme(self) -> Theme {
+        match self {
+            Self::Dark => Theme::DARK,
+            Self::Light => Theme::LIGHT,
+        }
+    }
+}
+
+/// Current theme, set by [set_theme]
+pub fn theme(ctx: &egui::Context) -> Theme {
+    ctx.data(|d| d.get_temp(egui::Id::new("theme")))
+        .unwrap_or_default()
+}
+
+/// Makes `theme` current and colors egui's widgets with it
+pub fn set_theme(ctx: &egui::Context, theme: Theme) {
+    ctx.set_visuals(theme.visuals());
+    ctx.data_mut(|d| d.insert_temp(egui::Id::new("theme"), theme));
+}
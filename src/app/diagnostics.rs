@@ -0,0 +1,170 @@
+//! Diagnostics
+//!
+//! Lists recent queries with how long they took and how much came back, along with totals for
+//! each backend and how often the disk caches saved a lookup, so a slow run can be pinned on
+//! Splunk, HDTools, or ipinfo.io.
+use super::color;
+use crate::store::{Diagnostics as Report, Store};
+use crate::user::zone::Zone;
+use egui::RichText;
+use egui_extras::Column;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How often the report is pulled while open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct Diagnostics {
+    store: Rc<Store>,
+    report: Option<Report>,
+    /// When [report](Self::report) was last pulled
+    last_refresh: Option<Instant>,
+}
+
+impl Diagnostics {
+    pub fn new(store: Rc<Store>) -> Self {
+        Self {
+            store,
+            report: None,
+            last_refresh: None,
+        }
+    }
+}
+
+impl super::panels::Panel for Diagnostics {
+    fn name(&self) -> &'static str {
+        "🩺Diagnostics"
+    }
+
+    fn desc(&self) -> &'static str {
+        "How long queries are taking and how often the caches help"
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        if !*open {
+            self.last_refresh = None;
+            return None;
+        }
+        if self
+            .last_refresh
+            .is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL)
+        {
+            self.report = Some(self.store.diagnostics());
+            self.last_refresh = Some(Instant::now());
+        }
+        // Keep refreshing while open, queries finish on other threads
+        ctx.request_repaint_after(REFRESH_INTERVAL);
+
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .default_size(egui::vec2(600.0, 400.0))
+                .vscroll(false),
+            place,
+            |ui| {
+                if let Some(report) = &self.report {
+                    report_ui(ui, report, self.store.display_zone());
+                }
+            },
+        )
+    }
+}
+
+fn report_ui(ui: &mut egui::Ui, report: &Report, zone: Zone) {
+    let theme = color::theme(ui.ctx());
+    egui::Grid::new("diagnostics_totals")
+        .striped(true)
+        .show(ui, |ui| {
+            for title in ["Backend", "Calls", "Failed", "Time", "Bytes", "Rows"] {
+                ui.label(RichText::new(title).color(theme.pine));
+            }
+            ui.end_row();
+            for (backend, totals) in &report.totals {
+                ui.label(backend.to_string());
+                ui.label(totals.calls.to_string());
+                ui.label(totals.failures.to_string());
+                ui.label(format!("{:.1}s", totals.duration.as_secs_f32()));
+                ui.label(bytes(totals.bytes));
+                ui.label(totals.rows.to_string());
+                ui.end_row();
+            }
+        });
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        for (cache, hits, misses) in &report.caches {
+            let lookups = hits + misses;
+            let rate = if lookups == 0 {
+                "-".to_owned()
+            } else {
+                format!("{:.0}%", *hits as f32 / lookups as f32 * 100.0)
+            };
+            ui.label(format!(
+                "{}: {} hits, {} misses ({})",
+                cache, hits, misses, rate
+            ))
+            .on_hover_text("Lookups answered from the disk cache this session");
+            ui.separator();
+        }
+    });
+    ui.separator();
+
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .columns(Column::auto(), 6)
+        .column(Column::remainder())
+        .header(20.0, |mut header| {
+            for title in ["Finished", "Backend", "Query", "Time", "Bytes", "Rows", ""] {
+                header.col(|ui| {
+                    ui.label(RichText::new(title).color(theme.pine));
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(20.0, report.recent.len(), |i, mut row| {
+                let record = &report.recent[i];
+                row.col(|ui| {
+                    ui.label(zone.to_zone(record.finished).format("%T").to_string());
+                });
+                row.col(|ui| {
+                    ui.label(record.backend.to_string());
+                });
+                row.col(|ui| {
+                    ui.label(record.name);
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.2}s", record.duration.as_secs_f32()));
+                });
+                row.col(|ui| {
+                    ui.label(bytes(record.bytes));
+                });
+                row.col(|ui| {
+                    ui.label(record.rows.to_string());
+                });
+                row.col(|ui| {
+                    if !record.ok {
+                        ui.label(RichText::new("Failed").color(theme.love));
+                    }
+                });
+            });
+        });
+}
+
+/// Byte count with a unit, 0 reads as unknown since some backends don't say
+fn bytes(bytes: usize) -> String {
+    match bytes {
+        0 => "-".to_owned(),
+        b if b < 1_000 => format!("{} B", b),
+        b if b < 1_000_000 => format!("{:.1} KB", b as f32 / 1e3),
+        b => format!("{:.1} MB", b as f32 / 1e6),
+    }
+}
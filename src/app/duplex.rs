@@ -1,33 +1,142 @@
 //! UI for Duplex
 use crate::{
-    app::color,
-    queries::{osiris, splunk::TimeSpan},
-    store::Store,
+    app::{
+        color, keymap, notify,
+        panels::{Badge, Mailbox, PanelMessage},
+        simplex::Simplex,
+        sonar::Sonar,
+        visor::Visor,
+    },
+    prefs::keymap::{key_name, Action},
+    queries::{
+        osiris,
+        splunk::{LoginFilter, Search, Splunk, TimeSpan},
+        QueryError,
+    },
+    store::{Progress, Store},
     user::{
-        login::{Integration, Login, LoginResult, Reason},
-        User,
+        admin::AdminEvent,
+        login::{FlagReason, Integration, Login, Reason},
+        zone::Zone,
+        QueueStats, User,
     },
 };
 use chrono::{NaiveDate, Timelike};
 use egui::{Key, Label, ProgressBar, RichText, TextEdit};
 use egui_extras::{Column, DatePickerButton, Size, StripBuilder, TableBuilder};
-use std::{rc::Rc, thread::JoinHandle};
+use std::{collections::HashSet, net::Ipv4Addr, rc::Rc, thread::JoinHandle, time::Instant};
 
 trait View {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) -> DuplexAction;
     fn store(&self) -> &Rc<Store>;
+    /// See [Panel::badge](super::panels::Panel::badge)
+    fn badge(&self) -> Option<Badge> {
+        None
+    }
+    /// Called every frame, even while the window is closed, for work that shouldn't wait on it
+    fn poll(&mut self, _ctx: &egui::Context) -> DuplexAction {
+        DuplexAction::None
+    }
+    /// Nothing is running or being worked through, so a scheduled run can start
+    fn idle(&self) -> bool {
+        false
+    }
 }
 
 pub struct Duplex {
     panel: Box<dyn View>,
+    /// Where "Open in" messages for the other apps go
+    mailbox: Mailbox,
 }
 
 impl Duplex {
-    pub fn new(store: Rc<Store>) -> Self {
+    pub fn new(store: Rc<Store>, mailbox: Mailbox) -> Self {
         Self {
             panel: Box::new(DateSelectUi::new(store)),
+            mailbox,
+        }
+    }
+
+    fn apply(&mut self, ctx: &egui::Context, action: DuplexAction) {
+        match action {
+            DuplexAction::None => (),
+            DuplexAction::Query {
+                store,
+                user_range,
+                filter,
+                users,
+                refresh,
+            } => {
+                log::info!("Switching to loading screen");
+                let history_range: TimeSpan = chrono::Duration::days(HISTORY_DAYS).into();
+                let searches = store.duplex_searches(&user_range, &history_range, filter, &users);
+                let run = store.run_duplex(user_range, history_range, filter, users.clone());
+                self.panel = Box::new(LoadingUi::new(
+                    store, user_range, filter, users, refresh, run, searches,
+                ));
+            }
+            DuplexAction::Start {
+                store,
+                users,
+                range,
+                refresh,
+                searches,
+            } => {
+                self.panel = Box::new(MainUi::new(store, users, range, refresh, searches));
+            }
+            DuplexAction::Done {
+                store,
+                stats,
+                users,
+                range,
+            } => {
+                self.panel = Box::new(DoneUi::new(store, stats, users, range));
+            }
+            DuplexAction::Reset => {
+                let store = self.panel.store();
+
+                self.panel = Box::new(DateSelectUi::new(Rc::clone(store)));
+            }
+            DuplexAction::OpenIn { to, message } => {
+                self.mailbox.open_with(to, message);
+                ctx.request_repaint(); // The message is handed out next frame
+            }
         }
     }
+
+    /// Starts a run over everything since the last one finished once the scheduled interval has
+    /// passed, as long as Duplex is idle.  The results are queued in the background.
+    fn schedule(&mut self, ctx: &egui::Context) {
+        let store = Rc::clone(self.panel.store());
+        if !store.schedule() || !self.panel.idle() {
+            return;
+        }
+        let now = chrono::Utc::now();
+        let interval = chrono::Duration::minutes(store.schedule_minutes());
+        let last = store.last_run_end().unwrap_or(now - interval);
+        let due = last + interval;
+        if now < due {
+            // Nothing else may repaint before then
+            ctx.request_repaint_after((due - now).to_std().unwrap_or_default());
+            return;
+        }
+        // After HORUS was closed for a while, only the latest stretch is worth pulling
+        let start = last.max(now - chrono::Duration::hours(SCHEDULE_MAX_HOURS));
+        if start > last {
+            log::warn!("Scheduled run skipping logins from {} to {}", last, start);
+        }
+        log::info!("Starting scheduled Duplex run from {}", start);
+        self.apply(
+            ctx,
+            DuplexAction::Query {
+                store,
+                user_range: TimeSpan { start, end: now },
+                filter: LoginFilter::All,
+                users: vec![],
+                refresh: None,
+            },
+        );
+    }
 }
 
 impl super::panels::Panel for Duplex {
@@ -35,44 +144,39 @@ impl super::panels::Panel for Duplex {
         "📱Duplex"
     }
 
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
-        egui::Window::new(
-            RichText::new(format!("{}: Don't Drink and Duplex", self.name())).color(color::GOLD),
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        let action = self.panel.poll(ctx);
+        self.apply(ctx, action);
+        self.schedule(ctx);
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(
+                RichText::new(format!("{}: Don't Drink and Duplex", self.name())).color(theme.gold),
+            )
+            .open(open)
+            .default_size(egui::vec2(800.0, 600.0))
+            .vscroll(false),
+            place,
+            |ui| {
+                let action = self.panel.ui(ui, ctx);
+                self.apply(ctx, action);
+            },
         )
-        .open(open)
-        .default_size(egui::vec2(800.0, 600.0))
-        .vscroll(false)
-        .show(ctx, |ui| {
-            let resp = self.panel.ui(ui, ctx);
-
-            match resp {
-                DuplexAction::None => (),
-                DuplexAction::Query { store, user_range } => {
-                    log::info!("Switching to loading screen");
-                    let run = store.run_duplex(user_range, chrono::Duration::days(7).into());
-                    self.panel = Box::new(LoadingUi::new(store, run));
-                }
-                DuplexAction::Start { store, users } => {
-                    self.panel = Box::new(MainUi::new(store, users));
-                }
-                DuplexAction::Done {
-                    store,
-                    investigations,
-                } => {
-                    self.panel = Box::new(DoneUi::new(store, investigations));
-                }
-                DuplexAction::Reset => {
-                    let store = self.panel.store();
-
-                    self.panel = Box::new(DateSelectUi::new(Rc::clone(store)));
-                }
-            }
-        });
     }
 
     fn desc(&self) -> &'static str {
         "Duo Multi and Duo Fraud"
     }
+
+    fn badge(&self) -> Option<Badge> {
+        self.panel.badge()
+    }
 }
 
 pub enum DuplexAction {
@@ -80,21 +184,50 @@ pub enum DuplexAction {
     Query {
         store: Rc<Store>,
         user_range: TimeSpan,
+        filter: LoginFilter,
+        /// Only check these users, every Duo user in the range if empty
+        users: Vec<String>,
+        /// Minutes between re-runs over the past as many minutes, see [AutoRefresh]
+        refresh: Option<i64>,
     },
     Start {
         store: Rc<Store>,
         users: Vec<User>,
+        /// Range the users were flagged in
+        range: TimeSpan,
+        refresh: Option<AutoRefresh>,
+        /// Splunk searches the run made, see [super::copy_searches_button]
+        searches: Vec<Search>,
     },
     Done {
         store: Rc<Store>,
-        investigations: usize,
+        stats: QueueStats,
+        /// The users reviewed, for the run report
+        users: Vec<User>,
+        range: TimeSpan,
     },
     Reset,
+    /// Open another app with `message`, see [Mailbox::open_with]
+    OpenIn {
+        to: &'static str,
+        message: PanelMessage,
+    },
 }
 
 // -------------------- Date Select UI --------------------
 
 const TIME_FMT: &str = "%H:%M";
+/// Days of logins pulled for each user's history, which failures are forgiven and new devices
+/// are found against
+const HISTORY_DAYS: i64 = 7;
+/// Minutes between auto-refresh runs that can be picked
+const REFRESH_MINUTES: std::ops::RangeInclusive<i64> = 5..=240;
+/// Minutes between scheduled runs that can be picked
+const SCHEDULE_MINUTES: std::ops::RangeInclusive<i64> = 15..=24 * 60;
+/// Longest range a scheduled run covers, however long it's been since the last one
+const SCHEDULE_MAX_HOURS: i64 = 24;
+/// Event counts in the range above this are worth narrowing it for
+const LARGE_ESTIMATE: usize = 500_000;
 
 pub struct DateSelectUi {
     store: Rc<Store>,
@@ -102,6 +235,21 @@ pub struct DateSelectUi {
     user_time: (String, String),
     issue: Option<String>,
     action: Option<DuplexAction>,
+    /// Users scoring below this are hidden once loaded
+    min_score: usize,
+    /// Which logins to pull for the run
+    filter: LoginFilter,
+    /// Comma or newline separated users to check instead of everyone in the range
+    users: String,
+    /// Keep re-running over the past [refresh_minutes](Self::refresh_minutes) instead of the
+    /// range, see [AutoRefresh]
+    auto_refresh: bool,
+    refresh_minutes: i64,
+    /// Counting the events in [Self::estimate_range] and the history
+    estimate_run: Option<JoinHandle<Result<(usize, usize), QueryError>>>,
+    /// Range the estimate was asked for, it's thrown out once the range changes
+    estimate_range: ((NaiveDate, NaiveDate), (String, String)),
+    estimate: Option<Result<(usize, usize), QueryError>>,
 }
 
 impl DateSelectUi {
@@ -112,12 +260,21 @@ impl DateSelectUi {
             .format(TIME_FMT)
             .to_string();
         let time = now.format(TIME_FMT).to_string();
+        let min_score = store.min_score();
         Self {
             store,
             user_date: (date, date),
             user_time: (hour_ago, time),
             issue: None,
             action: None,
+            min_score,
+            filter: LoginFilter::All,
+            users: String::new(),
+            auto_refresh: false,
+            refresh_minutes: 15,
+            estimate_run: None,
+            estimate_range: ((date, date), (String::new(), String::new())),
+            estimate: None,
         }
     }
 
@@ -150,6 +307,11 @@ impl DateSelectUi {
             }
         }
 
+        if let Err(user) = Splunk::parse_users(&self.users) {
+            self.issue = Some(format!("{} isn't a valid username", user));
+            return false;
+        }
+
         self.issue = None;
         true
     }
@@ -159,72 +321,210 @@ impl DateSelectUi {
             return;
         }
 
+        let refresh = self.auto_refresh.then_some(self.refresh_minutes);
         self.action = Some(DuplexAction::Query {
             store: Rc::clone(&self.store),
-            user_range: crate::queries::splunk::TimeSpan::from(self.user_date, &self.user_time),
+            user_range: match refresh {
+                Some(minutes) => chrono::Duration::minutes(minutes).into(),
+                None => TimeSpan::from(self.user_date, &self.user_time),
+            },
+            filter: self.filter,
+            users: Splunk::parse_users(&self.users).unwrap_or_default(),
+            refresh,
         });
     }
+
+    fn action_estimate(&mut self) {
+        if !self.vibe_check() {
+            return;
+        }
+
+        self.estimate_range = (self.user_date, self.user_time.clone());
+        self.estimate = None;
+        self.estimate_run = Some(self.store.estimate_duplex(
+            TimeSpan::from(self.user_date, &self.user_time),
+            chrono::Duration::days(HISTORY_DAYS).into(),
+        ));
+    }
+
+    /// Shows the event count for the range, if one was asked for
+    fn estimate_ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        if self.estimate_range != (self.user_date, self.user_time.clone()) {
+            self.estimate_run = None;
+            self.estimate = None;
+            return;
+        }
+
+        if self
+            .estimate_run
+            .as_ref()
+            .is_some_and(|run| run.is_finished())
+        {
+            if let Some(run) = self.estimate_run.take() {
+                self.estimate = Some(run.join().expect("Couldn't get estimate from thread"));
+            }
+        }
+
+        if self.estimate_run.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Counting events...");
+            });
+            ui.ctx().request_repaint();
+            return;
+        }
+
+        match &self.estimate {
+            Some(Ok((range, history))) => {
+                let total = format!(
+                    "About {} events, {} of them from the {} day history",
+                    range + history,
+                    history,
+                    HISTORY_DAYS
+                );
+                // Only the range can be narrowed, the history is always pulled
+                if *range >= LARGE_ESTIMATE {
+                    ui.label(
+                        RichText::new(format!("{}, try a smaller range for a quicker run", total))
+                            .color(theme.gold),
+                    );
+                } else {
+                    ui.label(total);
+                }
+            }
+            Some(Err(e)) => {
+                ui.label(RichText::new(format!("Couldn't count events: {}", e)).color(theme.love));
+            }
+            None => (),
+        }
+    }
 }
 
 impl View for DateSelectUi {
     fn ui(&mut self, ui: &mut egui::Ui, _ctx: &egui::Context) -> DuplexAction {
+        let theme = color::theme(ui.ctx());
         if !self.store.has_hdtools() {
-            ui.label(egui::RichText::new("You did not provide a shibession and won't be\nable to take advantage of advanced filtering").color(super::color::LOVE));
-        }
-
-        egui::Grid::new("time_range")
-            .min_col_width(50.0)
-            .show(ui, |ui| {
-                ui.label("Time Range:");
-                ui.menu_button("📅", |ui| {
-                    ui.vertical_centered(|ui| ui.label("Presets"));
-                    if ui.button("Past Hour").clicked() {
-                        let now = chrono::Local::now();
-                        let hour = now.hour();
-                        self.user_date = (now.date_naive(), now.date_naive());
-                        self.user_time = (format!("{:02}:00", hour - 1), format!("{:02}:00", hour));
-                        ui.close_menu();
-                    }
-                    if ui.button("Over night").clicked() {
-                        let now = chrono::Local::now();
-                        self.user_date = (
-                            now.date_naive() - chrono::Duration::days(1),
-                            now.date_naive(),
-                        );
-                        self.user_time = ("16:00".to_owned(), now.format(TIME_FMT).to_string());
-                        ui.close_menu();
-                    }
-                    if ui.button("Over weekend").clicked() {
-                        let now = chrono::Local::now();
-                        self.user_date = (
-                            now.date_naive() - chrono::Duration::days(3),
-                            now.date_naive(),
-                        );
-                        self.user_time = ("16:00".to_owned(), now.format(TIME_FMT).to_string());
-                        ui.close_menu();
-                    }
+            ui.label(egui::RichText::new("You did not provide a shibession and won't be\nable to take advantage of advanced filtering").color(theme.love));
+        }
+
+        ui.add_enabled_ui(!self.auto_refresh, |ui| {
+            egui::Grid::new("time_range")
+                .min_col_width(50.0)
+                .show(ui, |ui| {
+                    ui.label("Time Range:");
+                    ui.menu_button("📅", |ui| {
+                        ui.vertical_centered(|ui| ui.label("Presets"));
+                        if ui.button("Past Hour").clicked() {
+                            let now = chrono::Local::now();
+                            let hour = now.hour();
+                            self.user_date = (now.date_naive(), now.date_naive());
+                            self.user_time =
+                                (format!("{:02}:00", hour - 1), format!("{:02}:00", hour));
+                            ui.close_menu();
+                        }
+                        if ui.button("Over night").clicked() {
+                            let now = chrono::Local::now();
+                            self.user_date = (
+                                now.date_naive() - chrono::Duration::days(1),
+                                now.date_naive(),
+                            );
+                            self.user_time = ("16:00".to_owned(), now.format(TIME_FMT).to_string());
+                            ui.close_menu();
+                        }
+                        if ui.button("Over weekend").clicked() {
+                            let now = chrono::Local::now();
+                            self.user_date = (
+                                now.date_naive() - chrono::Duration::days(3),
+                                now.date_naive(),
+                            );
+                            self.user_time = ("16:00".to_owned(), now.format(TIME_FMT).to_string());
+                            ui.close_menu();
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.add(DatePickerButton::new(&mut self.user_date.0).id_source("UL"));
+                    ui.add(TextEdit::singleline(&mut self.user_time.0).desired_width(40.0));
+                    ui.end_row();
+
+                    ui.add(DatePickerButton::new(&mut self.user_date.1).id_source("UU"));
+                    ui.add(TextEdit::singleline(&mut self.user_time.1).desired_width(40.0));
+                    ui.end_row();
                 });
-                ui.end_row();
+        });
 
-                ui.add(DatePickerButton::new(&mut self.user_date.0).id_source("UL"));
-                ui.add(TextEdit::singleline(&mut self.user_time.0).desired_width(40.0));
-                ui.end_row();
+        let slider = ui
+            .add(egui::Slider::new(&mut self.min_score, 0..=50).text("min score"))
+            .on_hover_text("Hide users scoring below this, they can be shown after the queue");
+        if slider.changed() {
+            self.store.set_min_score(self.min_score);
+        }
 
-                ui.add(DatePickerButton::new(&mut self.user_date.1).id_source("UU"));
-                ui.add(TextEdit::singleline(&mut self.user_time.1).desired_width(40.0));
-                ui.end_row();
-            });
+        let mut suspicious = self.filter == LoginFilter::Suspicious;
+        if ui
+            .checkbox(&mut suspicious, "Suspicious users only")
+            .on_hover_text("Only pull users with a failure, fraud report, or OFAC deny.  Much faster on busy nights, but users who only succeeded aren't checked for impossible travel or new devices")
+            .changed()
+        {
+            self.filter = if suspicious {
+                LoginFilter::Suspicious
+            } else {
+                LoginFilter::All
+            };
+        }
+
+        let mut overview = self.store.duplex_overview();
+        if ui
+            .checkbox(&mut overview, "Start with an overview")
+            .on_hover_text(
+                "List every flagged user to pick from before going through them one by one",
+            )
+            .changed()
+        {
+            self.store.set_duplex_overview(overview);
+        }
+
+        ui.add(
+            TextEdit::multiline(&mut self.users)
+                .hint_text("Only these users (optional)")
+                .desired_rows(2)
+                .desired_width(200.0),
+        )
+        .on_hover_text("Comma or newline separated usernames to check instead of every Duo user in the range, such as accounts from a tip");
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.auto_refresh, "Auto-refresh every")
+                .on_hover_text("Run over the past interval instead of the range, then keep re-running while triaging.  New flagged users are added to the end of the queue");
+            ui.add_enabled(
+                self.auto_refresh,
+                egui::DragValue::new(&mut self.refresh_minutes)
+                    .clamp_range(REFRESH_MINUTES)
+                    .suffix(" min"),
+            );
+        });
+        schedule_ui(ui, &self.store);
 
         let enabled = self.vibe_check();
         ui.add_enabled_ui(enabled, |ui| {
-            let button = ui.add_sized(egui::vec2(140.0, 25.0), egui::Button::new("Let's ride!"));
-            if button.clicked() {
-                self.action_login();
-            }
+            ui.horizontal(|ui| {
+                let button =
+                    ui.add_sized(egui::vec2(140.0, 25.0), egui::Button::new("Let's ride!"));
+                if button.clicked() {
+                    self.action_login();
+                }
+                let preview = ui
+                    .add_enabled(self.estimate_run.is_none(), egui::Button::new("Preview"))
+                    .on_hover_text("Count the events in the range before committing to a run");
+                if preview.clicked() {
+                    self.action_estimate();
+                }
+            });
         });
+        self.estimate_ui(ui);
 
         if let Some(issue) = &self.issue {
-            ui.label(egui::RichText::new(issue).color(super::color::LOVE));
+            ui.label(egui::RichText::new(issue).color(theme.love));
         }
 
         self.action.take().unwrap_or(DuplexAction::None)
@@ -233,57 +533,96 @@ impl View for DateSelectUi {
     fn store(&self) -> &Rc<Store> {
         &self.store
     }
+
+    fn idle(&self) -> bool {
+        self.estimate_run.is_none()
+    }
 }
 
 // -------------------- Loading UI --------------------
 
 pub struct LoadingUi {
     pub store: Rc<Store>,
-    run: Option<JoinHandle<Vec<User>>>,
+    /// Kept around to retry the run if it fails
+    user_range: TimeSpan,
+    filter: LoginFilter,
+    users: Vec<String>,
+    /// Minutes between re-runs once loaded, see [AutoRefresh]
+    refresh: Option<i64>,
+    run: Option<JoinHandle<Result<Vec<User>, QueryError>>>,
+    /// Splunk searches the run makes, handed to [MainUi]
+    searches: Vec<Search>,
     action: Option<DuplexAction>,
+    error: Option<QueryError>,
 }
 
 impl LoadingUi {
-    pub fn new(store: Rc<Store>, run: JoinHandle<Vec<User>>) -> Self {
+    pub fn new(
+        store: Rc<Store>,
+        user_range: TimeSpan,
+        filter: LoginFilter,
+        users: Vec<String>,
+        refresh: Option<i64>,
+        run: JoinHandle<Result<Vec<User>, QueryError>>,
+        searches: Vec<Search>,
+    ) -> Self {
         LoadingUi {
             store,
+            user_range,
+            filter,
+            users,
+            refresh,
             run: Some(run),
+            searches,
             action: None,
+            error: None,
         }
     }
 }
 
 impl View for LoadingUi {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) -> DuplexAction {
-        if self
-            .run
-            .as_ref()
-            .expect("LoadingUi run should be some by now")
-            .is_finished()
-        {
-            let users = self
-                .run
-                .take()
-                .expect("Failed to take users from JoinHandle")
-                .join()
-                .expect("Couldn't get users from thread");
-            self.action = Some(DuplexAction::Start {
-                store: Rc::clone(&self.store),
-                users,
+        let theme = color::theme(ui.ctx());
+        if let Some(e) = &self.error {
+            // Don't let a failed query pass for a quiet night
+            ui.heading(RichText::new("Splunk query failed").color(theme.love));
+            ui.label(format!("Users were not checked: {}", e));
+            ui.horizontal(|ui| {
+                if ui.button("Retry").clicked() {
+                    self.action = Some(DuplexAction::Query {
+                        store: Rc::clone(&self.store),
+                        // An auto-refresh run looks back from now
+                        user_range: match self.refresh {
+                            Some(minutes) => chrono::Duration::minutes(minutes).into(),
+                            None => self.user_range,
+                        },
+                        filter: self.filter,
+                        users: self.users.clone(),
+                        refresh: self.refresh,
+                    });
+                }
+                if ui.button("Back").clicked() {
+                    self.action = Some(DuplexAction::Reset);
+                }
             });
-        } else {
-            let s = self.store.progress();
-            if s == 0.0 {
+            return self.action.take().unwrap_or(DuplexAction::None);
+        }
+
+        let s = match self.store.progress() {
+            Progress::Querying(s) => {
                 ui.label("Querying splunk...");
-            } else {
+                s
+            }
+            Progress::Checking(s) => {
                 ui.label("Vibe checking users...");
+                s
             }
-            ui.add(
-                egui::widgets::ProgressBar::new(s)
-                    .animate(true)
-                    .desired_width(325.0),
-            );
-        }
+        };
+        ui.add(
+            egui::widgets::ProgressBar::new(s)
+                .animate(true)
+                .desired_width(325.0),
+        );
 
         std::thread::sleep(std::time::Duration::from_millis(10));
         ctx.request_repaint(); // Call repaint to re-check if the thread is finished
@@ -294,88 +633,812 @@ impl View for LoadingUi {
     fn store(&self) -> &Rc<Store> {
         &self.store
     }
+
+    fn badge(&self) -> Option<Badge> {
+        if let Some(e) = &self.error {
+            return Some(Badge::Warning(format!("Duplex run failed: {}", e)));
+        }
+        match &self.run {
+            Some(run) if run.is_finished() => Some(Badge::Ready),
+            _ => Some(Badge::Busy),
+        }
+    }
+
+    /// Collects the run, so a scheduled one lands in the queue with the window closed
+    fn poll(&mut self, ctx: &egui::Context) -> DuplexAction {
+        let Some(run) = &self.run else {
+            return DuplexAction::None;
+        };
+        if !run.is_finished() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+            return DuplexAction::None;
+        }
+        let users = self
+            .run
+            .take()
+            .expect("Failed to take users from JoinHandle")
+            .join()
+            .expect("Couldn't get users from thread");
+        let body = match &users {
+            Ok(users) => match users.len() {
+                1 => "1 user was flagged".to_owned(),
+                n => format!("{} users were flagged", n),
+            },
+            Err(e) => format!("The run failed: {}", e),
+        };
+        notify::run_finished(ctx, body, self.store.notify_run_done());
+        match users {
+            Ok(users) => {
+                // Runs for a list of users didn't look at anyone else
+                if self.users.is_empty() {
+                    self.store.set_last_run_end(self.user_range.end);
+                }
+                DuplexAction::Start {
+                    store: Rc::clone(&self.store),
+                    users,
+                    range: self.user_range,
+                    refresh: self.refresh.map(|minutes| {
+                        AutoRefresh::new(
+                            minutes,
+                            self.filter,
+                            self.users.clone(),
+                            self.user_range.end,
+                        )
+                    }),
+                    searches: std::mem::take(&mut self.searches),
+                }
+            }
+            Err(e) => {
+                self.error = Some(e);
+                DuplexAction::None
+            }
+        }
+    }
 }
 
 // -------------------- Main UI --------------------
 
+/// Columns the overview can be sorted by, see [MainUi::overview_ui]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OverviewSort {
+    User,
+    /// Highest first, the same order the queue starts in
+    Score,
+    Reason,
+    Location,
+}
+
+impl OverviewSort {
+    const ALL: [Self; 4] = [Self::User, Self::Score, Self::Reason, Self::Location];
+}
+
+impl std::fmt::Display for OverviewSort {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::User => "User",
+                Self::Score => "Score",
+                Self::Reason => "Top reason",
+                Self::Location => "Location",
+            }
+        )
+    }
+}
+
+/// Cherwell templates that can be copied with [Action::CopyTemplate] and a second key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Template {
+    FirstContact,
+    PasswordReset,
+    ShortDescription,
+    ScoreBreakdown,
+    /// The whole user for a ticket body, see [User::incident_summary]
+    IncidentSummary,
+}
+
+impl Template {
+    const ALL: [Self; 5] = [
+        Self::ShortDescription,
+        Self::FirstContact,
+        Self::ScoreBreakdown,
+        Self::IncidentSummary,
+        Self::PasswordReset,
+    ];
+
+    /// Key pressed after [Action::CopyTemplate], fixed so they can't clash with Duplex's own
+    fn key(self) -> Key {
+        match self {
+            Self::FirstContact => Key::F,
+            Self::PasswordReset => Key::P,
+            Self::ShortDescription => Key::S,
+            Self::ScoreBreakdown => Key::B,
+            Self::IncidentSummary => Key::I,
+        }
+    }
+
+    /// Signed with the analyst's name, so it can't be copied until one is set
+    fn signed(self) -> bool {
+        matches!(self, Self::FirstContact | Self::PasswordReset)
+    }
+
+    /// Fills in the template for `login`
+    fn text(self, user: &User, login: &Login, analyst_name: &str, zone: Zone) -> String {
+        match self {
+            Self::FirstContact => super::first_contact(login, analyst_name, zone),
+            Self::PasswordReset => format!(
+                std::include_str!("../../templates/password_reset.txt"),
+                analyst_name, analyst_name,
+            ),
+            Self::ShortDescription => "Duo Multi Login Suspicious Activity".to_owned(),
+            Self::ScoreBreakdown => {
+                format!(
+                    "{} scored {}\n{}",
+                    user.name, user.score, user.score_breakdown
+                )
+            }
+            Self::IncidentSummary => user.incident_summary(analyst_name, zone),
+        }
+    }
+}
+
+impl std::fmt::Display for Template {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::FirstContact => "Copy first contact",
+                Self::PasswordReset => "Copy password reset",
+                Self::ShortDescription => "Copy short description",
+                Self::ScoreBreakdown => "Copy score breakdown",
+                Self::IncidentSummary => "Copy incident summary",
+            }
+        )
+    }
+}
+
+/// Re-runs Duplex over the past few minutes on a timer while triaging, for keeping watch.  Only
+/// one run goes at a time, the next is timed from when the last one finished and looks back to
+/// where the last good one ended.
+pub struct AutoRefresh {
+    /// Minutes between runs
+    minutes: i64,
+    filter: LoginFilter,
+    users: Vec<String>,
+    /// When the next run starts
+    next: Instant,
+    run: Option<JoinHandle<Result<Vec<User>, QueryError>>>,
+    /// End of the range [run](Self::run) covers
+    run_end: chrono::DateTime<chrono::Utc>,
+    /// End of the last run that finished, the next one starts here so logins that came in while
+    /// a run was going, or during one that failed, aren't missed
+    covered: chrono::DateTime<chrono::Utc>,
+    /// Why the last run failed
+    error: Option<QueryError>,
+    /// Users already sent as a desktop notification, so each is only sent once
+    notified: HashSet<String>,
+}
+
+impl AutoRefresh {
+    /// Refreshes from `covered`, the end of the run that started triage
+    pub fn new(
+        minutes: i64,
+        filter: LoginFilter,
+        users: Vec<String>,
+        covered: chrono::DateTime<chrono::Utc>,
+    ) -> Self {
+        let mut refresh = Self {
+            minutes,
+            filter,
+            users,
+            next: Instant::now(),
+            run: None,
+            run_end: covered,
+            covered,
+            error: None,
+            notified: HashSet::new(),
+        };
+        refresh.schedule();
+        refresh
+    }
+
+    fn schedule(&mut self) {
+        self.next = Instant::now() + std::time::Duration::from_secs(self.minutes as u64 * 60);
+    }
+}
+
+/// More logs pulls that can run at once, more wait for one to finish
+const MAX_MORE_LOGS: usize = 3;
+/// Accounts younger than this when flagged have their age in gold
+const NEW_ACCOUNT_DAYS: i64 = 30;
+
+/// A More logs pull, added to the user with the name when it finishes even if they aren't shown
+struct MoreLogs {
+    user: String,
+    days: i64,
+    rx: JoinHandle<Result<Vec<Login>, QueryError>>,
+}
+
+/// A Duo admin events pull and the index of the user it's for
+type AdminEventsPull = (JoinHandle<Result<Vec<AdminEvent>, QueryError>>, usize);
+
 pub struct MainUi {
     days: i64,
-    more_logs: Option<(JoinHandle<Option<Vec<Login>>>, usize)>,
+    more_logs: Vec<MoreLogs>,
+    /// Why the last More logs pull failed, and who it was for
+    more_logs_error: Option<(String, QueryError)>,
+    /// Pulling Duo admin events for the user at the index, who is re-scored with them
+    admin_events: Option<AdminEventsPull>,
+    admin_events_error: Option<QueryError>,
     store: Rc<Store>,
     user_idx: usize,
     users: Vec<User>,
     action: Option<DuplexAction>,
+    /// Only bulk ignore users below [ignore_score](Self::ignore_score)
+    ignore_by_score: bool,
+    ignore_score: usize,
+    /// Users scoring below this are hidden, but not removed
+    min_score: usize,
+    /// Show users below [min_score](Self::min_score) after the rest of the queue
+    show_hidden: bool,
+    /// [User::search_text] for each user, built once after load
+    search_index: Vec<String>,
+    search: String,
+    /// Users matching the search
+    matches: Vec<usize>,
+    /// Index into [matches](Self::matches) of the user last jumped to
+    match_idx: usize,
+    /// Re-sort users by score after re-scoring one
+    resort: bool,
+    /// File IP enrichment is exported to
+    export_file: String,
+    /// Export every cached IP instead of only those in these users' logins
+    export_all: bool,
+    export_rx: Option<JoinHandle<()>>,
+    /// Only logins from this [Login::summary_location] are shown
+    location_filter: Option<String>,
+    /// The current user's logins are mapped above the table, see [super::travel_map]
+    show_map: bool,
+    /// Login clicked on the map, the table scrolls to it on the next frame
+    scroll_to: Option<usize>,
+    /// Third vibe check rerun over the loaded users, and how many users there were before it
+    recheck: Option<(JoinHandle<Vec<User>>, usize)>,
+    /// Users that passed the last re-check
+    recheck_dropped: Option<usize>,
+    /// Why the current user is being ignored, saved with the ignore
+    ignore_note: String,
+    /// When the queue was loaded
+    started: Instant,
+    /// Shortcut help is open
+    show_help: bool,
+    /// [Action::CopyTemplate] was pressed and the next key picks the template
+    template_pending: bool,
+    refresh: Option<AutoRefresh>,
+    /// Users added by auto-refresh since they were last looked at
+    new_users: usize,
+    /// Index of the first of [new_users](Self::new_users)
+    first_new: usize,
+    /// Splunk searches of the last run, see [super::copy_searches_button]
+    searches: Vec<Search>,
+    login_export: super::LoginExport,
+    raw_event: super::RawEventWindow,
+    repeats: super::Repeats,
+    /// Every shown user is listed instead of the current one, see [overview_ui](Self::overview_ui)
+    overview: bool,
+    /// Column the overview is sorted by, and whether it's reversed
+    overview_sort: (OverviewSort, bool),
+    /// Range the queue covers, stretched by each auto-refresh
+    range: TimeSpan,
 }
 
 impl MainUi {
-    pub fn new(store: Rc<Store>, users: Vec<User>) -> Self {
-        Self {
+    pub fn new(
+        store: Rc<Store>,
+        users: Vec<User>,
+        range: TimeSpan,
+        refresh: Option<AutoRefresh>,
+        searches: Vec<Search>,
+    ) -> Self {
+        let max_score = users.iter().map(|u| u.score).max().unwrap_or(0);
+        let min_score = store.min_score().min(max_score);
+        let search_index = users.iter().map(|u| u.search_text()).collect();
+        let overview = store.duplex_overview();
+        let mut main = Self {
             users,
             store,
             user_idx: 0,
-            more_logs: None,
+            more_logs: Vec::new(),
+            more_logs_error: None,
+            admin_events: None,
+            admin_events_error: None,
             days: 30,
             action: None,
-        }
+            ignore_by_score: false,
+            ignore_score: 10,
+            min_score,
+            show_hidden: false,
+            search_index,
+            search: String::new(),
+            matches: vec![],
+            match_idx: 0,
+            resort: false,
+            export_file: "ips.csv".to_owned(),
+            export_all: false,
+            export_rx: None,
+            location_filter: None,
+            show_map: false,
+            scroll_to: None,
+            recheck: None,
+            recheck_dropped: None,
+            ignore_note: String::new(),
+            started: Instant::now(),
+            show_help: false,
+            template_pending: false,
+            refresh,
+            new_users: 0,
+            first_new: 0,
+            searches,
+            login_export: super::LoginExport::default(),
+            raw_event: super::RawEventWindow::new("duplex_raw_event"),
+            repeats: super::Repeats::default(),
+            overview,
+            overview_sort: (OverviewSort::Score, false),
+            range,
+        };
+        main.snap_to_visible();
+        main
     }
 
     fn cur_user(&self) -> &User {
         &self.users[self.user_idx]
     }
 
+    fn visible(&self, i: usize) -> bool {
+        self.show_hidden || self.users[i].score >= self.min_score
+    }
+
+    /// Number of users scoring below the threshold
+    fn below_threshold_count(&self) -> usize {
+        self.users
+            .iter()
+            .filter(|u| u.score < self.min_score)
+            .count()
+    }
+
+    /// Number of users shown with the current score threshold
+    fn visible_count(&self) -> usize {
+        (0..self.users.len()).filter(|&i| self.visible(i)).count()
+    }
+
+    /// Position of the current user among the shown users, starting at 1
+    fn position(&self) -> usize {
+        (0..=self.user_idx).filter(|&i| self.visible(i)).count()
+    }
+
+    /// Moves off the current user if it has been hidden, preferring the next shown user
+    fn snap_to_visible(&mut self) {
+        if self.users.is_empty() || self.visible(self.user_idx) {
+            return;
+        }
+        if let Some(i) = (self.user_idx..self.users.len()).find(|&i| self.visible(i)) {
+            self.user_idx = i;
+        } else if let Some(i) = (0..self.user_idx).rev().find(|&i| self.visible(i)) {
+            self.user_idx = i;
+        }
+    }
+
     fn next_user(&mut self) {
-        if self.user_idx + 1 >= self.users.len() {
-            self.action = Some(DuplexAction::Done {
-                store: Rc::clone(&self.store),
-                investigations: self.users.len(),
-            });
+        match (self.user_idx + 1..self.users.len()).find(|&i| self.visible(i)) {
+            Some(i) => self.user_idx = i,
+            // Wait for the next refresh instead of finishing up
+            None if self.refresh.is_some() => (),
+            None => self.done(self.users.len()),
+        }
+    }
+
+    /// Shown users from the current one onward that the bulk ignore would mark investigated
+    fn bulk_ignore_users(&self) -> Vec<String> {
+        self.users[self.user_idx..]
+            .iter()
+            .filter(|u| !u.investigated && (self.show_hidden || u.score >= self.min_score))
+            .filter(|u| !self.ignore_by_score || u.score < self.ignore_score)
+            .map(|u| u.name.to_owned())
+            .collect()
+    }
+
+    /// Marks every user from the current one onward as investigated and finishes up
+    fn bulk_ignore(&mut self) {
+        let names = self.bulk_ignore_users();
+        log::info!("Bulk ignoring {} users", names.len());
+        self.store.mark_many_investigated(&names);
+        for user in &mut self.users[self.user_idx..] {
+            if names.contains(&user.name) {
+                user.investigated = true;
+            }
+        }
+        self.done(self.users.len());
+    }
+
+    /// Finishes up with the shown users before `end` as the ones reviewed
+    fn done(&mut self, end: usize) {
+        let reviewed: Vec<User> = (0..end)
+            .filter(|&i| self.visible(i))
+            .map(|i| self.users[i].clone())
+            .collect();
+        self.action = Some(DuplexAction::Done {
+            store: Rc::clone(&self.store),
+            stats: QueueStats::new(&reviewed, self.started.elapsed()),
+            users: reviewed,
+            range: self.range,
+        });
+    }
+
+    /// Starts an auto-refresh run when one is due, and adds the users it flags that aren't already
+    /// queued to the end of the queue
+    fn auto_refresh(&mut self, ctx: &egui::Context) {
+        let Some(refresh) = &mut self.refresh else {
+            return;
+        };
+        let users = match &refresh.run {
+            Some(run) if run.is_finished() => {
+                let run = refresh.run.take().expect("Failed to take auto-refresh run");
+                refresh.schedule();
+                match run.join().expect("Couldn't get users from thread") {
+                    Ok(users) => {
+                        refresh.error = None;
+                        refresh.covered = refresh.run_end;
+                        self.range.end = refresh.run_end;
+                        users
+                    }
+                    Err(e) => {
+                        refresh.error = Some(e);
+                        return;
+                    }
+                }
+            }
+            Some(_) => {
+                ctx.request_repaint_after(std::time::Duration::from_millis(250));
+                return;
+            }
+            None => {
+                let now = Instant::now();
+                if now >= refresh.next {
+                    log::info!("Auto-refreshing since {}", refresh.covered);
+                    let user_range = TimeSpan {
+                        start: refresh.covered,
+                        end: chrono::Utc::now(),
+                    };
+                    let history_range: TimeSpan = chrono::Duration::days(HISTORY_DAYS).into();
+                    self.searches = self.store.duplex_searches(
+                        &user_range,
+                        &history_range,
+                        refresh.filter,
+                        &refresh.users,
+                    );
+                    refresh.run_end = user_range.end;
+                    refresh.run = Some(self.store.run_duplex(
+                        user_range,
+                        history_range,
+                        refresh.filter,
+                        refresh.users.clone(),
+                    ));
+                }
+                // Check back when it's due, or soon after starting, without anything to click
+                ctx.request_repaint_after(
+                    refresh
+                        .next
+                        .saturating_duration_since(now)
+                        .max(std::time::Duration::from_millis(250)),
+                );
+                return;
+            }
+        };
+
+        let new: Vec<User> = users
+            .into_iter()
+            .filter(|u| !self.users.iter().any(|q| q.name == u.name))
+            .collect();
+        if new.is_empty() {
+            return;
+        }
+        log::info!("Auto-refresh flagged {} new users", new.len());
+        if self.store.notify() {
+            let min_score = self.store.notify_score();
+            let redact = self.store.redact();
+            let high: Vec<(String, usize)> = new
+                .iter()
+                .filter(|u| u.score >= min_score && refresh.notified.insert(u.name.clone()))
+                .map(|u| (redact.user(&u.name), u.score))
+                .collect();
+            notify::high_scores(high);
+        }
+        if self.new_users == 0 {
+            self.first_new = self.users.len();
+        }
+        self.new_users += new.len();
+        self.search_index
+            .extend(new.iter().map(|u| u.search_text()));
+        self.users.extend(new);
+        self.find_matches();
+        self.snap_to_visible();
+    }
+
+    /// Adds the logins from a finished More logs pull to whoever it was for
+    fn more_logs_done(&mut self, fetch: MoreLogs) {
+        let logins = match fetch.rx.join().expect("Couldn't get more logs from thread") {
+            Ok(logins) => logins,
+            Err(e) => {
+                self.more_logs_error = Some((fetch.user, e));
+                return;
+            }
+        };
+        if self
+            .more_logs_error
+            .as_ref()
+            .is_some_and(|(user, _)| *user == fetch.user)
+        {
+            self.more_logs_error = None;
+        }
+        let Some(i) = self.users.iter().position(|u| u.name == fetch.user) else {
+            return;
+        };
+        // Logins already shown keep their flags, so they go first and win the dedup
+        let user_logins = &mut self.users[i].logins;
+        user_logins.extend(logins);
+        user_logins.sort();
+        user_logins.dedup();
+        self.search_index[i] = self.users[i].search_text();
+    }
+
+    /// When the next auto-refresh runs, the new users badge, and a way to stop refreshing
+    fn refresh_ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let Some(refresh) = &self.refresh else {
             return;
+        };
+        if refresh.run.is_some() {
+            ui.spinner();
+            ui.label("Refreshing");
+        } else {
+            let wait = refresh.next.saturating_duration_since(Instant::now());
+            ui.label(format!("Refresh in {}m", wait.as_secs() / 60 + 1))
+                .on_hover_text(format!(
+                    "Re-running over the past {} minutes",
+                    refresh.minutes
+                ));
+        }
+        if let Some(e) = &refresh.error {
+            ui.label(RichText::new("Refresh failed").color(theme.love))
+                .on_hover_text(e.to_string());
+        }
+        if self.new_users > 0 {
+            let badge = ui
+                .button(RichText::new(format!("🔔 {} new", self.new_users)).color(theme.gold))
+                .on_hover_text("Jump to the first user added since last looking");
+            if badge.clicked() {
+                self.user_idx = self.first_new;
+                self.new_users = 0;
+                self.snap_to_visible();
+            }
+        }
+        if ui
+            .button("Stop")
+            .on_hover_text("Stop refreshing, the queue finishes up as usual")
+            .clicked()
+        {
+            self.refresh = None;
+        }
+        ui.separator();
+    }
+
+    /// Finds every user matching the search and jumps to the first
+    fn search(&mut self) {
+        self.find_matches();
+        self.match_idx = 0;
+        self.jump_to_match();
+    }
+
+    fn find_matches(&mut self) {
+        let search = self.search.trim().to_lowercase();
+        self.matches = if search.is_empty() {
+            vec![]
+        } else {
+            (0..self.users.len())
+                .filter(|&i| self.search_index[i].contains(&search))
+                .collect()
+        };
+    }
+
+    /// Every IP in the loaded users' logins
+    fn user_ips(&self) -> Vec<Ipv4Addr> {
+        let mut ips: Vec<Ipv4Addr> = self
+            .users
+            .iter()
+            .flat_map(|u| u.logins.iter().filter_map(|l| l.ip))
+            .collect();
+        ips.sort();
+        ips.dedup();
+        ips
+    }
+
+    /// Reruns the first vibe check on the current user with the current settings and loaded
+    /// logins, optionally re-sorting while staying on the same user
+    fn rescore(&mut self) {
+        self.store.rescore_user(&mut self.users[self.user_idx]);
+        if !self.resort {
+            return;
+        }
+
+        let name = self.cur_user().name.to_owned();
+        self.users.sort();
+        self.user_idx = self
+            .users
+            .iter()
+            .position(|u| u.name == name)
+            .expect("Rescored user went missing");
+        self.search_index = self.users.iter().map(|u| u.search_text()).collect();
+        self.find_matches();
+        self.match_idx = self
+            .matches
+            .iter()
+            .position(|&i| i == self.user_idx)
+            .unwrap_or(0);
+    }
+
+    /// Swaps in the users that are still funky after a location re-check, staying on the same
+    /// user if they're still around
+    fn recheck_done(&mut self, users: Vec<User>, before: usize) {
+        let name = self.cur_user().name.to_owned();
+        self.recheck_dropped = Some(before - users.len());
+        self.users = users;
+        if self.users.is_empty() {
+            return;
+        }
+        self.user_idx = self.users.iter().position(|u| u.name == name).unwrap_or(0);
+        self.search_index = self.users.iter().map(|u| u.search_text()).collect();
+        self.find_matches();
+        self.match_idx = 0;
+        self.snap_to_visible();
+    }
+
+    /// Goes to the current match, lowering the min score if it is hidden
+    fn jump_to_match(&mut self) {
+        let Some(&i) = self.matches.get(self.match_idx) else {
+            return;
+        };
+        self.user_idx = i;
+        if !self.visible(i) {
+            self.min_score = self.users[i].score;
+        }
+    }
+
+    /// Ignores or un-ignores the current user, saving the ignore note with an ignore
+    fn mark_investigated(&mut self, mark: bool) {
+        let note = self.ignore_note.trim();
+        let note = (mark && !note.is_empty()).then(|| note.to_owned());
+        let user = &mut self.users[self.user_idx];
+        self.store
+            .mark_investigated(user.name.to_owned(), mark, note.as_deref());
+        user.investigated = mark;
+        if mark {
+            // Without a new note the last one is kept
+            if note.is_some() {
+                user.investigated_note = note;
+            }
+            self.ignore_note.clear();
         }
-        self.user_idx += 1;
     }
 
     fn prev_user(&mut self) {
-        self.user_idx = self.user_idx.saturating_sub(1);
+        if let Some(i) = (0..self.user_idx).rev().find(|&i| self.visible(i)) {
+            self.user_idx = i;
+        }
     }
 
     fn progress(&self) -> f32 {
-        (self.user_idx + 1) as f32 / self.users.len() as f32
+        self.position() as f32 / self.visible_count() as f32
+    }
+
+    /// Login the template shortcuts copy for, the first flagged one shown or else the newest
+    fn template_login(&self) -> Option<&Login> {
+        let mut shown = self.cur_user().logins.iter().filter(|l| {
+            self.location_filter
+                .as_ref()
+                .is_none_or(|f| *f == l.summary_location())
+        });
+        let newest = shown.clone().next();
+        shown.find(|l| !l.flag_reasons.is_empty()).or(newest)
+    }
+
+    /// Handles the key after [Action::CopyTemplate], any key other than a template's cancels
+    fn handle_template_key(&mut self, ctx: &egui::Context) {
+        let Some(template) = ctx.input(|i| {
+            Template::ALL
+                .into_iter()
+                .find(|t| i.key_pressed(t.key()))
+                .map(Some)
+                .or_else(|| {
+                    i.events
+                        .iter()
+                        .any(|e| matches!(e, egui::Event::Key { pressed: true, .. }))
+                        .then_some(None)
+                })
+        }) else {
+            egui::show_tooltip_at_pointer(ctx, egui::Id::new("template_keys"), |ui| {
+                for template in Template::ALL {
+                    ui.label(format!("{}: {}", key_name(template.key()), template));
+                }
+            });
+            return;
+        };
+        self.template_pending = false;
+
+        let Some(template) = template else {
+            return;
+        };
+        let analyst_name = self.store.analyst_name();
+        if template.signed() && analyst_name.is_empty() {
+            log::info!("Not copying {:?} without an analyst name", template);
+            return;
+        }
+        if self.store.redact().0 {
+            log::info!("Not copying {:?} while redacting", template);
+            return;
+        }
+        let Some(login) = self.template_login() else {
+            return;
+        };
+        let text = template.text(
+            self.cur_user(),
+            login,
+            analyst_name,
+            self.store.display_zone(),
+        );
+        ctx.output_mut(|o| o.copied_text = text);
     }
 
     fn handle_keypresses(&mut self, ctx: &egui::Context) {
+        if self.template_pending {
+            self.handle_template_key(ctx);
+            return;
+        }
+
+        let keymap = self.store.keymap();
         ctx.input(|i| {
-            if i.key_pressed(Key::P) || i.key_pressed(Key::K) || i.key_pressed(Key::ArrowLeft) {
+            if keymap::help_pressed(i) {
+                self.show_help = !self.show_help;
+            }
+            if keymap.pressed(i, Action::PrevUser) {
                 self.prev_user()
             }
-            if i.key_pressed(Key::N) || i.key_pressed(Key::J) || i.key_pressed(Key::ArrowRight) {
+            if keymap.pressed(i, Action::NextUser) {
                 self.next_user();
             }
-            if i.key_pressed(Key::I) {
+            if keymap.pressed(i, Action::ToggleIgnore) {
                 // Toggle investigated
-                let user = self.cur_user();
-
-                let investigated = user.investigated;
-                self.store
-                    .mark_investigated(user.name.to_owned(), !investigated);
-                self.users[self.user_idx].investigated = !investigated;
+                let investigated = self.cur_user().investigated;
+                self.mark_investigated(!investigated);
+            }
+            if keymap.pressed(i, Action::CopyTemplate) {
+                self.template_pending = true;
             }
         });
     }
 
     fn top_bar(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
             let user = &self.cur_user();
+            let name = self.store.redact().user(&user.name);
             ui.heading("User");
             let heading = ui.add(
-                Label::new(
-                    RichText::new(user.name.to_owned())
-                        .heading()
-                        .color(color::PINE),
-                )
-                .sense(egui::Sense::click()),
+                Label::new(RichText::new(name.as_str()).heading().color(theme.pine))
+                    .sense(egui::Sense::click()),
             );
             if heading.clicked() {
-                ui.output_mut(|o| o.copied_text = user.name.to_owned());
+                ui.output_mut(|o| o.copied_text = name);
             }
             let reason = user
                 .reasons
@@ -383,90 +1446,500 @@ impl MainUi {
                 .map(|r| r.to_string())
                 .collect::<Vec<String>>()
                 .join(", ");
-            ui.heading(format!("flagged for {} - score {}", reason, user.score));
+            ui.menu_button(
+                RichText::new(format!("flagged for {} - score {}", reason, user.score)).heading(),
+                |ui| {
+                    egui::Grid::new("score_breakdown")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for title in ["Reason", "Count", "Weight", "Points"] {
+                                ui.label(RichText::new(title).color(theme.pine));
+                            }
+                            ui.end_row();
+                            for component in &user.score_breakdown.components {
+                                ui.label(component.reason.to_string());
+                                ui.label(component.count.to_string());
+                                match component.weight {
+                                    Some(weight) => ui.label(weight.to_string()),
+                                    None => ui
+                                        .label("speed")
+                                        .on_hover_text("Each trip is scored by how fast it was"),
+                                };
+                                ui.label(component.contribution.to_string());
+                                ui.end_row();
+                            }
+                            ui.label("Total");
+                            ui.label("");
+                            ui.label("");
+                            ui.label(user.score_breakdown.total().to_string());
+                            ui.end_row();
+                        });
+                    if ui.button("Copy breakdown").clicked() {
+                        ui.output_mut(|o| o.copied_text = user.score_breakdown.to_string());
+                        ui.close_menu();
+                    }
+                },
+            )
+            .response
+            .on_hover_text("Click for how the score adds up");
 
             ui.with_layout(egui::Layout::right_to_left(egui::Align::TOP), |ui| {
-                ui.menu_button("More logs", |ui| {
-                    ui.add(egui::Slider::new(&mut self.days, 7..=90).text("days"));
-                    if ui.button("Get").clicked() {
-                        ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
-                        let user = self.cur_user().name.to_owned();
-                        self.more_logs =
-                            Some((self.store.more_info(user, self.days), self.user_idx));
-                        ui.close_menu();
+                ui.add_enabled_ui(self.recheck.is_none(), |ui| {
+                    let name = self.cur_user().name.to_owned();
+                    ui.menu_button("More logs", |ui| {
+                        if let Some((user, e)) = &self.more_logs_error {
+                            if *user == name {
+                                ui.label(
+                                    RichText::new(format!("Last query failed: {}", e))
+                                        .color(theme.love),
+                                );
+                            }
+                        }
+                        ui.add(egui::Slider::new(&mut self.days, 7..=90).text("days"));
+                        let duplicate = self
+                            .more_logs
+                            .iter()
+                            .any(|f| f.user == name && f.days == self.days);
+                        let full = self.more_logs.len() >= MAX_MORE_LOGS;
+                        let get = ui
+                            .add_enabled(!duplicate && !full, egui::Button::new("Get"))
+                            .on_disabled_hover_text(if duplicate {
+                                "Already pulling these days for this user".to_owned()
+                            } else {
+                                format!("{} pulls are running, wait for one", MAX_MORE_LOGS)
+                            });
+                        if get.clicked() {
+                            self.more_logs.push(MoreLogs {
+                                rx: self.store.more_info(name.clone(), self.days),
+                                user: name.clone(),
+                                days: self.days,
+                            });
+                            ui.close_menu();
+                        }
+                    });
+                    let pending: Vec<i64> = self
+                        .more_logs
+                        .iter()
+                        .filter(|f| f.user == name)
+                        .map(|f| f.days)
+                        .collect();
+                    if !pending.is_empty() {
+                        ui.spinner().on_hover_text(format!(
+                            "Pulling {} days of logs",
+                            pending
+                                .iter()
+                                .map(|d| d.to_string())
+                                .collect::<Vec<_>>()
+                                .join(" and ")
+                        ));
                     }
                 });
 
+                ui.add_enabled_ui(
+                    self.admin_events.is_none() && self.recheck.is_none(),
+                    |ui| {
+                        let mut button = ui.button("Admin events").on_hover_text(format!(
+                            "Pull device activations and bypass codes from the past {} days and re-score.  A device activated alongside another flag adds to the score",
+                            self.days
+                        ));
+                        if let Some(e) = &self.admin_events_error {
+                            button = button.on_hover_text(
+                                RichText::new(format!("Last query failed: {}", e))
+                                    .color(theme.love),
+                            );
+                        }
+                        if button.clicked() {
+                            let user = self.cur_user().name.to_owned();
+                            self.admin_events =
+                                Some((self.store.admin_events(user, self.days), self.user_idx));
+                        }
+                    },
+                );
+
+                // Admin events hold the index of the user they're for, and re-checking replaces
+                // every user when it's done, so nothing can move until pulls are done
+                let pending = !self.more_logs.is_empty() || self.admin_events.is_some();
+                ui.add_enabled_ui(!pending && self.recheck.is_none(), |ui| {
+                    ui.menu_button("Re-score", |ui| {
+                        ui.checkbox(&mut self.resort, "Re-sort users");
+                        if ui.button("Confirm").clicked() {
+                            self.rescore();
+                            ui.close_menu();
+                        }
+                    })
+                    .response
+                    .on_hover_text("Score this user again with the current settings and logs");
+
+                    ui.menu_button("Re-check locations", |ui| {
+                        if ui.button("Confirm").clicked() {
+                            let before = self.users.len();
+                            self.recheck =
+                                Some((self.store.rerun_ip_pass(self.users.clone()), before));
+                            ui.close_menu();
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Run the IP location pass again over every user without querying Splunk",
+                    );
+                });
+
+                ui.menu_button("Export IPs", |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("File");
+                        ui.text_edit_singleline(&mut self.export_file)
+                            .on_hover_text("Saved as JSON if it ends in .json, otherwise CSV");
+                    });
+                    ui.checkbox(
+                        &mut self.export_all,
+                        "Every cached IP, not just these users'",
+                    );
+                    ui.add_enabled_ui(self.export_rx.is_none(), |ui| {
+                        if ui.button("Save").clicked() {
+                            let ips = (!self.export_all).then(|| self.user_ips());
+                            self.export_rx = Some(
+                                self.store
+                                    .save_ip_enrichment(self.export_file.to_owned(), ips),
+                            );
+                            ui.close_menu();
+                        }
+                    });
+                })
+                .response
+                .on_hover_text("Save cached IP info and threat data for sharing");
+
+                super::copy_searches_button(ui, &self.searches, self.store.redact());
+
+                let user = &self.users[self.user_idx];
+                let location_filter = &self.location_filter;
+                self.login_export.menu(ui, &self.store, user, || {
+                    (0..user.logins.len())
+                        .filter(|&i| {
+                            location_filter
+                                .as_ref()
+                                .is_none_or(|l| *l == user.logins[i].summary_location())
+                        })
+                        .collect()
+                });
+
+                ui.menu_button("Ignore rest", |ui| {
+                    ui.checkbox(&mut self.ignore_by_score, "Only scores below");
+                    if self.ignore_by_score {
+                        let max = self.users.iter().map(|u| u.score).max().unwrap_or(0) + 1;
+                        ui.add(egui::Slider::new(&mut self.ignore_score, 1..=max).text("score"));
+                    }
+                    let count = self.bulk_ignore_users().len();
+                    ui.label(
+                        RichText::new(format!("{} users will not reappear for 24 hours", count))
+                            .color(theme.love),
+                    );
+                    if ui.button("Confirm").clicked() {
+                        self.bulk_ignore();
+                        ui.close_menu();
+                    }
+                })
+                .response
+                .on_hover_text("Ignore every remaining user and go to final screen");
+
                 if ui
                     .button("I'm done")
                     .on_hover_text("Go to final screen")
                     .clicked()
                 {
-                    self.action = Some(DuplexAction::Done {
-                        store: Rc::clone(&self.store),
-                        investigations: self.user_idx + 1,
-                    });
+                    self.done(self.user_idx + 1);
                 }
 
-                let user = &self.cur_user();
-                if !user.investigated {
+                if !self.cur_user().investigated {
                     let button = ui
                         .button("(I)gnore")
                         .on_hover_text("User will not reapprear for 24 hours");
                     if button.clicked() {
-                        self.store.mark_investigated(user.name.to_owned(), true);
-                        self.users[self.user_idx].investigated = true;
+                        self.mark_investigated(true);
                     }
+                    ui.add(
+                        TextEdit::singleline(&mut self.ignore_note)
+                            .hint_text("Ignore note")
+                            .desired_width(150.0),
+                    )
+                    .on_hover_text("Why this user is being ignored, shown if they reappear");
                 } else if ui.button("Un(I)gnore").clicked() {
-                    self.store.mark_investigated(user.name.to_owned(), false);
-                    self.users[self.user_idx].investigated = false;
+                    self.mark_investigated(false);
                 }
 
-                if ui.button("(N)ext").clicked() {
+                let keymap = self.store.keymap();
+                let next = ui
+                    .button("(N)ext")
+                    .on_hover_text(format!("Shortcut: {}", keymap.describe(Action::NextUser)));
+                if next.clicked() {
                     self.next_user();
                 }
-                if ui.button("(P)revious").clicked() {
+                let prev = ui
+                    .button("(P)revious")
+                    .on_hover_text(format!("Shortcut: {}", keymap.describe(Action::PrevUser)));
+                if prev.clicked() {
                     self.prev_user();
                 }
+
+                ui.menu_button("Open in", |ui| {
+                    if let Some((to, message)) = open_in_buttons(ui, &self.cur_user().name) {
+                        self.action = Some(DuplexAction::OpenIn { to, message });
+                    }
+                })
+                .response
+                .on_hover_text("Look this user up in another app");
+
+                if ui
+                    .button("Overview")
+                    .on_hover_text("List every user to jump to one")
+                    .clicked()
+                {
+                    self.overview = true;
+                }
+
+                ui.toggle_value(&mut self.show_map, "Map")
+                    .on_hover_text("Plot the checked logins, click one to find it in the table");
             });
         });
     }
 
+    /// Every shown user with their score, top reason, and where most of their logins came from,
+    /// sortable by clicking a column.  Clicking a user goes to them, and Next and Previous carry
+    /// on from there in queue order.
+    fn overview_ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let redact = self.store.redact();
+        ui.horizontal(|ui| {
+            self.refresh_ui(ui);
+            ui.label(format!("[{} users]", self.visible_count()));
+            if ui
+                .button("Go through in order")
+                .on_hover_text("Back to the current user")
+                .clicked()
+            {
+                self.overview = false;
+            }
+        });
+
+        let mut rows: Vec<(usize, Option<FlagReason>, String)> = (0..self.users.len())
+            .filter(|&i| self.visible(i))
+            .map(|i| {
+                let user = &self.users[i];
+                let location = user
+                    .location_summary()
+                    .into_iter()
+                    .next()
+                    .map(|l| l.location)
+                    .unwrap_or_default();
+                (i, user.top_reason(), location)
+            })
+            .collect();
+        let (sort, reversed) = self.overview_sort;
+        // Stable, so ties stay in queue order
+        rows.sort_by(|a, b| {
+            let order = match sort {
+                OverviewSort::User => self.users[a.0].name.cmp(&self.users[b.0].name),
+                OverviewSort::Score => self.users[a.0].cmp(&self.users[b.0]),
+                OverviewSort::Reason => a.1.map(|r| r.to_string()).cmp(&b.1.map(|r| r.to_string())),
+                OverviewSort::Location => a.2.cmp(&b.2),
+            };
+            if reversed {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+
+        let row_height = super::row_height(ui);
+        let mut open = None;
+        TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .columns(Column::auto(), 3)
+            .column(Column::remainder())
+            .header(20.0, |mut header| {
+                for column in OverviewSort::ALL {
+                    header.col(|ui| {
+                        let title = match (sort == column, reversed) {
+                            (true, false) => format!("{} ⏷", column),
+                            (true, true) => format!("{} ⏶", column),
+                            (false, _) => column.to_string(),
+                        };
+                        if ui
+                            .selectable_label(
+                                sort == column,
+                                RichText::new(title).color(theme.pine),
+                            )
+                            .clicked()
+                        {
+                            self.overview_sort = (column, sort == column && !reversed);
+                        }
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(row_height, rows.len(), |i, mut row| {
+                    let (idx, reason, location) = &rows[i];
+                    let user = &self.users[*idx];
+                    row.col(|ui| {
+                        let name =
+                            RichText::new(redact.user(&user.name)).color(if user.investigated {
+                                theme.muted
+                            } else {
+                                theme.text
+                            });
+                        let mut link = ui.link(name);
+                        if user.investigated {
+                            link = link.on_hover_text("Ignored");
+                        }
+                        if link.clicked() {
+                            open = Some(*idx);
+                        }
+                    });
+                    row.col(|ui| {
+                        let color = if user.fraud() > 0 {
+                            theme.love
+                        } else {
+                            theme.text
+                        };
+                        ui.label(RichText::new(user.score.to_string()).color(color));
+                    });
+                    row.col(|ui| {
+                        if let Some(reason) = reason {
+                            ui.label(reason.to_string());
+                        }
+                    });
+                    row.col(|ui| {
+                        ui.label(location);
+                    });
+                });
+            });
+
+        if let Some(i) = open {
+            self.user_idx = i;
+            self.location_filter = None;
+            self.overview = false;
+        }
+    }
+
     fn hdtools_bar(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
             let user = &self.cur_user();
             if user.creation_date.is_some() || user.location.is_some() {
                 if let Some(cd) = &user.creation_date {
-                    ui.label(format!("Created {}", cd.format("%m/%d/%Y")));
+                    let created = format!("Created {}", cd.format("%m/%d/%Y"));
+                    match user.account_age() {
+                        Some((age, flagged)) => {
+                            let new = age < chrono::Duration::days(NEW_ACCOUNT_DAYS);
+                            ui.label(
+                                RichText::new(format!("{} — {}", created, age_text(age, flagged)))
+                                    .color(if new { theme.gold } else { theme.text }),
+                            )
+                        }
+                        None => ui.label(created),
+                    };
                     ui.separator();
                 }
                 if let Some(loc) = &user.location {
-                    ui.label(loc.to_string());
+                    let label = ui.label(loc.to_string());
+                    if let Some(home) = user.home {
+                        label.context_menu(|ui| super::location_menu(ui, home));
+                    }
+                }
+                if let Some(record) = &user.address_record {
+                    super::address_record(ui, *record, &user.addresses);
+                }
+                if let Some(affiliation) = &user.affiliation {
+                    ui.separator();
+                    ui.label(affiliation);
+                }
+                if let Some(status) = &user.status {
+                    ui.separator();
+                    ui.label(RichText::new(status).color(if user.is_disabled() {
+                        theme.love
+                    } else {
+                        theme.text
+                    }));
                 }
             } else {
-                ui.label(RichText::new("No HDTools info").color(color::ROSE));
+                super::hdtools_missing(ui, user.hdtools_status);
+            }
+            if let Some(note) = &user.investigated_note {
+                ui.separator();
+                ui.label(RichText::new(format!("Last ignored: {}", note)).color(theme.gold));
+            }
+            if !user.admin_events.is_empty() {
+                ui.separator();
+                super::admin_events_menu(ui, &user.admin_events, self.store.display_zone());
             }
         });
     }
 
     fn table(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.separator();
 
-        let table = TableBuilder::new(ui)
+        let row_height = super::row_height(ui);
+        let mut table = TableBuilder::new(ui)
             .striped(true)
             .resizable(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(Column::auto(), 6)
+            .columns(Column::auto(), 7)
             .column(Column::remainder());
+        let scroll_to = self.scroll_to.take();
         let user = &self.cur_user();
+        let zone = self.store.display_zone();
+        let redact = self.store.redact();
+        // Set from the context menus, sent once the table no longer borrows the user
+        let mut open = None;
+        let mut raw_event = None;
+        let mut disposition = None;
+        let mut location_override = None;
+        let rows: Vec<usize> = (0..user.logins.len())
+            .filter(|&i| {
+                self.location_filter
+                    .as_ref()
+                    .is_none_or(|l| *l == user.logins[i].summary_location())
+            })
+            .collect();
+        let display = self.repeats.display(&user.logins, &rows);
+        // A marker row goes above the first login from before the account was created, HDTools
+        // dates are local
+        let created_row = user.creation_date.and_then(|created| {
+            display
+                .iter()
+                .position(|&(r, _)| Zone::Local.to_zone(user.logins[rows[r]].time) < created)
+                .map(|at| (at, created))
+        });
+        // A login clicked on the map, or the collapsed run it's in
+        if let Some(login) = scroll_to {
+            let row = rows
+                .iter()
+                .position(|&r| r == login)
+                .and_then(|at| display.iter().rposition(|&(r, _)| r <= at));
+            if let Some(row) = row {
+                let row = match created_row {
+                    Some((at, _)) if row >= at => row + 1,
+                    _ => row,
+                };
+                table = table.scroll_to_row(row, Some(egui::Align::Center));
+            }
+        }
+        // Set from the header and badges, applied once the table no longer borrows the user
+        let mut collapse = self.repeats.collapse;
+        let mut toggle = None;
         table
-            .header(20.0, |mut header| {
+            .header(row_height, |mut header| {
                 header.col(|ui| {
-                    ui.label("Time")
-                        .on_hover_text("Right click for Cherwell templates");
+                    ui.label(format!("Time ({})", zone.abbreviation(chrono::Utc::now()))).on_hover_text(format!(
+                        "Right click for Cherwell templates\nOr press {} then a key from ? to copy one for the first flagged login",
+                        self.store.keymap().describe(Action::CopyTemplate)
+                    ));
                 });
                 header.col(|ui| {
                     ui.label("Result");
+                    super::Repeats::header_toggle(ui, &mut collapse);
                 });
                 header.col(|ui| {
                     ui.label("Reason").on_hover_text("Hehe monkey");
@@ -477,32 +1950,60 @@ impl MainUi {
                 header.col(|ui| {
                     ui.label("Integration");
                 });
+                header.col(|ui| {
+                    ui.label("Device").on_hover_ui(|ui| {
+                        ui.label("Mouse over for versions and trust");
+                        ui.label(RichText::new("- Orange for untrusted device").color(theme.rose));
+                    });
+                });
                 header.col(|ui| {
                     ui.label("IP").on_hover_ui(|ui| {
                         ui.label(
                             "Left click to copy to clipboard\nRight click to view service details\nMouse over for ASN",
                         );
-                        ui.label(RichText::new("- Green for CUVPN IP").color(color::FOAM));
-                        ui.label(RichText::new("- Orange for known proxy").color(color::ROSE));
+                        ui.label(RichText::new("- Green for CUVPN IP").color(theme.foam));
+                        ui.label(RichText::new("- Orange for known proxy").color(theme.rose));
+                        ui.label(RichText::new("- Purple for trusted IP").color(theme.iris));
                     });
                 });
                 header.col(|ui| {
                     ui.label("Location").on_hover_text(
-                        "Left click to copy to clipboard\nRight click to copy coordinates",
+                        "Left click to copy to clipboard\nRight click for coordinates and a map link",
                     );
                 });
             })
             .body(|body| {
-                body.rows(20.0, user.logins.len(), |i, mut row| {
-                    let login = &user.logins[i];
+                let len = display.len() + usize::from(created_row.is_some());
+                body.rows(row_height, len, |i, mut row| {
+                    let i = match created_row {
+                        Some((at, created)) if i == at => {
+                            row.col(|ui| {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "Account created {}",
+                                        created.format("%m/%d/%Y")
+                                    ))
+                                    .color(theme.gold),
+                                )
+                                .on_hover_text("Logins below are from before the account was created in HDTools");
+                            });
+                            return;
+                        }
+                        Some((at, _)) if i > at => i - 1,
+                        _ => i,
+                    };
+                    let (r, run) = display[i];
+                    let login = &user.logins[rows[r]];
                     row.col(|ui| {
                         ui.add(
                             egui::Label::new(
-                                RichText::new(format!("{}", login.time.format("%T %D"))).color(
+                                RichText::new(format!("{}", zone.to_zone(login.time).format("%T %D"))).color(
                                     if login.flag_reasons.is_empty() {
-                                        color::TEXT
+                                        theme.text
+                                    } else if login.flag_reasons.contains(&FlagReason::Restricted) {
+                                        theme.gold
                                     } else {
-                                        color::LOVE
+                                        theme.love
                                     },
                                 ),
                             )
@@ -510,54 +2011,34 @@ impl MainUi {
                         )
                         .context_menu(|ui| {
                             if ui.button("Copy username").clicked() {
-                                ui.output_mut(|o| o.copied_text = login.user.to_owned());
+                                ui.output_mut(|o| o.copied_text = redact.user(&login.user));
                             }
-                            if ui.button("Copy short description").clicked() {
-                                ui.output_mut(|o| {
-                                    o.copied_text = "Duo Multi Login Suspicious Activity".to_owned()
-                                });
+                            if let Some(message) = open_in_buttons(ui, &login.user) {
+                                open = Some(message);
                             }
-                            let analyst_name = self.store.analyst_name();
-                            if !analyst_name.is_empty() && ui.button("Copy first contact").clicked()
-                            {
-                                ui.output_mut(|o| {
-                                    if login.result == LoginResult::Fraud {
-                                        o.copied_text = format!(
-                                            std::include_str!(
-                                                "../../templates/first_contact_fraud.txt"
-                                            ),
-                                            analyst_name,
-                                            login.time.format("%m/%d"),
-                                            login.time.format("%I:%M %p"),
-                                            login.factor,
-                                            login
-                                                .format_location()
-                                                .unwrap_or_else(|| "CUVPN".to_owned()),
-                                            analyst_name
-                                        )
-                                    } else {
-                                        o.copied_text = format!(
-                                            std::include_str!("../../templates/first_contact.txt"),
-                                            analyst_name,
-                                            login.time.format("%m/%d"),
-                                            login.time.format("%I:%M %p"),
-                                            login.factor,
-                                            login
-                                                .format_location()
-                                                .unwrap_or_else(|| "CUVPN".to_owned()),
-                                            analyst_name
-                                        )
-                                    }
-                                });
+                            if let Some(event) = super::raw_event_button(ui, login, redact) {
+                                raw_event = Some(event);
                             }
-                            if ui.button("Copy password reset").clicked() {
-                                ui.output_mut(|o| {
-                                    o.copied_text = format!(
-                                        std::include_str!("../../templates/password_reset.txt"),
-                                        analyst_name, analyst_name,
-                                    )
-                                });
+                            if let Some(phone) = user.phone.as_ref().filter(|_| !redact.0) {
+                                if ui.button("Copy phone").clicked() {
+                                    ui.output_mut(|o| o.copied_text = phone.to_owned());
+                                }
                             }
+                            let analyst_name = self.store.analyst_name();
+                            // Templates have the real name, phone, and IPs
+                            ui.add_enabled_ui(!redact.0, |ui| {
+                                for template in Template::ALL {
+                                    if template == Template::FirstContact && analyst_name.is_empty() {
+                                        continue;
+                                    }
+                                    if ui.button(template.to_string()).clicked() {
+                                        ui.output_mut(|o| {
+                                            o.copied_text =
+                                                template.text(user, login, analyst_name, zone)
+                                        });
+                                    }
+                                }
+                            });
                             if ui.button("Copy service class").clicked() {
                                 ui.output_mut(|o| {
                                     o.copied_text =
@@ -566,21 +2047,24 @@ impl MainUi {
                                 ui.close_menu();
                             }
                         });
+                        if let Some(len) = run {
+                            let open = self.repeats.is_open(login);
+                            if super::Repeats::badge(ui, len, open) {
+                                toggle = Some(rows[r]);
+                            }
+                        }
                     });
                     row.col(|ui| {
-                        ui.label(RichText::new(login.result.to_string()).color(
-                            match login.result {
-                                LoginResult::Failure => color::ROSE,
-                                LoginResult::Fraud => color::LOVE,
-                                _ => color::TEXT,
-                            },
-                        ));
+                        if let Some(picked) = super::login_result(ui, login) {
+                            disposition = Some((rows[r], picked));
+                        }
                     });
                     row.col(|ui| {
                         ui.label(RichText::new(login.reason.to_string()).color(
                             match login.reason {
-                                Reason::DenyUnenrolledUser => color::ROSE,
-                                _ => color::TEXT,
+                                Reason::DenyUnenrolledUser => theme.rose,
+                                Reason::RestrictedOFAC => theme.gold,
+                                _ => theme.text,
                             },
                         ));
                     });
@@ -588,118 +2072,246 @@ impl MainUi {
                         ui.label(login.factor.to_string());
                     });
                     row.col(|ui| {
-                        ui.label(RichText::new(login.integration.to_string()).color(
-                            match login.integration {
-                                Integration::CuVpn => color::FOAM,
-                                Integration::Citrix => color::FOAM,
-                                Integration::Dmp => color::LOVE,
-                                _ => color::TEXT,
-                            },
-                        ));
+                        let integration =
+                            ui.label(RichText::new(login.integration.to_string()).color(
+                                match login.integration {
+                                    Integration::CuVpn => theme.foam,
+                                    Integration::Citrix => theme.foam,
+                                    Integration::Dmp => theme.love,
+                                    _ => theme.text,
+                                },
+                            ));
+                        if let Some(key) = &login.application_key {
+                            integration.on_hover_text(format!("Application key {}", key));
+                        }
+                    });
+                    row.col(|ui| {
+                        if let Some(device) = login.format_device() {
+                            ui.label(RichText::new(device).color(
+                                if login.trusted_device == Some(false) {
+                                    theme.rose
+                                } else {
+                                    theme.text
+                                },
+                            ))
+                            .on_hover_ui(|ui| {
+                                if let Some(browser) = &login.browser {
+                                    ui.label(format!(
+                                        "{} {}",
+                                        browser,
+                                        login.browser_version.as_deref().unwrap_or_default()
+                                    ));
+                                }
+                                if let Some(os) = &login.os {
+                                    ui.label(format!(
+                                        "{} {}",
+                                        os,
+                                        login.os_version.as_deref().unwrap_or_default()
+                                    ));
+                                }
+                                ui.label(match login.trusted_device {
+                                    Some(true) => "Trusted",
+                                    Some(false) => "Not trusted",
+                                    None => "Trust unknown",
+                                });
+                            });
+                        }
                     });
                     row.col(|ui| {
                         if let Some(ip) = login.ip {
                             let lable = ui
                                 .add(
-                                    Label::new(RichText::new(ip.to_string()).color(
+                                    Label::new(RichText::new(redact.ip(ip)).color(
                                         if login.is_vpn_ip() {
-                                            color::FOAM
+                                            theme.foam
+                                        } else if self.store.is_ip_allowlisted(ip)
+                                            || self.store.trusted_range(ip).is_some()
+                                        {
+                                            theme.iris
                                         } else if login.is_relay {
-                                            color::ROSE
+                                            theme.rose
                                         } else {
-                                            color::TEXT
+                                            theme.text
                                         },
                                     ))
                                     .sense(egui::Sense::click()),
                                 )
                                 .on_hover_text(login.asn.as_deref().unwrap_or_default())
                                 .context_menu(|ui| {
-                                    if let Some(ipinfo) = self.store.get_ipthreat(ip) {
-                                        if ipinfo.vibe_check() {
-                                            ui.label("Nothing funky");
-                                        } else {
-                                            ui.vertical(|ui| {
-                                                if ipinfo.is_tor {
-                                                    ui.label("✅Tor");
-                                                }
-
-                                                if ipinfo.is_icloud_relay {
-                                                    ui.label("✅iCloud Relay");
-                                                }
-
-                                                if ipinfo.is_proxy {
-                                                    ui.label("✅Proxy");
-                                                }
-
-                                                if ipinfo.is_datacenter {
-                                                    ui.label("✅Datacenter");
-                                                }
-
-                                                if ipinfo.is_anonymous {
-                                                    ui.label("✅Anonymous");
-                                                }
-
-                                                if ipinfo.is_known_attacker {
-                                                    ui.label("✅Known Attacker");
-                                                }
-
-                                                if ipinfo.is_known_abuser {
-                                                    ui.label("✅Known Abuser");
-                                                }
-
-                                                if ipinfo.is_threat {
-                                                    ui.label("✅Threat");
-                                                }
-
-                                                if ipinfo.is_bogon {
-                                                    ui.label("✅Bogon");
-                                                }
-
-                                                if !ipinfo.blocklists.is_empty() {
-                                                    ui.label("✅Blocklists");
-                                                }
-                                            });
+                                    if ui.button("Open in Sonar").clicked() {
+                                        open = Some((
+                                            Sonar::NAME,
+                                            PanelMessage::SetLookup(ip.to_string()),
+                                        ));
+                                        ui.close_menu();
+                                    }
+                                    if self.store.is_ip_allowlisted(ip) {
+                                        if ui.button("Untrust this IP").clicked() {
+                                            self.store.remove_allowlisted_ip(ip);
+                                            ui.close_menu();
+                                        }
+                                    } else if ui.button("Trust this IP").clicked() {
+                                        self.store.add_allowlisted_ip(ip);
+                                        ui.close_menu();
+                                    }
+                                    if self.store.trusted_range(ip).is_some() {
+                                        ui.label("In a trusted range");
+                                    }
+                                    ui.separator();
+                                    match self.store.get_ipthreat(ip) {
+                                        Ok(ipinfo) => {
+                                            if ipinfo.vibe_check() {
+                                                ui.label("Nothing funky");
+                                            } else {
+                                                ui.vertical(|ui| {
+                                                    if ipinfo.is_tor {
+                                                        ui.label("✅Tor");
+                                                    }
+
+                                                    if ipinfo.is_icloud_relay {
+                                                        ui.label("✅iCloud Relay");
+                                                    }
+
+                                                    if ipinfo.is_proxy {
+                                                        ui.label("✅Proxy");
+                                                    }
+
+                                                    if ipinfo.is_datacenter {
+                                                        ui.label("✅Datacenter");
+                                                    }
+
+                                                    if ipinfo.is_anonymous {
+                                                        ui.label("✅Anonymous");
+                                                    }
+
+                                                    if ipinfo.is_known_attacker {
+                                                        ui.label("✅Known Attacker");
+                                                    }
+
+                                                    if ipinfo.is_known_abuser {
+                                                        ui.label("✅Known Abuser");
+                                                    }
+
+                                                    if ipinfo.is_threat {
+                                                        ui.label("✅Threat");
+                                                    }
+
+                                                    if ipinfo.is_bogon {
+                                                        ui.label("✅Bogon");
+                                                    }
+
+                                                    if !ipinfo.blocklists.is_empty() {
+                                                        ui.label("✅Blocklists");
+                                                    }
+                                                });
+                                            }
+                                        }
+                                        Err(e) if e.is_transient() => {
+                                            ui.label(
+                                                RichText::new(format!("IP info temporarily unavailable, {}", e))
+                                                    .color(theme.gold),
+                                            );
+                                        }
+                                        Err(_) => {
+                                            ui.label(
+                                                RichText::new("Could not fetch IP info")
+                                                    .color(theme.rose),
+                                            );
                                         }
-                                    } else {
-                                        ui.label(
-                                            RichText::new("Could not fetch IP info")
-                                                .color(color::ROSE),
-                                        );
                                     }
                                 });
                             if lable.clicked() {
-                                ui.output_mut(|o| o.copied_text = ip.to_string());
+                                ui.output_mut(|o| o.copied_text = redact.ip(ip));
                             }
                         }
                     });
                     row.col(|ui| {
-                        if let Some(loc) = login.format_location() {
-                            let label =
-                                ui.add(Label::new(loc.as_str()).sense(egui::Sense::click()));
-                            if label.clicked() {
-                                ui.output_mut(|o| o.copied_text = loc);
-                            }
-                            if label.secondary_clicked() {
-                                ui.output_mut(|o| {
-                                    o.copied_text = login
-                                        .location
-                                        .map(|l| format!("{}, {}", l.0, l.1))
-                                        .unwrap_or_default()
-                                });
-                            }
+                        if let Some(picked) = super::login_location(ui, login) {
+                            location_override = Some(picked);
                         }
                     });
                 });
             });
+        if let Some((to, message)) = open {
+            self.action = Some(DuplexAction::OpenIn { to, message });
+        }
+        if let Some(event) = raw_event {
+            self.raw_event.open(event);
+        }
+        if let Some((i, disposition)) = disposition {
+            self.store
+                .set_fraud_disposition(&mut self.users[self.user_idx], i, disposition);
+            self.rescore();
+        }
+        if let Some((ip, location)) = location_override {
+            self.store
+                .set_location_override(&mut self.users[self.user_idx], ip, location);
+            self.rescore();
+        }
+        self.repeats.collapse = collapse;
+        if let Some(i) = toggle {
+            let login = &self.users[self.user_idx].logins[i];
+            self.repeats.toggle(login);
+        }
     }
 
     fn progress_bar(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
+            self.refresh_ui(ui);
             ui.label(format!(
                 "[{}/{} users]",
-                self.user_idx + 1,
-                self.users.len()
+                self.position(),
+                self.visible_count()
             ));
+            if let Some(dropped) = self.recheck_dropped {
+                ui.label(RichText::new(format!("{} users dropped off", dropped)).color(theme.foam))
+                    .on_hover_text("Users that passed the last location re-check");
+            }
+
+            let max_score = self.users.iter().map(|u| u.score).max().unwrap_or(0);
+            let slider = ui
+                .add(egui::Slider::new(&mut self.min_score, 0..=max_score).text("min score"))
+                .on_hover_text("Hide users scoring below this");
+            if slider.changed() {
+                self.snap_to_visible();
+                self.store.set_min_score(self.min_score);
+            }
+            let below = self.below_threshold_count();
+            if below > 0 {
+                let state = if self.show_hidden { "shown" } else { "hidden" };
+                ui.label(format!("{} users below threshold {}", below, state));
+                let toggle = ui
+                    .checkbox(&mut self.show_hidden, "Show after queue")
+                    .on_hover_text("Review users below the threshold once the rest are done");
+                if toggle.changed() {
+                    self.snap_to_visible();
+                }
+            }
+
+            let search = ui
+                .add(
+                    TextEdit::singleline(&mut self.search)
+                        .hint_text("Search users, IPs, ASNs, locations")
+                        .desired_width(250.0),
+                )
+                .on_hover_text("Searches every user, not just the current one");
+            if search.changed() {
+                self.search();
+            }
+            if !self.search.trim().is_empty() {
+                if self.matches.is_empty() {
+                    ui.label(RichText::new("No matches").color(theme.love));
+                } else {
+                    ui.label(format!("{}/{}", self.match_idx + 1, self.matches.len()));
+                    let cycle = search.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter));
+                    if ui.button("Next").clicked() || cycle {
+                        self.match_idx = (self.match_idx + 1) % self.matches.len();
+                        self.jump_to_match();
+                    }
+                }
+            }
+
             ui.add(ProgressBar::new(self.progress()).show_percentage());
         });
     }
@@ -707,8 +2319,11 @@ impl MainUi {
 
 impl View for MainUi {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) -> DuplexAction {
+        self.auto_refresh(ctx);
         if self.users.is_empty() {
-            ui.heading("No users to check");
+            ui.heading("No flagged users found");
+            ui.horizontal(|ui| self.refresh_ui(ui));
+            super::copy_searches_button(ui, &self.searches, self.store.redact());
             if ui.button("Rerun").clicked() {
                 self.action = Some(DuplexAction::Reset);
             }
@@ -716,19 +2331,30 @@ impl View for MainUi {
             return DuplexAction::None;
         }
 
-        if let Some(more_logs) = &self.more_logs {
-            if more_logs.0.is_finished() {
-                if let Some((rx, i)) = self.more_logs.take() {
-                    if let Some(logins) = rx.join().expect("Couldn't get more logs from thread") {
-                        for login in logins {
-                            if !self.users[i].logins.contains(&login) {
-                                self.users[i].logins.push(login);
-                            }
+        let (done, pending) = std::mem::take(&mut self.more_logs)
+            .into_iter()
+            .partition(|f| f.rx.is_finished());
+        self.more_logs = pending;
+        for fetch in done {
+            self.more_logs_done(fetch);
+        }
+        if !self.more_logs.is_empty() {
+            // Nothing is waiting on these, just check back for them
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
+        if let Some((rx, _)) = &self.admin_events {
+            if rx.is_finished() {
+                if let Some((rx, i)) = self.admin_events.take() {
+                    match rx.join().expect("Couldn't get admin events from thread") {
+                        Ok(events) => {
+                            self.users[i].admin_events = events;
+                            self.store.rescore_user(&mut self.users[i]);
+                            self.admin_events_error = None;
                         }
-                        self.users[i].logins.sort();
+                        Err(e) => self.admin_events_error = Some(e),
                     }
                 }
-                self.more_logs = None;
             } else {
                 ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
                 std::thread::sleep(std::time::Duration::from_millis(10));
@@ -736,18 +2362,79 @@ impl View for MainUi {
             }
         }
 
-        StripBuilder::new(ui)
-            .sizes(Size::exact(20.0), 3)
+        if self.export_rx.as_ref().is_some_and(|rx| rx.is_finished()) {
+            self.export_rx = None;
+        }
+
+        if let Some((rx, _)) = &self.recheck {
+            if rx.is_finished() {
+                if let Some((rx, before)) = self.recheck.take() {
+                    let users = rx.join().expect("Couldn't get users from thread");
+                    self.recheck_done(users, before);
+                }
+                if self.users.is_empty() {
+                    return DuplexAction::None;
+                }
+            } else {
+                ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                ctx.request_repaint(); // Call repaint to re-check if the thread is finished
+            }
+        }
+
+        if self.overview {
+            self.overview_ui(ui);
+            return self.action.take().unwrap_or(DuplexAction::None);
+        }
+
+        let show_map = self.show_map;
+        let mut strip = StripBuilder::new(ui).sizes(Size::exact(20.0), 5);
+        if show_map {
+            strip = strip.size(Size::relative(0.4).at_least(150.0));
+        }
+        strip
             .size(Size::remainder().at_least(100.0))
             .vertical(|mut strip| {
                 strip.cell(|ui| self.progress_bar(ui));
                 strip.cell(|ui| self.top_bar(ui));
                 strip.cell(|ui| self.hdtools_bar(ui));
+                strip.cell(|ui| super::activity_strip(ui, &self.cur_user().activity_summary()));
+                strip.cell(|ui| {
+                    let summary = self.cur_user().location_summary();
+                    super::location_chips(ui, &summary, &mut self.location_filter);
+                });
+                if show_map {
+                    strip.cell(|ui| {
+                        let zone = self.store.display_zone();
+                        let redact = self.store.redact();
+                        if let Some(i) =
+                            super::travel_map::travel_map(ui, self.cur_user(), zone, redact)
+                        {
+                            // The login might be filtered out of the table
+                            self.location_filter = None;
+                            self.scroll_to = Some(i);
+                        }
+                    });
+                }
                 strip.cell(|ui| self.table(ui));
             });
         if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
             self.handle_keypresses(ctx);
         }
+        let template_keys: Vec<(String, String)> = Template::ALL
+            .iter()
+            .map(|t| (format!("then {}", key_name(t.key())), t.to_string()))
+            .collect();
+        let mut extra = vec![("Enter", "Next search match")];
+        extra.extend(template_keys.iter().map(|(k, d)| (k.as_str(), d.as_str())));
+        keymap::help_overlay(
+            ctx,
+            &self.store.keymap(),
+            "Duplex",
+            &extra,
+            &mut self.show_help,
+        );
+        self.raw_event.show(ctx);
 
         self.action.take().unwrap_or(DuplexAction::None)
     }
@@ -755,6 +2442,19 @@ impl View for MainUi {
     fn store(&self) -> &Rc<Store> {
         &self.store
     }
+
+    /// A run that flagged nobody has nothing to work through
+    fn idle(&self) -> bool {
+        self.users.is_empty() && self.refresh.is_none()
+    }
+
+    /// Users left in the queue, counting the current one
+    fn badge(&self) -> Option<Badge> {
+        let left = (self.user_idx..self.users.len())
+            .filter(|&i| self.visible(i) && !self.users[i].investigated)
+            .count();
+        (left > 0).then_some(Badge::Count(left))
+    }
 }
 
 // -------------------- Completed Ui --------------------
@@ -762,25 +2462,181 @@ impl View for MainUi {
 pub struct DoneUi {
     pub store: Rc<Store>,
     action: Option<DuplexAction>,
-    investigations: usize,
-    tx: Option<JoinHandle<Option<()>>>,
-    failed: bool,
+    stats: QueueStats,
+    /// Users reviewed and the range they were flagged in, for the run report
+    users: Vec<User>,
+    range: TimeSpan,
+    tx: Option<JoinHandle<Result<(), QueryError>>>,
+    error: Option<QueryError>,
+    /// File the run report is saved to
+    report_file: String,
+    report_rx: Option<JoinHandle<Result<(), String>>>,
+    /// Whether the last report was saved, or why it wasn't
+    report_result: Option<Result<(), String>>,
 }
 
 impl DoneUi {
-    pub fn new(store: Rc<Store>, investigations: usize) -> Self {
+    pub fn new(store: Rc<Store>, stats: QueueStats, users: Vec<User>, range: TimeSpan) -> Self {
         Self {
             store,
             action: None,
-            investigations,
+            stats,
+            users,
+            range,
             tx: None,
-            failed: false,
+            error: None,
+            report_file: "duplex.html".to_owned(),
+            report_rx: None,
+            report_result: None,
+        }
+    }
+
+    /// Menu saving the run report, see [super::report]
+    fn report_menu(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        if self.report_rx.as_ref().is_some_and(|rx| rx.is_finished()) {
+            let rx = self.report_rx.take().expect("Failed to take run report");
+            self.report_result = Some(rx.join().expect("Couldn't get run report from thread"));
+        } else if self.report_rx.is_some() {
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
+        // Names users and IPs like the exports
+        ui.add_enabled_ui(!self.store.redact().0, |ui| {
+            ui.menu_button("Generate report", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.report_file);
+                });
+                ui.add_enabled_ui(self.report_rx.is_none(), |ui| {
+                    if ui.button("Save").clicked() {
+                        let report = super::report::run_report(
+                            &self.users,
+                            &self.stats,
+                            &self.range,
+                            self.store.display_zone(),
+                        );
+                        self.report_result = None;
+                        self.report_rx = Some(
+                            self.store
+                                .save_run_report(self.report_file.to_owned(), report),
+                        );
+                        ui.close_menu();
+                    }
+                });
+            })
+            .response
+            .on_hover_text("Save the flagged users and their logins as a web page for sharing")
+            .on_disabled_hover_text("Reports aren't redacted, turn off redaction to save one");
+        });
+        if self.report_rx.is_some() {
+            ui.spinner();
         }
+        match &self.report_result {
+            Some(Ok(())) => {
+                ui.label(RichText::new("Saved report").color(theme.foam));
+            }
+            Some(Err(e)) => {
+                ui.label(RichText::new(format!("Report failed: {}", e)).color(theme.love));
+            }
+            None => (),
+        }
+    }
+
+    fn investigations(&self) -> usize {
+        self.stats.investigated + self.stats.skipped
+    }
+
+    /// Table of [QueueStats], clicking a value copies it
+    fn stats_grid(&self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let stats = &self.stats;
+        let mut rows = vec![
+            (
+                "Investigations".to_owned(),
+                self.investigations().to_string(),
+            ),
+            (
+                "Marked investigated".to_owned(),
+                stats.investigated.to_string(),
+            ),
+            ("Skipped".to_owned(), stats.skipped.to_string()),
+            ("Highest score".to_owned(), stats.highest_score.to_string()),
+            ("Elapsed".to_owned(), stats.elapsed_text()),
+        ];
+        rows.extend(
+            stats
+                .reasons
+                .iter()
+                .map(|(r, c)| (r.to_string(), c.to_string())),
+        );
+
+        egui::Grid::new("queue_stats").striped(true).show(ui, |ui| {
+            for (name, value) in &rows {
+                ui.label(name);
+                copy_label(ui, value);
+                ui.end_row();
+            }
+            if !stats.countries.is_empty() {
+                ui.label(RichText::new("Top countries").color(theme.pine));
+                ui.end_row();
+                for (country, count) in &stats.countries {
+                    ui.label(country);
+                    copy_label(ui, &count.to_string());
+                    ui.end_row();
+                }
+            }
+        });
+    }
+}
+
+/// Buttons sending `user` to the other apps, returns the app clicked and its message
+fn open_in_buttons(ui: &mut egui::Ui, user: &str) -> Option<(&'static str, PanelMessage)> {
+    let mut message = None;
+    if ui.button("Open in Simplex").clicked() {
+        message = Some((Simplex::NAME, PanelMessage::SetUser(user.to_owned())));
+    }
+    if ui.button("Open in Visor").clicked() {
+        message = Some((Visor::NAME, PanelMessage::SetUser(user.to_owned())));
+    }
+    if ui.button("Open in Sonar").clicked() {
+        message = Some((Sonar::NAME, PanelMessage::SetLookup(user.to_owned())));
+    }
+    if message.is_some() {
+        ui.close_menu();
+    }
+    message
+}
+
+/// How long before the login [User::account_age] measured from the account was created
+fn age_text(age: chrono::Duration, flagged: bool) -> String {
+    let login = if flagged {
+        "first flagged login"
+    } else {
+        "latest login"
+    };
+    let days = |d: i64| format!("{} day{}", d, if d == 1 { "" } else { "s" });
+    match age.num_days() {
+        d if d < 0 => format!("{} after {}", days(-d), login),
+        0 => format!("same day as {}", login),
+        d => format!("{} before {}", days(d), login),
+    }
+}
+
+/// Label that copies its text when clicked
+fn copy_label(ui: &mut egui::Ui, text: &str) {
+    let label = ui
+        .add(Label::new(text).sense(egui::Sense::click()))
+        .on_hover_text("Click to copy");
+    if label.clicked() {
+        ui.output_mut(|o| o.copied_text = text.to_owned());
     }
 }
 
 impl View for DoneUi {
     fn ui(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) -> DuplexAction {
+        let theme = color::theme(ui.ctx());
         if let Some(tx) = &self.tx {
             if tx.is_finished() {
                 let resp = self
@@ -790,10 +2646,10 @@ impl View for DoneUi {
                     .join()
                     .expect("Couldn't join post_osiris thread");
                 match resp {
-                    None => self.failed = true,
-                    Some(()) => {
+                    Err(e) => self.error = Some(e),
+                    Ok(()) => {
                         self.tx = None;
-                        self.failed = false
+                        self.error = None
                     }
                 }
             } else {
@@ -804,19 +2660,14 @@ impl View for DoneUi {
         }
         ui.vertical(|ui| {
             ui.heading("🎉 Yeehaw! You're done 🎉");
+            self.stats_grid(ui);
             ui.horizontal(|ui| {
-                ui.label("Investigations");
-                let investigations = ui.add(
-                    egui::Label::new(self.investigations.to_string()).sense(egui::Sense::click()),
-                );
-                if investigations.clicked() {
-                    ui.output_mut(|o| o.copied_text = self.investigations.to_string());
+                if ui.button("Copy stats").clicked() {
+                    ui.output_mut(|o| o.copied_text = self.stats.to_string());
                 }
-            });
-            ui.horizontal(|ui| {
                 if ui.button("Send to Osiris").clicked() {
                     let data = osiris::Data {
-                        investigations: vec![("Duo".to_owned(), self.investigations as i64)],
+                        investigations: vec![("Duo".to_owned(), self.investigations() as i64)],
                         incidents: vec![],
                     };
 
@@ -825,10 +2676,17 @@ impl View for DoneUi {
                             .post_osiris(chrono::Local::now().date_naive(), data),
                     );
                 }
+                self.report_menu(ui);
                 if ui.button("Rerun duplex").clicked() {
                     self.action = Some(DuplexAction::Reset);
                 }
             });
+            schedule_ui(ui, &self.store);
+            if let Some(e) = &self.error {
+                ui.label(
+                    RichText::new(format!("Couldn't send to Osiris: {}", e)).color(theme.love),
+                );
+            }
         });
 
         self.action.take().unwrap_or(DuplexAction::None)
@@ -837,4 +2695,42 @@ impl View for DoneUi {
     fn store(&self) -> &Rc<Store> {
         &self.store
     }
+
+    fn idle(&self) -> bool {
+        self.tx.is_none() && self.report_rx.is_none()
+    }
+}
+
+/// Turns scheduled runs on and off and sets how often they run, with when the next one is due.
+/// Shown where Duplex sits idle, see [Duplex::schedule].
+fn schedule_ui(ui: &mut egui::Ui, store: &Store) {
+    ui.horizontal(|ui| {
+        let mut schedule = store.schedule();
+        if ui
+            .checkbox(&mut schedule, "Scheduled run every")
+            .on_hover_text("While Duplex waits here or on the final screen, run over everything since the last run finished.  The users are queued without opening the window")
+            .changed()
+        {
+            store.set_schedule(schedule);
+        }
+        let mut minutes = store.schedule_minutes();
+        ui.add_enabled(
+            schedule,
+            egui::DragValue::new(&mut minutes)
+                .clamp_range(SCHEDULE_MINUTES)
+                .suffix(" min"),
+        );
+        if minutes != store.schedule_minutes() {
+            store.set_schedule_minutes(minutes);
+        }
+        if schedule {
+            let next = store.last_run_end().map_or_else(chrono::Utc::now, |end| {
+                (end + chrono::Duration::minutes(minutes)).max(chrono::Utc::now())
+            });
+            ui.label(format!(
+                "next at {}",
+                store.display_zone().to_zone(next).format(TIME_FMT)
+            ));
+        }
+    });
 }
@@ -0,0 +1,254 @@
+//! Ignored users
+//!
+//! Lists every user marked investigated in Duplex that will stay hidden, along with who ignored
+//! them and why, so suppression can be undone without finding the user again.  Every ignore and
+//! un-ignore is also kept in a history that never expires, for looking back at who ignored a user.
+use super::color;
+use crate::storage::{IgnoreEvent, Investigated};
+use crate::store::Store;
+use egui::RichText;
+use egui_extras::Column;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// How often the list is pulled from the disk cache while open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct Ignored {
+    store: Rc<Store>,
+    users: Vec<Investigated>,
+    /// When [users](Self::users) was last pulled
+    last_refresh: Option<Instant>,
+    history_open: bool,
+    history: Vec<IgnoreEvent>,
+    /// Only history of users whose name contains this is shown
+    history_filter: String,
+}
+
+impl Ignored {
+    pub fn new(store: Rc<Store>) -> Self {
+        Self {
+            store,
+            users: vec![],
+            last_refresh: None,
+            history_open: false,
+            history: vec![],
+            history_filter: String::new(),
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.users = self.store.list_investigated();
+        if self.history_open {
+            self.history = self.store.investigated_history(&self.history_filter);
+        }
+        self.last_refresh = Some(Instant::now());
+    }
+}
+
+impl super::panels::Panel for Ignored {
+    fn name(&self) -> &'static str {
+        "🔕Ignored"
+    }
+
+    fn desc(&self) -> &'static str {
+        "Users Duplex won't show for now"
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        if !*open {
+            // Pull a fresh list as soon as it is opened again
+            self.last_refresh = None;
+            return None;
+        }
+        if self
+            .last_refresh
+            .is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL)
+        {
+            self.refresh();
+        }
+
+        let rect = super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .default_size(egui::vec2(500.0, 300.0))
+                .vscroll(false),
+            place,
+            |ui| {
+                self.ui(ui);
+            },
+        );
+        self.history_window(ctx);
+        rect
+    }
+}
+
+impl Ignored {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        ui.horizontal(|ui| {
+            ui.label(format!("{} users ignored", self.users.len()));
+            if ui.button("Refresh").clicked() {
+                self.refresh();
+            }
+            ui.add_enabled_ui(!self.users.is_empty(), |ui| {
+                ui.menu_button("Clear all", |ui| {
+                    ui.label(
+                        RichText::new("Every user here will show up in the next Duplex run")
+                            .color(theme.love),
+                    );
+                    if ui.button("Confirm").clicked() {
+                        self.store.clear_investigated();
+                        self.refresh();
+                        ui.close_menu();
+                    }
+                });
+            });
+            if ui
+                .button("History")
+                .on_hover_text("Every ignore and un-ignore, with who did it")
+                .clicked()
+            {
+                self.history_open = !self.history_open;
+                self.refresh();
+            }
+        });
+        ui.separator();
+
+        let mut unignore = None;
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .resizable(true)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .columns(Column::auto(), 4)
+            .column(Column::remainder())
+            .column(Column::exact(70.0))
+            .header(20.0, |mut header| {
+                for title in ["User", "Ignored", "By", "Remaining", "Note", ""] {
+                    header.col(|ui| {
+                        ui.label(RichText::new(title).color(theme.pine));
+                    });
+                }
+            })
+            .body(|body| {
+                let zone = self.store.display_zone();
+                let redact = self.store.redact();
+                body.rows(20.0, self.users.len(), |i, mut row| {
+                    let user = &self.users[i];
+                    row.col(|ui| {
+                        ui.label(redact.user(&user.name));
+                    });
+                    row.col(|ui| {
+                        let time = user.time.with_timezone(&chrono::Utc);
+                        ui.label(format!(
+                            "{} {}",
+                            zone.to_zone(time).format("%T %D"),
+                            zone.abbreviation(time)
+                        ));
+                    });
+                    row.col(|ui| {
+                        ui.label(&user.analyst);
+                    });
+                    row.col(|ui| {
+                        let remaining = user.remaining();
+                        ui.label(format!(
+                            "{}h {:02}m",
+                            remaining.num_hours(),
+                            remaining.num_minutes() % 60
+                        ));
+                    });
+                    row.col(|ui| {
+                        ui.label(&user.note).on_hover_text(&user.note);
+                    });
+                    row.col(|ui| {
+                        if ui.button("Un-ignore").clicked() {
+                            unignore = Some(i);
+                        }
+                    });
+                });
+            });
+
+        if let Some(i) = unignore {
+            let user = self.users.remove(i);
+            self.store.mark_investigated(user.name, false, None);
+            self.refresh();
+        }
+    }
+
+    /// Read only list of recent ignores and un-ignores, filtered by user
+    fn history_window(&mut self, ctx: &egui::Context) {
+        let theme = color::theme(ctx);
+        let mut open = self.history_open;
+        egui::Window::new(RichText::new("Ignore history").color(theme.gold))
+            .open(&mut open)
+            .default_size(egui::vec2(550.0, 300.0))
+            .vscroll(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let filter = ui.add(
+                        egui::TextEdit::singleline(&mut self.history_filter)
+                            .hint_text("User")
+                            .desired_width(150.0),
+                    );
+                    if filter.changed() {
+                        self.history = self.store.investigated_history(&self.history_filter);
+                    }
+                    ui.label(format!("{} entries", self.history.len()));
+                });
+                ui.separator();
+
+                egui_extras::TableBuilder::new(ui)
+                    .striped(true)
+                    .resizable(true)
+                    .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                    .columns(Column::auto(), 4)
+                    .column(Column::remainder())
+                    .header(20.0, |mut header| {
+                        for title in ["Time", "User", "", "By", "Note"] {
+                            header.col(|ui| {
+                                ui.label(RichText::new(title).color(theme.pine));
+                            });
+                        }
+                    })
+                    .body(|body| {
+                        let zone = self.store.display_zone();
+                        let redact = self.store.redact();
+                        body.rows(20.0, self.history.len(), |i, mut row| {
+                            let event = &self.history[i];
+                            row.col(|ui| {
+                                let time = event.time.with_timezone(&chrono::Utc);
+                                ui.label(format!(
+                                    "{} {}",
+                                    zone.to_zone(time).format("%T %D"),
+                                    zone.abbreviation(time)
+                                ));
+                            });
+                            row.col(|ui| {
+                                ui.label(redact.user(&event.name));
+                            });
+                            row.col(|ui| {
+                                if event.ignored {
+                                    ui.label("Ignored");
+                                } else {
+                                    ui.label(RichText::new("Un-ignored").color(theme.foam));
+                                }
+                            });
+                            row.col(|ui| {
+                                ui.label(&event.analyst);
+                            });
+                            row.col(|ui| {
+                                ui.label(&event.note).on_hover_text(&event.note);
+                            });
+                        });
+                    });
+            });
+        self.history_open = open;
+    }
+}
@@ -0,0 +1,57 @@
+//! Shortcut input and help
+//!
+//! What the shortcuts are and how they're saved is in [prefs::keymap](crate::prefs::keymap), this
+//! is checking them against egui's input and listing them for a panel.
+use super::color;
+use crate::prefs::keymap::{Action, Keymap};
+use egui::RichText;
+
+impl Keymap {
+    pub fn pressed(&self, input: &egui::InputState, action: Action) -> bool {
+        self.keys(action).iter().any(|&k| input.key_pressed(k))
+    }
+}
+
+/// Whether `?` was typed, which toggles the shortcut help
+pub fn help_pressed(input: &egui::InputState) -> bool {
+    input
+        .events
+        .iter()
+        .any(|e| matches!(e, egui::Event::Text(t) if t == "?"))
+}
+
+/// Lists a panel's shortcuts in a small window
+pub fn help_overlay(
+    ctx: &egui::Context,
+    keymap: &Keymap,
+    panel: &str,
+    extra: &[(&str, &str)],
+    open: &mut bool,
+) {
+    let theme = color::theme(ctx);
+    egui::Window::new(RichText::new(format!("{} shortcuts", panel)).color(theme.gold))
+        .id(egui::Id::new(("shortcut_help", panel)))
+        .open(open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            egui::Grid::new(("shortcut_grid", panel))
+                .striped(true)
+                .show(ui, |ui| {
+                    for action in Action::for_panel(panel) {
+                        ui.label(RichText::new(keymap.describe(action)).color(theme.pine));
+                        ui.label(action.to_string());
+                        ui.end_row();
+                    }
+                    for (keys, desc) in extra {
+                        ui.label(RichText::new(*keys).color(theme.pine));
+                        ui.label(*desc);
+                        ui.end_row();
+                    }
+                    ui.label(RichText::new("?").color(theme.pine));
+                    ui.label("Toggle this help");
+                    ui.end_row();
+                });
+            ui.label("Rebind shortcuts in Settings");
+        });
+}
@@ -1,8 +1,14 @@
 //! Login page for HORUS
 //!
 //! HORUS will check credentials upon login and will refuse if they are invalid.  The analyst_name
-//! is used for Cherwell ticket templates and cannot be changed after logging in.
-use crate::{app::color, storage::Storage};
+//! is used for Cherwell ticket templates and cannot be changed after logging in.  Settings are
+//! loaded from the profile for the Splunk username.
+use crate::{
+    app::color,
+    prefs::ThemeKind,
+    queries::{hdtools::HDToolsConfig, splunk::IndexConfig, QueryError},
+    storage::Storage,
+};
 use egui::{RichText, TextEdit};
 
 pub struct LoginUI {
@@ -10,7 +16,16 @@ pub struct LoginUI {
     username: String,
     password: String,
     shibsession: [String; 2],
+    /// Splunk indexes searched, for deployments other than Clemson's
+    index_config: IndexConfig,
+    /// Where HDTools lives, for deployments other than Clemson's
+    hdtools_config: HDToolsConfig,
     analyst_name: String,
+    /// Splunk usernames with saved settings
+    profiles: Vec<String>,
+    /// UI scale from the profile, see [UI_SCALE](crate::prefs::UI_SCALE)
+    ui_scale: f32,
+    theme: ThemeKind,
     issue: Option<String>,
     action: Option<super::StateUIAction>,
 }
@@ -21,10 +36,26 @@ impl super::StateUIVariant for LoginUI {
         self.handle_keypresses(ctx);
         self.action.take().unwrap_or(super::StateUIAction::None)
     }
+
+    fn ui_scale(&self) -> f32 {
+        self.ui_scale
+    }
+
+    fn set_ui_scale(&mut self, scale: f32) {
+        self.ui_scale = scale;
+        if let Some(storage) = &self.storage {
+            storage.set_ui_scale(scale);
+        }
+    }
+
+    fn theme(&self) -> ThemeKind {
+        self.theme
+    }
 }
 
 impl LoginUI {
     fn ui(&mut self, ui: &mut egui::Ui) -> egui::Response {
+        let theme = color::theme(ui.ctx());
         let available = ui.available_size();
 
         ui.centered_and_justified(|ui| {
@@ -83,7 +114,7 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
                 `"""""""Y888888888888888888888P"""""""'"#,
                     )
                     .size(20.0)
-                    .color(color::MUTED)
+                    .color(theme.muted)
                     .monospace(),
                 )
                 .wrap(false),
@@ -103,29 +134,44 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
             ui.painter(),
             paint_rect,
             ui.visuals().window_fill,
-            egui::Stroke::new(1.0, color::HIGHLIGHT_HIGH),
+            egui::Stroke::new(1.0, theme.highlight_high),
             12.0,
             egui::epaint::Shadow::big_dark(),
         );
 
         let response = ui.allocate_ui_at_rect(center, |ui| {
-            ui.vertical_centered(|ui| ui.heading(RichText::new("👁HORUS").color(color::GOLD)));
+            ui.vertical_centered(|ui| ui.heading(RichText::new("👁HORUS").color(theme.gold)));
 
             ui.style_mut()
                 .visuals
                 .widgets
                 .noninteractive
                 .bg_stroke
-                .color = color::IRIS;
+                .color = theme.iris;
             ui.separator();
 
-            ui.label("Splunk credentials");
             ui.horizontal(|ui| {
-                ui.add(
-                    TextEdit::singleline(&mut self.username)
-                        .desired_width(100.0)
-                        .hint_text("username"),
-                );
+                ui.label("Splunk credentials");
+                ui.add_enabled_ui(!self.profiles.is_empty(), |ui| {
+                    ui.menu_button("👤", |ui| self.profile_menu(ui))
+                        .response
+                        .on_hover_text("Saved profiles");
+                });
+                ui.menu_button("⚙", |ui| self.splunk_indexes(ui))
+                    .response
+                    .on_hover_text("Splunk indexes");
+            });
+            ui.horizontal(|ui| {
+                if ui
+                    .add(
+                        TextEdit::singleline(&mut self.username)
+                            .desired_width(100.0)
+                            .hint_text("username"),
+                    )
+                    .changed()
+                {
+                    self.load_profile();
+                }
                 ui.add(
                     TextEdit::singleline(&mut self.password)
                         .desired_width(100.0)
@@ -136,7 +182,12 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
 
             ui.add_space(7.0);
 
-            ui.label("HDTools shibsession cookie (optional)");
+            ui.horizontal(|ui| {
+                ui.label("HDTools shibsession cookie (optional)");
+                ui.menu_button("⚙", |ui| self.hdtools_urls(ui))
+                    .response
+                    .on_hover_text("HDTools URLs");
+            });
             ui.horizontal(|ui| {
                 ui.add(
                     TextEdit::singleline(&mut self.shibsession[0])
@@ -171,13 +222,114 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
             });
 
             if let Some(issue) = &self.issue {
-                ui.vertical_centered(|ui| ui.label(RichText::new(issue).color(color::LOVE)));
+                ui.vertical_centered(|ui| ui.label(RichText::new(issue).color(theme.love)));
             }
         });
 
         response.response
     }
 
+    /// Switches to or deletes a saved profile
+    fn profile_menu(&mut self, ui: &mut egui::Ui) {
+        let mut select = None;
+        let mut delete = None;
+        egui::Grid::new("profiles").show(ui, |ui| {
+            for profile in &self.profiles {
+                if ui
+                    .selectable_label(*profile == self.username, profile)
+                    .clicked()
+                {
+                    select = Some(profile.to_owned());
+                    ui.close_menu();
+                }
+                if ui
+                    .button("🗑")
+                    .on_hover_text("Forget this profile's settings and layout")
+                    .clicked()
+                {
+                    delete = Some(profile.to_owned());
+                }
+                ui.end_row();
+            }
+        });
+
+        if let Some(profile) = select {
+            self.username = profile;
+            self.load_profile();
+        }
+        if let Some(profile) = delete {
+            let storage = self.storage.as_ref().expect("Failed to get storage");
+            storage.delete_profile(&profile);
+            self.profiles = storage.list_profiles();
+            if profile == self.username {
+                self.analyst_name.clear();
+            }
+        }
+    }
+
+    /// Fills in the analyst name when the username has a saved profile
+    fn load_profile(&mut self) {
+        if !self.profiles.contains(&self.username) {
+            return;
+        }
+        let storage = self.storage.as_mut().expect("Failed to get storage");
+        storage.set_profile(&self.username);
+        self.analyst_name = storage.get_analyst_name();
+        self.ui_scale = storage.get_ui_scale();
+        self.theme = storage.get_theme();
+    }
+
+    fn splunk_indexes(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        egui::Grid::new("splunk_indexes").show(ui, |ui| {
+            let config = &mut self.index_config;
+            for (name, index, hover) in [
+                ("Duo", &mut config.duo, "Duo authentications"),
+                ("ISE", &mut config.ise, "VPN sessions and MAC addresses"),
+                ("DHCP", &mut config.dhcp, "IP and MAC leases"),
+                ("Cisco", &mut config.cisco, "Which user had an IP"),
+            ] {
+                ui.label(name).on_hover_text(hover);
+                ui.add(TextEdit::singleline(index).desired_width(150.0));
+                ui.end_row();
+            }
+        });
+        if let Err(e) = self.index_config.validate() {
+            ui.label(RichText::new(e).color(theme.love));
+        }
+        if ui.button("Reset").clicked() {
+            self.index_config = IndexConfig::default();
+        }
+    }
+
+    fn hdtools_urls(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        egui::Grid::new("hdtools_urls").show(ui, |ui| {
+            let config = &mut self.hdtools_config;
+            for (name, url, hover) in [
+                (
+                    "Base",
+                    &mut config.base,
+                    "Only loads with a good shibsession",
+                ),
+                ("Lookup", &mut config.lookup, "Username is appended"),
+                ("Person", &mut config.person, "zid is appended"),
+                ("Student", &mut config.student, "zid is appended"),
+                ("Employee", &mut config.employee, "zid is appended"),
+            ] {
+                ui.label(name).on_hover_text(hover);
+                ui.add(TextEdit::singleline(url).desired_width(250.0));
+                ui.end_row();
+            }
+        });
+        if let Err(e) = self.hdtools_config.validate() {
+            ui.label(RichText::new(e).color(theme.love));
+        }
+        if ui.button("Reset").clicked() {
+            self.hdtools_config = HDToolsConfig::default();
+        }
+    }
+
     fn action_login(&mut self) {
         if self.username.is_empty() {
             self.issue = Some("Username is empty".to_owned());
@@ -187,35 +339,65 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
             return;
         }
 
-        let hdtools = if !self.shibsession.iter().any(|s| s.is_empty()) {
+        if let Err(e) = self.index_config.validate() {
+            self.issue = Some(e);
+            return;
+        }
+
+        let use_hdtools = !self.shibsession.iter().any(|s| s.is_empty());
+        if use_hdtools {
+            if let Err(e) = self.hdtools_config.validate() {
+                self.issue = Some(e);
+                return;
+            }
+        }
+
+        let storage = self.storage.as_mut().expect("Failed to get storage");
+        storage.set_profile(&self.username);
+        storage.set_username(self.username.to_owned());
+        storage.set_analyst_name(self.analyst_name.to_owned());
+        storage.set_hdtools_config(&self.hdtools_config);
+        storage.set_index_config(&self.index_config);
+        let timeouts = storage.get_timeouts();
+
+        let hdtools = if use_hdtools {
             let shib = self.shibsession.join("=");
+            let config = self.hdtools_config.clone();
             Some(std::thread::spawn(move || {
-                crate::queries::hdtools::HDTools::new(shib)
+                crate::queries::hdtools::HDTools::new(shib, config, timeouts.hdtools)
             }))
         } else {
             None
         };
 
-        let storage = self.storage.as_mut().expect("Failed to get storage");
-        storage.set_username(self.username.to_owned());
-        storage.set_analyst_name(self.analyst_name.to_owned());
-
-        let splunk = match crate::queries::splunk::Splunk::new(&self.username, Some(&self.password))
-        {
-            Some(s) => s,
-            None => {
+        let splunk = match crate::queries::splunk::Splunk::new(
+            &self.username,
+            Some(&self.password),
+            self.index_config.clone(),
+            timeouts.splunk,
+        ) {
+            Ok(s) => s,
+            Err(QueryError::Auth) => {
                 self.issue = Some("Invalid Splunk creds".to_owned());
                 return;
             }
+            Err(e) => {
+                self.issue = Some(format!("Couldn't reach Splunk: {}", e));
+                return;
+            }
         };
 
         let hdtools = match hdtools {
             Some(j) => match j.join().expect("Failed to join with hdtools thread") {
-                Some(hdtools) => Some(hdtools),
-                None => {
+                Ok(hdtools) => Some(hdtools),
+                Err(QueryError::Auth) => {
                     self.issue = Some("Invalid shibsession".to_owned());
                     return;
                 }
+                Err(e) => {
+                    self.issue = Some(format!("Couldn't reach HDTools: {}", e));
+                    return;
+                }
             },
             None => None,
         };
@@ -229,7 +411,9 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
             self.analyst_name.to_owned(),
         );
 
-        self.action = Some(super::StateUIAction::Login { store });
+        self.action = Some(super::StateUIAction::Login {
+            store: Box::new(store),
+        });
     }
 
     fn handle_keypresses(&mut self, ctx: &egui::Context) {
@@ -241,11 +425,20 @@ Ya<>-<>-<>-<>-<8::::::::::::8 8:::::::::8 8::::::::::::8>-<>-<>-<>-<>aP
 
 impl Default for LoginUI {
     fn default() -> Self {
-        let storage = Storage::load();
+        let mut storage = Storage::load();
+        let username = storage.get_username();
+        if !username.is_empty() {
+            storage.set_profile(&username);
+        }
         LoginUI {
-            username: storage.get_username(),
+            profiles: storage.list_profiles(),
+            ui_scale: storage.get_ui_scale(),
+            theme: storage.get_theme(),
+            username,
             password: "".to_owned(),
             shibsession: ["".to_owned(), "".to_owned()],
+            index_config: storage.get_index_config(),
+            hdtools_config: storage.get_hdtools_config(),
             analyst_name: storage.get_analyst_name(),
             storage: Some(storage),
             issue: None,
@@ -0,0 +1,249 @@
+//! Logs
+//!
+//! HORUS logs a lot, but only to a terminal with `RUST_LOG` set, which most analysts never see.
+//! Records at info and above are also kept here, newest first, so a failed query or a bad IP
+//! database can be looked into without restarting from a terminal.  The terminal still gets
+//! whatever `RUST_LOG` asks for.
+use super::color;
+use crate::store::Store;
+use crate::user::zone::Zone;
+use egui::RichText;
+use egui_extras::Column;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Records kept for the panel, older ones are dropped
+pub(super) const RECENT: usize = 1000;
+/// Lowest level kept whatever `RUST_LOG` says.  Debug records are chatty and include parsed
+/// HDTools records.
+const KEPT: log::LevelFilter = log::LevelFilter::Info;
+/// How often the records are pulled while open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// A logged message
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    pub time: chrono::DateTime<chrono::Utc>,
+    pub level: log::Level,
+    /// Module that logged it
+    pub target: String,
+    pub message: String,
+}
+
+/// The most recent [RECENT] records
+#[derive(Default)]
+pub(super) struct LogBuffer {
+    records: Mutex<VecDeque<LogRecord>>,
+}
+
+impl LogBuffer {
+    pub(super) fn push(&self, record: LogRecord) {
+        let mut records = self.records.lock().expect("Failed to get log lock");
+        if records.len() == RECENT {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    /// Records at `level` or worse, newest first
+    pub(super) fn recent(&self, level: log::Level) -> Vec<LogRecord> {
+        let records = self.records.lock().expect("Failed to get log lock");
+        records
+            .iter()
+            .rev()
+            .filter(|r| r.level <= level)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Sends records to env_logger and keeps them in a [LogBuffer]
+struct Logger {
+    terminal: env_logger::Logger,
+    buffer: LogBuffer,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= KEPT || self.terminal.enabled(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        // env_logger checks RUST_LOG itself
+        self.terminal.log(record);
+        if record.level() <= KEPT {
+            self.buffer.push(LogRecord {
+                time: chrono::Utc::now(),
+                level: record.level(),
+                target: record.target().to_owned(),
+                message: record.args().to_string(),
+            });
+        }
+    }
+
+    fn flush(&self) {
+        self.terminal.flush();
+    }
+}
+
+/// Starts logging, in place of `env_logger::init`
+pub fn init() {
+    let terminal = env_logger::Builder::from_default_env().build();
+    let max_level = terminal.filter().max(KEPT);
+    let logger = LOGGER.get_or_init(|| Logger {
+        terminal,
+        buffer: LogBuffer::default(),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+pub struct Logs {
+    store: Rc<Store>,
+    /// Least severe level shown
+    level: log::Level,
+    records: Vec<LogRecord>,
+    /// When [records](Self::records) were last pulled
+    last_refresh: Option<Instant>,
+}
+
+impl Logs {
+    pub fn new(store: Rc<Store>) -> Self {
+        Self {
+            store,
+            level: log::Level::Info,
+            records: vec![],
+            last_refresh: None,
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.records = LOGGER
+            .get()
+            .map(|l| l.buffer.recent(self.level))
+            .unwrap_or_default();
+        self.last_refresh = Some(Instant::now());
+    }
+}
+
+impl super::panels::Panel for Logs {
+    fn name(&self) -> &'static str {
+        "📜Logs"
+    }
+
+    fn desc(&self) -> &'static str {
+        "What HORUS has logged, for when a query or database fails"
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        if !*open {
+            self.last_refresh = None;
+            return None;
+        }
+        if self
+            .last_refresh
+            .is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL)
+        {
+            self.refresh();
+        }
+        // Keep refreshing while open, records come from every thread
+        ctx.request_repaint_after(REFRESH_INTERVAL);
+
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .default_size(egui::vec2(700.0, 400.0))
+                .vscroll(false),
+            place,
+            |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Show");
+                    let level = self.level;
+                    for (choice, text) in [
+                        (log::Level::Error, "Errors"),
+                        (log::Level::Warn, "Warnings and up"),
+                        (log::Level::Info, "Everything"),
+                    ] {
+                        ui.selectable_value(&mut self.level, choice, text);
+                    }
+                    if self.level != level {
+                        self.refresh();
+                    }
+                    ui.separator();
+                    ui.label(format!("{} records", self.records.len()));
+                });
+                ui.separator();
+
+                // Messages name users, IPs, and paths that can't be picked out to redact
+                if self.store.redact().0 {
+                    ui.label(
+                        RichText::new("Logs aren't redacted, turn off redaction to view them")
+                            .color(theme.subtle),
+                    );
+                    return;
+                }
+                records_ui(ui, &self.records, self.store.display_zone());
+            },
+        )
+    }
+}
+
+fn records_ui(ui: &mut egui::Ui, records: &[LogRecord], zone: Zone) {
+    let theme = color::theme(ui.ctx());
+    egui_extras::TableBuilder::new(ui)
+        .striped(true)
+        .resizable(true)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+        .columns(Column::auto(), 3)
+        .column(Column::remainder())
+        .header(20.0, |mut header| {
+            for title in ["Time", "Level", "Module", "Message"] {
+                header.col(|ui| {
+                    ui.label(RichText::new(title).color(theme.pine));
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(20.0, records.len(), |i, mut row| {
+                let record = &records[i];
+                row.col(|ui| {
+                    ui.label(zone.to_zone(record.time).format("%T").to_string());
+                });
+                row.col(|ui| {
+                    ui.label(
+                        RichText::new(record.level.to_string())
+                            .color(level_color(&theme, record.level)),
+                    );
+                });
+                row.col(|ui| {
+                    ui.label(RichText::new(&record.target).color(theme.subtle));
+                });
+                row.col(|ui| {
+                    // Long messages are cut off, the whole thing is on hover
+                    ui.add(egui::Label::new(record.message.as_str()).wrap(false))
+                        .on_hover_text(record.message.as_str());
+                });
+            });
+        });
+}
+
+fn level_color(theme: &color::Theme, level: log::Level) -> egui::Color32 {
+    match level {
+        log::Level::Error => theme.love,
+        log::Level::Warn => theme.gold,
+        log::Level::Info => theme.foam,
+        log::Level::Debug | log::Level::Trace => theme.subtle,
+    }
+}
@@ -1,12 +1,24 @@
 //! Main ui for HORUS
 use super::{color, panels::Panels};
-use crate::store::Store;
+use crate::{prefs::ThemeKind, queries::QueryError, store::Store};
 use chrono::Datelike;
-use std::rc::Rc;
+use std::{
+    rc::Rc,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How often Splunk is checked, often enough to catch an expired session before a long run
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
 pub struct MainUI {
+    store: Rc<Store>,
     /// Apps are held in this struct
     panels: Panels,
+    splunk_check: Option<JoinHandle<Result<(), QueryError>>>,
+    /// Result of the last Splunk health check
+    splunk_health: Result<(), QueryError>,
+    last_check: Instant,
     /// Image of Horus in the background
     horus: Option<egui::TextureHandle>,
     /// :)
@@ -16,25 +28,47 @@ pub struct MainUI {
 
 impl super::StateUIVariant for MainUI {
     fn update_panel(&mut self, ctx: &egui::Context) -> super::StateUIAction {
+        let theme = color::theme(ctx);
+        self.check_splunk(ctx);
+
+        let mut action = None;
         egui::SidePanel::right("right_panel")
             .resizable(false)
             .default_width(150.0)
             .show(ctx, |ui| {
                 ui.vertical_centered(|ui| {
-                    ui.label(egui::RichText::new("👁HORUS").heading().color(color::GOLD))
+                    ui.label(egui::RichText::new("👁HORUS").heading().color(theme.gold))
                 });
+                if self.splunk_status(ui) {
+                    action = Some(super::StateUIAction::Logout);
+                }
+                if let Some(e) = self.store.ipdb_error() {
+                    ui.label(egui::RichText::new("⚠ No IP geolocation").color(theme.gold))
+                        .on_hover_text(format!(
+                            "The IP databases failed to load, so logins have no location, proxy, or ASN\n{}",
+                            e
+                        ));
+                }
                 ui.scope(|ui| {
                     ui.style_mut()
                         .visuals
                         .widgets
                         .noninteractive
                         .bg_stroke
-                        .color = color::IRIS;
+                        .color = theme.iris;
                     ui.separator();
                 });
                 egui::ScrollArea::vertical().show(ui, |ui| {
                     ui.with_layout(egui::Layout::top_down_justified(egui::Align::LEFT), |ui| {
                         self.panels.checkboxes(ui);
+                        ui.separator();
+                        if ui
+                            .button("Reset layout")
+                            .on_hover_text("Move every window back to where it starts")
+                            .clicked()
+                        {
+                            self.panels.reset_layout(ctx);
+                        }
                     });
                 });
             });
@@ -46,7 +80,7 @@ impl super::StateUIVariant for MainUI {
 
                 if self.color_my_pencils {
                     let funky = chrono::Local::now();
-                    let monkey = funky.day() % 10 == 0;
+                    let monkey = funky.day().is_multiple_of(10);
                     if self.smells_like < 42
                         && monkey
                         && !std::path::Path::new("/tmp/shiver_me_timbers").exists()
@@ -89,7 +123,19 @@ impl super::StateUIVariant for MainUI {
 
         self.panels.windows(ctx);
 
-        super::StateUIAction::None
+        action.unwrap_or(super::StateUIAction::None)
+    }
+
+    fn ui_scale(&self) -> f32 {
+        self.store.ui_scale()
+    }
+
+    fn set_ui_scale(&mut self, scale: f32) {
+        self.store.set_ui_scale(scale);
+    }
+
+    fn theme(&self) -> ThemeKind {
+        self.store.theme()
     }
 }
 
@@ -99,11 +145,66 @@ impl MainUI {
         let in_here = store.analyst_name();
         Self {
             smells_like: up_dog(in_here),
-            panels: Panels::new(store),
+            panels: Panels::new(Rc::clone(&store)),
+            store,
+            splunk_check: None,
+            // Logging in just checked Splunk
+            splunk_health: Ok(()),
+            last_check: Instant::now(),
             horus: None,
             color_my_pencils: true,
         }
     }
+
+    /// Checks Splunk every [HEALTH_CHECK_INTERVAL] in the background
+    fn check_splunk(&mut self, ctx: &egui::Context) {
+        if self
+            .splunk_check
+            .as_ref()
+            .is_some_and(|rx| rx.is_finished())
+        {
+            if let Some(rx) = self.splunk_check.take() {
+                self.splunk_health = rx.join().unwrap_or(Err(QueryError::Network));
+                if let Err(e) = self.splunk_health {
+                    log::warn!("Splunk health check failed: {}", e);
+                }
+            }
+        } else if self.splunk_check.is_none() && self.last_check.elapsed() >= HEALTH_CHECK_INTERVAL
+        {
+            self.last_check = Instant::now();
+            self.splunk_check = Some(self.store.check_splunk());
+        }
+
+        if self.splunk_check.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        } else {
+            ctx.request_repaint_after(
+                HEALTH_CHECK_INTERVAL.saturating_sub(self.last_check.elapsed()),
+            );
+        }
+    }
+
+    /// Status dot for Splunk, returns true if the analyst wants to log in again
+    fn splunk_status(&self, ui: &mut egui::Ui) -> bool {
+        let theme = color::theme(ui.ctx());
+        let (dot, hover) = match self.splunk_health {
+            Ok(()) => (theme.foam, "Splunk is reachable".to_owned()),
+            Err(e) => (theme.love, format!("Last Splunk check failed: {}", e)),
+        };
+        ui.horizontal(|ui| {
+            let (rect, dot_resp) =
+                ui.allocate_exact_size(egui::vec2(10.0, 10.0), egui::Sense::hover());
+            ui.painter().circle_filled(rect.center(), 4.0, dot);
+            dot_resp.on_hover_text(&hover);
+            ui.label("Splunk").on_hover_text(hover);
+        });
+
+        self.splunk_health.is_err()
+            && ui
+                .button("Log in again")
+                .on_hover_text("Splunk stopped answering, log in again before starting a run")
+                .clicked()
+    }
 }
 
 fn up_dog(what_is: &str) -> usize {
@@ -4,23 +4,53 @@
 //! HOURS, such as Duplex and Sonar. States are the UIs that lead to the MainUi where the apps are
 //! visible, this includes login and main.
 
-mod color;
+pub mod color;
+mod diagnostics;
 mod duplex;
+mod ignored;
+mod keymap;
 pub mod login;
+pub mod logs;
 pub mod main;
+mod notify;
 mod panels;
+mod report;
+mod settings;
 mod simplex;
 pub mod sonar;
+mod test;
+mod travel_map;
 mod visor;
 mod zeppelin;
+mod zeppelin_diff;
+use crate::prefs::{redact::Redact, ThemeKind, UI_SCALE};
+use crate::queries::{
+    hdtools::{AddressRecord, HDToolsStatus},
+    splunk::Search,
+};
 use crate::store::Store;
+use crate::user::{
+    admin::{AdminAction, AdminEvent},
+    login::{repeat_runs, FraudDisposition, LocationSource, Login, LoginResult},
+    zone::Zone,
+    ActivitySummary, Location, LocationCount, User,
+};
+use chrono::{DateTime, Utc};
 use log::info;
+use std::{collections::HashSet, net::Ipv4Addr, thread::JoinHandle};
+
+/// How much Ctrl+= and Ctrl+- change the UI scale
+const UI_SCALE_STEP: f32 = 0.1;
 
 /// This enum is how states communciate between each other.  For example, when you click the login
 /// button, the login state will do some basic checks and then return a StateUIAction::Login which
 /// will tell the StateUI to switch to the main state.
 pub enum StateUIAction {
-    Login { store: Store },
+    Login {
+        store: Box<Store>,
+    },
+    /// Back to the login page, such as when the Splunk session expires
+    Logout,
     None,
 }
 
@@ -32,6 +62,12 @@ pub struct StateUI {
 /// Any state must imply this trait to be a main state of HORUS
 pub trait StateUIVariant {
     fn update_panel(&mut self, ctx: &egui::Context) -> StateUIAction;
+    /// Scale on top of the OS's, within [UI_SCALE]
+    fn ui_scale(&self) -> f32;
+    /// Saves a new scale for [StateUI] to apply
+    fn set_ui_scale(&mut self, scale: f32);
+    /// Theme for [StateUI] to apply, saved in the profile
+    fn theme(&self) -> ThemeKind;
 }
 
 #[allow(clippy::derivable_impls)]
@@ -44,78 +80,136 @@ impl Default for StateUI {
 }
 
 impl eframe::App for StateUI {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        let visuals = egui::Visuals {
-            override_text_color: None,
-            hyperlink_color: color::IRIS,
-            faint_bg_color: color::SURFACE, // Table stripes
-            extreme_bg_color: color::HIGHLIGHT_LOW,
-            code_bg_color: color::HIGHLIGHT_MED,
-            warn_fg_color: color::GOLD,
-            error_fg_color: color::LOVE,
-            window_fill: color::OVERLAY, // Widget background
-            panel_fill: color::BASE,     // Background background
-            widgets: egui::style::Widgets {
-                noninteractive: egui::style::WidgetVisuals {
-                    bg_fill: color::SURFACE,
-                    weak_bg_fill: color::SURFACE,
-                    bg_stroke: egui::Stroke::new(1.0, color::HIGHLIGHT_MED), // Separator color
-                    rounding: egui::Rounding::same(4.0),
-                    fg_stroke: egui::Stroke::new(1.0, color::TEXT),
-                    expansion: 1.0,
-                },
-                inactive: egui::style::WidgetVisuals {
-                    bg_fill: color::MUTED,
-                    weak_bg_fill: color::MUTED,
-                    bg_stroke: egui::Stroke::new(1.0, color::OVERLAY),
-                    rounding: egui::Rounding::same(4.0),
-                    fg_stroke: egui::Stroke::new(1.0, color::TEXT),
-                    expansion: 1.0,
-                },
-                hovered: egui::style::WidgetVisuals {
-                    bg_fill: color::MUTED,
-                    weak_bg_fill: color::MUTED,
-                    bg_stroke: egui::Stroke::new(1.0, color::MUTED),
-                    rounding: egui::Rounding::same(4.0),
-                    fg_stroke: egui::Stroke::new(1.0, color::TEXT),
-                    expansion: 1.0,
-                },
-                active: egui::style::WidgetVisuals {
-                    bg_fill: color::SUBTLE,
-                    weak_bg_fill: color::SUBTLE,
-                    bg_stroke: egui::Stroke::new(1.0, color::SUBTLE),
-                    rounding: egui::Rounding::same(4.0),
-                    fg_stroke: egui::Stroke::new(1.0, color::TEXT),
-                    expansion: 1.0,
-                },
-                open: egui::style::WidgetVisuals {
-                    bg_fill: color::SUBTLE,
-                    weak_bg_fill: color::SUBTLE,
-                    bg_stroke: egui::Stroke::new(1.0, color::MUTED),
-                    rounding: egui::Rounding::same(4.0),
-                    fg_stroke: egui::Stroke::new(1.0, color::TEXT),
-                    expansion: 1.0,
-                },
-            },
-            selection: egui::style::Selection {
-                bg_fill: color::PINE,
-                stroke: egui::Stroke::new(1.0, color::TEXT),
-            },
-            ..ctx.style().visuals.clone()
-        };
-        ctx.set_visuals(visuals);
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        self.scale_ui(ctx, frame);
+        color::set_theme(ctx, self.panel.theme().theme());
+        if notify::take_attention(ctx) {
+            frame.request_user_attention(egui::UserAttentionType::Informational);
+        }
         let resp = self.panel.update_panel(ctx);
 
         match resp {
             StateUIAction::Login { store } => {
                 info!("Swiching to loading screen");
-                self.panel = Box::new(main::MainUI::new(store));
+                self.panel = Box::new(main::MainUI::new(*store));
+            }
+            StateUIAction::Logout => {
+                info!("Returning to login");
+                self.panel = Box::<login::LoginUI>::default();
             }
             StateUIAction::None => (),
         }
     }
 }
 
+impl StateUI {
+    /// Handles Ctrl+= and Ctrl+- and applies the state's UI scale.  The keys are consumed so
+    /// panels that bind + and - don't see them.
+    fn scale_ui(&mut self, ctx: &egui::Context, frame: &eframe::Frame) {
+        let mut scale = self.panel.ui_scale();
+        ctx.input_mut(|i| {
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::PlusEquals) {
+                scale += UI_SCALE_STEP;
+            }
+            if i.consume_key(egui::Modifiers::COMMAND, egui::Key::Minus) {
+                scale -= UI_SCALE_STEP;
+            }
+        });
+        let scale = scale.clamp(*UI_SCALE.start(), *UI_SCALE.end());
+        if scale != self.panel.ui_scale() {
+            self.panel.set_ui_scale(scale);
+        }
+
+        let native = frame.info().native_pixels_per_point.unwrap_or(1.0);
+        if ctx.pixels_per_point() != native * scale {
+            ctx.set_pixels_per_point(native * scale);
+        }
+    }
+}
+
+/// Height of a table row that fits a line of text or a button at the current text size
+pub fn row_height(ui: &egui::Ui) -> f32 {
+    ui.spacing()
+        .interact_size
+        .y
+        .max(ui.text_style_height(&egui::TextStyle::Body))
+        + 2.0
+}
+
+/// Row of location chips above a login table, used by Duplex and Simplex.  Clicking a chip only
+/// shows logins from there and clicking it again shows them all.  A filter for a place the user
+/// has no logins from, such as after switching users, is dropped.
+pub fn location_chips(ui: &mut egui::Ui, summary: &[LocationCount], filter: &mut Option<String>) {
+    let theme = color::theme(ui.ctx());
+    if filter
+        .as_ref()
+        .is_some_and(|f| !summary.iter().any(|s| &s.location == f))
+    {
+        *filter = None;
+    }
+
+    egui::ScrollArea::horizontal()
+        .id_source("location_chips")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for location in summary {
+                    let selected = filter.as_ref() == Some(&location.location);
+                    let text = egui::RichText::new(format!(
+                        "{} from {}",
+                        location.count, location.location
+                    ))
+                    .color(if location.flagged > 0 {
+                        theme.love
+                    } else {
+                        theme.text
+                    });
+                    let chip = ui
+                        .selectable_label(selected, text)
+                        .on_hover_text(format!("{} flagged, click to filter", location.flagged));
+                    if chip.clicked() {
+                        *filter = if selected {
+                            None
+                        } else {
+                            Some(location.location.to_owned())
+                        };
+                    }
+                }
+            });
+        });
+}
+
+/// One line tally of the countries, states, and ASNs a user logged in from, used by Duplex and
+/// Simplex.  Mousing over it shows the counts in columns.
+pub fn activity_strip(ui: &mut egui::Ui, summary: &ActivitySummary) {
+    let theme = color::theme(ui.ctx());
+    egui::ScrollArea::horizontal()
+        .id_source("activity_strip")
+        .show(ui, |ui| {
+            ui.label(egui::RichText::new(summary.to_string()).color(theme.subtle))
+                .on_hover_ui(|ui| {
+                    ui.horizontal_top(|ui| {
+                        for (title, tally) in [
+                            ("Countries", &summary.countries),
+                            ("States", &summary.states),
+                            ("ASNs", &summary.asns),
+                        ] {
+                            egui::Grid::new(("activity_tally", title))
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label(egui::RichText::new(title).color(theme.pine));
+                                    ui.end_row();
+                                    for (value, count) in tally {
+                                        ui.label(value);
+                                        ui.label(count.to_string());
+                                        ui.end_row();
+                                    }
+                                });
+                        }
+                    });
+                });
+        });
+}
+
 /// This draws a shadow behind a panel and is used by the loginUI
 pub fn shadow_background(
     painter: &egui::Painter,
@@ -137,3 +231,512 @@ pub fn shadow_background(
     let shape = egui::Shape::Vec(vec![shadow, frame_shape]);
     painter.add(shape);
 }
+
+/// Cherwell first contact for a login, with its time in `zone` and the zone's abbreviation so
+/// the user isn't left guessing.  Fraud reports get their own wording.
+pub fn first_contact(login: &Login, analyst_name: &str, zone: Zone) -> String {
+    let date = zone.to_zone(login.time).format("%m/%d");
+    let time = format!(
+        "{} {}",
+        zone.to_zone(login.time).format("%I:%M %p"),
+        zone.abbreviation(login.time)
+    );
+    if login.result == LoginResult::Fraud {
+        format!(
+            std::include_str!("../../templates/first_contact_fraud.txt"),
+            analyst_name,
+            date,
+            time,
+            login.factor,
+            login.format_source(),
+            analyst_name
+        )
+    } else {
+        format!(
+            std::include_str!("../../templates/first_contact.txt"),
+            analyst_name,
+            date,
+            time,
+            login.factor,
+            login.format_source(),
+            analyst_name
+        )
+    }
+}
+
+/// Right click menu for a location with coordinates, used by Duplex, Simplex, and Visor
+pub fn location_menu(ui: &mut egui::Ui, location: (f32, f32)) {
+    if ui.button("Copy coordinates").clicked() {
+        ui.output_mut(|o| o.copied_text = format!("{}, {}", location.0, location.1));
+        ui.close_menu();
+    }
+    let url = crate::user::map_url(location);
+    if ui.button("Copy map link").clicked() {
+        ui.output_mut(|o| o.copied_text = url.to_owned());
+        ui.close_menu();
+    }
+    if ui.button("Open map").clicked() {
+        ui.output_mut(|o| o.open_url = Some(egui::output::OpenUrl::new_tab(url)));
+        ui.close_menu();
+    }
+}
+
+/// The HDTools record the user's address came from.  Hovering lists every address that was
+/// considered, best first.  Used by Duplex and Simplex.
+pub fn address_record(
+    ui: &mut egui::Ui,
+    record: AddressRecord,
+    addresses: &[(AddressRecord, Location)],
+) {
+    let theme = color::theme(ui.ctx());
+    ui.label(egui::RichText::new(record.to_string()).color(theme.iris))
+        .on_hover_ui(|ui| {
+            ui.label("HDTools record the address came from");
+            if addresses.len() > 1 {
+                ui.separator();
+                for (i, (record, location)) in addresses.iter().enumerate() {
+                    let text = egui::RichText::new(format!("{}: {}", record, location));
+                    ui.label(if i == 0 { text.color(theme.iris) } else { text });
+                }
+            }
+        });
+}
+
+/// A login's location, colored by where it came from so travel flags can be weighed by how far
+/// the location is trusted.  Clicking copies it and right clicking opens [location_menu], along
+/// with coordinates for the login's IP that can be set by hand.  Those are returned with the IP
+/// once set, or with [None] once cleared.  Used by Duplex and Simplex.
+pub fn login_location(ui: &mut egui::Ui, login: &Login) -> Option<(Ipv4Addr, Option<(f32, f32)>)> {
+    let loc = login.format_location()?;
+    let theme = color::theme(ui.ctx());
+    // VPN logins are shown as VPN whatever the databases say
+    let source = login.location_source.filter(|_| !login.is_vpn_ip());
+    let text = match source {
+        Some(LocationSource::IpInfo) => egui::RichText::new(loc.as_str()).color(theme.foam),
+        Some(LocationSource::Duo) => egui::RichText::new(loc.as_str())
+            .color(theme.subtle)
+            .italics(),
+        Some(LocationSource::Analyst) => egui::RichText::new(loc.as_str())
+            .color(theme.iris)
+            .underline(),
+        Some(LocationSource::Database) | None => egui::RichText::new(loc.as_str()),
+    };
+    let mut label = ui.add(egui::Label::new(text).sense(egui::Sense::click()));
+    if let Some(source) = source {
+        label = label.on_hover_text(source.desc());
+    }
+    if label.clicked() {
+        ui.output_mut(|o| o.copied_text = loc);
+    }
+    // VPN and private IPs are placed by who's behind them, not by the IP
+    let ip = login
+        .ip
+        .filter(|_| !login.is_vpn_ip() && !login.is_priv_ip());
+    let mut picked = None;
+    if login.location.is_some() || ip.is_some() {
+        label.context_menu(|ui| {
+            if let Some(location) = login.location {
+                location_menu(ui, location);
+            }
+            if let Some(ip) = ip {
+                ui.separator();
+                picked = location_override_menu(ui, login, ip).map(|l| (ip, l));
+            }
+        });
+    }
+    picked
+}
+
+/// Coordinates typed in for a login's IP, see [login_location]
+fn location_override_menu(
+    ui: &mut egui::Ui,
+    login: &Login,
+    ip: Ipv4Addr,
+) -> Option<Option<(f32, f32)>> {
+    // Kept while the menu is open, starting from the login's current coordinates
+    let id = egui::Id::new("location_override").with(ip);
+    let mut text = ui
+        .data_mut(|d| d.get_temp::<String>(id))
+        .unwrap_or_else(|| {
+            login
+                .location
+                .map(|(lat, lon)| format!("{}, {}", lat, lon))
+                .unwrap_or_default()
+        });
+    let coordinates = crate::user::parse_coordinates(&text);
+    let mut picked = None;
+    ui.label("Coordinates for this IP");
+    ui.text_edit_singleline(&mut text).on_hover_text(
+        "Latitude and longitude, like 34.68, -82.84, used for every login from this IP",
+    );
+    ui.horizontal(|ui| {
+        if ui
+            .add_enabled(coordinates.is_some(), egui::Button::new("Set"))
+            .clicked()
+        {
+            picked = Some(coordinates);
+            ui.close_menu();
+        }
+        if login.location_source == Some(LocationSource::Analyst)
+            && ui
+                .button("Clear")
+                .on_hover_text("Go back to the IP database's coordinates")
+                .clicked()
+        {
+            picked = Some(None);
+            ui.close_menu();
+        }
+    });
+    if picked.is_some() {
+        ui.data_mut(|d| d.remove::<String>(id));
+    } else {
+        ui.data_mut(|d| d.insert_temp(id, text));
+    }
+    picked
+}
+
+/// A login's result, colored by how bad it is.  Results a site mapped to failures or fraud, see
+/// [ResultMap](crate::user::login::ResultMap), show what Duo logged in rose.  Fraud reports show
+/// their disposition and can be given one by right clicking, which is returned with [None] for
+/// clearing it.  Used by Duplex and Simplex.
+pub fn login_result(ui: &mut egui::Ui, login: &Login) -> Option<Option<FraudDisposition>> {
+    let theme = color::theme(ui.ctx());
+    let color = match (&login.duo_result, &login.result) {
+        (Some(_), _) => theme.rose,
+        (None, LoginResult::Failure) => theme.rose,
+        (None, LoginResult::Fraud) => theme.love,
+        _ => theme.text,
+    };
+    let mut label = ui.add(
+        egui::Label::new(egui::RichText::new(login.format_result()).color(color))
+            .sense(egui::Sense::click()),
+    );
+    if login.duo_result.is_some() {
+        label = label.on_hover_text(format!("Counted as {}", login.result));
+    }
+    if login.result != LoginResult::Fraud {
+        return None;
+    }
+
+    if let Some(disposition) = login.fraud_disposition {
+        let color = match disposition {
+            FraudDisposition::Pending => theme.gold,
+            FraudDisposition::Confirmed => theme.love,
+            FraudDisposition::FalsePositive => theme.foam,
+        };
+        ui.label(egui::RichText::new(disposition.icon()).color(color))
+            .on_hover_text(disposition.to_string());
+    }
+    let mut picked = None;
+    label.context_menu(|ui| {
+        for disposition in FraudDisposition::ALL {
+            let selected = login.fraud_disposition == Some(disposition);
+            if ui
+                .selectable_label(selected, format!("{} {}", disposition.icon(), disposition))
+                .clicked()
+            {
+                picked = Some(Some(disposition));
+                ui.close_menu();
+            }
+        }
+        if ui
+            .add_enabled(
+                login.fraud_disposition.is_some(),
+                egui::Button::new("Clear disposition"),
+            )
+            .clicked()
+        {
+            picked = Some(None);
+            ui.close_menu();
+        }
+    });
+    picked
+}
+
+/// Collapses runs of repeated logins in Duplex's and Simplex's tables, see [repeat_runs].  Only
+/// what's shown changes, the user's logins are left alone.
+#[derive(Default)]
+pub struct Repeats {
+    /// Set from the toggle in the table header
+    pub collapse: bool,
+    /// Runs the analyst opened, by the time of their first login so they stay open when More logs
+    /// shifts the rows
+    opened: HashSet<DateTime<Utc>>,
+}
+
+impl Repeats {
+    /// What the table shows, as indexes into `rows` along with the length of the run each one
+    /// starts.  Closed runs only show their first login.
+    pub fn display(&self, logins: &[Login], rows: &[usize]) -> Vec<(usize, Option<usize>)> {
+        if !self.collapse {
+            return (0..rows.len()).map(|i| (i, None)).collect();
+        }
+        let mut display = Vec::with_capacity(rows.len());
+        let mut next = 0;
+        for run in repeat_runs(logins, rows) {
+            display.extend((next..run.start).map(|i| (i, None)));
+            display.push((run.start, Some(run.len())));
+            if self.is_open(&logins[rows[run.start]]) {
+                display.extend((run.start + 1..run.end).map(|i| (i, None)));
+            }
+            next = run.end;
+        }
+        display.extend((next..rows.len()).map(|i| (i, None)));
+        display
+    }
+
+    pub fn is_open(&self, first: &Login) -> bool {
+        self.opened.contains(&first.time)
+    }
+
+    /// Opens or closes the run starting with `first`
+    pub fn toggle(&mut self, first: &Login) {
+        if !self.opened.remove(&first.time) {
+            self.opened.insert(first.time);
+        }
+    }
+
+    /// Header toggle for [collapse](Self::collapse)
+    pub fn header_toggle(ui: &mut egui::Ui, collapse: &mut bool) {
+        ui.toggle_value(collapse, "×N")
+            .on_hover_text("Collapse runs of the same result, reason, factor, integration, and IP into one row.  Flagged logins always get their own row.");
+    }
+
+    /// "×N" badge on the first login of a run, true when clicked
+    pub fn badge(ui: &mut egui::Ui, len: usize, open: bool) -> bool {
+        ui.add(egui::Button::new(format!("×{}", len)).small())
+            .on_hover_text(if open {
+                "Collapse the repeats"
+            } else {
+                "Show each repeat"
+            })
+            .clicked()
+    }
+}
+
+/// Stands in for a user's HDTools details when there aren't any, saying whether HDTools has no
+/// record of them or couldn't be asked, so analysts know whether to log in again or trust the
+/// absence.  Used by Duplex and Simplex.
+pub fn hdtools_missing(ui: &mut egui::Ui, status: HDToolsStatus) {
+    let theme = color::theme(ui.ctx());
+    match status {
+        HDToolsStatus::Failed(e) => {
+            ui.label(egui::RichText::new(format!("HDTools failed: {}", e)).color(theme.love))
+                .on_hover_text(
+                    "HDTools didn't answer the lookup, log in again with a fresh shibsession if this keeps happening",
+                );
+        }
+        HDToolsStatus::NoRecord => {
+            ui.label(egui::RichText::new("No HDTools record").color(theme.rose))
+                .on_hover_text("HDTools answered but has no record of this user");
+        }
+        HDToolsStatus::Unchecked | HDToolsStatus::Found => {
+            ui.label(egui::RichText::new("No HDTools info").color(theme.subtle))
+                .on_hover_text("HDTools wasn't asked, it needs a shibsession when logging in");
+        }
+    }
+}
+
+/// View raw event for a login's right click menu, returns the event to show once clicked.  It's
+/// disabled if the event wasn't kept, or while redacting since events aren't redacted.  Used by
+/// Duplex and Simplex.
+pub fn raw_event_button(ui: &mut egui::Ui, login: &Login, redact: Redact) -> Option<String> {
+    let view = ui
+        .add_enabled(
+            login.raw.is_some() && !redact.0,
+            egui::Button::new("View raw event"),
+        )
+        .on_disabled_hover_text(if redact.0 {
+            "Raw events aren't redacted, turn off redaction to view them"
+        } else {
+            "Raw events weren't kept for this pull, they can be turned on in Settings"
+        });
+    if view.clicked() {
+        ui.close_menu();
+        login.raw_pretty()
+    } else {
+        None
+    }
+}
+
+/// Read only window with the event from [raw_event_button], for working out why a login parsed
+/// the way it did without searching for it in Splunk
+pub struct RawEventWindow {
+    id: &'static str,
+    event: Option<String>,
+}
+
+impl RawEventWindow {
+    /// `id` keeps Duplex's and Simplex's windows apart
+    pub fn new(id: &'static str) -> Self {
+        Self { id, event: None }
+    }
+
+    pub fn open(&mut self, event: String) {
+        self.event = Some(event);
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let Some(event) = &self.event else {
+            return;
+        };
+        let mut open = true;
+        egui::Window::new("Raw event")
+            .id(egui::Id::new(self.id))
+            .open(&mut open)
+            .default_size(egui::vec2(500.0, 400.0))
+            .show(ctx, |ui| {
+                if ui.button("Copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = event.to_owned());
+                }
+                ui.separator();
+                egui::ScrollArea::both().show(ui, |ui| {
+                    ui.add(
+                        egui::TextEdit::multiline(&mut event.as_str())
+                            .code_editor()
+                            .desired_width(f32::INFINITY),
+                    );
+                });
+            });
+        if !open {
+            self.event = None;
+        }
+    }
+}
+
+/// Copies the Splunk searches behind what's shown, time ranges and all, for running them in
+/// Splunk's web UI.  Used by Duplex, Simplex, and Sonar.
+pub fn copy_searches_button(ui: &mut egui::Ui, searches: &[Search], redact: Redact) {
+    // Searches name the users and IPs they look for
+    let copy = ui
+        .add_enabled(
+            !searches.is_empty() && !redact.0,
+            egui::Button::new("Copy Splunk search"),
+        )
+        .on_hover_text("Copy the exact searches that were run, to open them in Splunk")
+        .on_disabled_hover_text(if redact.0 {
+            "Searches aren't redacted, turn off redaction to copy them"
+        } else {
+            "Nothing has been searched yet"
+        });
+    if copy.clicked() {
+        ui.output_mut(|o| {
+            o.copied_text = searches
+                .iter()
+                .map(Search::spl)
+                .collect::<Vec<_>>()
+                .join("\n\n")
+        });
+    }
+}
+
+/// Export logins menu and how the last export went, used by Duplex and Simplex
+pub struct LoginExport {
+    file: String,
+    rx: Option<JoinHandle<Result<usize, String>>>,
+    /// Logins written by the last export, or why it failed
+    result: Option<Result<usize, String>>,
+}
+
+impl Default for LoginExport {
+    fn default() -> Self {
+        Self {
+            file: "logins.csv".to_owned(),
+            rx: None,
+            result: None,
+        }
+    }
+}
+
+impl LoginExport {
+    /// Menu saving the user's logins at `rows`, which should be the ones shown after filters.
+    /// `rows` is only called when saving.
+    pub fn menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        store: &Store,
+        user: &User,
+        rows: impl FnOnce() -> Vec<usize>,
+    ) {
+        let theme = color::theme(ui.ctx());
+        if self.rx.as_ref().is_some_and(|rx| rx.is_finished()) {
+            let rx = self.rx.take().expect("Failed to take export");
+            self.result = Some(rx.join().expect("Couldn't get export from thread"));
+        } else if self.rx.is_some() {
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
+        // Exports are evidence, so they're never redacted
+        let redact = store.redact();
+        ui.add_enabled_ui(!redact.0, |ui| {
+            ui.menu_button("Export logins", |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("File");
+                    ui.text_edit_singleline(&mut self.file)
+                        .on_hover_text("Saved as JSON if it ends in .json, otherwise CSV");
+                });
+                ui.add_enabled_ui(self.rx.is_none(), |ui| {
+                    if ui.button("Save").clicked() {
+                        self.result = None;
+                        self.rx = Some(store.export_logins(user, &rows(), self.file.to_owned()));
+                        ui.close_menu();
+                    }
+                });
+            })
+            .response
+            .on_hover_text("Save the logins shown, after filters, for escalating")
+            .on_disabled_hover_text("Exports aren't redacted, turn off redaction to save one");
+        });
+        if self.rx.is_some() {
+            ui.spinner();
+        }
+        match &self.result {
+            Some(Ok(count)) => {
+                ui.label(egui::RichText::new(format!("Saved {} logins", count)).color(theme.foam));
+            }
+            Some(Err(e)) => {
+                ui.label(egui::RichText::new(format!("Export failed: {}", e)).color(theme.love));
+            }
+            None => (),
+        }
+    }
+}
+
+/// Menu listing a user's Duo administrator events, red when a device was activated.  Used by
+/// Duplex and Simplex.
+pub fn admin_events_menu(ui: &mut egui::Ui, events: &[AdminEvent], zone: Zone) {
+    let theme = color::theme(ui.ctx());
+    let notable = |e: &AdminEvent| !matches!(e.action, AdminAction::Other(_));
+    let title = format!("{} admin events", events.len());
+    let color = if events.iter().any(notable) {
+        theme.love
+    } else {
+        theme.gold
+    };
+    ui.menu_button(egui::RichText::new(title).color(color), |ui| {
+        egui::Grid::new("admin_events")
+            .striped(true)
+            .show(ui, |ui| {
+                let time = format!("Time ({})", zone.abbreviation(chrono::Utc::now()));
+                for title in [time.as_str(), "Action", "By", "Details"] {
+                    ui.label(egui::RichText::new(title).color(theme.pine));
+                }
+                ui.end_row();
+                for event in events {
+                    ui.label(zone.to_zone(event.time).format("%T %D").to_string());
+                    let color = if notable(event) {
+                        theme.love
+                    } else {
+                        theme.text
+                    };
+                    ui.label(egui::RichText::new(event.action.to_string()).color(color));
+                    ui.label(event.admin.as_deref().unwrap_or_default());
+                    ui.label(event.description.as_deref().unwrap_or_default());
+                    ui.end_row();
+                }
+            });
+    })
+    .response
+    .on_hover_text("Device activations and bypass codes are in red");
+}
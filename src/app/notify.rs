@@ -0,0 +1,72 @@
+//! Desktop notifications
+//!
+//! Auto-refresh keeps Duplex running while the analyst works on something else, so new users
+//! scoring at or above the threshold in Settings pop up an OS notification.  Long Duplex runs can
+//! send one when they finish too.  Showing one can block on the notification daemon, so it's sent
+//! from its own thread.
+
+/// Sends one notification listing `users` by name and score, highest first
+pub fn high_scores(mut users: Vec<(String, usize)>) {
+    if users.is_empty() {
+        return;
+    }
+    users.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+    let summary = match users.len() {
+        1 => "Duplex flagged a high scoring user".to_owned(),
+        n => format!("Duplex flagged {} high scoring users", n),
+    };
+    let body = users
+        .iter()
+        .map(|(name, score)| format!("{} scored {}", name, score))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    std::thread::spawn(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .appname("Horus")
+            .summary(&summary)
+            .body(&body)
+            .show()
+        {
+            log::warn!("Couldn't send desktop notification: {}", e);
+        }
+    });
+}
+
+/// Says a Duplex run finished with `body`.  Without a desktop notification, because it's turned
+/// off or couldn't be shown, the window asks for attention instead, which flashes it in the
+/// taskbar.
+pub fn run_finished(ctx: &egui::Context, body: String, desktop: bool) {
+    if !desktop {
+        request_attention(ctx);
+        return;
+    }
+    let ctx = ctx.clone();
+    std::thread::spawn(move || {
+        if let Err(e) = notify_rust::Notification::new()
+            .appname("Horus")
+            .summary("Duplex run finished")
+            .body(&body)
+            .show()
+        {
+            log::warn!("Couldn't send desktop notification: {}", e);
+            request_attention(&ctx);
+        }
+    });
+}
+
+/// Has [StateUI](super::StateUI) flash the window on the next frame, see [take_attention]
+fn request_attention(ctx: &egui::Context) {
+    ctx.data_mut(|d| d.insert_temp(egui::Id::new("attention"), true));
+    ctx.request_repaint();
+}
+
+/// Whether the window was asked to flash since the last call
+pub fn take_attention(ctx: &egui::Context) -> bool {
+    let id = egui::Id::new("attention");
+    ctx.data_mut(|d| {
+        let asked = d.get_temp::<bool>(id).unwrap_or_default();
+        d.remove::<bool>(id);
+        asked
+    })
+}
@@ -2,7 +2,11 @@
 //!
 //! Each app must implement the Panel trait and be included in the panels vector to show in the
 //! MainUI.
-use std::{collections::BTreeSet, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
+    rc::Rc,
+};
 
 use crate::store::Store;
 
@@ -12,8 +16,146 @@ pub trait Panel {
     fn name(&self) -> &'static str;
     /// Returns the description of the app to be used in the context menu when hovering over the app's button
     fn desc(&self) -> &'static str;
-    /// Shows the app
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool);
+    /// Shows the app, placing its window at `place` if it was saved from an earlier session.
+    /// Returns where the window ended up, see [show_window].
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect>;
+    /// Handles a message sent to this app through the [Mailbox], before it is shown.  Apps ignore
+    /// messages they have no use for.
+    fn handle_message(&mut self, _message: PanelMessage) {}
+    /// Shown next to the app's button, so work finishing in a closed or hidden window isn't
+    /// missed.  Called every frame, so it can't wait on long locks.
+    fn badge(&self) -> Option<Badge> {
+        None
+    }
+}
+
+/// Small indicator next to an app's button in the sidebar, see [Panel::badge]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Badge {
+    /// Things waiting on the analyst, such as users left in Duplex's queue
+    Count(usize),
+    /// Work running in the background
+    Busy,
+    /// Background work finished and is waiting to be looked at
+    Ready,
+    /// Something failed, the text says what
+    Warning(String),
+}
+
+impl Badge {
+    fn ui(&self, ui: &mut egui::Ui) {
+        let theme = super::color::theme(ui.ctx());
+        match self {
+            Self::Count(count) => {
+                ui.label(
+                    egui::RichText::new(count.to_string())
+                        .color(theme.gold)
+                        .strong(),
+                );
+            }
+            Self::Busy => {
+                ui.label(egui::RichText::new("●").color(theme.foam))
+                    .on_hover_text("Running");
+            }
+            Self::Ready => {
+                ui.label(egui::RichText::new("✔").color(theme.foam))
+                    .on_hover_text("Finished, open to see the results");
+            }
+            Self::Warning(warning) => {
+                ui.label(egui::RichText::new("⚠").color(theme.love))
+                    .on_hover_text(warning);
+            }
+        }
+    }
+}
+
+/// Tells an app to do something, so a user found in one app doesn't have to be retyped in the
+/// next
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PanelMessage {
+    /// Open the app's window, [Panels] does this before passing it on
+    Open,
+    /// Bring the app's window to the front and focus its input
+    Focus,
+    /// Pull this user, such as their logs in Simplex or VPN activity in Visor
+    SetUser(String),
+    /// Look up this IP, MAC, or user in Sonar
+    SetLookup(String),
+}
+
+/// A [PanelMessage] for the app named `to`, see [Panel::name]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Envelope {
+    pub to: &'static str,
+    pub message: PanelMessage,
+}
+
+/// Messages waiting to be handed out by [Panels::windows], shared by every app that sends them
+#[derive(Clone, Default)]
+pub struct Mailbox(Rc<RefCell<Vec<Envelope>>>);
+
+impl Mailbox {
+    pub fn send(&self, to: &'static str, message: PanelMessage) {
+        self.0.borrow_mut().push(Envelope { to, message });
+    }
+
+    /// Opens the app named `to`, brings it to the front, and hands it `message`
+    pub fn open_with(&self, to: &'static str, message: PanelMessage) {
+        self.send(to, PanelMessage::Open);
+        self.send(to, PanelMessage::Focus);
+        self.send(to, message);
+    }
+
+    pub(super) fn take(&self) -> Vec<Envelope> {
+        std::mem::take(&mut *self.0.borrow_mut())
+    }
+}
+
+/// Hands each message to the app it's addressed to, in the order they were sent.  Returns the
+/// apps sent [PanelMessage::Open].  Messages for apps that don't exist are dropped.
+pub(super) fn route(panels: &mut [Box<dyn Panel>], messages: Vec<Envelope>) -> Vec<&'static str> {
+    let mut opened = vec![];
+    for Envelope { to, message } in messages {
+        let Some(panel) = panels.iter_mut().find(|p| p.name() == to) else {
+            log::warn!("No app named {} for {:?}", to, message);
+            continue;
+        };
+        if message == PanelMessage::Open && !opened.contains(&to) {
+            opened.push(to);
+        }
+        panel.handle_message(message);
+    }
+    opened
+}
+
+/// Shows a panel's window, starting at `place` if given.  Returns the window's position with the
+/// size of its contents, which is what `place` expects, or [None] if it is closed or collapsed.
+pub fn show_window(
+    ctx: &egui::Context,
+    mut window: egui::Window<'_>,
+    place: Option<egui::Rect>,
+    add_contents: impl FnOnce(&mut egui::Ui),
+) -> Option<egui::Rect> {
+    if let Some(place) = place {
+        window = window.default_pos(place.min).default_size(place.size());
+    }
+
+    let mut size = None;
+    let response = window.show(ctx, |ui| {
+        size = Some(ui.max_rect().size());
+        add_contents(ui);
+    })?;
+    response.inner?;
+
+    let size = size
+        .filter(|s| s.is_finite())
+        .unwrap_or(response.response.rect.size());
+    Some(egui::Rect::from_min_size(response.response.rect.min, size))
 }
 
 pub struct Panels {
@@ -21,52 +163,119 @@ pub struct Panels {
     panels: Vec<Box<dyn Panel>>,
     /// Defines which apps are open
     open: BTreeSet<String>,
+    /// Where each app's window was last left
+    rects: BTreeMap<String, egui::Rect>,
+    /// Messages between apps, see [PanelMessage]
+    mailbox: Mailbox,
+    store: Rc<Store>,
 }
 
 impl Panels {
     /// Creates a new Panels struct and defines what apps are available
     pub fn new(store: Rc<Store>) -> Self {
+        let mailbox = Mailbox::default();
         let panels: Vec<Box<dyn Panel>> = vec![
-            Box::new(super::duplex::Duplex::new(Rc::clone(&store))),
+            Box::new(super::duplex::Duplex::new(
+                Rc::clone(&store),
+                mailbox.clone(),
+            )),
             Box::new(super::simplex::Simplex::new(Rc::clone(&store))),
             Box::new(super::visor::Visor::new(Rc::clone(&store))),
             Box::new(super::sonar::Sonar::new(Rc::clone(&store))),
             Box::new(super::zeppelin::Zeppelin::new(Rc::clone(&store))),
+            Box::new(super::zeppelin_diff::ZeppelinDiff::new(Rc::clone(&store))),
+            Box::new(super::ignored::Ignored::new(Rc::clone(&store))),
+            Box::new(super::settings::Settings::new(Rc::clone(&store))),
+            Box::new(super::diagnostics::Diagnostics::new(Rc::clone(&store))),
+            Box::new(super::logs::Logs::new(Rc::clone(&store))),
         ];
-        let open = BTreeSet::new();
+        let mut open = BTreeSet::new();
+        let mut rects = BTreeMap::new();
+        for layout in store.panel_layout() {
+            if let Some(rect) = layout.rect {
+                rects.insert(layout.name.clone(), rect);
+            }
+            if layout.open {
+                open.insert(layout.name);
+            }
+        }
 
-        Self { panels, open }
+        Self {
+            panels,
+            open,
+            rects,
+            mailbox,
+            store,
+        }
     }
 
     /// Shows the buttons on the right side
     pub fn checkboxes(&mut self, ui: &mut egui::Ui) {
-        let Self { panels, open } = self;
+        let Self {
+            panels,
+            open,
+            store,
+            ..
+        } = self;
         for panel in panels {
             let mut is_open = open.contains(panel.name());
-            ui.toggle_value(&mut is_open, panel.name())
-                .on_hover_text(panel.desc());
-            set_open(open, panel.name(), is_open);
+            let badge = panel.badge();
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut is_open, panel.name())
+                    .on_hover_text(panel.desc());
+                if let Some(badge) = badge {
+                    badge.ui(ui);
+                }
+            });
+            set_open(store, open, panel.name(), is_open);
         }
     }
 
     /// Shows open apps
     pub fn windows(&mut self, ctx: &egui::Context) {
-        let Self { panels, open } = self;
+        let Self {
+            panels,
+            open,
+            rects,
+            mailbox,
+            store,
+        } = self;
+        // Taken first, apps may send more while they're shown and those wait for the next frame
+        for name in route(panels, mailbox.take()) {
+            set_open(store, open, name, true);
+        }
+        // Only save once a drag or resize is let go, not every frame of it
+        let settled = !ctx.input(|i| i.pointer.any_down());
         for panel in panels {
             let mut is_open = open.contains(panel.name());
-            panel.show(ctx, &mut is_open);
-            set_open(open, panel.name(), is_open);
+            let rect = panel.show(ctx, &mut is_open, rects.get(panel.name()).copied());
+            set_open(store, open, panel.name(), is_open);
+
+            if let Some(rect) = rect.filter(|_| settled) {
+                if rects.get(panel.name()) != Some(&rect) {
+                    store.set_panel_rect(panel.name(), rect);
+                    rects.insert(panel.name().to_owned(), rect);
+                }
+            }
         }
     }
+
+    /// Puts every window back where it starts on a fresh install
+    pub fn reset_layout(&mut self, ctx: &egui::Context) {
+        self.rects.clear();
+        self.store.clear_panel_rects();
+        ctx.memory_mut(|m| m.reset_areas());
+    }
 }
 
-/// Sets whether an app is open
-fn set_open(open: &mut BTreeSet<String>, key: &'static str, is_open: bool) {
+/// Sets whether an app is open, saving it when it changes
+fn set_open(store: &Store, open: &mut BTreeSet<String>, key: &'static str, is_open: bool) {
     if is_open {
         if !open.contains(key) {
             open.insert(key.to_owned());
+            store.set_panel_open(key, true);
         }
-    } else {
-        open.remove(key);
+    } else if open.remove(key) {
+        store.set_panel_open(key, false);
     }
 }
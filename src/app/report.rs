@@ -0,0 +1,203 @@
+//! Duplex run reports
+//!
+//! Exports are raw evidence for another team, this is a summary for people, such as management or
+//! a team that asked what a night looked like.  It's a single HTML file with its styles inline, so
+//! it can be attached to an email or opened from a share as is.
+use super::color::Theme;
+use crate::queries::splunk::TimeSpan;
+use crate::user::{login::LoginRecord, zone::Zone, QueueStats, User};
+use chrono::{DateTime, Utc};
+use egui::Color32;
+
+/// Renders the users a run flagged, their scores and flagged logins, and how the queue went.
+/// Colored with the light palette, since reports mostly end up on white pages or printed.
+pub fn run_report(users: &[User], stats: &QueueStats, range: &TimeSpan, zone: Zone) -> String {
+    let theme = Theme::LIGHT;
+    let time = |t: DateTime<Utc>| {
+        format!(
+            "{} {}",
+            zone.to_zone(t).format("%F %R"),
+            zone.abbreviation(t)
+        )
+    };
+
+    let mut html = format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>Duplex report</title>
+<style>
+body {{ background: {base}; color: {text}; font-family: sans-serif; margin: 2em; }}
+h1, h2 {{ color: {pine}; }}
+h3 {{ color: {love}; margin-bottom: 0.2em; }}
+table {{ border-collapse: collapse; margin-bottom: 1em; }}
+th {{ color: {pine}; text-align: left; }}
+th, td {{ padding: 0.2em 0.8em; }}
+tr:nth-child(even) {{ background: {surface}; }}
+.flag {{ color: {love}; }}
+.muted {{ color: {subtle}; }}
+</style>
+</head>
+<body>
+<h1>Duplex report</h1>
+<p class=\"muted\">{start} to {end}, made {now}</p>
+",
+        base = hex(theme.base),
+        text = hex(theme.text),
+        pine = hex(theme.pine),
+        love = hex(theme.love),
+        surface = hex(theme.surface),
+        subtle = hex(theme.subtle),
+        start = time(range.start),
+        end = time(range.end),
+        now = time(Utc::now()),
+    );
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    let mut rows = vec![
+        ("Users flagged".to_owned(), users.len().to_string()),
+        (
+            "Marked investigated".to_owned(),
+            stats.investigated.to_string(),
+        ),
+        ("Skipped".to_owned(), stats.skipped.to_string()),
+        ("Highest score".to_owned(), stats.highest_score.to_string()),
+        ("Time spent".to_owned(), stats.elapsed_text()),
+    ];
+    rows.extend(
+        stats
+            .reasons
+            .iter()
+            .map(|(r, c)| (format!("Flagged for {}", r), c.to_string())),
+    );
+    for (name, value) in &rows {
+        html.push_str(&row("td", &[name.as_str(), value.as_str()]));
+    }
+    html.push_str("</table>\n");
+
+    if !stats.countries.is_empty() {
+        html.push_str("<h2>Top countries</h2>\n<table>\n");
+        html.push_str(&row("th", &["Country", "Users"]));
+        for (country, count) in &stats.countries {
+            html.push_str(&row("td", &[country.as_str(), count.to_string().as_str()]));
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Users</h2>\n");
+    for user in users {
+        html.push_str(&user_section(user, zone));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// A user's score, reasons, HDTools info, and flagged logins
+fn user_section(user: &User, zone: Zone) -> String {
+    let mut html = format!(
+        "<h3>{} &mdash; {} pts</h3>\n",
+        escape(&user.name),
+        user.score
+    );
+
+    let mut about = vec![];
+    if user.investigated {
+        about.push("Marked investigated".to_owned());
+    }
+    if let Some(location) = &user.location {
+        about.push(format!("Home {}", location));
+    }
+    if let Some(created) = user.creation_date {
+        about.push(format!("Created {}", created.format("%F")));
+    }
+    if let Some(affiliation) = &user.affiliation {
+        about.push(affiliation.to_owned());
+    }
+    if !about.is_empty() {
+        html.push_str(&format!(
+            "<p class=\"muted\">{}</p>\n",
+            escape(&about.join(", "))
+        ));
+    }
+
+    html.push_str("<ul>\n");
+    for component in &user.score_breakdown.components {
+        html.push_str(&format!("<li>{}</li>\n", escape(&component.to_string())));
+    }
+    html.push_str("</ul>\n");
+
+    let flagged: Vec<LoginRecord> = user
+        .logins
+        .iter()
+        .filter(|l| !l.flag_reasons.is_empty())
+        .map(|l| LoginRecord::new(l, zone))
+        .collect();
+    if flagged.is_empty() {
+        return html;
+    }
+    html.push_str("<table>\n");
+    html.push_str(&row(
+        "th",
+        &[
+            "Time",
+            "Result",
+            "Reason",
+            "Factor",
+            "Integration",
+            "IP",
+            "ASN",
+            "Location",
+            "Flagged for",
+        ],
+    ));
+    for login in &flagged {
+        let ip = login.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        html.push_str(&format!(
+            "<tr>{}<td class=\"flag\">{}</td></tr>\n",
+            cells(
+                "td",
+                &[
+                    login.time.as_str(),
+                    login.result.as_str(),
+                    login.reason.as_str(),
+                    login.factor.as_str(),
+                    login.integration.as_str(),
+                    ip.as_str(),
+                    login.asn.as_deref().unwrap_or_default(),
+                    login.location.as_deref().unwrap_or_default(),
+                ],
+            ),
+            escape(&login.flag_reasons.join(", "))
+        ));
+    }
+    html.push_str("</table>\n");
+    html
+}
+
+/// One table row of `texts`
+fn row(tag: &str, texts: &[&str]) -> String {
+    format!("<tr>{}</tr>\n", cells(tag, texts))
+}
+
+/// Each of `texts` escaped in a `tag` cell
+fn cells(tag: &str, texts: &[&str]) -> String {
+    texts
+        .iter()
+        .map(|t| format!("<{tag}>{}</{tag}>", escape(t)))
+        .collect()
+}
+
+/// `text` safe to put in an element
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// CSS color of a palette color
+fn hex(color: Color32) -> String {
+    format!("#{:02x}{:02x}{:02x}", color.r(), color.g(), color.b())
+}
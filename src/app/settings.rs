@@ -0,0 +1,720 @@
+//! HORUS settings
+//!
+//! Holds settings that change how the other apps behave.  Everything here is saved to the disk
+//! cache as soon as it is changed.
+use super::color;
+use crate::prefs::{
+    keymap::{self, Action, Keymap},
+    ThemeKind, UI_SCALE,
+};
+use crate::queries::{TimeoutConfig, Timeouts};
+use crate::store::Store;
+use crate::user::{login::ResultMap, zone::Zone, FailureMatch, IpRange, NewAccountPass, Scoring};
+use egui::RichText;
+use egui_extras::Column;
+use std::rc::Rc;
+
+pub struct Settings {
+    store: Rc<Store>,
+    /// ASNs whose logins Duplex doesn't score for impossible travel
+    trusted_asns: Vec<String>,
+    /// Stores the new ASN name
+    new_asn: String,
+    /// Stores the new trusted IP range
+    new_range: String,
+    /// The new range couldn't be read
+    new_range_error: bool,
+    /// Unknown Duo results counted as failures or fraud
+    result_map: ResultMap,
+    /// Stores the new result name
+    new_result: String,
+    /// Whether the new result counts as fraud rather than a failure
+    new_result_fraud: bool,
+    /// Timezone Splunk's timestamps are read in
+    splunk_zone: Zone,
+    /// IANA zone typed in for showing times, for zones not in the list
+    named_zone: String,
+    /// Why the typed zone wasn't used
+    named_zone_error: Option<String>,
+    /// Thresholds for Duplex's checks
+    scoring: Scoring,
+    keymap: Keymap,
+    /// Why the last key couldn't be bound
+    keymap_error: Option<String>,
+    /// Connect and read timeouts for each query source, saved as they're changed
+    timeouts: TimeoutConfig,
+    /// UI scale while the slider is dragged, it is only applied once let go so the slider doesn't
+    /// move out from under the pointer
+    ui_scale: Option<f32>,
+}
+
+impl Settings {
+    pub fn new(store: Rc<Store>) -> Self {
+        let trusted_asns = store.trusted_asns();
+        let result_map = store.result_map();
+        let splunk_zone = store.splunk_zone();
+        let scoring = store.scoring();
+        let keymap = store.keymap();
+        let timeouts = store.timeouts();
+        Self {
+            store,
+            trusted_asns,
+            new_asn: String::new(),
+            new_range: String::new(),
+            new_range_error: false,
+            result_map,
+            new_result: String::new(),
+            new_result_fraud: false,
+            splunk_zone,
+            named_zone: String::new(),
+            named_zone_error: None,
+            scoring,
+            keymap,
+            keymap_error: None,
+            timeouts,
+            ui_scale: None,
+        }
+    }
+}
+
+impl super::panels::Panel for Settings {
+    fn name(&self) -> &'static str {
+        "⚙ Settings"
+    }
+
+    fn desc(&self) -> &'static str {
+        "Tune how HORUS checks users"
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .fixed_size(egui::vec2(300.0, 400.0))
+                .vscroll(true),
+            place,
+            |ui| {
+                self.ui(ui);
+            },
+        )
+    }
+}
+
+impl Settings {
+    fn ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        ui.vertical_centered(|ui| ui.label(RichText::new("Timezones").heading().color(theme.pine)));
+        egui::Grid::new("zone_grid").show(ui, |ui| {
+            ui.label("Splunk").on_hover_text(
+                "Timezone of Splunk's timestamps that don't carry their own, applies to logs pulled after it is changed",
+            );
+            let splunk_zone = self.splunk_zone;
+            zone_combo(ui, "splunk_zone", &mut self.splunk_zone);
+            if self.splunk_zone != splunk_zone {
+                self.store.set_splunk_zone(self.splunk_zone);
+            }
+            ui.end_row();
+
+            ui.label("Display")
+                .on_hover_text("Timezone times are shown in");
+            let mut display_zone = self.store.display_zone();
+            zone_combo(ui, "display_zone", &mut display_zone);
+            if display_zone != self.store.display_zone() {
+                self.store.set_display_zone(display_zone);
+            }
+            ui.end_row();
+
+            ui.label("");
+            let named = ui
+                .add(
+                    egui::TextEdit::singleline(&mut self.named_zone)
+                        .hint_text("America/Chicago")
+                        .desired_width(120.0),
+                )
+                .on_hover_text("Any IANA zone name, press Enter to show times in it");
+            if named.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                match self.named_zone.trim().parse() {
+                    Ok(zone @ Zone::Named(_)) => {
+                        self.store.set_display_zone(zone);
+                        self.named_zone.clear();
+                        self.named_zone_error = None;
+                    }
+                    _ => {
+                        self.named_zone_error =
+                            Some(format!("{} isn't an IANA zone", self.named_zone.trim()));
+                    }
+                }
+            }
+            ui.end_row();
+        });
+        if let Some(error) = &self.named_zone_error {
+            ui.label(RichText::new(error).color(theme.love));
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| ui.label(RichText::new("Display").heading().color(theme.pine)));
+        ui.horizontal(|ui| {
+            ui.label("UI scale")
+                .on_hover_text("Also changed with Ctrl+= and Ctrl+-");
+            let mut scale = self.ui_scale.unwrap_or_else(|| self.store.ui_scale());
+            let slider = ui.add(
+                egui::Slider::new(&mut scale, UI_SCALE)
+                    .step_by(0.05)
+                    .suffix("x"),
+            );
+            if slider.dragged() {
+                self.ui_scale = Some(scale);
+            } else {
+                self.ui_scale = None;
+                if scale != self.store.ui_scale() {
+                    self.store.set_ui_scale(scale);
+                }
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Theme")
+                .on_hover_text("Light is easier to read in bright rooms");
+            let current = self.store.theme();
+            for kind in ThemeKind::ALL {
+                if ui
+                    .selectable_label(current == kind, kind.to_string())
+                    .clicked()
+                    && current != kind
+                {
+                    self.store.set_theme(kind);
+                }
+            }
+        });
+        let mut redact = self.store.redact();
+        if ui
+            .checkbox(&mut redact.0, "Redact usernames and IPs")
+            .on_hover_text(
+                "Shows pseudonyms for screenshots and demos, the same user always gets the same one",
+            )
+            .changed()
+        {
+            self.store.set_redact(redact);
+        }
+        ui.horizontal(|ui| {
+            let mut notify = self.store.notify();
+            if ui
+                .checkbox(&mut notify, "Notify for new users scoring at least")
+                .on_hover_text(
+                    "Auto-refresh in Duplex sends a desktop notification when it adds a user at or above this score",
+                )
+                .changed()
+            {
+                self.store.set_notify(notify);
+            }
+            let mut score = self.store.notify_score();
+            ui.add_enabled(
+                notify,
+                egui::DragValue::new(&mut score).clamp_range(1..=500),
+            );
+            if score != self.store.notify_score() {
+                self.store.set_notify_score(score);
+            }
+        });
+        let mut notify_run_done = self.store.notify_run_done();
+        if ui
+            .checkbox(&mut notify_run_done, "Notify when a Duplex run finishes")
+            .on_hover_text(
+                "Sends a desktop notification with how many users were flagged, the taskbar flashes instead if notifications can't be shown",
+            )
+            .changed()
+        {
+            self.store.set_notify_run_done(notify_run_done);
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| ui.label(RichText::new("Splunk").heading().color(theme.pine)));
+        ui.horizontal(|ui| {
+            ui.label("Search job past").on_hover_text(
+                "Login pulls longer than this run as a search job and are paged through, so large exports aren't cut off",
+            );
+            let mut hours = self.store.job_hours();
+            ui.add(
+                egui::DragValue::new(&mut hours)
+                    .clamp_range(1..=24 * 90)
+                    .suffix(" hours"),
+            );
+            if hours != self.store.job_hours() {
+                self.store.set_job_hours(hours);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Sonar events").on_hover_text(
+                "Events each Sonar lookup reads, the most frequent match among them is used",
+            );
+            let mut limit = self.store.sonar_limit();
+            ui.add(egui::DragValue::new(&mut limit).clamp_range(1..=10_000));
+            if limit != self.store.sonar_limit() {
+                self.store.set_sonar_limit(limit);
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.label("Keep raw events in").on_hover_text(
+                "Keeps the Splunk event behind each login for View raw event, which takes a lot of memory for a full Duplex run",
+            );
+            let mut duplex = self.store.raw_duplex();
+            if ui.checkbox(&mut duplex, "Duplex").changed() {
+                self.store.set_raw_duplex(duplex);
+            }
+            let mut simplex = self.store.raw_simplex();
+            if ui.checkbox(&mut simplex, "Simplex").changed() {
+                self.store.set_raw_simplex(simplex);
+            }
+        });
+
+        ui.separator();
+        ui.vertical_centered(|ui| ui.label(RichText::new("Timeouts").heading().color(theme.pine)))
+            .response
+            .on_hover_text(
+                "How long to wait to connect to each source and for each read, used from the next login",
+            );
+        if timeouts_ui(ui, &mut self.timeouts) {
+            self.store.set_timeouts(&self.timeouts);
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| ui.label(RichText::new("Scoring").heading().color(theme.pine)));
+        let scoring = self.scoring;
+        egui::Grid::new("scoring_grid").show(ui, |ui| {
+            ui.label("MFA fatigue pushes")
+                .on_hover_text("Unanswered pushes before an approval that count as push bombing");
+            ui.add(egui::DragValue::new(&mut self.scoring.fatigue_pushes).clamp_range(1..=50));
+            ui.end_row();
+
+            ui.label("MFA fatigue window")
+                .on_hover_text("Minutes before the approval that unanswered pushes are counted");
+            ui.add(
+                egui::DragValue::new(&mut self.scoring.fatigue_minutes)
+                    .clamp_range(1..=240)
+                    .suffix(" min"),
+            );
+            ui.end_row();
+
+            ui.label("Failure forgiveness window")
+                .on_hover_text("Minutes after a failure that a matching success forgives it");
+            ui.add(
+                egui::DragValue::new(&mut self.scoring.failure_policy.window_minutes)
+                    .clamp_range(0..=1440)
+                    .suffix(" min"),
+            );
+            ui.end_row();
+
+            ui.label("Minimum failures")
+                .on_hover_text("Unforgiven failures needed before a user is flagged for failures");
+            ui.add(
+                egui::DragValue::new(&mut self.scoring.failure_policy.min_count)
+                    .clamp_range(1..=20),
+            );
+            ui.end_row();
+
+            ui.label("Impossible travel speed").on_hover_text(
+                "Travel at or above this speed is impossible, slower speeds also check older logs",
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.scoring.max_kph)
+                    .clamp_range(300..=5000)
+                    .speed(10)
+                    .suffix(" kph"),
+            );
+            ui.end_row();
+
+            ui.label("New account window").on_hover_text(
+                "Accounts created this recently pass when denied for not being enrolled in Duo",
+            );
+            ui.add(
+                egui::DragValue::new(&mut self.scoring.new_account_months)
+                    .clamp_range(1..=36)
+                    .suffix(" months"),
+            );
+            ui.end_row();
+
+            ui.label("Failure forgiven by")
+                .on_hover_text("What a success has to share with a failure to forgive it");
+            let matching = &mut self.scoring.failure_policy.matching;
+            egui::ComboBox::from_id_source("failure_match")
+                .selected_text(matching.to_string())
+                .show_ui(ui, |ui| {
+                    for choice in FailureMatch::choices() {
+                        ui.selectable_value(matching, choice, choice.to_string());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("New accounts pass with").on_hover_text(
+                "What else a new account denied for not being enrolled can be flagged for and still pass",
+            );
+            let pass = &mut self.scoring.new_account_pass;
+            egui::ComboBox::from_id_source("new_account_pass")
+                .selected_text(pass.to_string())
+                .show_ui(ui, |ui| {
+                    for choice in NewAccountPass::choices() {
+                        ui.selectable_value(pass, choice, choice.to_string());
+                    }
+                });
+            ui.end_row();
+
+            ui.label("Proxy logins").on_hover_text(
+                "Successes through a known proxy or relay add this much to the score each, even in state",
+            );
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.scoring.flag_relays, "Flag");
+                ui.add_enabled(
+                    self.scoring.flag_relays,
+                    egui::DragValue::new(&mut self.scoring.relay_weight)
+                        .clamp_range(1..=50)
+                        .suffix(" points"),
+                );
+            });
+            ui.end_row();
+        });
+        if self.scoring != scoring {
+            self.store.set_scoring(self.scoring);
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("Trusted ASNs").heading().color(theme.pine))
+        });
+        ui.label("Travel between two logins from trusted ASNs isn't scored by Duplex");
+
+        let mut remove = None;
+        ui.push_id("trusted_asn_table", |ui| {
+            ui.set_max_height(150.0);
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::remainder())
+                .column(Column::exact(60.0))
+                .header(20.0, |mut header| {
+                    for title in ["ASN", ""] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, self.trusted_asns.len(), |i, mut row| {
+                        row.col(|ui| {
+                            ui.label(&self.trusted_asns[i]);
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    });
+                });
+        });
+
+        if let Some(i) = remove {
+            let asn = self.trusted_asns.remove(i);
+            self.store.remove_trusted_asn(&asn);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_asn).desired_width(200.0));
+            let asn = self.new_asn.trim();
+            if ui.button("Add ASN").clicked() && !asn.is_empty() {
+                self.store.add_trusted_asn(asn);
+                self.trusted_asns = self.store.trusted_asns();
+                self.new_asn.clear();
+            }
+        });
+
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("Duo Results").heading().color(theme.pine))
+        });
+        ui.label("Results Duo adds over time aren't scored unless they're listed here");
+        ui.label("Only applies to logs pulled after they're changed");
+
+        let mut remove = None;
+        ui.push_id("result_map_table", |ui| {
+            ui.set_max_height(150.0);
+            let rows: Vec<(&String, &str)> = self
+                .result_map
+                .failure
+                .iter()
+                .map(|r| (r, "Failure"))
+                .chain(self.result_map.fraud.iter().map(|r| (r, "Fraud")))
+                .collect();
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::remainder())
+                .column(Column::exact(80.0))
+                .column(Column::exact(60.0))
+                .header(20.0, |mut header| {
+                    for title in ["Result", "Counts as", ""] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, rows.len(), |i, mut row| {
+                        let (result, kind) = rows[i];
+                        row.col(|ui| {
+                            ui.label(RichText::new(result).color(theme.rose));
+                        });
+                        row.col(|ui| {
+                            ui.label(kind);
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                remove = Some(i);
+                            }
+                        });
+                    });
+                });
+        });
+
+        if let Some(i) = remove {
+            let failures = self.result_map.failure.len();
+            if i < failures {
+                self.result_map.failure.remove(i);
+            } else {
+                self.result_map.fraud.remove(i - failures);
+            }
+            self.store.set_result_map(self.result_map.clone());
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_result).desired_width(200.0));
+            egui::ComboBox::from_id_source("new_result_kind")
+                .selected_text(if self.new_result_fraud {
+                    "Fraud"
+                } else {
+                    "Failure"
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.new_result_fraud, false, "Failure");
+                    ui.selectable_value(&mut self.new_result_fraud, true, "Fraud");
+                });
+            let result = self.new_result.trim().to_uppercase();
+            if ui.button("Add result").clicked() && !result.is_empty() {
+                if self.new_result_fraud {
+                    self.result_map.fraud.push(result);
+                } else {
+                    self.result_map.failure.push(result);
+                }
+                self.store.set_result_map(self.result_map.clone());
+                self.new_result.clear();
+            }
+        });
+
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            ui.label(RichText::new("Trusted IPs").heading().color(theme.pine))
+        });
+        ui.label("Logins from trusted IPs aren't scored for failures or travel");
+        ui.label("Right click an IP in Duplex or Simplex to trust it");
+
+        let ips = self.store.ip_allowlist();
+        let mut remove = None;
+        ui.push_id("trusted_ip_table", |ui| {
+            ui.set_max_height(150.0);
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::remainder())
+                .column(Column::exact(60.0))
+                .header(20.0, |mut header| {
+                    for title in ["IP", ""] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, ips.len(), |i, mut row| {
+                        row.col(|ui| {
+                            ui.label(ips[i].to_string());
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                remove = Some(ips[i]);
+                            }
+                        });
+                    });
+                });
+        });
+
+        if let Some(ip) = remove {
+            self.store.remove_allowlisted_ip(ip);
+        }
+
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            ui.label(
+                RichText::new("Trusted IP ranges")
+                    .heading()
+                    .color(theme.pine),
+            )
+        });
+        ui.label("Logins from anywhere in these ranges are trusted the same as trusted IPs");
+
+        let ranges = self.store.ip_ranges();
+        let mut remove = None;
+        ui.push_id("trusted_range_table", |ui| {
+            ui.set_max_height(150.0);
+            egui_extras::TableBuilder::new(ui)
+                .striped(true)
+                .resizable(false)
+                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+                .column(Column::remainder())
+                .column(Column::exact(60.0))
+                .header(20.0, |mut header| {
+                    for title in ["Range", ""] {
+                        header.col(|ui| {
+                            ui.label(title);
+                        });
+                    }
+                })
+                .body(|body| {
+                    body.rows(20.0, ranges.len(), |i, mut row| {
+                        row.col(|ui| {
+                            ui.label(ranges[i].to_string());
+                        });
+                        row.col(|ui| {
+                            if ui.button("Remove").clicked() {
+                                remove = Some(ranges[i]);
+                            }
+                        });
+                    });
+                });
+        });
+
+        if let Some(range) = remove {
+            self.store.remove_ip_range(range);
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.new_range)
+                    .hint_text("130.127.0.0/16")
+                    .desired_width(200.0),
+            );
+            if ui.button("Add range").clicked() && !self.new_range.trim().is_empty() {
+                match self.new_range.parse::<IpRange>() {
+                    Ok(range) => {
+                        self.store.add_ip_range(range);
+                        self.new_range.clear();
+                        self.new_range_error = false;
+                    }
+                    Err(()) => self.new_range_error = true,
+                }
+            }
+            if self.new_range_error {
+                ui.label(RichText::new("Not a range, such as 10.0.0.0/8").color(theme.love));
+            }
+        });
+
+        ui.separator();
+        ui.vertical_centered(|ui| ui.label(RichText::new("Shortcuts").heading().color(theme.pine)));
+        ui.label("Press ? over Duplex or Simplex to see its shortcuts");
+        self.keymap_ui(ui);
+    }
+
+    fn keymap_ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let keymap = self.keymap.clone();
+        egui::Grid::new("keymap_grid").striped(true).show(ui, |ui| {
+            for action in Action::ALL {
+                ui.label(action.panel());
+                ui.label(action.to_string());
+                ui.horizontal(|ui| {
+                    for key in self.keymap.keys(action).to_vec() {
+                        if ui
+                            .small_button(format!("{} ✖", keymap::key_name(key)))
+                            .on_hover_text("Unbind")
+                            .clicked()
+                        {
+                            self.keymap.unbind(action, key);
+                        }
+                    }
+                    egui::ComboBox::from_id_source(("add_key", action.to_string()))
+                        .selected_text("Add")
+                        .width(50.0)
+                        .show_ui(ui, |ui| {
+                            for key in keymap::bindable_keys() {
+                                if ui.selectable_label(false, keymap::key_name(key)).clicked() {
+                                    self.keymap_error = self.keymap.bind(action, key).err();
+                                }
+                            }
+                        });
+                });
+                ui.end_row();
+            }
+        });
+        if let Some(e) = &self.keymap_error {
+            ui.label(RichText::new(e).color(theme.love));
+        }
+        if ui.button("Reset shortcuts").clicked() {
+            self.keymap = Keymap::default();
+            self.keymap_error = None;
+        }
+        if self.keymap != keymap {
+            self.store.set_keymap(self.keymap.clone());
+        }
+    }
+}
+
+/// Connect and read seconds for each source, true if any changed
+fn timeouts_ui(ui: &mut egui::Ui, timeouts: &mut TimeoutConfig) -> bool {
+    let mut changed = false;
+    egui::Grid::new("timeouts").show(ui, |ui| {
+        ui.label("");
+        ui.label("Connect");
+        ui.label("Read");
+        ui.end_row();
+        let sources: [(&str, &mut Timeouts); 4] = [
+            ("Splunk", &mut timeouts.splunk),
+            ("HDTools", &mut timeouts.hdtools),
+            ("IP lookups", &mut timeouts.ip),
+            ("Osiris", &mut timeouts.osiris),
+        ];
+        for (name, source) in sources {
+            ui.label(name);
+            for duration in [&mut source.connect, &mut source.read] {
+                let mut secs = duration.as_secs();
+                ui.add(
+                    egui::DragValue::new(&mut secs)
+                        .clamp_range(1..=600)
+                        .suffix(" s"),
+                );
+                if secs != duration.as_secs() {
+                    *duration = std::time::Duration::from_secs(secs);
+                    changed = true;
+                }
+            }
+            ui.end_row();
+        }
+    });
+    changed
+}
+
+/// Dropdown of every [Zone] on offer
+fn zone_combo(ui: &mut egui::Ui, id: &str, zone: &mut Zone) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(zone.to_string())
+        .show_ui(ui, |ui| {
+            for choice in Zone::choices() {
+                ui.selectable_value(zone, choice, choice.to_string());
+            }
+        });
+}
@@ -1,23 +1,88 @@
 //! Duplex but for one user
 //!
 //! This app shows the Duo logs of a single user.
-use super::color;
+use super::{
+    color, keymap,
+    panels::{Badge, PanelMessage},
+};
 use crate::{
+    prefs::keymap::Action,
+    queries::{
+        splunk::{Search, TimeSpan},
+        QueryError,
+    },
     store::Store,
     user::{
-        login::{Integration, LoginResult, Reason},
-        User,
+        login::{Integration, Login, LoginResult, Reason},
+        ActivitySummary, LocationCount, User,
     },
 };
+use chrono::{DateTime, Utc};
 use egui::{Label, RichText};
 use std::{rc::Rc, thread::JoinHandle};
 
+/// Filters the table is built with, see [Shown]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RowFilter {
+    /// Only logins from this [Login::summary_location]
+    location: Option<String>,
+    /// Only logins from the past this many days
+    days: Option<i64>,
+    /// Only logins that didn't succeed
+    failures: bool,
+}
+
+impl RowFilter {
+    fn shows(&self, login: &Login, now: DateTime<Utc>) -> bool {
+        self.days
+            .is_none_or(|d| login.time >= now - chrono::Duration::days(d))
+            && (!self.failures || login.result != LoginResult::Success)
+            && self
+                .location
+                .as_ref()
+                .is_none_or(|l| *l == login.summary_location())
+    }
+}
+
+/// What's shown for the pulled user.  Tallying and filtering tens of thousands of logins every
+/// frame makes long pulls crawl, so this is built once per pull and the rows again only when a
+/// filter changes.
+struct Shown {
+    summary: Vec<LocationCount>,
+    activity: ActivitySummary,
+    filter: RowFilter,
+    /// Indexes into the user's logins
+    rows: Vec<usize>,
+    /// Oldest and newest of the rows
+    span: Option<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
 pub struct Simplex {
     days: i64,
-    pull_user: Option<JoinHandle<Option<User>>>,
+    pull_user: Option<JoinHandle<Result<User, QueryError>>>,
+    /// Why the last pull failed
+    error: Option<QueryError>,
     store: Rc<Store>,
     user: Option<User>,
     user_name: String,
+    /// Only logins from this [Login::summary_location] are shown
+    location_filter: Option<String>,
+    /// Only show logins from the past [recent_days](Self::recent_days)
+    recent_only: bool,
+    recent_days: i64,
+    /// Only show logins that didn't succeed
+    failures_only: bool,
+    /// Built from [user](Self::user) and the filters, see [Simplex::update_shown]
+    shown: Option<Shown>,
+    /// Shortcut help is open
+    show_help: bool,
+    /// Focus the user input next frame, see [PanelMessage::Focus]
+    focus: bool,
+    /// Splunk searches of the last pull, see [super::copy_searches_button]
+    searches: Vec<Search>,
+    login_export: super::LoginExport,
+    raw_event: super::RawEventWindow,
+    repeats: super::Repeats,
 }
 
 impl Simplex {
@@ -27,33 +92,173 @@ impl Simplex {
             user_name: String::new(),
             store,
             pull_user: None,
+            error: None,
             days: 14,
+            location_filter: None,
+            recent_only: false,
+            recent_days: 7,
+            failures_only: false,
+            shown: None,
+            show_help: false,
+            focus: false,
+            searches: Vec::new(),
+            login_export: super::LoginExport::default(),
+            raw_event: super::RawEventWindow::new("simplex_raw_event"),
+            repeats: super::Repeats::default(),
+        }
+    }
+
+    pub const NAME: &'static str = "☎ Simplex";
+
+    /// Starts pulling `user`'s logs over the chosen days
+    fn pull(&mut self, user: String) {
+        let timespan: TimeSpan = chrono::Duration::days(self.days).into();
+        self.searches = self.store.simplex_searches(&user, &timespan);
+        self.pull_user = Some(self.store.run_simplex(user, timespan));
+    }
+
+    fn row_filter(&self) -> RowFilter {
+        RowFilter {
+            location: self.location_filter.clone(),
+            days: self.recent_only.then_some(self.recent_days),
+            failures: self.failures_only,
+        }
+    }
+
+    /// Rebuilds [shown](Self::shown) if the user or a filter changed since it was built
+    fn update_shown(&mut self) {
+        let Some(user) = &self.user else {
+            self.shown = None;
+            return;
+        };
+        let filter = self.row_filter();
+        if self.shown.as_ref().is_some_and(|s| s.filter == filter) {
+            return;
+        }
+        let (summary, activity) = match self.shown.take() {
+            Some(shown) => (shown.summary, shown.activity),
+            None => (user.location_summary(), user.activity_summary()),
+        };
+        let now = Utc::now();
+        let rows: Vec<usize> = (0..user.logins.len())
+            .filter(|&i| filter.shows(&user.logins[i], now))
+            .collect();
+        let times = rows.iter().map(|&i| user.logins[i].time);
+        let span = times.clone().min().zip(times.max());
+        self.shown = Some(Shown {
+            summary,
+            activity,
+            filter,
+            rows,
+            span,
+        });
+    }
+
+    /// Quick filters for long pulls
+    fn filter_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.recent_only, "Only the past");
+            ui.add_enabled(
+                self.recent_only,
+                egui::DragValue::new(&mut self.recent_days)
+                    .clamp_range(1..=90)
+                    .suffix(" days"),
+            );
+            ui.separator();
+            ui.checkbox(&mut self.failures_only, "Only failures")
+                .on_hover_text("Failures, fraud reports, and anything else that didn't succeed");
+        });
+    }
+
+    /// How many logins are shown and when they're from
+    fn footer(&self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let (Some(user), Some(shown)) = (&self.user, &self.shown) else {
+            return;
+        };
+        let zone = self.store.display_zone();
+        let mut text = format!("{} of {} logins", shown.rows.len(), user.logins.len());
+        if let Some((oldest, newest)) = shown.span {
+            text += &format!(
+                ", {} to {}",
+                zone.to_zone(oldest).format("%T %D"),
+                zone.to_zone(newest).format("%T %D")
+            );
+        }
+        ui.label(RichText::new(text).color(theme.subtle));
+    }
+
+    fn handle_keypresses(&mut self, ctx: &egui::Context) {
+        let keymap = self.store.keymap();
+        ctx.input(|i| {
+            if keymap::help_pressed(i) {
+                self.show_help = !self.show_help;
+            }
+            if i.key_pressed(egui::Key::Enter) && self.pull_user.is_none() {
+                self.pull(self.user_name.to_owned());
+            }
+            if keymap.pressed(i, Action::MoreDays) {
+                self.days = (self.days + 7).min(90);
+            }
+            if keymap.pressed(i, Action::FewerDays) {
+                self.days = (self.days - 7).max(7);
+            }
+        });
+        if ctx.input(|i| keymap.pressed(i, Action::CopySummary)) {
+            // The summary has the real name, phone, and IPs
+            if let Some(user) = self.user.as_ref().filter(|_| !self.store.redact().0) {
+                ctx.output_mut(|o| o.copied_text = summary(user));
+            }
         }
     }
 
     fn top_bar(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
             ui.horizontal(|ui| {
                 ui.heading("User");
                 let enabled = self.pull_user.is_none();
                 ui.add_enabled_ui(enabled, |ui| {
-                    ui.text_edit_singleline(&mut self.user_name);
+                    let input = ui.text_edit_singleline(&mut self.user_name);
+                    if std::mem::take(&mut self.focus) {
+                        input.request_focus();
+                        ui.ctx().move_to_top(ui.layer_id());
+                    }
                     ui.add(egui::Slider::new(&mut self.days, 7..=90).text("days"));
 
                     if ui.button("Pull logs").clicked() {
                         ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
-                        self.pull_user =
-                            Some(self.store.run_simplex(self.user_name.to_owned(), self.days));
+                        self.pull(self.user_name.to_owned());
                     }
                 });
                 if !enabled {
                     ui.spinner();
                 }
+                if let Some(user) = self.user.as_ref().filter(|_| !self.store.redact().0) {
+                    let keys = self.store.keymap().describe(Action::CopySummary);
+                    let copy = ui
+                        .button("Copy summary")
+                        .on_hover_text(format!("Shortcut: {}", keys));
+                    if copy.clicked() {
+                        ui.output_mut(|o| o.copied_text = summary(user));
+                    }
+                }
+                if self.user.is_some() {
+                    super::copy_searches_button(ui, &self.searches, self.store.redact());
+                }
+                if let (Some(user), Some(shown)) = (&self.user, &self.shown) {
+                    self.login_export
+                        .menu(ui, &self.store, user, || shown.rows.clone());
+                }
+                if let Some(e) = &self.error {
+                    ui.label(RichText::new(format!("Lookup failed: {}", e)).color(theme.rose));
+                }
             });
         });
     }
 
-    fn hdtools_bar(&mut self, ui: &mut egui::Ui) {
+    fn hdtools_bar(&self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
             let user = &self.user.as_ref().expect("Simplex failed to get user");
             if user.creation_date.is_some() || user.location.is_some() {
@@ -62,32 +267,72 @@ impl Simplex {
                     ui.separator();
                 }
                 if let Some(loc) = &user.location {
-                    ui.label(loc.to_string());
+                    let label = ui.label(loc.to_string());
+                    if let Some(home) = user.home {
+                        label.context_menu(|ui| super::location_menu(ui, home));
+                    }
+                }
+                if let Some(record) = &user.address_record {
+                    super::address_record(ui, *record, &user.addresses);
+                }
+                if let Some(affiliation) = &user.affiliation {
+                    ui.separator();
+                    ui.label(affiliation);
+                }
+                if let Some(status) = &user.status {
+                    ui.separator();
+                    ui.label(RichText::new(status).color(if user.is_disabled() {
+                        theme.love
+                    } else {
+                        theme.text
+                    }));
                 }
             } else {
-                ui.label(RichText::new("No HDTools info").color(color::ROSE));
+                super::hdtools_missing(ui, user.hdtools_status);
+            }
+            if !user.admin_events.is_empty() {
+                ui.separator();
+                super::admin_events_menu(ui, &user.admin_events, self.store.display_zone());
             }
         });
     }
 
     fn table(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.separator();
 
+        let row_height = super::row_height(ui);
         let table = egui_extras::TableBuilder::new(ui)
             .striped(true)
             .resizable(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(egui_extras::Column::auto(), 6)
+            .columns(egui_extras::Column::auto(), 7)
             .column(egui_extras::Column::remainder());
         let user = &self.user.as_ref().expect("Simplex failed to get user");
+        let rows = &self
+            .shown
+            .as_ref()
+            .expect("Simplex failed to build rows")
+            .rows;
+        let zone = self.store.display_zone();
+        let redact = self.store.redact();
+        let display = self.repeats.display(&user.logins, rows);
+        // Set from the context menus, header, and badges, applied once the table no longer
+        // borrows the user
+        let mut raw_event = None;
+        let mut disposition = None;
+        let mut location_override = None;
+        let mut collapse = self.repeats.collapse;
+        let mut toggle = None;
         table
-            .header(20.0, |mut header| {
+            .header(row_height, |mut header| {
                 header.col(|ui| {
-                    ui.label("Time")
+                    ui.label(format!("Time ({})", zone.abbreviation(chrono::Utc::now())))
                         .on_hover_text("Right click for Cherwell templates");
                 });
                 header.col(|ui| {
                     ui.label("Result");
+                    super::Repeats::header_toggle(ui, &mut collapse);
                 });
                 header.col(|ui| {
                     ui.label("Reason").on_hover_text("Hehe monkey");
@@ -98,32 +343,51 @@ impl Simplex {
                 header.col(|ui| {
                     ui.label("Integration");
                 });
+                header.col(|ui| {
+                    ui.label("Device").on_hover_ui(|ui| {
+                        ui.label("Mouse over for versions and trust");
+                        ui.label(RichText::new("- Orange for untrusted device").color(theme.rose));
+                    });
+                });
                 header.col(|ui| {
                     ui.label("IP").on_hover_ui(|ui| {
                         ui.label(
                             "Left click to copy to clipboard\nRight click to view service details",
                         );
-                        ui.label(RichText::new("- Green for CUVPN IP").color(color::FOAM));
-                        ui.label(RichText::new("- Orange for known proxy").color(color::ROSE));
+                        ui.label(RichText::new("- Green for CUVPN IP").color(theme.foam));
+                        ui.label(RichText::new("- Orange for known proxy").color(theme.rose));
+                        ui.label(RichText::new("- Purple for trusted IP").color(theme.iris));
                     });
                 });
                 header.col(|ui| {
                     ui.label("Location").on_hover_text(
-                        "Left click to copy to clipboard\nRight click to copy coordinates",
+                        "Left click to copy to clipboard\nRight click for coordinates and a map link",
                     );
                 });
             })
             .body(|body| {
-                body.rows(20.0, user.logins.len(), |i, mut row| {
-                    let login = &user.logins[i];
+                body.rows(row_height, display.len(), |i, mut row| {
+                    let (r, run) = display[i];
+                    let login = &user.logins[rows[r]];
                     row.col(|ui| {
                         ui.add(
-                            egui::Label::new(format!("{}", login.time.format("%T %D")))
-                                .sense(egui::Sense::click()),
+                            egui::Label::new(format!(
+                                "{}",
+                                zone.to_zone(login.time).format("%T %D")
+                            ))
+                            .sense(egui::Sense::click()),
                         )
                         .context_menu(|ui| {
                             if ui.button("Copy username").clicked() {
-                                ui.output_mut(|o| o.copied_text = login.user.to_owned());
+                                ui.output_mut(|o| o.copied_text = redact.user(&login.user));
+                            }
+                            if let Some(event) = super::raw_event_button(ui, login, redact) {
+                                raw_event = Some(event);
+                            }
+                            if let Some(phone) = user.phone.as_ref().filter(|_| !redact.0) {
+                                if ui.button("Copy phone").clicked() {
+                                    ui.output_mut(|o| o.copied_text = phone.to_owned());
+                                }
                             }
                             if ui.button("Copy short description").clicked() {
                                 ui.output_mut(|o| {
@@ -134,33 +398,8 @@ impl Simplex {
                             if !analyst_name.is_empty() && ui.button("Copy first contact").clicked()
                             {
                                 ui.output_mut(|o| {
-                                    if login.result == LoginResult::Fraud {
-                                        o.copied_text = format!(
-                                            std::include_str!(
-                                                "../../templates/first_contact_fraud.txt"
-                                            ),
-                                            analyst_name,
-                                            login.time.format("%m/%d"),
-                                            login.time.format("%I:%M %p"),
-                                            login.factor,
-                                            login
-                                                .format_location()
-                                                .unwrap_or_else(|| "CUVPN".to_owned()),
-                                            analyst_name
-                                        )
-                                    } else {
-                                        o.copied_text = format!(
-                                            std::include_str!("../../templates/first_contact.txt"),
-                                            analyst_name,
-                                            login.time.format("%m/%d"),
-                                            login.time.format("%I:%M %p"),
-                                            login.factor,
-                                            login
-                                                .format_location()
-                                                .unwrap_or_else(|| "CUVPN".to_owned()),
-                                            analyst_name
-                                        )
-                                    }
+                                    o.copied_text =
+                                        super::first_contact(login, analyst_name, zone)
                                 });
                             }
                             if ui.button("Copy password reset").clicked() {
@@ -179,21 +418,24 @@ impl Simplex {
                                 ui.close_menu();
                             }
                         });
+                        if let Some(len) = run {
+                            let open = self.repeats.is_open(login);
+                            if super::Repeats::badge(ui, len, open) {
+                                toggle = Some(rows[r]);
+                            }
+                        }
                     });
                     row.col(|ui| {
-                        ui.label(RichText::new(login.result.to_string()).color(
-                            match login.result {
-                                LoginResult::Failure => color::ROSE,
-                                LoginResult::Fraud => color::LOVE,
-                                _ => color::TEXT,
-                            },
-                        ));
+                        if let Some(picked) = super::login_result(ui, login) {
+                            disposition = Some((rows[r], picked));
+                        }
                     });
                     row.col(|ui| {
                         ui.label(RichText::new(login.reason.to_string()).color(
                             match login.reason {
-                                Reason::DenyUnenrolledUser => color::ROSE,
-                                _ => color::TEXT,
+                                Reason::DenyUnenrolledUser => theme.rose,
+                                Reason::RestrictedOFAC => theme.gold,
+                                _ => theme.text,
                             },
                         ));
                     });
@@ -201,120 +443,197 @@ impl Simplex {
                         ui.label(login.factor.to_string());
                     });
                     row.col(|ui| {
-                        ui.label(RichText::new(login.integration.to_string()).color(
-                            match login.integration {
-                                Integration::CuVpn => color::FOAM,
-                                Integration::Citrix => color::FOAM,
-                                Integration::Dmp => color::LOVE,
-                                _ => color::TEXT,
-                            },
-                        ));
+                        let integration =
+                            ui.label(RichText::new(login.integration.to_string()).color(
+                                match login.integration {
+                                    Integration::CuVpn => theme.foam,
+                                    Integration::Citrix => theme.foam,
+                                    Integration::Dmp => theme.love,
+                                    _ => theme.text,
+                                },
+                            ));
+                        if let Some(key) = &login.application_key {
+                            integration.on_hover_text(format!("Application key {}", key));
+                        }
+                    });
+                    row.col(|ui| {
+                        if let Some(device) = login.format_device() {
+                            ui.label(RichText::new(device).color(
+                                if login.trusted_device == Some(false) {
+                                    theme.rose
+                                } else {
+                                    theme.text
+                                },
+                            ))
+                            .on_hover_ui(|ui| {
+                                if let Some(browser) = &login.browser {
+                                    ui.label(format!(
+                                        "{} {}",
+                                        browser,
+                                        login.browser_version.as_deref().unwrap_or_default()
+                                    ));
+                                }
+                                if let Some(os) = &login.os {
+                                    ui.label(format!(
+                                        "{} {}",
+                                        os,
+                                        login.os_version.as_deref().unwrap_or_default()
+                                    ));
+                                }
+                                ui.label(match login.trusted_device {
+                                    Some(true) => "Trusted",
+                                    Some(false) => "Not trusted",
+                                    None => "Trust unknown",
+                                });
+                            });
+                        }
                     });
                     row.col(|ui| {
                         if let Some(ip) = login.ip {
                             let lable = ui
                                 .add(
-                                    Label::new(RichText::new(ip.to_string()).color(
+                                    Label::new(RichText::new(redact.ip(ip)).color(
                                         if login.is_vpn_ip() {
-                                            color::FOAM
+                                            theme.foam
+                                        } else if self.store.is_ip_allowlisted(ip)
+                                            || self.store.trusted_range(ip).is_some()
+                                        {
+                                            theme.iris
                                         } else if login.is_relay {
-                                            color::ROSE
+                                            theme.rose
                                         } else {
-                                            color::TEXT
+                                            theme.text
                                         },
                                     ))
                                     .sense(egui::Sense::click()),
                                 )
                                 .on_hover_text(login.asn.as_deref().unwrap_or_default())
                                 .context_menu(|ui| {
-                                    if let Some(ipinfo) = self.store.get_ipthreat(ip) {
-                                        if ipinfo.vibe_check() {
-                                            ui.label("Nothing funky");
-                                        } else {
-                                            ui.vertical(|ui| {
-                                                if ipinfo.is_tor {
-                                                    ui.label("✅Tor");
-                                                }
-
-                                                if ipinfo.is_icloud_relay {
-                                                    ui.label("✅iCloud Relay");
-                                                }
-
-                                                if ipinfo.is_proxy {
-                                                    ui.label("✅Proxy");
-                                                }
-
-                                                if ipinfo.is_datacenter {
-                                                    ui.label("✅Datacenter");
-                                                }
-
-                                                if ipinfo.is_anonymous {
-                                                    ui.label("✅Anonymous");
-                                                }
-
-                                                if ipinfo.is_known_attacker {
-                                                    ui.label("✅Known Attacker");
-                                                }
-
-                                                if ipinfo.is_known_abuser {
-                                                    ui.label("✅Known Abuser");
-                                                }
-
-                                                if ipinfo.is_threat {
-                                                    ui.label("✅Threat");
-                                                }
-
-                                                if ipinfo.is_bogon {
-                                                    ui.label("✅Bogon");
-                                                }
-
-                                                if !ipinfo.blocklists.is_empty() {
-                                                    ui.label("✅Blocklists");
-                                                }
-                                            });
+                                    if self.store.is_ip_allowlisted(ip) {
+                                        if ui.button("Untrust this IP").clicked() {
+                                            self.store.remove_allowlisted_ip(ip);
+                                            ui.close_menu();
+                                        }
+                                    } else if ui.button("Trust this IP").clicked() {
+                                        self.store.add_allowlisted_ip(ip);
+                                        ui.close_menu();
+                                    }
+                                    if self.store.trusted_range(ip).is_some() {
+                                        ui.label("In a trusted range");
+                                    }
+                                    ui.separator();
+                                    match self.store.get_ipthreat(ip) {
+                                        Ok(ipinfo) => {
+                                            if ipinfo.vibe_check() {
+                                                ui.label("Nothing funky");
+                                            } else {
+                                                ui.vertical(|ui| {
+                                                    if ipinfo.is_tor {
+                                                        ui.label("✅Tor");
+                                                    }
+
+                                                    if ipinfo.is_icloud_relay {
+                                                        ui.label("✅iCloud Relay");
+                                                    }
+
+                                                    if ipinfo.is_proxy {
+                                                        ui.label("✅Proxy");
+                                                    }
+
+                                                    if ipinfo.is_datacenter {
+                                                        ui.label("✅Datacenter");
+                                                    }
+
+                                                    if ipinfo.is_anonymous {
+                                                        ui.label("✅Anonymous");
+                                                    }
+
+                                                    if ipinfo.is_known_attacker {
+                                                        ui.label("✅Known Attacker");
+                                                    }
+
+                                                    if ipinfo.is_known_abuser {
+                                                        ui.label("✅Known Abuser");
+                                                    }
+
+                                                    if ipinfo.is_threat {
+                                                        ui.label("✅Threat");
+                                                    }
+
+                                                    if ipinfo.is_bogon {
+                                                        ui.label("✅Bogon");
+                                                    }
+
+                                                    if !ipinfo.blocklists.is_empty() {
+                                                        ui.label("✅Blocklists");
+                                                    }
+                                                });
+                                            }
+                                        }
+                                        Err(e) if e.is_transient() => {
+                                            ui.label(
+                                                RichText::new(format!(
+                                                    "IP info temporarily unavailable, {}",
+                                                    e
+                                                ))
+                                                .color(theme.gold),
+                                            );
+                                        }
+                                        Err(_) => {
+                                            ui.label(
+                                                RichText::new("Could not fetch IP info")
+                                                    .color(theme.rose),
+                                            );
                                         }
-                                    } else {
-                                        ui.label(
-                                            RichText::new("Could not fetch IP info")
-                                                .color(color::ROSE),
-                                        );
                                     }
                                 });
                             if lable.clicked() {
-                                ui.output_mut(|o| o.copied_text = ip.to_string());
+                                ui.output_mut(|o| o.copied_text = redact.ip(ip));
                             }
                         }
                     });
                     row.col(|ui| {
-                        if let Some(loc) = login.format_location() {
-                            let label =
-                                ui.add(Label::new(loc.as_str()).sense(egui::Sense::click()));
-                            if label.clicked() {
-                                ui.output_mut(|o| o.copied_text = loc);
-                            }
-                            if label.secondary_clicked() {
-                                ui.output_mut(|o| {
-                                    o.copied_text = login
-                                        .location
-                                        .map(|l| format!("{}, {}", l.0, l.1))
-                                        .unwrap_or_default()
-                                });
-                            }
+                        if let Some(picked) = super::login_location(ui, login) {
+                            location_override = Some(picked);
                         }
                     });
                 });
             });
+        if let Some(event) = raw_event {
+            self.raw_event.open(event);
+        }
+        if let Some((i, disposition)) = disposition {
+            let user = self.user.as_mut().expect("Simplex failed to get user");
+            self.store.set_fraud_disposition(user, i, disposition);
+        }
+        if let Some((ip, location)) = location_override {
+            let user = self.user.as_mut().expect("Simplex failed to get user");
+            self.store.set_location_override(user, ip, location);
+        }
+        self.repeats.collapse = collapse;
+        if let Some(i) = toggle {
+            let user = self.user.as_ref().expect("Simplex failed to get user");
+            self.repeats.toggle(&user.logins[i]);
+        }
     }
 
     fn ui(&mut self, ui: &mut egui::Ui) {
+        self.update_shown();
         egui_extras::StripBuilder::new(ui)
-            .sizes(egui_extras::Size::exact(20.0), 2)
+            .sizes(egui_extras::Size::exact(20.0), 5)
             .size(egui_extras::Size::remainder().at_least(100.0))
+            .size(egui_extras::Size::exact(20.0))
             .vertical(|mut strip| {
                 strip.cell(|ui| self.top_bar(ui));
-                if self.user.is_some() {
+                if let Some(shown) = &self.shown {
                     strip.cell(|ui| self.hdtools_bar(ui));
+                    strip.cell(|ui| super::activity_strip(ui, &shown.activity));
+                    strip.cell(|ui| {
+                        super::location_chips(ui, &shown.summary, &mut self.location_filter)
+                    });
+                    strip.cell(|ui| self.filter_bar(ui));
                     strip.cell(|ui| self.table(ui));
+                    strip.cell(|ui| self.footer(ui));
                 }
             });
     }
@@ -322,15 +641,26 @@ impl Simplex {
 
 impl super::panels::Panel for Simplex {
     fn name(&self) -> &'static str {
-        "☎ Simplex"
+        Self::NAME
     }
 
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
         if let Some(pull_user) = &self.pull_user {
             if pull_user.is_finished() {
                 if let Some(rx) = self.pull_user.take() {
-                    if let Some(l) = rx.join().expect("Couldn't get more logs from thread") {
-                        self.user = Some(l);
+                    match rx.join().expect("Couldn't get more logs from thread") {
+                        Ok(l) => {
+                            self.user = Some(l);
+                            self.shown = None;
+                            self.error = None;
+                        }
+                        Err(e) => self.error = Some(e),
                     }
                 }
                 self.pull_user = None;
@@ -340,30 +670,93 @@ impl super::panels::Panel for Simplex {
             }
         }
 
-        egui::Window::new(
-            RichText::new(format!("{}: Just a Few Beers Please", self.name())).color(color::GOLD),
-        )
-        .open(open)
-        .default_size(egui::vec2(800.0, 600.0))
-        .vscroll(false)
-        .show(ctx, |ui| {
-            if self.pull_user.is_some() {
-                ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
-            }
-            self.ui(ui);
+        let rect = super::panels::show_window(
+            ctx,
+            egui::Window::new(
+                RichText::new(format!("{}: Just a Few Beers Please", self.name()))
+                    .color(theme.gold),
+            )
+            .open(open)
+            .default_size(egui::vec2(800.0, 600.0))
+            .vscroll(false),
+            place,
+            |ui| {
+                if self.pull_user.is_some() {
+                    ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Progress);
+                }
+                self.ui(ui);
 
-            if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
-                ctx.input(|o| {
-                    if o.key_pressed(egui::Key::Enter) && self.pull_user.is_none() {
-                        self.pull_user =
-                            Some(self.store.run_simplex(self.user_name.to_owned(), self.days));
-                    }
-                });
-            }
-        });
+                if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
+                    self.handle_keypresses(ctx);
+                }
+            },
+        );
+        if *open {
+            keymap::help_overlay(
+                ctx,
+                &self.store.keymap(),
+                "Simplex",
+                &[("Enter", "Pull logs")],
+                &mut self.show_help,
+            );
+            self.raw_event.show(ctx);
+        }
+
+        rect
     }
 
     fn desc(&self) -> &'static str {
         "Lookup single user"
     }
+
+    fn badge(&self) -> Option<Badge> {
+        if self.pull_user.is_some() {
+            Some(Badge::Busy)
+        } else {
+            self.error
+                .as_ref()
+                .map(|e| Badge::Warning(format!("Lookup failed: {}", e)))
+        }
+    }
+
+    fn handle_message(&mut self, message: PanelMessage) {
+        match message {
+            PanelMessage::Focus => self.focus = true,
+            // A pull already running is left to finish
+            PanelMessage::SetUser(user) if self.pull_user.is_none() => {
+                self.pull(user.to_owned());
+                self.user_name = user;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The user's HDTools details, login results, and where they logged in from, for tickets
+fn summary(user: &User) -> String {
+    let mut lines = vec![format!("User: {}", user.name)];
+    if let Some(loc) = &user.location {
+        lines.push(format!("Home: {}", loc));
+    }
+    if let Some(cd) = &user.creation_date {
+        lines.push(format!("Created: {}", cd.format("%m/%d/%Y")));
+    }
+    if let Some(status) = &user.status {
+        lines.push(format!("Status: {}", status));
+    }
+    let count = |result: LoginResult| user.logins.iter().filter(|l| l.result == result).count();
+    lines.push(format!(
+        "Logins: {} ({} successes, {} failures, {} fraud)",
+        user.logins.len(),
+        count(LoginResult::Success),
+        count(LoginResult::Failure),
+        count(LoginResult::Fraud)
+    ));
+    for loc in user.location_summary() {
+        lines.push(format!(
+            "{}: {} logins, {} flagged",
+            loc.location, loc.count, loc.flagged
+        ));
+    }
+    lines.join("\n")
 }
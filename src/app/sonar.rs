@@ -2,18 +2,29 @@
 //!
 //! This app queies the splunk `splunk_network_cisco` and `splunk_network_ise` indexes for IP/MAC/User
 //! of a specified IP/MAC/User.
-use std::{net::Ipv4Addr, rc::Rc};
+use std::{
+    net::Ipv4Addr,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
-use egui::{Label, RichText};
+use egui::{Label, ProgressBar, RichText};
 
-use crate::store::Store;
+use crate::{queries::splunk::Search, store::Store};
 
-use super::color;
+use super::{
+    color,
+    panels::{Badge, PanelMessage},
+};
 
 pub struct Sonar {
     store: Rc<Store>,
     lookup: String,
     details: std::sync::Arc<std::sync::RwLock<Details>>,
+    /// Focus the lookup input next frame, see [PanelMessage::Focus]
+    focus: bool,
+    /// Splunk searches the last lookup started with, see [super::copy_searches_button]
+    searches: Vec<Search>,
 }
 
 impl Sonar {
@@ -22,41 +33,67 @@ impl Sonar {
             store,
             lookup: String::default(),
             details: std::sync::Arc::new(std::sync::RwLock::new(Details::default())),
+            focus: false,
+            searches: Vec::new(),
         }
     }
+
+    pub const NAME: &'static str = "🔘 Sonar";
+
+    /// Clears the last lookup's details and starts looking up `lookup`
+    fn pull(&mut self, lookup: String) {
+        self.details
+            .write()
+            .expect("Failed to get write lock on details")
+            .clear();
+        self.searches = self.store.sonar_searches(&lookup);
+        self.store.run_sonar(lookup, &self.details);
+    }
 }
 
 impl super::panels::Panel for Sonar {
     fn name(&self) -> &'static str {
-        "🔘 Sonar"
+        Self::NAME
     }
 
     fn desc(&self) -> &'static str {
         "Find IP/MAC/User"
     }
 
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
-        egui::Window::new(
-            RichText::new(format!("{}: I'm up in yo crib dawg", self.name())).color(color::GOLD),
-        )
-        .open(open)
-        .vscroll(false)
-        .resizable(true)
-        .fixed_size(egui::vec2(200.0, 100.0))
-        .show(ctx, |ui| {
-            self.ui(ui);
-            if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
-                ctx.input(|o| {
-                    if o.key_pressed(egui::Key::Enter) {
-                        self.details
-                            .write()
-                            .expect("Failed to get write lock on details")
-                            .clear();
-                        self.store.run_sonar(self.lookup.to_string(), &self.details);
-                    }
-                });
-            }
-        });
+    fn badge(&self) -> Option<Badge> {
+        // Only locked for writing while a result is added, which means it's running anyway
+        let running = self.details.try_read().ok().is_none_or(|d| d.running);
+        running.then_some(Badge::Busy)
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        let rect = super::panels::show_window(
+            ctx,
+            egui::Window::new(
+                RichText::new(format!("{}: I'm up in yo crib dawg", self.name())).color(theme.gold),
+            )
+            .open(open)
+            .vscroll(false)
+            .resizable(true)
+            .fixed_size(egui::vec2(200.0, 100.0)),
+            place,
+            |ui| {
+                self.ui(ui);
+                if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
+                    ctx.input(|o| {
+                        if o.key_pressed(egui::Key::Enter) {
+                            self.pull(self.lookup.to_string());
+                        }
+                    });
+                }
+            },
+        );
 
         if self
             .details
@@ -67,10 +104,31 @@ impl super::panels::Panel for Sonar {
             std::thread::sleep(std::time::Duration::from_millis(10));
             ctx.request_repaint(); // Call repaint to re-check if the thread is finished
         }
+
+        rect
+    }
+
+    fn handle_message(&mut self, message: PanelMessage) {
+        match message {
+            PanelMessage::Focus => self.focus = true,
+            // A lookup already running is left to finish
+            PanelMessage::SetLookup(lookup) if !self.running() => {
+                self.pull(lookup.to_owned());
+                self.lookup = lookup;
+            }
+            _ => (),
+        }
     }
 }
 
 impl Sonar {
+    fn running(&self) -> bool {
+        self.details
+            .read()
+            .expect("Failed to get read lock on details")
+            .running
+    }
+
     fn ui(&mut self, ui: &mut egui::Ui) {
         egui_extras::StripBuilder::new(ui)
             .size(egui_extras::Size::exact(20.0))
@@ -85,18 +143,19 @@ impl Sonar {
                             .expect("Failed to get read lock on details")
                             .running;
                         ui.add_enabled_ui(enabled, |ui| {
-                            ui.text_edit_singleline(&mut self.lookup);
+                            let input = ui.text_edit_singleline(&mut self.lookup);
+                            if std::mem::take(&mut self.focus) {
+                                input.request_focus();
+                                ui.ctx().move_to_top(ui.layer_id());
+                            }
                             if ui.button("Pull details").clicked() {
-                                self.details
-                                    .write()
-                                    .expect("Failed to get write lock on details")
-                                    .clear();
-                                self.store.run_sonar(self.lookup.to_string(), &self.details);
+                                self.pull(self.lookup.to_string());
                             }
                         });
                         if !enabled {
                             ui.spinner();
                         }
+                        super::copy_searches_button(ui, &self.searches, self.store.redact());
                     });
                 });
                 strip.cell(|ui| {
@@ -111,8 +170,26 @@ impl Sonar {
                 .details
                 .read()
                 .expect("Failed to get read lock on details");
+            let redact = self.store.redact();
             if details.running {
                 ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Wait);
+                ui.label("Progress");
+                let elapsed = details.started.map(|s| s.elapsed()).unwrap_or_default();
+                let text = match details.eta(elapsed) {
+                    Some(eta) => format!(
+                        "{}/{} queries, ~{}s left",
+                        details.completed,
+                        details.planned,
+                        eta.as_secs()
+                    ),
+                    None => format!("{}/{} queries", details.completed, details.planned),
+                };
+                ui.add(
+                    ProgressBar::new(details.progress())
+                        .desired_width(250.0)
+                        .text(text),
+                );
+                ui.end_row();
             }
             ui.label("IP");
             let ip = ui.add(
@@ -120,7 +197,7 @@ impl Sonar {
                     details
                         .ips
                         .iter()
-                        .map(|ip| ip.to_string())
+                        .map(|ip| redact.ip(ip))
                         .collect::<Vec<String>>()
                         .join(", "),
                 )
@@ -131,30 +208,29 @@ impl Sonar {
                     o.copied_text = details
                         .ips
                         .first()
-                        .map(|ip| ip.to_string())
+                        .map(|ip| redact.ip(ip))
                         .unwrap_or_default()
                 });
             }
             ui.end_row();
 
             ui.label("MAC");
-            let mac = ui.add(Label::new(details.macs.join(", ")).sense(egui::Sense::click()));
+            let macs: Vec<String> = details.macs.iter().map(|m| redact.mac(m)).collect();
+            let mac = ui.add(Label::new(macs.join(", ")).sense(egui::Sense::click()));
             if mac.clicked() {
-                ui.output_mut(|o| {
-                    o.copied_text = details.macs.first().cloned().unwrap_or_default()
-                });
+                ui.output_mut(|o| o.copied_text = macs.first().cloned().unwrap_or_default());
             }
             ui.end_row();
 
             ui.label("User");
-            let user = ui.add(
-                Label::new(details.user.as_deref().unwrap_or_default().to_string())
-                    .sense(egui::Sense::click()),
-            );
+            let name = details
+                .user
+                .as_deref()
+                .map(|u| redact.user(u))
+                .unwrap_or_default();
+            let user = ui.add(Label::new(name.as_str()).sense(egui::Sense::click()));
             if user.clicked() {
-                ui.output_mut(|o| {
-                    o.copied_text = details.user.as_deref().unwrap_or_default().to_string()
-                });
+                ui.output_mut(|o| o.copied_text = name);
             }
             ui.end_row();
         });
@@ -167,6 +243,13 @@ pub struct Details {
     pub macs: Vec<String>,
     pub user: Option<String>,
     pub running: bool,
+    /// Splunk queries the lookup is expected to make.  Later rounds search for whatever earlier
+    /// ones find, so this is revised each round, see [plan](Self::plan).
+    pub planned: usize,
+    /// Splunk queries finished so far, found something or not
+    pub completed: usize,
+    /// When the lookup started
+    pub started: Option<Instant>,
 }
 
 impl Details {
@@ -175,5 +258,47 @@ impl Details {
         self.macs.clear();
         self.user = None;
         self.running = false;
+        self.planned = 0;
+        self.completed = 0;
+        self.started = None;
+    }
+
+    /// Queries one round makes with what's been found so far.  Every MAC and IP is searched for
+    /// the other, and for the user until one is known.  A known user is searched for both.
+    pub fn round_queries(ips: usize, macs: usize, user: bool) -> usize {
+        if user {
+            ips + macs + 2
+        } else {
+            (ips + macs) * 2
+        }
+    }
+
+    /// Expects `remaining` more queries after those already finished
+    pub fn plan(&mut self, remaining: usize) {
+        self.planned = self.completed + remaining;
+    }
+
+    /// Counts a finished query, stretching the plan if a round found more than expected
+    pub fn finish_query(&mut self) {
+        self.completed += 1;
+        self.planned = self.planned.max(self.completed);
+    }
+
+    /// Fraction of planned queries finished
+    pub fn progress(&self) -> f32 {
+        if self.planned == 0 {
+            return 0.0;
+        }
+        self.completed as f32 / self.planned as f32
+    }
+
+    /// Rough time left after `elapsed`, from the average query so far.  [None] until a query
+    /// finishes.
+    pub fn eta(&self, elapsed: Duration) -> Option<Duration> {
+        if self.completed == 0 {
+            return None;
+        }
+        let remaining = self.planned.saturating_sub(self.completed) as u32;
+        Some(elapsed / self.completed as u32 * remaining)
     }
 }
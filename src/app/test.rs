@@ -0,0 +1,390 @@
+#![cfg(test)]
+use super::panels::{self, Envelope, Mailbox, Panel, PanelMessage};
+use std::{cell::RefCell, rc::Rc};
+
+// -------------------- Panels --------------------
+
+/// Messages a [Fake] was handed, shared with the test
+type Inbox = Rc<RefCell<Vec<PanelMessage>>>;
+
+/// Keeps every message it's handed
+struct Fake {
+    name: &'static str,
+    messages: Inbox,
+}
+
+impl Panel for Fake {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn desc(&self) -> &'static str {
+        "Fake"
+    }
+
+    fn show(
+        &mut self,
+        _ctx: &egui::Context,
+        _open: &mut bool,
+        _place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        None
+    }
+
+    fn handle_message(&mut self, message: PanelMessage) {
+        self.messages.borrow_mut().push(message);
+    }
+}
+
+/// Fake panels named `names` and what each was handed
+fn fakes(names: &[&'static str]) -> (Vec<Box<dyn Panel>>, Vec<Inbox>) {
+    let inboxes: Vec<_> = names.iter().map(|_| Rc::default()).collect();
+    let panels = names
+        .iter()
+        .zip(&inboxes)
+        .map(|(&name, messages)| {
+            Box::new(Fake {
+                name,
+                messages: Rc::clone(messages),
+            }) as Box<dyn Panel>
+        })
+        .collect();
+    (panels, inboxes)
+}
+
+#[test]
+fn route_to_named_panel() {
+    let (mut panels, inboxes) = fakes(&["Simplex", "Sonar"]);
+    let opened = panels::route(
+        &mut panels,
+        vec![
+            Envelope {
+                to: "Sonar",
+                message: PanelMessage::SetLookup("10.0.0.1".to_owned()),
+            },
+            Envelope {
+                to: "Simplex",
+                message: PanelMessage::Open,
+            },
+            Envelope {
+                to: "Simplex",
+                message: PanelMessage::SetUser("abc123".to_owned()),
+            },
+            Envelope {
+                to: "Simplex",
+                message: PanelMessage::Open,
+            },
+        ],
+    );
+
+    assert_eq!(opened, vec!["Simplex"]);
+    assert_eq!(
+        *inboxes[0].borrow(),
+        vec![
+            PanelMessage::Open,
+            PanelMessage::SetUser("abc123".to_owned()),
+            PanelMessage::Open,
+        ]
+    );
+    assert_eq!(
+        *inboxes[1].borrow(),
+        vec![PanelMessage::SetLookup("10.0.0.1".to_owned())]
+    );
+}
+
+#[test]
+fn route_drops_unknown_panel() {
+    let (mut panels, inboxes) = fakes(&["Visor"]);
+    let opened = panels::route(
+        &mut panels,
+        vec![Envelope {
+            to: "Zeppelin",
+            message: PanelMessage::Open,
+        }],
+    );
+
+    assert!(opened.is_empty());
+    assert!(inboxes[0].borrow().is_empty());
+}
+
+#[test]
+fn mailbox_open_with() {
+    let mailbox = Mailbox::default();
+    mailbox
+        .clone()
+        .open_with("Visor", PanelMessage::SetUser("abc123".to_owned()));
+    let (mut panels, inboxes) = fakes(&["Visor"]);
+    let opened = panels::route(&mut panels, mailbox.take());
+
+    assert_eq!(opened, vec!["Visor"]);
+    assert_eq!(
+        *inboxes[0].borrow(),
+        vec![
+            PanelMessage::Open,
+            PanelMessage::Focus,
+            PanelMessage::SetUser("abc123".to_owned()),
+        ]
+    );
+    // Taken messages aren't handed out again
+    assert!(mailbox.take().is_empty());
+}
+
+// -------------------- Travel map --------------------
+
+#[test]
+fn travel_map_points() {
+    use super::travel_map::{hops, map_points};
+    use crate::user::{
+        login::{FlagReason, LoginResult, Reason},
+        test::{login, new_user},
+    };
+
+    let clemson = (34.68, -82.84);
+    let moscow = (55.75, 37.62);
+    let from = |minutes, location, travel| {
+        let mut login = login(minutes, LoginResult::Success, Reason::UserApproved);
+        login.location = location;
+        if travel {
+            login.flag_reasons.push(FlagReason::Travel);
+        }
+        login
+    };
+    let paris = (48.86, 2.35);
+    let mut user = new_user(vec![
+        from(0, Some(clemson), true),
+        from(1, Some(moscow), true),
+        from(2, Some(moscow), true),
+        from(3, None, false),
+        from(4, Some(clemson), true),
+        from(5, Some(paris), true),
+        // Too old to be checked
+        from(5000, Some(moscow), false),
+    ]);
+    user.travel_hops = vec![(0, 1), (4, 5)];
+
+    let points = map_points(&user);
+    // Longitude goes across
+    let at = |(lat, lon): (f32, f32)| [f64::from(lon), f64::from(lat)];
+    assert_eq!(
+        points,
+        vec![
+            (0, at(clemson)),
+            (1, at(moscow)),
+            (2, at(moscow)),
+            (4, at(clemson)),
+            (5, at(paris)),
+        ]
+    );
+    // Nothing between the two Moscow logins, and flagged ends only make travel when they were
+    // scored as a pair
+    assert_eq!(
+        hops(&user, &points),
+        vec![
+            (at(clemson), at(moscow), true),
+            (at(moscow), at(clemson), false),
+            (at(clemson), at(paris), true),
+        ]
+    );
+}
+
+// -------------------- Ignored --------------------
+
+#[test]
+fn ignored_list() {
+    use crate::storage::Storage;
+
+    let storage = Storage::in_memory();
+    storage.set_analyst_name("Sam".to_owned());
+    storage.mark_investigated("Tiger".to_owned(), true, Some("Called them"));
+    storage.mark_many_investigated(&["cat".to_owned()]);
+
+    let mut ignored = storage.list_investigated();
+    ignored.sort_by(|a, b| a.name.cmp(&b.name));
+    let rows: Vec<(&str, &str, &str)> = ignored
+        .iter()
+        .map(|i| (i.name.as_str(), i.note.as_str(), i.analyst.as_str()))
+        .collect();
+    assert_eq!(rows, [("cat", "", "Sam"), ("tiger", "Called them", "Sam")]);
+    // Just marked, so nearly the whole day is left
+    let remaining = ignored[1].remaining();
+    assert!(remaining <= chrono::Duration::hours(24));
+    assert!(remaining > chrono::Duration::hours(23));
+
+    // Un-ignoring takes a user off the list
+    storage.mark_investigated("tiger".to_owned(), false, None);
+    let names: Vec<String> = storage
+        .list_investigated()
+        .into_iter()
+        .map(|i| i.name)
+        .collect();
+    assert_eq!(names, ["cat"]);
+
+    storage.clear_investigated();
+    assert!(storage.list_investigated().is_empty());
+    assert!(!storage.investigated("cat"));
+}
+
+// -------------------- Sonar --------------------
+
+#[test]
+fn sonar_progress() {
+    use super::sonar::Details;
+    use std::time::Duration;
+
+    let mut details = Details::default();
+    assert_eq!(details.progress(), 0.0);
+    assert_eq!(details.eta(Duration::from_secs(5)), None);
+
+    // One IP and no user, two rounds of a MAC and a user lookup
+    details.plan(2 * Details::round_queries(1, 0, false));
+    assert_eq!(details.planned, 4);
+    details.finish_query();
+    assert_eq!(details.progress(), 0.25);
+    assert_eq!(
+        details.eta(Duration::from_secs(10)),
+        Some(Duration::from_secs(30))
+    );
+
+    // The first round found a MAC, so the second has more to do
+    details.finish_query();
+    details.plan(Details::round_queries(1, 1, false));
+    assert_eq!(details.planned, 6);
+
+    // Running over the plan stretches it rather than passing 100%
+    for _ in 0..5 {
+        details.finish_query();
+    }
+    assert_eq!(details.planned, 7);
+    assert_eq!(details.progress(), 1.0);
+    assert_eq!(details.eta(Duration::from_secs(7)), Some(Duration::ZERO));
+}
+
+// -------------------- Logs --------------------
+
+#[test]
+fn log_buffer() {
+    use super::logs::{self, LogBuffer, LogRecord};
+
+    let buffer = LogBuffer::default();
+    let record = |level, message: String| LogRecord {
+        time: chrono::Utc::now(),
+        level,
+        target: "horus::store".to_owned(),
+        message,
+    };
+    for i in 0..logs::RECENT + 5 {
+        buffer.push(record(log::Level::Info, i.to_string()));
+    }
+    buffer.push(record(log::Level::Warn, "slow".to_owned()));
+    buffer.push(record(log::Level::Error, "failed".to_owned()));
+
+    let everything = buffer.recent(log::Level::Info);
+    assert_eq!(everything.len(), logs::RECENT);
+    assert_eq!(everything[0].message, "failed");
+    // The oldest were dropped
+    assert_eq!(everything[logs::RECENT - 1].message, "7");
+
+    let warnings = buffer.recent(log::Level::Warn);
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|r| r.message.as_str())
+            .collect::<Vec<_>>(),
+        vec!["failed", "slow"]
+    );
+    assert_eq!(buffer.recent(log::Level::Error).len(), 1);
+}
+
+// -------------------- Zeppelin --------------------
+
+#[test]
+fn zeppelin_negative_totals() {
+    use super::zeppelin::negative_totals;
+
+    let counts = vec![
+        ("Phishing".to_owned(), 3),
+        ("Malware".to_owned(), 0),
+        ("Compromised account".to_owned(), 5),
+    ];
+    assert!(negative_totals(&counts, &counts, &[0, 0, 0]).is_empty());
+    // Taking a total to zero is fine
+    assert!(negative_totals(&counts, &counts, &[-3, 2, -5]).is_empty());
+    assert_eq!(negative_totals(&counts, &counts, &[-4, -1, -5]), vec![0, 1]);
+
+    // 2 under Phising and 3 under Phishing, shown as 5 under Phishing email.  Osiris has nothing
+    // under the new name to take away from.
+    let counts = vec![("Phishing email".to_owned(), 5)];
+    let posted = vec![("Phising".to_owned(), 2), ("Phishing".to_owned(), 3)];
+    assert_eq!(negative_totals(&counts, &posted, &[-4]), vec![0]);
+    assert!(negative_totals(&counts, &posted, &[4]).is_empty());
+    // Only what's under the name sent can be taken away
+    let posted = vec![("Phising".to_owned(), 2), ("Phishing email".to_owned(), 3)];
+    assert!(negative_totals(&counts, &posted, &[-3]).is_empty());
+    assert_eq!(negative_totals(&counts, &posted, &[-4]), vec![0]);
+}
+
+#[test]
+fn zeppelin_rename_row() {
+    use super::zeppelin::rename_row;
+
+    let mut counts = vec![
+        ("Phising".to_owned(), 2),
+        ("Malware".to_owned(), 1),
+        ("Phishing".to_owned(), 3),
+    ];
+    let mut add = vec![1, 0, 2];
+    rename_row(&mut counts, &mut add, "Malware", "Malicious software");
+    assert_eq!(counts[1].0, "Malicious software");
+    // Renaming into an existing row adds them together
+    rename_row(&mut counts, &mut add, "Phising", "Phishing");
+    assert_eq!(
+        counts,
+        vec![
+            ("Malicious software".to_owned(), 1),
+            ("Phishing".to_owned(), 5),
+        ]
+    );
+    assert_eq!(add, vec![0, 3]);
+    // Nothing to rename
+    rename_row(&mut counts, &mut add, "Fraud", "Scam");
+    assert_eq!(counts.len(), 2);
+}
+
+// -------------------- Run report --------------------
+
+#[test]
+fn run_report() {
+    use super::report::{escape, run_report};
+    use crate::queries::splunk::TimeSpan;
+    use crate::user::{login::FlagReason, zone::Zone, QueueStats, User};
+    use chrono::TimeZone;
+
+    assert_eq!(
+        escape("<b>\"Tom & Jerry\"</b>"),
+        "&lt;b&gt;&quot;Tom &amp; Jerry&quot;&lt;/b&gt;"
+    );
+
+    let range = TimeSpan {
+        start: chrono::Utc.with_ymd_and_hms(2024, 3, 1, 22, 0, 0).unwrap(),
+        end: chrono::Utc.with_ymd_and_hms(2024, 3, 2, 6, 0, 0).unwrap(),
+    };
+    let mut user = User::new(
+        "<tiger>".to_owned(),
+        vec![],
+        &range.start,
+        chrono::Duration::days(1),
+    );
+    user.score = 20;
+    user.reasons = vec![FlagReason::Fraud];
+    let users = vec![user];
+    let stats = QueueStats::new(&users, std::time::Duration::from_secs(90));
+
+    let html = run_report(&users, &stats, &range, Zone::Utc);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("2024-03-01 22:00 UTC to 2024-03-02 06:00 UTC"));
+    assert!(html.contains("<tr><td>Users flagged</td><td>1</td></tr>"));
+    assert!(html.contains("<tr><td>Flagged for Fraud</td><td>1</td></tr>"));
+    // Names are escaped
+    assert!(html.contains("<h3>&lt;tiger&gt; &mdash; 20 pts</h3>"));
+    assert!(!html.contains("<tiger>"));
+}
@@ -0,0 +1,142 @@
+//! Map of a Duplex user's logins
+//!
+//! Plots each checked login with a location on a flat longitude and latitude grid, with a line
+//! between each login and the one before it.  Hops flagged for impossible travel are drawn thick
+//! and red, which reads a lot quicker than a column of city names.  It's drawn offline with no
+//! tile service, so nothing about the user leaves the machine.
+use super::color;
+use crate::prefs::redact::Redact;
+use crate::user::{
+    login::{FlagReason, Login},
+    zone::Zone,
+    User,
+};
+use egui::plot::{Line, MarkerShape, Plot, PlotPoint, PlotPoints, Points};
+use egui::RichText;
+
+/// How close a click has to be to a login to pick it, in points
+const CLICK_RADIUS: f32 = 10.0;
+
+/// Checked logins with a location, newest first, as indexes into the user's logins along with
+/// `[lon, lat]` so longitude runs across
+pub(super) fn map_points(user: &User) -> Vec<(usize, [f64; 2])> {
+    user.logins
+        .iter()
+        .take(user.checked_login_count)
+        .enumerate()
+        .filter_map(|(i, login)| {
+            login
+                .location
+                .map(|(lat, lon)| (i, [f64::from(lon), f64::from(lat)]))
+        })
+        .collect()
+}
+
+/// Lines between consecutive `points`, and whether that pair of logins scored as impossible
+/// travel.  Logins from the same place don't get a line.
+pub(super) fn hops(user: &User, points: &[(usize, [f64; 2])]) -> Vec<([f64; 2], [f64; 2], bool)> {
+    points
+        .windows(2)
+        .filter(|pair| pair[0].1 != pair[1].1)
+        .map(|pair| {
+            let travel = user.travel_hops.contains(&(pair[0].0, pair[1].0));
+            (pair[0].1, pair[1].1, travel)
+        })
+        .collect()
+}
+
+/// Shows the map, giving back the index of the login clicked on, if one was
+pub(super) fn travel_map(
+    ui: &mut egui::Ui,
+    user: &User,
+    zone: Zone,
+    redact: Redact,
+) -> Option<usize> {
+    let theme = color::theme(ui.ctx());
+    let points = map_points(user);
+    if points.is_empty() {
+        ui.label(RichText::new("No checked logins have a location to map").color(theme.subtle));
+        return None;
+    }
+    let hops = hops(user, &points);
+
+    Plot::new(("travel_map", &user.name))
+        .data_aspect(1.0)
+        .show_axes([false, false])
+        .label_formatter(|name, value| {
+            if name.is_empty() {
+                format!("{:.2}, {:.2}", value.y, value.x)
+            } else {
+                name.to_owned()
+            }
+        })
+        .show(ui, |plot_ui| {
+            for (from, to, travel) in hops {
+                plot_ui.line(
+                    Line::new(PlotPoints::new(vec![from, to]))
+                        .color(if travel { theme.love } else { theme.subtle })
+                        .width(if travel { 2.5 } else { 1.0 }),
+                );
+            }
+            if let Some((lat, lon)) = user.home {
+                plot_ui.points(
+                    Points::new(vec![[f64::from(lon), f64::from(lat)]])
+                        .shape(MarkerShape::Diamond)
+                        .radius(6.0)
+                        .color(theme.iris)
+                        .name("Home"),
+                );
+            }
+            for &(i, position) in &points {
+                let login = &user.logins[i];
+                plot_ui.points(
+                    Points::new(vec![position])
+                        .radius(4.0)
+                        .color(login_color(&theme, login))
+                        .name(hover_text(login, zone, redact)),
+                );
+            }
+
+            if !plot_ui.plot_clicked() {
+                return None;
+            }
+            let pointer = plot_ui.screen_from_plot(plot_ui.pointer_coordinate()?);
+            points
+                .iter()
+                .map(|&(i, [x, y])| {
+                    let point = plot_ui.screen_from_plot(PlotPoint::new(x, y));
+                    (i, point.distance(pointer))
+                })
+                .filter(|&(_, distance)| distance <= CLICK_RADIUS)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i)
+        })
+        .inner
+}
+
+/// Same colors as the time column of the login table
+fn login_color(theme: &color::Theme, login: &Login) -> egui::Color32 {
+    if login.flag_reasons.is_empty() {
+        theme.text
+    } else if login.flag_reasons.contains(&FlagReason::Restricted) {
+        theme.gold
+    } else {
+        theme.love
+    }
+}
+
+/// Time, IP, and city of a login, shown when it's hovered
+fn hover_text(login: &Login, zone: Zone, redact: Redact) -> String {
+    let ip = login
+        .ip
+        .map(|ip| redact.ip(ip))
+        .unwrap_or_else(|| "No IP".to_owned());
+    format!(
+        "{}\n{}\n{}",
+        zone.to_zone(login.time).format("%T %D"),
+        ip,
+        login
+            .format_location()
+            .unwrap_or_else(|| "Unknown".to_owned())
+    )
+}
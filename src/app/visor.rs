@@ -5,17 +5,22 @@
 //! correlate with.
 use egui::RichText;
 
-use crate::{store::Store, user::vpnlog::VpnLog};
+use crate::{queries::QueryError, store::Store, user::vpnlog::VpnLog};
 use std::rc::Rc;
 
-use super::color;
+use super::{
+    color,
+    panels::{Badge, PanelMessage},
+};
 
 pub struct Visor {
     store: Rc<Store>,
     user: String,
     vpn_logs: Vec<VpnLog>,
-    vpn_rx: Option<std::thread::JoinHandle<Option<Vec<VpnLog>>>>,
-    failed: bool,
+    vpn_rx: Option<std::thread::JoinHandle<Result<Vec<VpnLog>, QueryError>>>,
+    failed: Option<QueryError>,
+    /// Focus the user input next frame, see [PanelMessage::Focus]
+    focus: bool,
 }
 
 impl Visor {
@@ -25,11 +30,15 @@ impl Visor {
             user: String::new(),
             vpn_logs: vec![],
             vpn_rx: None,
-            failed: false,
+            failed: None,
+            focus: false,
         }
     }
 
+    pub const NAME: &'static str = "🕶 Visor";
+
     pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         egui_extras::StripBuilder::new(ui)
             .size(egui_extras::Size::exact(20.0))
             .size(egui_extras::Size::remainder())
@@ -39,7 +48,11 @@ impl Visor {
                         ui.label("User");
                         let enabled = self.vpn_rx.is_none();
                         ui.add_enabled_ui(enabled, |ui| {
-                            ui.text_edit_singleline(&mut self.user);
+                            let input = ui.text_edit_singleline(&mut self.user);
+                            if std::mem::take(&mut self.focus) {
+                                input.request_focus();
+                                ui.ctx().move_to_top(ui.layer_id());
+                            }
                             if ui.button("Pull vpn activity").clicked() {
                                 self.vpn_rx = Some(self.store.run_visor(self.user.to_string()));
                             }
@@ -47,8 +60,10 @@ impl Visor {
                         if !enabled {
                             ui.spinner();
                         }
-                        if self.failed {
-                            ui.label(RichText::new("Lookup failed").color(color::ROSE));
+                        if let Some(e) = &self.failed {
+                            ui.label(
+                                RichText::new(format!("Lookup failed: {}", e)).color(theme.rose),
+                            );
                         }
                     });
                 });
@@ -63,8 +78,11 @@ impl Visor {
                                 .join()
                                 .expect("Couldn't get logs from thread");
                             match logs {
-                                Some(logs) => self.vpn_logs = logs,
-                                None => self.failed = true,
+                                Ok(logs) => {
+                                    self.vpn_logs = logs;
+                                    self.failed = None;
+                                }
+                                Err(e) => self.failed = Some(e),
                             }
                             ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Default);
                             self.vpn_rx = None;
@@ -79,20 +97,31 @@ impl Visor {
     }
 
     fn table(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
+        let zone = self.store.display_zone();
+        let redact = self.store.redact();
+        let row_height = super::row_height(ui);
         egui_extras::TableBuilder::new(ui)
             .striped(true)
             .resizable(true)
             .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-            .columns(egui_extras::Column::auto(), 4)
+            .columns(egui_extras::Column::auto(), 5)
             .column(egui_extras::Column::remainder())
-            .header(20.0, |mut header| {
+            .header(row_height, |mut header| {
                 header.col(|ui| {
-                    ui.label("Time").on_hover_ui(|ui| {
-                        ui.label(
-                            RichText::new("Green for correlation with last log").color(color::FOAM),
-                        );
-                        ui.label(RichText::new("Red for no correlation").color(color::LOVE));
-                    });
+                    ui.label(format!("Time ({})", zone.abbreviation(chrono::Utc::now())))
+                        .on_hover_ui(|ui| {
+                            ui.label(
+                                RichText::new("Green for correlation with last log")
+                                    .color(theme.foam),
+                            );
+                            ui.label(RichText::new("Red for no correlation").color(theme.love));
+                        });
+                });
+                header.col(|ui| {
+                    ui.label("Session").on_hover_text(
+                        "How long the VPN stayed connected, hover for when it ended",
+                    );
                 });
                 header.col(|ui| {
                     ui.label("Source IP");
@@ -104,36 +133,45 @@ impl Visor {
                     ui.label("Platform");
                 });
                 header.col(|ui| {
-                    ui.label("Location");
+                    ui.label("Location")
+                        .on_hover_text("Right click for coordinates and a map link");
                 });
             })
             .body(|body| {
-                body.rows(20.0, self.vpn_logs.len(), |i, mut row| {
+                body.rows(row_height, self.vpn_logs.len(), |i, mut row| {
                     let log = &self.vpn_logs[i];
                     row.col(|ui| {
-                        ui.label(RichText::new(log.time.format("%T %D").to_string()).color(
-                            if log.correlate_prev {
-                                color::FOAM
-                            } else {
-                                color::LOVE
-                            },
-                        ));
+                        ui.label(
+                            RichText::new(zone.to_zone(log.time).format("%T %D").to_string())
+                                .color(if log.correlate_prev {
+                                    theme.foam
+                                } else {
+                                    theme.love
+                                }),
+                        );
+                    });
+
+                    row.col(|ui| {
+                        let label = ui.label(log.format_duration());
+                        if let Some(end) = log.end {
+                            label.on_hover_text(format!(
+                                "Ended {}",
+                                zone.to_zone(end).format("%T %D")
+                            ));
+                        }
                     });
 
                     row.col(|ui| {
                         let lable = ui
                             .add(
-                                egui::Label::new(RichText::new(log.source_ip.to_string()).color(
-                                    if log.is_relay {
-                                        color::ROSE
-                                    } else {
-                                        color::TEXT
-                                    },
-                                ))
+                                egui::Label::new(
+                                    RichText::new(redact.ip(log.source_ip))
+                                        .color(if log.is_relay { theme.rose } else { theme.text }),
+                                )
                                 .sense(egui::Sense::click()),
                             )
-                            .context_menu(|ui| {
-                                if let Some(ipinfo) = self.store.get_ipthreat(log.source_ip) {
+                            .context_menu(|ui| match self.store.get_ipthreat(log.source_ip) {
+                                Ok(ipinfo) => {
                                     if ipinfo.vibe_check() {
                                         ui.label("Nothing funky");
                                     } else {
@@ -179,27 +217,48 @@ impl Visor {
                                             }
                                         });
                                     }
-                                } else {
+                                }
+                                Err(e) if e.is_transient() => {
+                                    ui.label(
+                                        RichText::new(format!(
+                                            "IP info temporarily unavailable, {}",
+                                            e
+                                        ))
+                                        .color(theme.gold),
+                                    );
+                                }
+                                Err(_) => {
                                     ui.label(
-                                        RichText::new("Could not fetch IP info").color(color::ROSE),
+                                        RichText::new("Could not fetch IP info").color(theme.rose),
                                     );
                                 }
                             });
                         if lable.clicked() {
-                            ui.output_mut(|o| o.copied_text = log.source_ip.to_string());
+                            ui.output_mut(|o| o.copied_text = redact.ip(log.source_ip));
                         }
                     });
 
                     row.col(|ui| {
-                        ui.label(log.dev_mac.as_ref().unwrap_or(&"".to_string()));
+                        ui.label(
+                            log.dev_mac
+                                .as_deref()
+                                .map(|m| redact.mac(m))
+                                .unwrap_or_default(),
+                        );
                     });
 
                     row.col(|ui| {
-                        ui.label(&log.dev_platform);
+                        ui.label(&log.dev_platform).on_hover_text(&log.user_agent);
                     });
 
                     row.col(|ui| {
-                        ui.label(log.format_location().unwrap_or_default());
+                        let label = ui.add(
+                            egui::Label::new(log.format_location().unwrap_or_default())
+                                .sense(egui::Sense::click()),
+                        );
+                        if let Some(location) = log.location {
+                            label.context_menu(|ui| super::location_menu(ui, location));
+                        }
                     });
                 });
             });
@@ -208,37 +267,71 @@ impl Visor {
 
 impl super::panels::Panel for Visor {
     fn name(&self) -> &'static str {
-        "🕶 Visor"
+        Self::NAME
     }
 
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
-        egui::Window::new(
-            RichText::new(format!("{}: Your Grandmother's VPN Multi", self.name()))
-                .color(color::GOLD),
-        )
-        .open(open)
-        .vscroll(false)
-        .resizable(true)
-        .default_size(egui::vec2(500.0, 300.0))
-        .show(ctx, |ui| {
-            self.ui(ui);
-
-            if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
-                ctx.input(|i| {
-                    if i.key_pressed(egui::Key::Enter) && self.vpn_rx.is_none() {
-                        self.vpn_rx = Some(self.store.run_visor(self.user.to_string()));
-                    }
-                });
-            }
-        });
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        let rect = super::panels::show_window(
+            ctx,
+            egui::Window::new(
+                RichText::new(format!("{}: Your Grandmother's VPN Multi", self.name()))
+                    .color(theme.gold),
+            )
+            .open(open)
+            .vscroll(false)
+            .resizable(true)
+            .default_size(egui::vec2(500.0, 300.0)),
+            place,
+            |ui| {
+                self.ui(ui);
+
+                if ui.ui_contains_pointer() && !ctx.wants_keyboard_input() {
+                    ctx.input(|i| {
+                        if i.key_pressed(egui::Key::Enter) && self.vpn_rx.is_none() {
+                            self.vpn_rx = Some(self.store.run_visor(self.user.to_string()));
+                        }
+                    });
+                }
+            },
+        );
 
         if self.vpn_rx.is_some() {
             std::thread::sleep(std::time::Duration::from_millis(10));
             ctx.request_repaint(); // Call repaint to re-check if the thread is finished
         }
+
+        rect
     }
 
     fn desc(&self) -> &'static str {
         "VPN multi"
     }
+
+    fn badge(&self) -> Option<Badge> {
+        match (&self.vpn_rx, &self.failed) {
+            // Logs are only collected while the window is open
+            (Some(rx), _) if rx.is_finished() => Some(Badge::Ready),
+            (Some(_), _) => Some(Badge::Busy),
+            (None, Some(e)) => Some(Badge::Warning(format!("Lookup failed: {}", e))),
+            (None, None) => None,
+        }
+    }
+
+    fn handle_message(&mut self, message: PanelMessage) {
+        match message {
+            PanelMessage::Focus => self.focus = true,
+            // A pull already running is left to finish
+            PanelMessage::SetUser(user) if self.vpn_rx.is_none() => {
+                self.vpn_rx = Some(self.store.run_visor(user.to_owned()));
+                self.user = user;
+            }
+            _ => (),
+        }
+    }
 }
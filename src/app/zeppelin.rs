@@ -2,9 +2,10 @@
 //!
 //! This is the front end for Osiris, the backend metric tracker running on the old wiki server (RIP
 //! In Peace).  This stores no information on default categories and everything is pulled from the
-//! server.
-use super::color;
-use crate::queries::osiris;
+//! server.  Osiris can't rename or remove a category, so renames and archived categories are kept
+//! locally and applied to whatever it sends back.
+use super::{color, panels::Badge};
+use crate::queries::{osiris, QueryError};
 use crate::store::Store;
 use chrono::NaiveDate;
 use egui::RichText;
@@ -17,9 +18,9 @@ pub struct Zeppelin {
     /// Rx might contain a JoinHandle which might return a struct which contains a vector which
     /// contains a tupple which contains a string and a u64 and vector which contains a tupple
     /// which contains a string and a u64
-    rx: Option<JoinHandle<Option<osiris::Data>>>,
+    rx: Option<JoinHandle<Result<osiris::Data, QueryError>>>,
     /// Used to determine if POST was successful
-    tx: Option<JoinHandle<Option<()>>>,
+    tx: Option<JoinHandle<Result<(), QueryError>>>,
     /// Selected date to pull
     date: NaiveDate,
     /// List of incidents and count from server
@@ -34,22 +35,43 @@ pub struct Zeppelin {
     investigation_add: Vec<i64>,
     /// Stores new investigation name
     new_investigation: String,
-    /// True if Zeppelin failed to pull data from Osiris, false otherwise
-    failed: bool,
-    /// True if Zeppelin fails to send data to Osiris
-    post_failed: bool,
+    /// Counts as Osiris has them, before renames are applied.  Adds are sent under a row's name
+    /// only, so they can't take that name's own count below zero.
+    posted: osiris::Data,
+    /// Why Zeppelin failed to pull data from Osiris, if it did
+    failed: Option<QueryError>,
+    /// Why Zeppelin failed to send data to Osiris, if it did
+    post_failed: Option<QueryError>,
     /// Time range for report
     report: (NaiveDate, NaiveDate),
     /// Keeps track of pulling report data
-    report_rx: Option<JoinHandle<()>>,
+    report_rx: Option<JoinHandle<Result<usize, String>>>,
+    /// Rows left out of the last report for not having a date, or why it wasn't saved
+    report_result: Option<Result<usize, String>>,
     /// Output file name
     file: String,
+    /// How the report's rows are grouped
+    aggregation: osiris::Aggregation,
+    /// Whether the window asking to confirm negative adjustments is open
+    confirm_negative: bool,
+    /// Categories hidden unless [show_archived](Self::show_archived) is checked
+    archived: Vec<String>,
+    show_archived: bool,
+}
+
+/// What was picked from a category's right click menu
+enum CategoryAction {
+    /// Old name and new name
+    Rename(String, String),
+    /// Archive the category if true, bring it back if false
+    Archive(String, bool),
 }
 
 impl Zeppelin {
     pub fn new(store: Rc<Store>) -> Self {
         let date = chrono::Local::now().date_naive();
         let rx = Some(store.run_zeppelin(date));
+        let archived = store.archived_categories();
         Self {
             store,
             rx,
@@ -61,11 +83,17 @@ impl Zeppelin {
             investigations: vec![],
             investigation_add: vec![],
             new_investigation: String::new(),
-            failed: false,
-            post_failed: false,
+            posted: osiris::Data::default(),
+            failed: None,
+            post_failed: None,
             report: (date, date),
             report_rx: None,
+            report_result: None,
             file: String::new(),
+            aggregation: osiris::Aggregation::default(),
+            confirm_negative: false,
+            archived,
+            show_archived: false,
         }
     }
 }
@@ -79,12 +107,33 @@ impl super::panels::Panel for Zeppelin {
         "Metric Tracking with Osiris"
     }
 
-    fn show(&mut self, ctx: &egui::Context, open: &mut bool) {
-        egui::Window::new(RichText::new(self.name()).color(color::GOLD))
-            .open(open)
-            .fixed_size(egui::vec2(200.0, 800.0))
-            .vscroll(false)
-            .show(ctx, |ui| {
+    fn badge(&self) -> Option<Badge> {
+        if let Some(e) = &self.post_failed {
+            Some(Badge::Warning(format!("Sending to Osiris failed: {}", e)))
+        } else if let Some(e) = &self.failed {
+            Some(Badge::Warning(format!("Pulling from Osiris failed: {}", e)))
+        } else if self.rx.is_some() || self.tx.is_some() || self.report_rx.is_some() {
+            Some(Badge::Busy)
+        } else {
+            None
+        }
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .fixed_size(egui::vec2(200.0, 800.0))
+                .vscroll(false),
+            place,
+            |ui| {
                 if let Some(rx) = &self.rx {
                     if rx.is_finished() {
                         match self
@@ -94,14 +143,16 @@ impl super::panels::Panel for Zeppelin {
                             .join()
                             .expect("Failed to get Osiris info from thread")
                         {
-                            Some(data) => {
-                                self.failed = false;
+                            Ok(posted) => {
+                                self.failed = None;
+                                self.posted = posted.clone();
+                                let data = posted.alias(&self.store.category_aliases());
                                 self.investigation_add = vec![0; data.investigations.len()];
                                 self.investigations = data.investigations;
                                 self.incident_add = vec![0; data.incidents.len()];
                                 self.incidents = data.incidents;
                             }
-                            None => self.failed = true,
+                            Err(e) => self.failed = Some(e),
                         }
                     } else {
                         ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Wait);
@@ -119,11 +170,11 @@ impl super::panels::Panel for Zeppelin {
                             .join()
                             .expect("Failed to fet Osiris post status from thread")
                         {
-                            Some(_) => {
-                                self.post_failed = false;
+                            Ok(_) => {
+                                self.post_failed = None;
                                 self.rx = Some(self.store.run_zeppelin(self.date));
                             }
-                            None => self.post_failed = true,
+                            Err(e) => self.post_failed = Some(e),
                         }
                     } else {
                         ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Wait);
@@ -134,7 +185,9 @@ impl super::panels::Panel for Zeppelin {
 
                 if let Some(rx) = &self.report_rx {
                     if rx.is_finished() {
-                        self.report_rx = None;
+                        let rx = self.report_rx.take().expect("Failed to take report");
+                        self.report_result =
+                            Some(rx.join().expect("Couldn't get report from thread"));
                     } else {
                         ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Wait);
                         std::thread::sleep(std::time::Duration::from_millis(10));
@@ -142,20 +195,48 @@ impl super::panels::Panel for Zeppelin {
                     }
                 }
 
-                if self.post_failed {
-                    ui.label(RichText::new("Couldn't post data to Osiris").color(color::LOVE));
+                if let Some(e) = &self.post_failed {
+                    ui.label(
+                        RichText::new(format!("Couldn't post data to Osiris: {}", e))
+                            .color(theme.love),
+                    );
                 }
-                if self.failed {
-                    ui.label(RichText::new("Couldn't fetch data from Osiris").color(color::LOVE));
+                if let Some(e) = &self.failed {
+                    ui.label(
+                        RichText::new(format!("Couldn't fetch data from Osiris: {}", e))
+                            .color(theme.love),
+                    );
+                }
+                match &self.report_result {
+                    None => {}
+                    Some(Ok(0)) => {
+                        ui.label(RichText::new("Saved report").color(theme.foam));
+                    }
+                    Some(Ok(skipped)) => {
+                        ui.label(
+                            RichText::new(format!(
+                                "Saved report, left out {} rows from Osiris without a date",
+                                skipped
+                            ))
+                            .color(theme.gold),
+                        );
+                    }
+                    Some(Err(e)) => {
+                        ui.label(
+                            RichText::new(format!("Couldn't save report: {}", e)).color(theme.love),
+                        );
+                    }
                 }
 
                 self.ui(ui);
-            });
+            },
+        )
     }
 }
 
 impl Zeppelin {
     fn ui(&mut self, ui: &mut egui::Ui) {
+        let theme = color::theme(ui.ctx());
         ui.horizontal(|ui| {
             ui.add(
                 egui_extras::DatePickerButton::new(&mut self.date)
@@ -167,6 +248,7 @@ impl Zeppelin {
                     self.rx = Some(self.store.run_zeppelin(self.date));
                 }
             });
+            ui.checkbox(&mut self.show_archived, "Show archived");
             ui.menu_button("Save report", |ui| {
                 ui.add(egui_extras::DatePickerButton::new(&mut self.report.0));
                 ui.add(egui_extras::DatePickerButton::new(&mut self.report.1));
@@ -174,50 +256,52 @@ impl Zeppelin {
                     ui.label("File");
                     ui.text_edit_singleline(&mut self.file);
                 });
+                ui.horizontal(|ui| {
+                    for choice in osiris::Aggregation::choices() {
+                        ui.selectable_value(&mut self.aggregation, choice, choice.to_string());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Only days in the range are saved, weeks and months partly in it are saved \
+                     whole",
+                );
                 if ui.button("Save").clicked() {
-                    self.report_rx =
-                        Some(self.store.save_report(self.file.to_owned(), self.report));
+                    self.report_result = None;
+                    self.report_rx = Some(self.store.save_report(
+                        self.file.to_owned(),
+                        self.report,
+                        self.aggregation,
+                    ));
                 }
             });
         });
 
-        if self.failed {
+        if self.failed.is_some() {
             return;
         }
 
         ui.vertical_centered(|ui| {
-            ui.label(RichText::new("Investigations").heading().color(color::PINE))
-        });
-        ui.push_id("investigation_table", |ui| {
-            ui.set_max_height(300.0);
-            egui_extras::TableBuilder::new(ui)
-                .striped(true)
-                .resizable(false)
-                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-                .column(Column::remainder())
-                .columns(Column::exact(40.0), 2)
-                .header(20.0, |mut header| {
-                    for title in ["Name", "Value", "Add"] {
-                        header.col(|ui| {
-                            ui.label(title);
-                        });
-                    }
-                })
-                .body(|body| {
-                    body.rows(20.0, self.investigations.len(), |i, mut row| {
-                        let inv = &self.investigations[i];
-                        row.col(|ui| {
-                            ui.label(&inv.0);
-                        });
-                        row.col(|ui| {
-                            ui.label(format!("{}", inv.1));
-                        });
-                        row.col(|ui| {
-                            ui.add(egui::DragValue::new(&mut self.investigation_add[i]).speed(0.3));
-                        });
-                    });
-                });
+            ui.label(RichText::new("Investigations").heading().color(theme.pine))
         });
+        // Osiris takes whatever it's sent, so a total below zero has to be caught here
+        let investigations_below = negative_totals(
+            &self.investigations,
+            &self.posted.investigations,
+            &self.investigation_add,
+        );
+        let incidents_below =
+            negative_totals(&self.incidents, &self.posted.incidents, &self.incident_add);
+
+        let investigation_action = counts_table(
+            ui,
+            "investigation_table",
+            &self.investigations,
+            &mut self.investigation_add,
+            &investigations_below,
+            &self.archived,
+            self.show_archived,
+        );
 
         ui.horizontal(|ui| {
             ui.add(egui::TextEdit::singleline(&mut self.new_investigation).desired_width(100.0));
@@ -230,39 +314,16 @@ impl Zeppelin {
         });
 
         ui.separator();
-        ui.vertical_centered(|ui| {
-            ui.label(RichText::new("Incidents").heading().color(color::PINE))
-        });
-        ui.push_id("incident_table", |ui| {
-            ui.set_max_height(300.0);
-            egui_extras::TableBuilder::new(ui)
-                .striped(true)
-                .resizable(false)
-                .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
-                .column(Column::remainder())
-                .columns(Column::exact(40.0), 2)
-                .header(20.0, |mut header| {
-                    for title in ["Name", "Value", "Add"] {
-                        header.col(|ui| {
-                            ui.label(title);
-                        });
-                    }
-                })
-                .body(|body| {
-                    body.rows(20.0, self.incidents.len(), |i, mut row| {
-                        let inc = &self.incidents[i];
-                        row.col(|ui| {
-                            ui.label(&inc.0);
-                        });
-                        row.col(|ui| {
-                            ui.label(format!("{}", inc.1));
-                        });
-                        row.col(|ui| {
-                            ui.add(egui::DragValue::new(&mut self.incident_add[i]).speed(0.3));
-                        });
-                    });
-                });
-        });
+        ui.vertical_centered(|ui| ui.label(RichText::new("Incidents").heading().color(theme.pine)));
+        let incident_action = counts_table(
+            ui,
+            "incident_table",
+            &self.incidents,
+            &mut self.incident_add,
+            &incidents_below,
+            &self.archived,
+            self.show_archived,
+        );
 
         ui.horizontal(|ui| {
             ui.add(egui::TextEdit::singleline(&mut self.new_incident).desired_width(100.0));
@@ -272,45 +333,310 @@ impl Zeppelin {
                 self.new_incident.clear();
             }
         });
+        // Only one menu can be open at a time
+        self.category_action(investigation_action.or(incident_action));
+
+        let below_zero = investigations_below.len() + incidents_below.len();
+        if below_zero > 0 {
+            ui.label(
+                RichText::new(format!(
+                    "{} {} would go below zero, fix the red rows before sending",
+                    below_zero,
+                    if below_zero == 1 { "total" } else { "totals" }
+                ))
+                .color(theme.love),
+            );
+        }
 
         ui.vertical_centered(|ui| {
-            ui.add_enabled_ui(self.tx.is_none(), |ui| {
+            ui.add_enabled_ui(self.tx.is_none() && below_zero == 0, |ui| {
                 if ui.button("Make it so!").clicked() {
-                    let incidents: Vec<_> = self
-                        .incident_add
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(i, inc)| {
-                            if *inc != 0 {
-                                Some((self.incidents[i].0.to_owned(), *inc))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-
-                    let investigations: Vec<_> = self
+                    let negative = self
                         .investigation_add
                         .iter()
-                        .enumerate()
-                        .filter_map(|(i, inv)| {
-                            if *inv != 0 {
-                                Some((self.investigations[i].0.to_owned(), *inv))
-                            } else {
-                                None
-                            }
-                        })
-                        .collect();
-
-                    self.tx = Some(self.store.post_osiris(
-                        self.date,
-                        osiris::Data {
-                            incidents,
-                            investigations,
-                        },
-                    ));
+                        .chain(&self.incident_add)
+                        .any(|add| *add < 0);
+                    if negative {
+                        self.confirm_negative = true;
+                    } else {
+                        self.post();
+                    }
                 }
             });
         });
+
+        self.confirm_ui(ui.ctx());
+    }
+
+    fn category_action(&mut self, action: Option<CategoryAction>) {
+        match action {
+            Some(CategoryAction::Rename(old, new)) => {
+                self.store.rename_category(&old, &new);
+                rename_row(
+                    &mut self.investigations,
+                    &mut self.investigation_add,
+                    &old,
+                    &new,
+                );
+                rename_row(&mut self.incidents, &mut self.incident_add, &old, &new);
+                self.archived = self.store.archived_categories();
+            }
+            Some(CategoryAction::Archive(name, archived)) => {
+                self.store.set_category_archived(&name, archived);
+                self.archived = self.store.archived_categories();
+            }
+            None => (),
+        }
+    }
+
+    /// Asks before sending adjustments that take away from a total
+    fn confirm_ui(&mut self, ctx: &egui::Context) {
+        if !self.confirm_negative {
+            return;
+        }
+        let theme = color::theme(ctx);
+        let removed: Vec<_> = self
+            .investigations
+            .iter()
+            .zip(&self.investigation_add)
+            .chain(self.incidents.iter().zip(&self.incident_add))
+            .filter(|(_, add)| **add < 0)
+            .map(|((name, value), add)| (name, *value, *add))
+            .collect();
+
+        let mut confirmed = false;
+        egui::Window::new("Remove from totals?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(
+                    RichText::new("These adjustments take away from Osiris' totals")
+                        .color(theme.gold),
+                );
+                egui::Grid::new("zeppelin_negative")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (name, value, add) in &removed {
+                            ui.label(name.as_str());
+                            ui.label(RichText::new(add.to_string()).color(theme.love));
+                            ui.label(format!("{} → {}", value, value + add));
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Confirm").clicked() {
+                        confirmed = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.confirm_negative = false;
+                    }
+                });
+            });
+
+        if confirmed {
+            self.confirm_negative = false;
+            self.post();
+        }
+    }
+
+    /// Sends every non-zero adjustment to Osiris
+    fn post(&mut self) {
+        let incidents: Vec<_> = self
+            .incident_add
+            .iter()
+            .enumerate()
+            .filter_map(|(i, inc)| {
+                if *inc != 0 {
+                    Some((self.incidents[i].0.to_owned(), *inc))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        let investigations: Vec<_> = self
+            .investigation_add
+            .iter()
+            .enumerate()
+            .filter_map(|(i, inv)| {
+                if *inv != 0 {
+                    Some((self.investigations[i].0.to_owned(), *inv))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        self.tx = Some(self.store.post_osiris(
+            self.date,
+            osiris::Data {
+                incidents,
+                investigations,
+            },
+        ));
+    }
+}
+
+/// Rows of `counts` that adding `add` would take below zero.  A renamed row's total includes
+/// counts under its old names, but the add is only sent under its new one, so taking away from
+/// it is checked against what `posted` has under that name too.
+pub(super) fn negative_totals(
+    counts: &[(String, i64)],
+    posted: &[(String, i64)],
+    add: &[i64],
+) -> Vec<usize> {
+    let own = |name: &str| {
+        posted
+            .iter()
+            .find(|(n, _)| n == name)
+            .map_or(0, |(_, count)| *count)
+    };
+    counts
+        .iter()
+        .zip(add)
+        .enumerate()
+        .filter(|(_, ((name, value), &add))| value + add < 0 || (add < 0 && own(name) + add < 0))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Renames a row to match a renamed category, adding it into the row that already has the new
+/// name if there is one
+pub(super) fn rename_row(
+    counts: &mut Vec<(String, i64)>,
+    add: &mut Vec<i64>,
+    old: &str,
+    new: &str,
+) {
+    let Some(from) = counts.iter().position(|(name, _)| name == old) else {
+        return;
+    };
+    match counts.iter().position(|(name, _)| name == new) {
+        Some(to) => {
+            counts[to].1 += counts[from].1;
+            add[to] += add[from];
+            counts.remove(from);
+            add.remove(from);
+        }
+        None => counts[from].0 = new.to_owned(),
+    }
+}
+
+/// Categories with their totals, what to add to each, and the total after adding it.  Rows that
+/// would go below zero, see [negative_totals], are red, and are shown even when archived so they
+/// can be fixed.
+fn counts_table(
+    ui: &mut egui::Ui,
+    id: &str,
+    counts: &[(String, i64)],
+    add: &mut [i64],
+    below_zero: &[usize],
+    archived: &[String],
+    show_archived: bool,
+) -> Option<CategoryAction> {
+    let theme = color::theme(ui.ctx());
+    let is_archived = |name: &str| archived.iter().any(|a| a == name);
+    let rows: Vec<usize> = (0..counts.len())
+        .filter(|&i| show_archived || !is_archived(&counts[i].0) || below_zero.contains(&i))
+        .collect();
+    let mut action = None;
+    ui.push_id(id, |ui| {
+        ui.set_max_height(300.0);
+        egui_extras::TableBuilder::new(ui)
+            .striped(true)
+            .resizable(false)
+            .cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+            .column(Column::remainder())
+            .columns(Column::exact(40.0), 3)
+            .header(20.0, |mut header| {
+                for title in ["Name", "Value", "Add", "New"] {
+                    header.col(|ui| {
+                        ui.label(title);
+                    });
+                }
+            })
+            .body(|body| {
+                body.rows(20.0, rows.len(), |r, mut row| {
+                    let i = rows[r];
+                    let (name, value) = &counts[i];
+                    let new = value + add[i];
+                    let below_zero = below_zero.contains(&i);
+                    let archived = is_archived(name);
+                    row.col(|ui| {
+                        let text = if below_zero {
+                            RichText::new(name).color(theme.love)
+                        } else if archived {
+                            RichText::new(name).italics().color(theme.subtle)
+                        } else {
+                            RichText::new(name)
+                        };
+                        ui.label(text)
+                            .on_hover_text("Right click to rename or archive")
+                            .context_menu(|ui| {
+                                if let Some(a) = category_menu(ui, name, archived) {
+                                    action = Some(a);
+                                }
+                            });
+                    });
+                    row.col(|ui| {
+                        ui.label(format!("{}", value));
+                    });
+                    row.col(|ui| {
+                        ui.add(egui::DragValue::new(&mut add[i]).speed(0.3));
+                    });
+                    row.col(|ui| {
+                        let color = if below_zero {
+                            theme.love
+                        } else if add[i] == 0 {
+                            theme.subtle
+                        } else {
+                            theme.text
+                        };
+                        ui.label(RichText::new(format!("{}", new)).color(color));
+                    });
+                });
+            });
+    });
+    action
+}
+
+/// Renames or archives a category
+fn category_menu(ui: &mut egui::Ui, name: &str, archived: bool) -> Option<CategoryAction> {
+    // Kept while the menu is open, starting from the current name
+    let id = egui::Id::new("zeppelin_rename").with(name);
+    let mut text = ui
+        .data_mut(|d| d.get_temp::<String>(id))
+        .unwrap_or_else(|| name.to_owned());
+    let mut picked = None;
+    ui.label("Rename");
+    ui.text_edit_singleline(&mut text).on_hover_text(
+        "Counts Osiris has under the old name are shown and reported under this one, and new \
+        counts are sent with it",
+    );
+    let new = text.trim();
+    if ui
+        .add_enabled(!new.is_empty() && new != name, egui::Button::new("Rename"))
+        .clicked()
+    {
+        picked = Some(CategoryAction::Rename(name.to_owned(), new.to_owned()));
+        ui.close_menu();
+    }
+    ui.separator();
+    let button = if archived {
+        ui.button("Unarchive")
+    } else {
+        ui.button("Archive")
+            .on_hover_text("Hide it unless Show archived is checked, its counts stay in reports")
+    };
+    if button.clicked() {
+        picked = Some(CategoryAction::Archive(name.to_owned(), !archived));
+        ui.close_menu();
+    }
+    if picked.is_some() {
+        ui.data_mut(|d| d.remove::<String>(id));
+    } else {
+        ui.data_mut(|d| d.insert_temp(id, text));
     }
+    picked
 }
@@ -0,0 +1,205 @@
+//! Comparing Osiris between two days
+//!
+//! Zeppelin shows one day at a time, which makes an odd day hard to spot.  This pulls two days the
+//! same way Zeppelin does and shows how much each category changed between them.  It's read only,
+//! counts are still fixed from Zeppelin.
+use super::{color, panels::Badge};
+use crate::queries::{osiris, QueryError};
+use crate::store::Store;
+use chrono::NaiveDate;
+use egui::RichText;
+use std::rc::Rc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often the pulls are checked on while running
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One day's pull from Osiris
+type Pull = JoinHandle<Result<osiris::Data, QueryError>>;
+
+pub struct ZeppelinDiff {
+    store: Rc<Store>,
+    /// Days picked to compare, the change is from the first to the second
+    dates: (NaiveDate, NaiveDate),
+    /// Days being pulled, in case [dates](Self::dates) change before the pulls finish
+    pulling: (NaiveDate, NaiveDate),
+    /// Pulls of each of [pulling](Self::pulling)
+    rx: Option<(Pull, Pull)>,
+    /// Days the shown counts are from, which stay put while new dates are picked
+    shown: Option<(NaiveDate, NaiveDate)>,
+    investigations: Vec<osiris::CountDiff>,
+    incidents: Vec<osiris::CountDiff>,
+    /// Why either day couldn't be pulled, if it couldn't
+    failed: Option<QueryError>,
+}
+
+impl ZeppelinDiff {
+    pub fn new(store: Rc<Store>) -> Self {
+        let today = chrono::Local::now().date_naive();
+        let dates = (today - chrono::Duration::days(1), today);
+        Self {
+            store,
+            dates,
+            pulling: dates,
+            rx: None,
+            shown: None,
+            investigations: vec![],
+            incidents: vec![],
+            failed: None,
+        }
+    }
+
+    /// Takes both pulls once they've finished
+    fn check_rx(&mut self, ctx: &egui::Context) {
+        let Some((before, after)) = &self.rx else {
+            return;
+        };
+        if !before.is_finished() || !after.is_finished() {
+            ctx.request_repaint_after(POLL_INTERVAL);
+            return;
+        }
+
+        let (before, after) = self
+            .rx
+            .take()
+            .expect("Failed to take rx from Zeppelin diff");
+        let before = before
+            .join()
+            .expect("Failed to get Osiris info from thread");
+        let after = after.join().expect("Failed to get Osiris info from thread");
+        match (before, after) {
+            (Ok(before), Ok(after)) => {
+                self.failed = None;
+                let aliases = self.store.category_aliases();
+                let (before, after) = (before.alias(&aliases), after.alias(&aliases));
+                self.shown = Some(self.pulling);
+                self.investigations =
+                    osiris::diff_counts(&before.investigations, &after.investigations);
+                self.incidents = osiris::diff_counts(&before.incidents, &after.incidents);
+            }
+            (Err(e), _) | (_, Err(e)) => self.failed = Some(e),
+        }
+    }
+}
+
+impl super::panels::Panel for ZeppelinDiff {
+    fn name(&self) -> &'static str {
+        "☫ Zeppelin diff"
+    }
+
+    fn desc(&self) -> &'static str {
+        "Compare Osiris metrics between two days"
+    }
+
+    fn badge(&self) -> Option<Badge> {
+        if let Some(e) = &self.failed {
+            Some(Badge::Warning(format!("Pulling from Osiris failed: {}", e)))
+        } else if self.rx.is_some() {
+            Some(Badge::Busy)
+        } else {
+            None
+        }
+    }
+
+    fn show(
+        &mut self,
+        ctx: &egui::Context,
+        open: &mut bool,
+        place: Option<egui::Rect>,
+    ) -> Option<egui::Rect> {
+        let theme = color::theme(ctx);
+        self.check_rx(ctx);
+        super::panels::show_window(
+            ctx,
+            egui::Window::new(RichText::new(self.name()).color(theme.gold))
+                .open(open)
+                .default_size(egui::vec2(350.0, 600.0)),
+            place,
+            |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui_extras::DatePickerButton::new(&mut self.dates.0)
+                            .id_source("diff_from"),
+                    );
+                    ui.label("to");
+                    ui.add(
+                        egui_extras::DatePickerButton::new(&mut self.dates.1).id_source("diff_to"),
+                    );
+                    ui.add_enabled_ui(self.rx.is_none(), |ui| {
+                        if ui.button("Compare").clicked() {
+                            self.pulling = self.dates;
+                            self.rx = Some((
+                                self.store.run_zeppelin(self.dates.0),
+                                self.store.run_zeppelin(self.dates.1),
+                            ));
+                        }
+                    });
+                    if self.rx.is_some() {
+                        ui.spinner();
+                    }
+                });
+
+                if let Some(e) = &self.failed {
+                    ui.label(
+                        RichText::new(format!("Couldn't fetch data from Osiris: {}", e))
+                            .color(theme.love),
+                    );
+                }
+                let Some(shown) = self.shown else {
+                    return;
+                };
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (title, diffs) in [
+                        ("Investigations", &self.investigations),
+                        ("Incidents", &self.incidents),
+                    ] {
+                        ui.vertical_centered(|ui| {
+                            ui.label(RichText::new(title).heading().color(theme.pine));
+                        });
+                        diff_grid(ui, title, diffs, shown);
+                    }
+                });
+            },
+        )
+    }
+}
+
+/// Each category's count on both days and the change, increases in love and decreases in foam
+fn diff_grid(
+    ui: &mut egui::Ui,
+    id: &str,
+    diffs: &[osiris::CountDiff],
+    dates: (NaiveDate, NaiveDate),
+) {
+    let theme = color::theme(ui.ctx());
+    egui::Grid::new(("zeppelin_diff", id))
+        .striped(true)
+        .show(ui, |ui| {
+            for title in [
+                "Category".to_owned(),
+                dates.0.format("%F").to_string(),
+                dates.1.format("%F").to_string(),
+                "Change".to_owned(),
+            ] {
+                ui.label(RichText::new(title).color(theme.pine));
+            }
+            ui.end_row();
+
+            for diff in diffs {
+                ui.label(diff.name.as_str());
+                ui.label(diff.before.to_string());
+                ui.label(diff.after.to_string());
+                let change = diff.change();
+                let text = match change {
+                    c if c > 0 => RichText::new(format!("+{}", c)).color(theme.love),
+                    c if c < 0 => RichText::new(c.to_string()).color(theme.foam),
+                    _ => RichText::new("0").color(theme.subtle),
+                };
+                ui.label(text);
+                ui.end_row();
+            }
+        });
+}
@@ -1,4 +1,5 @@
 mod app;
+mod prefs;
 mod queries;
 mod storage;
 mod store;
@@ -20,7 +21,7 @@ const PHRASES: [&str; 11] = [
 ];
 
 fn main() -> Result<(), eframe::Error> {
-    env_logger::init();
+    app::logs::init();
 
     // You need brail fonts to see this
     log::info!("  ⣀⣤⣶⠾⠿⠿⠿⠿⢶⣦⣤⣀⡀");
@@ -0,0 +1,246 @@
+//! Keyboard shortcuts
+//!
+//! Shortcuts are looked up by [Action] so they can be rebound in settings.  Each panel only
+//! handles its own actions, so the same key can be used by different panels but not twice in one.
+use egui::Key;
+
+/// Keys that can be bound, with the names they are saved and shown as
+const KEYS: [(&str, Key); 40] = [
+    ("A", Key::A),
+    ("B", Key::B),
+    ("C", Key::C),
+    ("D", Key::D),
+    ("E", Key::E),
+    ("F", Key::F),
+    ("G", Key::G),
+    ("H", Key::H),
+    ("I", Key::I),
+    ("J", Key::J),
+    ("K", Key::K),
+    ("L", Key::L),
+    ("M", Key::M),
+    ("N", Key::N),
+    ("O", Key::O),
+    ("P", Key::P),
+    ("Q", Key::Q),
+    ("R", Key::R),
+    ("S", Key::S),
+    ("T", Key::T),
+    ("U", Key::U),
+    ("V", Key::V),
+    ("W", Key::W),
+    ("X", Key::X),
+    ("Y", Key::Y),
+    ("Z", Key::Z),
+    ("0", Key::Num0),
+    ("1", Key::Num1),
+    ("2", Key::Num2),
+    ("3", Key::Num3),
+    ("4", Key::Num4),
+    ("5", Key::Num5),
+    ("6", Key::Num6),
+    ("7", Key::Num7),
+    ("8", Key::Num8),
+    ("9", Key::Num9),
+    ("-", Key::Minus),
+    ("+", Key::PlusEquals),
+    ("Left", Key::ArrowLeft),
+    ("Right", Key::ArrowRight),
+];
+
+/// Name a key is saved and shown as
+pub fn key_name(key: Key) -> &'static str {
+    KEYS.iter()
+        .find(|(_, k)| *k == key)
+        .map_or("?", |(name, _)| name)
+}
+
+fn key_from_name(name: &str) -> Option<Key> {
+    KEYS.iter().find(|(n, _)| *n == name).map(|(_, k)| *k)
+}
+
+/// Every key that can be bound
+pub fn bindable_keys() -> impl Iterator<Item = Key> {
+    KEYS.iter().map(|(_, k)| *k)
+}
+
+/// Something a shortcut does
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    NextUser,
+    PrevUser,
+    ToggleIgnore,
+    /// Waits for a second key saying which template to copy
+    CopyTemplate,
+    MoreDays,
+    FewerDays,
+    CopySummary,
+}
+
+impl Action {
+    pub const ALL: [Self; 7] = [
+        Self::NextUser,
+        Self::PrevUser,
+        Self::ToggleIgnore,
+        Self::CopyTemplate,
+        Self::MoreDays,
+        Self::FewerDays,
+        Self::CopySummary,
+    ];
+
+    /// Panel that handles the action
+    pub fn panel(self) -> &'static str {
+        match self {
+            Self::NextUser | Self::PrevUser | Self::ToggleIgnore | Self::CopyTemplate => "Duplex",
+            Self::MoreDays | Self::FewerDays | Self::CopySummary => "Simplex",
+        }
+    }
+
+    /// Actions handled by a panel
+    pub fn for_panel(panel: &str) -> impl Iterator<Item = Self> + '_ {
+        Self::ALL.into_iter().filter(move |a| a.panel() == panel)
+    }
+
+    /// Name the action is saved as
+    fn id(self) -> &'static str {
+        match self {
+            Self::NextUser => "next_user",
+            Self::PrevUser => "prev_user",
+            Self::ToggleIgnore => "toggle_ignore",
+            Self::CopyTemplate => "copy_template",
+            Self::MoreDays => "more_days",
+            Self::FewerDays => "fewer_days",
+            Self::CopySummary => "copy_summary",
+        }
+    }
+
+    fn default_keys(self) -> Vec<Key> {
+        match self {
+            Self::NextUser => vec![Key::N, Key::J, Key::ArrowRight],
+            Self::PrevUser => vec![Key::P, Key::K, Key::ArrowLeft],
+            Self::ToggleIgnore => vec![Key::I],
+            Self::CopyTemplate => vec![Key::C],
+            Self::MoreDays => vec![Key::PlusEquals],
+            Self::FewerDays => vec![Key::Minus],
+            Self::CopySummary => vec![Key::C],
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::NextUser => "Next user",
+                Self::PrevUser => "Previous user",
+                Self::ToggleIgnore => "Toggle ignore",
+                Self::CopyTemplate => "Copy template, then a key below",
+                Self::MoreDays => "More days",
+                Self::FewerDays => "Fewer days",
+                Self::CopySummary => "Copy summary",
+            }
+        )
+    }
+}
+
+/// Keys bound to each [Action]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    binds: Vec<(Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            binds: Action::ALL
+                .into_iter()
+                .map(|a| (a, a.default_keys()))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn keys(&self, action: Action) -> &[Key] {
+        self.binds
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map(|(_, keys)| keys.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Keys bound to an action, for showing
+    pub fn describe(&self, action: Action) -> String {
+        let keys = self.keys(action);
+        if keys.is_empty() {
+            return "Unbound".to_owned();
+        }
+        keys.iter()
+            .map(|&k| key_name(k))
+            .collect::<Vec<&str>>()
+            .join(", ")
+    }
+
+    /// Binds a key to an action, unless another action in the same panel already uses it
+    pub fn bind(&mut self, action: Action, key: Key) -> Result<(), String> {
+        if let Some(other) =
+            Action::for_panel(action.panel()).find(|&a| a != action && self.keys(a).contains(&key))
+        {
+            return Err(format!(
+                "{} is already bound to {} in {}",
+                key_name(key),
+                other,
+                action.panel()
+            ));
+        }
+        if let Some((_, keys)) = self.binds.iter_mut().find(|(a, _)| *a == action) {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn unbind(&mut self, action: Action, key: Key) {
+        if let Some((_, keys)) = self.binds.iter_mut().find(|(a, _)| *a == action) {
+            keys.retain(|&k| k != key);
+        }
+    }
+}
+
+/// Saved as `action=key key;action=key`
+impl std::fmt::Display for Keymap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let binds: Vec<String> = self
+            .binds
+            .iter()
+            .map(|(a, keys)| {
+                let keys: Vec<&str> = keys.iter().map(|&k| key_name(k)).collect();
+                format!("{}={}", a.id(), keys.join(" "))
+            })
+            .collect();
+        write!(f, "{}", binds.join(";"))
+    }
+}
+
+/// Actions missing from the saved keymap, such as ones added since, keep their default keys.
+/// Unknown actions and keys are dropped.
+impl std::str::FromStr for Keymap {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keymap = Self::default();
+        for bind in s.split(';').filter(|b| !b.is_empty()) {
+            let (id, keys) = bind.split_once('=').ok_or(())?;
+            let Some(action) = Action::ALL.into_iter().find(|a| a.id() == id) else {
+                continue;
+            };
+            if let Some((_, bound)) = keymap.binds.iter_mut().find(|(a, _)| *a == action) {
+                *bound = keys.split_whitespace().filter_map(key_from_name).collect();
+            }
+        }
+        Ok(keymap)
+    }
+}
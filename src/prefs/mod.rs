@@ -0,0 +1,49 @@
+//! Preferences saved with each profile
+//!
+//! Plain settings the disk cache saves and the UI reads, kept out of [app](crate::app) so
+//! [storage](crate::storage) doesn't depend on the UI.
+pub mod keymap;
+pub mod redact;
+mod test;
+
+use std::ops::RangeInclusive;
+
+/// How far the UI can be scaled on top of the OS's scale
+pub const UI_SCALE: RangeInclusive<f32> = 0.75..=2.0;
+
+/// Which built-in [Theme](crate::app::color::Theme) to use, saved per profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThemeKind {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl ThemeKind {
+    pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+}
+
+impl std::fmt::Display for ThemeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Dark => "Dark",
+                Self::Light => "Light",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for ThemeKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Dark" => Ok(Self::Dark),
+            "Light" => Ok(Self::Light),
+            _ => Err(()),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+//! Stand-ins for usernames, IPs, and MACs so screenshots and demos don't leak real ones
+//!
+//! While redacting, each value is swapped for a pseudonym made from a hash of it.  The hash is
+//! keyed with a random key picked when HORUS starts, so the same user or IP gets the same
+//! pseudonym for the whole session and logins can still be told apart and compared, but a
+//! pseudonym in a screenshot can't be matched by hashing a list of usernames or IPs.  Copying a
+//! redacted value copies its pseudonym.
+
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::OnceLock;
+
+/// Key for the session's pseudonyms, std seeds each [RandomState] from the OS
+static KEY: OnceLock<RandomState> = OnceLock::new();
+
+/// Whether values are shown as pseudonyms, from [Store::redact](crate::store::Store::redact)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Redact(pub bool);
+
+impl Redact {
+    pub fn user(self, name: &str) -> String {
+        self.show("user", &name.to_lowercase(), name)
+    }
+
+    pub fn ip(self, ip: impl std::fmt::Display) -> String {
+        let ip = ip.to_string();
+        self.show("ip", &ip, &ip)
+    }
+
+    pub fn mac(self, mac: &str) -> String {
+        self.show("mac", &mac.to_lowercase(), mac)
+    }
+
+    /// `value`, or a pseudonym made from `key` while redacting
+    fn show(self, prefix: &str, key: &str, value: &str) -> String {
+        if self.0 {
+            let hash = KEY.get_or_init(RandomState::new).hash_one(key);
+            format!("{}-{:012x}", prefix, hash >> 16)
+        } else {
+            value.to_owned()
+        }
+    }
+}
@@ -0,0 +1,161 @@
+#![cfg(test)]
+use super::keymap::{Action, Keymap};
+use super::redact::Redact;
+use super::ThemeKind;
+use crate::queries::splunk::IndexConfig;
+use crate::storage::Storage;
+use egui::Key;
+
+// -------------------- Keymap --------------------
+
+#[test]
+fn keymap_parse() {
+    let keymap: Keymap = "next_user=Right L;toggle_ignore=;bogus=A;prev_user=P Nope"
+        .parse()
+        .unwrap();
+    assert_eq!(keymap.keys(Action::NextUser), [Key::ArrowRight, Key::L]);
+    // Emptied on purpose
+    assert!(keymap.keys(Action::ToggleIgnore).is_empty());
+    assert_eq!(keymap.describe(Action::ToggleIgnore), "Unbound");
+    // Unknown keys are dropped
+    assert_eq!(keymap.keys(Action::PrevUser), [Key::P]);
+    // Missing from what was saved, so still the default
+    assert_eq!(
+        keymap.keys(Action::CopyTemplate),
+        Keymap::default().keys(Action::CopyTemplate)
+    );
+
+    assert_eq!("".parse(), Ok(Keymap::default()));
+    assert_eq!("next_user".parse::<Keymap>(), Err(()));
+
+    let keymap = Keymap::default();
+    assert_eq!(keymap.to_string().parse(), Ok(keymap));
+}
+
+#[test]
+fn keymap_bind() {
+    let mut keymap = Keymap::default();
+    // Duplex already uses N for the next user
+    assert!(keymap.bind(Action::PrevUser, Key::N).is_err());
+    // Simplex doesn't
+    keymap.bind(Action::MoreDays, Key::N).unwrap();
+    assert_eq!(keymap.describe(Action::MoreDays), "+, N");
+
+    keymap.unbind(Action::MoreDays, Key::PlusEquals);
+    assert_eq!(keymap.keys(Action::MoreDays), [Key::N]);
+}
+
+#[test]
+fn keymap_saved() {
+    let storage = Storage::in_memory();
+    assert_eq!(storage.get_keymap(), Keymap::default());
+
+    let mut keymap = Keymap::default();
+    keymap.unbind(Action::NextUser, Key::J);
+    keymap.bind(Action::CopySummary, Key::S).unwrap();
+    storage.set_keymap(&keymap);
+    assert_eq!(storage.get_keymap(), keymap);
+}
+
+// -------------------- Theme --------------------
+
+#[test]
+fn theme_saved() {
+    for kind in ThemeKind::ALL {
+        assert_eq!(kind.to_string().parse(), Ok(kind));
+    }
+
+    let storage = Storage::in_memory();
+    assert_eq!(storage.get_theme(), ThemeKind::Dark);
+    storage.set_theme(ThemeKind::Light);
+    assert_eq!(storage.get_theme(), ThemeKind::Light);
+}
+
+// -------------------- Redact --------------------
+
+#[test]
+fn redact_pseudonyms() {
+    let redact = Redact(true);
+    let tiger = redact.user("tiger");
+    assert_eq!(tiger, redact.user("TIGER"));
+    assert_ne!(tiger, redact.user("tigress"));
+    // 48 bits of a keyed hash, so it can't be looked up from a list of names
+    assert_eq!(tiger.len(), "user-".len() + 12);
+    assert!(tiger.starts_with("user-"));
+
+    let ip: std::net::Ipv4Addr = "130.127.1.2".parse().unwrap();
+    assert_eq!(redact.ip(ip), redact.ip("130.127.1.2"));
+    assert_ne!(redact.ip(ip), redact.ip("130.127.1.3"));
+    assert_eq!(redact.mac("AA:BB"), redact.mac("aa:bb"));
+
+    let shown = Redact(false);
+    assert_eq!(shown.user("Tiger"), "Tiger");
+    assert_eq!(shown.ip(ip), "130.127.1.2");
+}
+
+// -------------------- Profiles --------------------
+
+#[test]
+fn profiles_switch() {
+    let mut storage = Storage::in_memory();
+    storage.set_profile("tiger");
+    storage.set_theme(ThemeKind::Light);
+
+    // A new profile starts from the defaults
+    storage.set_profile("cat");
+    assert_eq!(storage.get_theme(), ThemeKind::Dark);
+    storage.set_keymap(&Keymap::default());
+    assert_eq!(storage.list_profiles(), ["cat", "tiger"]);
+
+    storage.set_profile("tiger");
+    assert_eq!(storage.get_theme(), ThemeKind::Light);
+
+    // Deleted profiles start over
+    storage.delete_profile("tiger");
+    assert_eq!(storage.list_profiles(), ["cat"]);
+    storage.set_profile("tiger");
+    assert_eq!(storage.get_theme(), ThemeKind::Dark);
+}
+
+#[test]
+fn profiles_share_global() {
+    let mut storage = Storage::in_memory();
+    assert_eq!(storage.get_username(), "");
+
+    // Saved from nothing, then replaced
+    storage.set_username("tiger".to_owned());
+    storage.set_username("cat".to_owned());
+    assert_eq!(storage.get_username(), "cat");
+    let indexes = IndexConfig {
+        ise: "network-ise".to_owned(),
+        ..IndexConfig::default()
+    };
+    storage.set_index_config(&indexes);
+
+    // Every profile sees the same
+    storage.set_profile("tiger");
+    assert_eq!(storage.get_username(), "cat");
+    storage.set_profile("lion");
+    assert_eq!(storage.get_username(), "cat");
+    assert_eq!(storage.get_index_config(), indexes);
+}
+
+#[test]
+fn profiles_adopt() {
+    let mut storage = Storage::in_memory();
+    storage.set_theme_before_profiles(ThemeKind::Light);
+
+    // The first profile takes the settings from before profiles
+    storage.set_profile("tiger");
+    assert_eq!(storage.get_theme(), ThemeKind::Light);
+
+    // Only the first
+    storage.set_profile("cat");
+    assert_eq!(storage.get_theme(), ThemeKind::Dark);
+
+    // And they aren't taken again once every profile is gone
+    storage.delete_profile("tiger");
+    storage.delete_profile("cat");
+    storage.set_profile("lion");
+    assert_eq!(storage.get_theme(), ThemeKind::Dark);
+}
@@ -0,0 +1,159 @@
+//! Timings of recent queries
+//!
+//! Every query records how long it took and how much came back, so a slow run can be pinned on
+//! Splunk, HDTools, or ipinfo.io without trawling the logs.  Recent queries are kept in a ring
+//! buffer, totals are kept for the whole session.
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Queries kept for the Diagnostics panel, older ones are only counted in the totals
+pub(super) const RECENT: usize = 200;
+
+/// Where a query went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Backend {
+    Splunk,
+    HDTools,
+    IpInfo,
+    IpData,
+    Osiris,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Splunk => "Splunk",
+                Self::HDTools => "HDTools",
+                Self::IpInfo => "ipinfo.io",
+                Self::IpData => "ipdata.co",
+                Self::Osiris => "Osiris",
+            }
+        )
+    }
+}
+
+/// A finished query
+#[derive(Debug, Clone)]
+pub struct QueryRecord {
+    pub backend: Backend,
+    /// Function that made the query
+    pub name: &'static str,
+    pub finished: chrono::DateTime<chrono::Utc>,
+    pub duration: Duration,
+    pub bytes: usize,
+    pub rows: usize,
+    /// False if the query returned an error
+    pub ok: bool,
+}
+
+/// Sums of every query to a backend this session
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub calls: usize,
+    pub failures: usize,
+    pub duration: Duration,
+    pub bytes: usize,
+    pub rows: usize,
+}
+
+#[derive(Default)]
+struct Inner {
+    recent: VecDeque<QueryRecord>,
+    totals: BTreeMap<Backend, Totals>,
+}
+
+/// Shared by every query source, see [Queries](super::Queries)
+#[derive(Default)]
+pub struct Diagnostics {
+    inner: Mutex<Inner>,
+}
+
+impl Diagnostics {
+    /// Starts timing a query.  It is recorded when the [Timer] is dropped, as failed unless
+    /// [Timer::finish] was called.
+    pub fn start(&self, backend: Backend, name: &'static str) -> Timer<'_> {
+        Timer {
+            diagnostics: self,
+            backend,
+            name,
+            start: Instant::now(),
+            bytes: 0,
+            rows: 0,
+            ok: false,
+        }
+    }
+
+    fn record(&self, record: QueryRecord) {
+        let mut inner = self.inner.lock().expect("Failed to get diagnostics lock");
+        let totals = inner.totals.entry(record.backend).or_default();
+        totals.calls += 1;
+        totals.failures += usize::from(!record.ok);
+        totals.duration += record.duration;
+        totals.bytes += record.bytes;
+        totals.rows += record.rows;
+
+        if inner.recent.len() == RECENT {
+            inner.recent.pop_front();
+        }
+        inner.recent.push_back(record);
+    }
+
+    /// Recent queries, newest first
+    pub fn recent(&self) -> Vec<QueryRecord> {
+        let inner = self.inner.lock().expect("Failed to get diagnostics lock");
+        inner.recent.iter().rev().cloned().collect()
+    }
+
+    /// Totals for each backend that has been queried
+    pub fn totals(&self) -> Vec<(Backend, Totals)> {
+        let inner = self.inner.lock().expect("Failed to get diagnostics lock");
+        inner.totals.iter().map(|(b, t)| (*b, *t)).collect()
+    }
+}
+
+/// Size of a response that is parsed straight from the reader, or 0 if the server didn't say
+pub fn content_length(resp: &ureq::Response) -> usize {
+    resp.header("Content-Length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Times a query until it is dropped, see [Diagnostics::start]
+pub struct Timer<'a> {
+    diagnostics: &'a Diagnostics,
+    backend: Backend,
+    name: &'static str,
+    start: Instant,
+    bytes: usize,
+    rows: usize,
+    ok: bool,
+}
+
+impl Timer<'_> {
+    /// Records the query as successful with what came back
+    pub fn finish(mut self, bytes: usize, rows: usize) {
+        self.bytes = bytes;
+        self.rows = rows;
+        self.ok = true;
+    }
+}
+
+impl Drop for Timer<'_> {
+    fn drop(&mut self) {
+        self.diagnostics.record(QueryRecord {
+            backend: self.backend,
+            name: self.name,
+            finished: chrono::Utc::now(),
+            duration: self.start.elapsed(),
+            bytes: self.bytes,
+            rows: self.rows,
+            ok: self.ok,
+        });
+    }
+}
@@ -1,31 +1,177 @@
 //! HDTools queries
 //!
 //! This module holds the shibsession and functions used to retrieve user data from HDTools
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
 
+use super::diagnostics::{Backend, Diagnostics};
+use super::QueryError;
 use crate::user::Location;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use cookie_store::{Cookie, CookieStore};
-use log::{debug, info};
+use log::{debug, error, info, warn};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use ureq::Agent;
 
 static USER_RE: OnceLock<Regex> = OnceLock::new();
 static CREATE_DATE_RE: OnceLock<Regex> = OnceLock::new();
-static STUDENT_ADDRESS_RE: OnceLock<Regex> = OnceLock::new();
+static STUDENT_PRIMARY_RE: OnceLock<Regex> = OnceLock::new();
+static STUDENT_CAMPUS_RE: OnceLock<Regex> = OnceLock::new();
 static EMPLOYEE_ADDRESS_RE: OnceLock<Regex> = OnceLock::new();
+static AFFILIATION_RE: OnceLock<Regex> = OnceLock::new();
+static STATUS_RE: OnceLock<Regex> = OnceLock::new();
+static PHONE_RE: OnceLock<Regex> = OnceLock::new();
+static TERM_RE: OnceLock<Regex> = OnceLock::new();
 
-pub type HDToolsInfo = (NaiveDateTime, Option<Location>);
+/// How long after a term starts the student record still counts as current, long enough to cover
+/// a summer off
+const CURRENT_TERM_DAYS: i64 = 270;
+
+/// Everything HORUS cares about from HDTools
+#[derive(Debug, Clone, PartialEq)]
+pub struct HDToolsInfo {
+    pub creation_date: NaiveDateTime,
+    pub location: Option<Location>,
+    /// Primary affiliation, such as student, employee, or alumni
+    pub affiliation: Option<String>,
+    /// Account status, such as active or disabled
+    pub status: Option<String>,
+    /// Record the location came from, [None] for rows cached before this was kept
+    pub address_record: Option<AddressRecord>,
+    /// Every address found, best first, the first being [location](Self::location).  Only the
+    /// chosen one is cached, so cached rows have at most one.
+    pub addresses: Vec<(AddressRecord, Location)>,
+    /// First phone number found in the person then student record.  The employee record is only
+    /// checked when it was fetched for the address, see [needs_employee].
+    pub phone: Option<String>,
+    /// Coordinates of the location, filled in by the [Store](crate::store::Store) from the IP
+    /// databases
+    pub home: Option<(f32, f32)>,
+}
+
+/// How a user's HDTools lookup went, so a user HDTools has no record of can be told apart from
+/// HDTools not answering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HDToolsStatus {
+    /// HDTools isn't signed in, or the user hasn't been looked up
+    #[default]
+    Unchecked,
+    /// Records were found, now or in the cache
+    Found,
+    /// HDTools answered and has no record of the user
+    NoRecord,
+    /// HDTools couldn't be asked or turned the lookup away, often an expired shibsession
+    Failed(QueryError),
+}
+
+impl HDToolsStatus {
+    pub fn new(result: &Result<HDToolsInfo, QueryError>) -> Self {
+        match result {
+            Ok(_) => Self::Found,
+            Err(QueryError::NotFound) => Self::NoRecord,
+            Err(e) => Self::Failed(*e),
+        }
+    }
+}
+
+/// HDTools record an address was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressRecord {
+    /// Student's permanent address
+    StudentPrimary,
+    /// Student's campus address, often a dorm
+    StudentCampus,
+    /// Employee's home address
+    Employee,
+}
+
+impl std::fmt::Display for AddressRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::StudentPrimary => "Student primary",
+                Self::StudentCampus => "Student campus",
+                Self::Employee => "Employee",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for AddressRecord {
+    type Err = ();
+
+    fn from_str(record: &str) -> Result<Self, Self::Err> {
+        match record {
+            "Student primary" => Ok(Self::StudentPrimary),
+            "Student campus" => Ok(Self::StudentCampus),
+            // Rows cached before the student addresses were told apart, primary was checked first
+            "Student" => Ok(Self::StudentPrimary),
+            "Employee" => Ok(Self::Employee),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Where HDTools lives.  Lookups are `{url}/{user}` and records are `{url}/{zid}`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HDToolsConfig {
+    /// Only loads with a good shibsession, the cookie is set for this URL
+    pub base: String,
+    pub lookup: String,
+    pub person: String,
+    pub student: String,
+    pub employee: String,
+}
+
+impl Default for HDToolsConfig {
+    fn default() -> Self {
+        Self {
+            base: "https://TOP_SNEAKY_URL".to_owned(),
+            lookup: "https://TOP_SNEAKY_URL".to_owned(),
+            person: "https://TOP_SNEAKY_URL".to_owned(),
+            student: "https://TOP_SNEAKY_URL".to_owned(),
+            employee: "https://TOP_SNEAKY_URL".to_owned(),
+        }
+    }
+}
+
+impl HDToolsConfig {
+    /// Checks every URL parses, returning the base URL
+    pub fn validate(&self) -> Result<url::Url, String> {
+        for (name, url) in [
+            ("Lookup", &self.lookup),
+            ("Person", &self.person),
+            ("Student", &self.student),
+            ("Employee", &self.employee),
+        ] {
+            if let Err(e) = url::Url::parse(url) {
+                return Err(format!("{} URL is invalid: {}", name, e));
+            }
+        }
+        url::Url::parse(&self.base).map_err(|e| format!("Base URL is invalid: {}", e))
+    }
+}
 
 pub struct HDTools {
     agent: Agent,
+    config: HDToolsConfig,
+    /// Replaced with the shared one by [Queries::new](super::Queries::new)
+    pub(super) diagnostics: Arc<Diagnostics>,
 }
 
 impl HDTools {
-    pub fn new(shibsession: String) -> Option<Self> {
-        let url: url::Url = "https://TOP_SNEAKY_URL"
-            .parse()
-            .expect("Bad HDTools URL");
+    pub fn new(
+        shibsession: String,
+        config: HDToolsConfig,
+        timeouts: super::Timeouts,
+    ) -> Result<Self, QueryError> {
+        let url = config.validate().map_err(|e| {
+            error!("Bad HDTools config: {}", e);
+            QueryError::Parse
+        })?;
 
         let cookie = Cookie::parse(shibsession, &url).expect("Failed to set shibsession cookie");
         let mut cookie_store = CookieStore::default();
@@ -33,125 +179,275 @@ impl HDTools {
             .insert(cookie, &url)
             .expect("Failed to insert cookie into cookie store");
 
-        let agent = ureq::builder()
+        let agent = timeouts
+            .builder()
             .cookie_store(cookie_store)
             .redirects(0)
             .build();
 
-        let status = match agent
-            .get("https://TOP_SNEAKY_URL")
-            .call()
-        {
+        let status = match agent.get(&config.base).call() {
             Ok(s) => s.status(),
-            Err(_) => return None,
+            Err(e) => return Err(super::log_error("HDTools login", e)),
         };
 
         info!("HDTools status was {}", status);
 
+        // Anything other than a 200 is a redirect to the login page from a bad shibsession
         if status == 200 {
-            Some(Self { agent })
+            Ok(Self {
+                agent,
+                config,
+                diagnostics: Arc::default(),
+            })
         } else {
-            None
+            Err(QueryError::Auth)
         }
     }
 
-    pub fn get_info(&self, user: &str) -> Option<HDToolsInfo> {
+    /// Pulls a user's HDTools records.  The lookup has to happen first to get the zid, but the
+    /// person and student records only need the zid and are fetched concurrently.  The employee
+    /// record is only fetched when the student record has no current address, see
+    /// [needs_employee].
+    pub fn get_info(&self, user: &str) -> Result<HDToolsInfo, QueryError> {
         info!("Fetching HDTools info for {}", user);
-        let resp = self
-            .agent
-            .get(&format!(
-                "https://TOP_SNEAKY_URL/{}",
-                user
-            ))
-            .call()
-            .ok()?
-            .into_string()
-            .ok()?;
+        let resp = self.get("lookup", &record_url(&self.config.lookup, user))?;
 
-        let zid = USER_RE
-            .get_or_init(|| Regex::new(r#""zid":"(\S+?)""#).unwrap())
-            .captures(&resp)?[1]
-            .to_owned();
+        let zid = lookup_zid(&resp)?;
+        let matches = count_zids(&resp);
+        if matches > 1 {
+            warn!(
+                "HDTools lookup for {} matched {} people, using the first",
+                user, matches
+            );
+        }
 
         debug!("Got zid: {}", zid);
 
-        let resp = self
-            .agent
-            .get(&format!(
-                "https://TOP_SNEAKY_URL/{}",
-                zid
-            ))
-            .call()
-            .ok()?
-            .into_string()
-            .ok()?;
+        let (person, student) = std::thread::scope(|s| {
+            let person = s.spawn(|| self.get("person", &record_url(&self.config.person, &zid)));
+            let student = s.spawn(|| self.get("student", &record_url(&self.config.student, &zid)));
+            (
+                person.join().expect("Couldn't join HDTools thread"),
+                student.join().expect("Couldn't join HDTools thread"),
+            )
+        });
 
         debug!("Processing creation date");
 
-        let creation_date = CREATE_DATE_RE
-            .get_or_init(|| Regex::new(r#""createDate":"(\S+?)""#).unwrap())
-            .captures(&resp)?;
+        let person = person?;
+        let mut info = parse_person(&person).ok_or(QueryError::Parse)?;
+
+        debug!("Got student records");
+
+        let student = student?;
+        info.phone = parse_phone(&person).or_else(|| parse_phone(&student));
 
-        let creation_date: NaiveDateTime =
-            chrono::DateTime::parse_from_str(&creation_date[1], "%FT%T%z")
-                .ok()?
-                .with_timezone(&chrono::Local)
-                .naive_local();
+        let today = chrono::Local::now().date_naive();
+        let employee = if needs_employee(&student, today) {
+            // A missing employee record only matters if the student record has no address
+            let employee = match self.get("employee", &record_url(&self.config.employee, &zid)) {
+                Ok(employee) => employee,
+                Err(e) if parse_student_addresses(&student).is_empty() => return Err(e),
+                Err(_) => String::new(),
+            };
+            debug!("Got employee records");
+            employee
+        } else {
+            String::new()
+        };
+        info.phone = info.phone.or_else(|| parse_phone(&employee));
+
+        info.addresses = rank_addresses(&student, &employee, today);
+        if let Some((record, location)) = info.addresses.first() {
+            info.address_record = Some(*record);
+            info.location = Some(location.clone());
+        }
+
+        Ok(info)
+    }
 
+    /// GETs a url and returns the body, `name` is what it is recorded as in the diagnostics
+    fn get(&self, name: &'static str, url: &str) -> Result<String, QueryError> {
+        let timer = self.diagnostics.start(Backend::HDTools, name);
         let resp = self
             .agent
-            .get(&format!(
-                "https://TOP_SNEAKY_URL/{}",
-                zid
-            ))
+            .get(url)
             .call()
+            .map_err(|e| super::log_error("HDTools request", e))?;
+        // Redirects aren't followed, an expired shibsession redirects to the login page
+        if resp.status() != 200 {
+            error!(
+                "HDTools {} returned {}, the shibsession may have expired",
+                name,
+                resp.status()
+            );
+            return Err(QueryError::Auth);
+        }
+        let body = resp.into_string()?;
+        timer.finish(body.len(), 1);
+        Ok(body)
+    }
+}
+
+/// Appends a username or zid to a configured URL, which may or may not end in a slash
+pub(super) fn record_url(url: &str, id: &str) -> String {
+    format!("{}/{}", url.trim_end_matches('/'), id)
+}
+
+/// The zid from a lookup.  A JSON answer without one means HDTools has no record of the user,
+/// anything else isn't an answer at all.
+pub(super) fn lookup_zid(resp: &str) -> Result<String, QueryError> {
+    match parse_zid(resp) {
+        Some(zid) => Ok(zid),
+        None if resp.trim_start().starts_with(['{', '[']) => Err(QueryError::NotFound),
+        None => Err(QueryError::Parse),
+    }
+}
+
+/// Matches each zid in a user lookup, shared by [count_zids] and [parse_zid]
+fn user_re() -> &'static Regex {
+    USER_RE.get_or_init(|| Regex::new(r#""zid":"(\S+?)""#).unwrap())
+}
+
+/// How many different people a user lookup matched
+fn count_zids(resp: &str) -> usize {
+    user_re()
+        .captures_iter(resp)
+        .map(|c| c[1].to_owned())
+        .collect::<std::collections::HashSet<_>>()
+        .len()
+}
+
+/// Pulls the zid from a user lookup
+pub(super) fn parse_zid(resp: &str) -> Option<String> {
+    user_re().captures(resp).map(|c| c[1].to_owned())
+}
+
+/// Pulls the creation date, affiliation, and status from a person record.  The location is left
+/// empty.
+pub(super) fn parse_person(resp: &str) -> Option<HDToolsInfo> {
+    let creation_date = CREATE_DATE_RE
+        .get_or_init(|| Regex::new(r#""createDate":"(\S+?)""#).unwrap())
+        .captures(resp)?;
+
+    let creation_date: NaiveDateTime =
+        chrono::DateTime::parse_from_str(&creation_date[1], "%FT%T%z")
             .ok()?
-            .into_string()
-            .ok()?;
+            .with_timezone(&chrono::Local)
+            .naive_local();
 
-        debug!("Got student records");
+    let affiliation = AFFILIATION_RE
+        .get_or_init(|| Regex::new(r#""primaryAffiliation":"([^"]+)""#).unwrap())
+        .captures(resp)
+        .map(|c| c[1].to_owned());
 
-        let addr = STUDENT_ADDRESS_RE.get_or_init(|| Regex::new(r#""(?:primary|campus)AddressCity":"(?<city>[^"]*)"(?:,"(?:primary|campus)AddressState":"(?<state>[^"]*)")?(?:.*,"(?:primary|campus)AddressCountry":"(?<country>[^"]*)")?"#).unwrap()).captures(&resp);
+    let status = STATUS_RE
+        .get_or_init(|| Regex::new(r#""accountStatus":"([^"]+)""#).unwrap())
+        .captures(resp)
+        .map(|c| c[1].to_owned());
 
-        match addr {
-            Some(addr) => {
-                debug!("Capture: {}", &addr[0]);
-                let addr = Location {
-                    city: addr["city"].to_owned(),
-                    state: addr.name("state").map(|s| s.as_str().to_owned()),
-                    country: addr.name("country").map(|s| s.as_str().to_owned()),
-                };
+    debug!("Got affiliation {:?} and status {:?}", affiliation, status);
 
-                Some((creation_date, Some(addr)))
-            }
-            None => {
-                let resp = self
-                    .agent
-                    .get(&format!(
-                        "https://TOP_SNEAKY_URL/{}",
-                        zid
-                    ))
-                    .call()
-                    .ok()?
-                    .into_string()
-                    .ok()?;
-
-                debug!("Got employee records");
-
-                let addr = EMPLOYEE_ADDRESS_RE
-                    .get_or_init(|| {
-                        Regex::new(r#""hCity":"(?<city>[^"]*)","hState":"(?<state>[^"]*)""#)
-                            .unwrap()
-                    })
-                    .captures(&resp)
-                    .map(|cap| Location {
-                        city: cap["city"].to_owned(),
-                        state: Some(cap["state"].to_owned()),
-                        country: None,
-                    });
-
-                Some((creation_date, addr))
-            }
-        }
+    Some(HDToolsInfo {
+        creation_date,
+        location: None,
+        affiliation,
+        status,
+        address_record: None,
+        addresses: vec![],
+        phone: None,
+        home: None,
+    })
+}
+
+/// Every address in the student and employee records, best first.  Student addresses come first
+/// while the student record has a current term, otherwise the employee home address does, since a
+/// campus address from years ago says little about where the user is now.
+pub(super) fn rank_addresses(
+    student: &str,
+    employee: &str,
+    today: NaiveDate,
+) -> Vec<(AddressRecord, Location)> {
+    let mut addresses = parse_student_addresses(student);
+    if let Some(home) = parse_employee_address(employee) {
+        let at = if current_term(student, today) {
+            addresses.len()
+        } else {
+            0
+        };
+        addresses.insert(at, (AddressRecord::Employee, home));
     }
+    addresses
+}
+
+/// Whether the employee record has to be fetched, which is only when the student record has no
+/// address or no current term for [rank_addresses] to put first
+pub(super) fn needs_employee(student: &str, today: NaiveDate) -> bool {
+    parse_student_addresses(student).is_empty() || !current_term(student, today)
+}
+
+/// Pulls the primary and campus addresses from a student record, primary first.  Addresses
+/// without a city are skipped.
+pub(super) fn parse_student_addresses(resp: &str) -> Vec<(AddressRecord, Location)> {
+    let primary = STUDENT_PRIMARY_RE.get_or_init(|| student_address_re("primary"));
+    let campus = STUDENT_CAMPUS_RE.get_or_init(|| student_address_re("campus"));
+    [
+        (AddressRecord::StudentPrimary, primary),
+        (AddressRecord::StudentCampus, campus),
+    ]
+    .into_iter()
+    .filter_map(|(record, re)| {
+        let addr = re.captures(resp)?;
+        debug!("Capture: {}", &addr[0]);
+        let location = Location {
+            city: addr["city"].to_owned(),
+            state: addr.name("state").map(|s| s.as_str().to_owned()),
+            country: addr.name("country").map(|s| s.as_str().to_owned()),
+        };
+        (!location.city.is_empty()).then_some((record, location))
+    })
+    .collect()
+}
+
+/// Matches one of the student record's addresses, `kind` being `primary` or `campus`
+fn student_address_re(kind: &str) -> Regex {
+    Regex::new(&format!(
+        r#""{kind}AddressCity":"(?<city>[^"]*)"(?:,"{kind}AddressState":"(?<state>[^"]*)")?(?:.*,"{kind}AddressCountry":"(?<country>[^"]*)")?"#
+    ))
+    .unwrap()
+}
+
+/// Whether the student record has a term that started within [CURRENT_TERM_DAYS] of `today`, or
+/// one that hasn't started yet.  Term codes are the year and month the term starts, like 202308.
+pub(super) fn current_term(resp: &str, today: NaiveDate) -> bool {
+    TERM_RE
+        .get_or_init(|| Regex::new(r#""termCode":"(\d{4})(\d{2})""#).unwrap())
+        .captures_iter(resp)
+        .filter_map(|c| NaiveDate::from_ymd_opt(c[1].parse().ok()?, c[2].parse().ok()?, 1))
+        .any(|start| (today - start).num_days() <= CURRENT_TERM_DAYS)
+}
+
+/// Pulls the home address from an employee record
+pub(super) fn parse_employee_address(resp: &str) -> Option<Location> {
+    EMPLOYEE_ADDRESS_RE
+        .get_or_init(|| {
+            Regex::new(r#""hCity":"(?<city>[^"]*)","hState":"(?<state>[^"]*)""#).unwrap()
+        })
+        .captures(resp)
+        .map(|cap| Location {
+            city: cap["city"].to_owned(),
+            state: Some(cap["state"].to_owned()),
+            country: None,
+        })
+}
+
+/// Pulls a phone number from any HDTools record.  The number itself is never logged.
+pub(super) fn parse_phone(resp: &str) -> Option<String> {
+    PHONE_RE
+        .get_or_init(|| {
+            Regex::new(r#""(?:phone|primaryPhone|campusPhone|hPhone|wPhone)":"([^"]*)""#).unwrap()
+        })
+        .captures_iter(resp)
+        .map(|c| c[1].trim().to_owned())
+        .find(|p| p.chars().filter(char::is_ascii_digit).count() >= 7)
 }
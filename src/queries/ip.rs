@@ -1,8 +1,12 @@
 //! IP related queires
-use log::info;
+use super::diagnostics::{self, Backend, Diagnostics};
+use super::QueryError;
+use log::{info, warn};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::net::Ipv4Addr;
+use std::sync::{Arc, OnceLock};
 
 /// Holds static IP databases used by Splunk to geolocate IPs from Duo logs.
 ///
@@ -15,6 +19,9 @@ pub struct IpDB {
     proxy_db: Vec<Proxy>,
     /// ASN (ISP) database
     asn_db: Vec<Asn>,
+    /// Lowercased city names to the first location range for each state and country with that
+    /// city, built the first time [geocode](Self::geocode) is called
+    cities: OnceLock<HashMap<String, Vec<usize>>>,
 }
 
 impl IpDB {
@@ -23,58 +30,111 @@ impl IpDB {
     /// pre-processing before HORUS will accept them.  The formats are specified in their
     /// respective structs.  For the lazy people who hate up to date IP databases, you can find a
     /// copy of the pre-processed DBs in [Dev Notes](https://example.org)
-    pub fn new() -> Self {
-        let empty_check = |s: String| if s == "-" { None } else { Some(s) };
-
-        let iploc_db: Vec<IpLoc> = std::include_str!("ip2location.csv")
-            .par_lines()
-            .map(|l| {
-                let l: Vec<&str> = l.split(',').collect();
-                IpLoc {
-                    lower: l[0].parse().unwrap(),
-                    upper: l[1].parse().unwrap(),
-                    country_code: empty_check(l[2].to_string()),
-                    country: empty_check(l[3].to_string()),
-                    state: empty_check(l[4].to_string()),
-                    city: empty_check(l[5].to_string()),
-                    lat: l[l.len() - 2].parse().unwrap(),
-                    lon: l[l.len() - 1].parse().unwrap(),
-                }
+    pub fn new() -> Result<Self, IpDbError> {
+        Self::from_csv(
+            std::include_str!("ip2location.csv"),
+            std::include_str!("ip2proxy.csv"),
+            std::include_str!("ip2asn.csv"),
+        )
+    }
+
+    /// Parses the databases from their CSVs, stopping at the first malformed line
+    pub(super) fn from_csv(iploc: &str, proxy: &str, asn: &str) -> Result<Self, IpDbError> {
+        let empty_check = |s: &str| if s == "-" { None } else { Some(s.to_string()) };
+
+        let iploc_db = parse_csv("ip2location.csv", iploc, |l| {
+            if l.len() < 8 {
+                return None;
+            }
+            Some(IpLoc {
+                lower: l[0].parse().ok()?,
+                upper: l[1].parse().ok()?,
+                country_code: empty_check(l[2]),
+                country: empty_check(l[3]),
+                state: empty_check(l[4]),
+                city: empty_check(l[5]),
+                lat: l[l.len() - 2].parse().ok()?,
+                lon: l[l.len() - 1].parse().ok()?,
             })
-            .collect();
-
-        let proxy_db: Vec<Proxy> = std::include_str!("ip2proxy.csv")
-            .par_lines()
-            .map(|l| {
-                let l: Vec<&str> = l.split(',').collect();
-                Proxy {
-                    lower: l[0].parse().unwrap(),
-                    upper: l[1].parse().unwrap(),
-                }
+        })?;
+
+        let proxy_db = parse_csv("ip2proxy.csv", proxy, |l| {
+            Some(Proxy {
+                lower: l.first()?.parse().ok()?,
+                upper: l.get(1)?.parse().ok()?,
             })
-            .collect();
-
-        let asn_db: Vec<Asn> = std::include_str!("ip2asn.csv")
-            .par_lines()
-            .map(|l| {
-                let l: Vec<&str> = l.split(',').collect();
-                Asn {
-                    lower: l[0].parse().unwrap(),
-                    upper: l[1].parse().unwrap(),
-                    asn: empty_check(l[2].to_string()),
-                }
+        })?;
+
+        let asn_db = parse_csv("ip2asn.csv", asn, |l| {
+            Some(Asn {
+                lower: l.first()?.parse().ok()?,
+                upper: l.get(1)?.parse().ok()?,
+                asn: empty_check(l.get(2)?),
             })
-            .collect();
+        })?;
 
         info!("Loaded IP databases");
 
-        Self {
+        Ok(Self {
             iploc_db,
             proxy_db,
             asn_db,
+            cities: OnceLock::new(),
+        })
+    }
+
+    /// No geolocation, proxy, or ASN info for any IP, used when the databases fail to load
+    pub fn empty() -> Self {
+        Self {
+            iploc_db: vec![],
+            proxy_db: vec![],
+            asn_db: vec![],
+            cities: OnceLock::new(),
         }
     }
 
+    /// Coordinates of a city from HDTools, taken from the IP2Location ranges in that city so
+    /// homes can be placed without a geocoding service.  The state and country have to match when
+    /// HDTools gave them.
+    pub fn geocode(&self, location: &crate::user::Location) -> Option<(f32, f32)> {
+        let cities = self.cities.get_or_init(|| {
+            let mut cities: HashMap<String, Vec<usize>> = HashMap::new();
+            for (i, loc) in self.iploc_db.iter().enumerate() {
+                let Some(city) = &loc.city else {
+                    continue;
+                };
+                let found = cities.entry(city.to_lowercase()).or_default();
+                if !found.iter().any(|&j| {
+                    let other = &self.iploc_db[j];
+                    other.state == loc.state && other.country_code == loc.country_code
+                }) {
+                    found.push(i);
+                }
+            }
+            cities
+        });
+
+        cities
+            .get(&location.city.trim().to_lowercase())?
+            .iter()
+            .map(|&i| &self.iploc_db[i])
+            .find(|loc| {
+                let state = location.state.as_deref().is_none_or(|state| {
+                    loc.state
+                        .as_deref()
+                        .is_some_and(|s| crate::user::same_state(state, s))
+                });
+                let country = location.country.as_deref().is_none_or(|country| {
+                    [&loc.country_code, &loc.country]
+                        .into_iter()
+                        .flatten()
+                        .any(|c| crate::user::same_country(country, c))
+                });
+                state && country
+            })
+            .map(|loc| (loc.lat, loc.lon))
+    }
+
     pub fn get_iploc(&self, ip: Ipv4Addr) -> Option<&IpLoc> {
         let ip: u32 = ip.into();
 
@@ -130,6 +190,49 @@ impl IpDB {
     }
 }
 
+/// Splits each line of a CSV on commas and parses it, in parallel.  A line `parse` gives up on is
+/// returned as the error.
+fn parse_csv<T: Send>(
+    file: &'static str,
+    csv: &str,
+    parse: impl Fn(&[&str]) -> Option<T> + Sync,
+) -> Result<Vec<T>, IpDbError> {
+    let lines: Vec<&str> = csv.lines().collect();
+    lines
+        .par_iter()
+        .enumerate()
+        .map(|(i, l)| {
+            let fields: Vec<&str> = l.split(',').collect();
+            parse(&fields).ok_or_else(|| IpDbError {
+                file,
+                line: i + 1,
+                content: l.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// A line in one of the IP databases that couldn't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpDbError {
+    pub file: &'static str,
+    /// Starts at 1
+    pub line: usize,
+    pub content: String,
+}
+
+impl std::fmt::Display for IpDbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} line {} is malformed: {}",
+            self.file, self.line, self.content
+        )
+    }
+}
+
+impl std::error::Error for IpDbError {}
+
 /// Holds the location for a range of IPs
 ///
 /// Here is the first ten lines of the CSV file:
@@ -202,13 +305,17 @@ struct Asn {
 /// right click an IP in Duplex, Simplex, or Visor.  The IP location information is used to
 /// help determine the location of duo logs, as the Maxmind databases are not very accurate.
 pub struct Ip {
+    agent: ureq::Agent,
     ipdata_key: &'static str,
     ipinfo_key: String,
+    diagnostics: Arc<Diagnostics>,
 }
 
 impl Ip {
-    pub fn new() -> Self {
+    pub fn new(timeouts: super::Timeouts, diagnostics: Arc<Diagnostics>) -> Self {
         Self {
+            agent: timeouts.agent(),
+            diagnostics,
             // API key for ipdata.co, you will have to get your own to compile
             ipdata_key: env!("IPDATA_KEY"),
             // API key for ipinfo.io, you will have to get your own to compile
@@ -217,32 +324,51 @@ impl Ip {
     }
 
     /// Queries ipdata.co for threat information about an IP
-    pub fn get_threat(&self, ip: Ipv4Addr) -> Option<IpThreat> {
+    pub fn get_threat(&self, ip: Ipv4Addr) -> Result<IpThreat, QueryError> {
         info!("Getting IP threat for {}", ip);
-        let resp = ureq::get(&format!("https://api.ipdata.co/{}/threat", ip))
+        let timer = self.diagnostics.start(Backend::IpData, "get_threat");
+        let resp = self
+            .agent
+            .get(&format!("https://api.ipdata.co/{}/threat", ip))
             .query_pairs([("api-key", self.ipdata_key)])
             .call()
-            .ok()?;
+            .map_err(|e| match e {
+                // ipdata.co responds with 400 for private and reserved IPs
+                ureq::Error::Status(400, _) => QueryError::NotFound,
+                e => super::log_error(&format!("IP threat for {}", ip), e),
+            })?;
 
-        let resp: IpThreat = resp.into_json().ok()?;
+        let bytes = diagnostics::content_length(&resp);
+        let resp: IpThreat = resp.into_json().map_err(|e| {
+            warn!("Failed to read IP threat for {}: {}", ip, e);
+            QueryError::from(e)
+        })?;
+        timer.finish(bytes, 1);
 
         info!("Got threat data");
 
-        Some(resp)
+        Ok(resp)
     }
 
     /// Queries ipinfo.io for location information about an IP
-    pub fn get_info(&self, ip: Ipv4Addr) -> Option<IpInfo> {
+    pub fn get_info(&self, ip: Ipv4Addr) -> Result<IpInfo, QueryError> {
         info!("Getting IP info for {}", ip);
-        let resp = ureq::get(&format!("https://ipinfo.io/{}", ip))
+        let timer = self.diagnostics.start(Backend::IpInfo, "get_info");
+        let resp = self
+            .agent
+            .get(&format!("https://ipinfo.io/{}", ip))
             .set("Authorization", &self.ipinfo_key)
             .call()
-            .ok()?
-            .into_json()
-            .ok()?;
+            .map_err(|e| super::log_error(&format!("IP info for {}", ip), e))?;
+        let bytes = diagnostics::content_length(&resp);
+        let resp = resp.into_json().map_err(|e| {
+            warn!("Failed to read IP info for {}: {}", ip, e);
+            QueryError::from(e)
+        })?;
+        timer.finish(bytes, 1);
 
         info!("Got info");
-        Some(resp)
+        Ok(resp)
     }
 }
 
@@ -342,3 +468,84 @@ impl Serialize for Location {
         serializer.serialize_str(&format!("{},{}", self.lat, self.lon))
     }
 }
+
+/// Everything cached about an IP, for sharing with threat intel
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct IpEnrichment {
+    pub ip: Ipv4Addr,
+    pub info: Option<IpInfo>,
+    pub threat: Option<IpThreat>,
+}
+
+impl IpEnrichment {
+    pub const CSV_HEADER: [&'static str; 19] = [
+        "ip",
+        "hostname",
+        "city",
+        "region",
+        "country",
+        "lat",
+        "lon",
+        "org",
+        "postal",
+        "timezone",
+        "is_tor",
+        "is_icloud_relay",
+        "is_proxy",
+        "is_datacenter",
+        "is_anonymous",
+        "is_known_attacker",
+        "is_known_abuser",
+        "is_threat",
+        "is_bogon",
+    ];
+
+    /// One CSV row in the order of [CSV_HEADER](Self::CSV_HEADER), missing data is left empty
+    pub fn csv_row(&self) -> String {
+        let mut row = vec![self.ip.to_string()];
+        match &self.info {
+            Some(info) => row.extend([
+                info.hostname.to_owned().unwrap_or_default(),
+                info.city.to_owned(),
+                info.region.to_owned(),
+                info.country.to_owned(),
+                info.loc.lat.to_string(),
+                info.loc.lon.to_string(),
+                info.org.to_owned(),
+                info.postal.to_owned(),
+                info.timezone.to_owned(),
+            ]),
+            None => row.extend(std::iter::repeat_n(String::new(), 9)),
+        }
+        match &self.threat {
+            Some(threat) => row.extend(
+                [
+                    threat.is_tor,
+                    threat.is_icloud_relay,
+                    threat.is_proxy,
+                    threat.is_datacenter,
+                    threat.is_anonymous,
+                    threat.is_known_attacker,
+                    threat.is_known_abuser,
+                    threat.is_threat,
+                    threat.is_bogon,
+                ]
+                .map(|b| b.to_string()),
+            ),
+            None => row.extend(std::iter::repeat_n(String::new(), 9)),
+        }
+        row.iter()
+            .map(|field| csv_field(field))
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+}
+
+/// Quotes a CSV field if it needs it, orgs love commas
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
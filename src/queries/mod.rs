@@ -1,5 +1,7 @@
 //! Holds network queries
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+pub mod diagnostics;
 pub mod hdtools;
 pub mod ip;
 pub mod osiris;
@@ -18,19 +20,195 @@ pub struct Queries {
     pub ipq: Arc<ip::Ip>,
     /// Osiris queries
     pub osiris: Arc<osiris::Osiris>,
+    /// Timings of every query above
+    pub diagnostics: Arc<diagnostics::Diagnostics>,
 }
 
 impl Queries {
-    pub fn new(splunk: splunk::Splunk, hdtools: Option<hdtools::HDTools>) -> Self {
+    /// IP and Osiris queries are made here with their [TimeoutConfig] entries, Splunk and
+    /// HDTools already have theirs from login
+    pub fn new(
+        mut splunk: splunk::Splunk,
+        mut hdtools: Option<hdtools::HDTools>,
+        timeouts: &TimeoutConfig,
+    ) -> Self {
+        // Splunk and HDTools are made at login, before there is anything to share
+        let diagnostics = Arc::new(diagnostics::Diagnostics::default());
+        splunk.diagnostics = Arc::clone(&diagnostics);
+        if let Some(hdtools) = hdtools.as_mut() {
+            hdtools.diagnostics = Arc::clone(&diagnostics);
+        }
         Queries {
             splunk: Arc::new(splunk),
             hdtools: hdtools.map(Arc::new),
-            ipq: Arc::new(ip::Ip::new()),
-            osiris: Arc::new(osiris::Osiris::new()),
+            ipq: Arc::new(ip::Ip::new(timeouts.ip, Arc::clone(&diagnostics))),
+            osiris: Arc::new(osiris::Osiris::new(
+                timeouts.osiris,
+                Arc::clone(&diagnostics),
+            )),
+            diagnostics,
+        }
+    }
+}
+
+/// Connect and read timeouts for a query source
+///
+/// Without these a single black-holed connection hangs its thread forever.  Splunk exports can
+/// take minutes to stream back, so it gets a much longer read timeout than everything else.  The
+/// constants are the defaults, see [TimeoutConfig] for what's used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub read: Duration,
+}
+
+impl Timeouts {
+    pub const SPLUNK: Self = Self {
+        connect: Duration::from_secs(10),
+        read: Duration::from_secs(120),
+    };
+    pub const HDTOOLS: Self = Self {
+        connect: Duration::from_secs(5),
+        read: Duration::from_secs(15),
+    };
+    pub const IP: Self = Self {
+        connect: Duration::from_secs(5),
+        read: Duration::from_secs(10),
+    };
+    pub const OSIRIS: Self = Self {
+        connect: Duration::from_secs(5),
+        read: Duration::from_secs(30),
+    };
+
+    /// Starts an agent with these timeouts set
+    pub fn builder(&self) -> ureq::AgentBuilder {
+        ureq::builder()
+            .timeout_connect(self.connect)
+            .timeout_read(self.read)
+            .timeout_write(self.read)
+    }
+
+    pub fn agent(&self) -> ureq::Agent {
+        self.builder().build()
+    }
+}
+
+/// Timeouts for each query source, saved so a slow network can be given longer.  Sources missing
+/// from what was saved get their default.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TimeoutConfig {
+    pub splunk: Timeouts,
+    pub hdtools: Timeouts,
+    pub ip: Timeouts,
+    pub osiris: Timeouts,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            splunk: Timeouts::SPLUNK,
+            hdtools: Timeouts::HDTOOLS,
+            ip: Timeouts::IP,
+            osiris: Timeouts::OSIRIS,
+        }
+    }
+}
+
+/// Why a query didn't come back with anything
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryError {
+    /// Credentials or session were rejected
+    Auth,
+    /// Couldn't talk to the service
+    Network,
+    /// The service didn't answer in time
+    Timeout,
+    /// The service answered with something unexpected
+    Parse,
+    /// Too many requests
+    RateLimited,
+    /// The service has nothing for what was asked
+    NotFound,
+}
+
+impl QueryError {
+    /// True if the query might work if tried again
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Network | Self::Timeout | Self::RateLimited)
+    }
+}
+
+impl std::fmt::Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Auth => "not authorized",
+                Self::Network => "network error",
+                Self::Timeout => "timed out",
+                Self::Parse => "unexpected response",
+                Self::RateLimited => "rate limited",
+                Self::NotFound => "not found",
+            }
+        )
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl From<ureq::Error> for QueryError {
+    fn from(e: ureq::Error) -> Self {
+        if is_timeout(&e) {
+            return Self::Timeout;
+        }
+        match e {
+            ureq::Error::Status(401 | 403, _) => Self::Auth,
+            ureq::Error::Status(404, _) => Self::NotFound,
+            ureq::Error::Status(429, _) => Self::RateLimited,
+            ureq::Error::Status(_, _) | ureq::Error::Transport(_) => Self::Network,
         }
     }
 }
 
+/// Reading a response body, ureq reports bad JSON as [InvalidData](std::io::ErrorKind::InvalidData)
+impl From<std::io::Error> for QueryError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => Self::Timeout,
+            std::io::ErrorKind::InvalidData => Self::Parse,
+            _ => Self::Network,
+        }
+    }
+}
+
+/// True if a request failed because it ran out of time, as opposed to being refused or erroring
+pub fn is_timeout(e: &ureq::Error) -> bool {
+    match e {
+        ureq::Error::Transport(t) => std::error::Error::source(t)
+            .and_then(|s| s.downcast_ref::<std::io::Error>())
+            .is_some_and(|e| {
+                matches!(
+                    e.kind(),
+                    std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                )
+            }),
+        ureq::Error::Status(_, _) => false,
+    }
+}
+
+/// Logs a failed request, calling out timeouts so they can be told apart from other failures,
+/// and converts it to a [QueryError]
+fn log_error(what: &str, e: ureq::Error) -> QueryError {
+    if is_timeout(&e) {
+        log::warn!("{} timed out: {}", what, e);
+    } else {
+        log::warn!("{} failed: {}", what, e);
+    }
+    QueryError::from(e)
+}
+
 /// Encodes username & password for basic HTTP auth in compliance with
 /// [RFC 7235](https://datatracker.ietf.org/doc/html/rfc7235)
 fn basic_auth<U, P>(username: U, password: Option<P>) -> String
@@ -1,65 +1,249 @@
 //! Osiris (Zeppelin backend) queries
+use super::diagnostics::{self, Backend, Diagnostics};
+use super::QueryError;
 use base64::{engine::general_purpose::STANDARD, Engine};
-use chrono::NaiveDate;
-use log::info;
+use chrono::{Datelike, NaiveDate};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 /// I tried to be a good little boy who uses TLS but the wiki certs don't have a local issuer
 /// certificate 😩
 const URL: &str = "http://csoc-wiki.clemson.edu";
 
 pub struct Osiris {
+    agent: ureq::Agent,
     /// The super secret API key shared by Horus and Osiris
     auth: String,
+    diagnostics: Arc<Diagnostics>,
 }
 
 impl Osiris {
-    pub fn new() -> Self {
+    pub fn new(timeouts: super::Timeouts, diagnostics: Arc<Diagnostics>) -> Self {
         Self {
+            agent: timeouts.agent(),
             auth: STANDARD.encode(env!("OSIRIS_API_KEY")),
+            diagnostics,
         }
     }
 
-    pub fn get_date(&self, day: NaiveDate) -> Option<Data> {
+    pub fn get_date(&self, day: NaiveDate) -> Result<Data, QueryError> {
         info!("Getting data for {} from Osiris", day.format("%F"));
-        let data = ureq::get(&format!("{}/{}", URL, day.format("%F")))
+        let timer = self.diagnostics.start(Backend::Osiris, "get_date");
+        let resp = self
+            .agent
+            .get(&format!("{}/{}", URL, day.format("%F")))
             .set("Authorization", &self.auth)
             .call()
-            .ok()?
-            .into_json()
-            .ok();
+            .map_err(|e| super::log_error("Osiris GET", e))?;
+        let bytes = diagnostics::content_length(&resp);
+        let data = resp.into_json()?;
+        timer.finish(bytes, 1);
 
         info!("Retrieved data");
-        data
+        Ok(data)
     }
 
-    pub fn post_date(&self, day: NaiveDate, data: Data) -> Option<()> {
+    pub fn post_date(&self, day: NaiveDate, data: Data) -> Result<(), QueryError> {
         info!("Posting data for {} to Osiris", day.format("%F"));
-        ureq::post(&format!("{}/{}", URL, day.format("%F")))
+        let timer = self.diagnostics.start(Backend::Osiris, "post_date");
+        self.agent
+            .post(&format!("{}/{}", URL, day.format("%F")))
             .set("Authorization", &self.auth)
             .send_json(data)
-            .ok()?;
+            .map_err(|e| super::log_error("Osiris POST", e))?;
+        timer.finish(0, 1);
 
         info!("Successfult sent data");
-        Some(())
+        Ok(())
     }
 
-    pub fn get(&self) -> Option<Vec<(String, Data)>> {
+    pub fn get(&self) -> Result<Vec<(String, Data)>, QueryError> {
         info!("Getting data from Osiris");
-        let resp = ureq::get(URL)
+        let timer = self.diagnostics.start(Backend::Osiris, "get");
+        let resp = self
+            .agent
+            .get(URL)
             .set("Authorization", &self.auth)
             .call()
-            .ok()?
-            .into_json()
-            .ok()?;
+            .map_err(|e| super::log_error("Osiris GET", e))?;
+        let bytes = diagnostics::content_length(&resp);
+        let data: Vec<(String, Data)> = resp.into_json()?;
+        timer.finish(bytes, data.len());
 
         info!("Got data");
-        resp
+        Ok(data)
     }
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize, Default)]
 pub struct Data {
     pub incidents: Vec<(String, i64)>,
     pub investigations: Vec<(String, i64)>,
 }
+
+impl Data {
+    /// Renames categories to the names analysts gave them, adding together categories that end
+    /// up with the same name.  Osiris can't rename a category, so renames are kept locally and
+    /// applied to whatever it sends back.
+    pub fn alias(self, aliases: &HashMap<String, String>) -> Self {
+        Self {
+            incidents: alias_counts(self.incidents, aliases),
+            investigations: alias_counts(self.investigations, aliases),
+        }
+    }
+}
+
+/// Renames and merges one list of counts, keeping the order each name first shows up in
+fn alias_counts(
+    counts: Vec<(String, i64)>,
+    aliases: &HashMap<String, String>,
+) -> Vec<(String, i64)> {
+    let mut merged: Vec<(String, i64)> = Vec::with_capacity(counts.len());
+    add_counts(
+        &mut merged,
+        counts
+            .into_iter()
+            .map(|(name, count)| (aliases.get(&name).cloned().unwrap_or(name), count)),
+    );
+    merged
+}
+
+/// Adds `counts` to the totals with the same name, new names go on the end
+fn add_counts(totals: &mut Vec<(String, i64)>, counts: impl IntoIterator<Item = (String, i64)>) {
+    for (name, count) in counts {
+        match totals.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += count,
+            None => totals.push((name, count)),
+        }
+    }
+}
+
+/// A category's count on two days, see [diff_counts]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CountDiff {
+    pub name: String,
+    pub before: i64,
+    pub after: i64,
+}
+
+impl CountDiff {
+    /// How much the count went up, negative if it went down
+    pub fn change(&self) -> i64 {
+        self.after - self.before
+    }
+}
+
+/// Lines up the counts of two days by category, in the order they show up on the first day with
+/// categories only on the second after.  A category missing from a day counts as 0 there.
+pub fn diff_counts(before: &[(String, i64)], after: &[(String, i64)]) -> Vec<CountDiff> {
+    let mut diffs: Vec<CountDiff> = before
+        .iter()
+        .map(|(name, count)| CountDiff {
+            name: name.to_owned(),
+            before: *count,
+            after: 0,
+        })
+        .collect();
+    for (name, count) in after {
+        match diffs.iter_mut().find(|d| d.name == *name) {
+            Some(diff) => diff.after = *count,
+            None => diffs.push(CountDiff {
+                name: name.to_owned(),
+                before: 0,
+                after: *count,
+            }),
+        }
+    }
+    diffs
+}
+
+/// How rows of a saved report are grouped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    /// One row for each day
+    #[default]
+    Daily,
+    /// ISO weeks, Monday to Sunday
+    Weekly,
+    Monthly,
+}
+
+impl Aggregation {
+    pub fn choices() -> impl Iterator<Item = Self> {
+        [Self::Daily, Self::Weekly, Self::Monthly].into_iter()
+    }
+
+    /// Label of the period `day` falls in, such as `2024-W07` or `2024-03`
+    pub fn period(self, day: NaiveDate) -> String {
+        match self {
+            Self::Daily => day.format("%F").to_string(),
+            Self::Weekly => {
+                let week = day.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Self::Monthly => day.format("%Y-%m").to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Daily => "Daily",
+                Self::Weekly => "Weekly",
+                Self::Monthly => "Monthly",
+            }
+        )
+    }
+}
+
+/// Adds up each category over each period of `aggregation`, oldest first.  A period is kept
+/// whole if any of its days are in `range`, so a week that starts before the range still has
+/// all of its days, and daily rows are only kept inside it.  Rows whose time isn't a date are
+/// left out and counted, that count comes back with the periods.
+pub fn aggregate(
+    rows: Vec<(String, Data)>,
+    range: (NaiveDate, NaiveDate),
+    aggregation: Aggregation,
+) -> (Vec<(String, Data)>, usize) {
+    let (start, end) = (range.0.min(range.1), range.0.max(range.1));
+    let periods: HashSet<String> = start
+        .iter_days()
+        .take_while(|d| *d <= end)
+        .map(|d| aggregation.period(d))
+        .collect();
+
+    let mut totals: Vec<(String, Data)> = vec![];
+    let mut skipped = 0;
+    for (time, data) in rows {
+        let Ok(day) = NaiveDate::parse_from_str(time.trim(), "%F") else {
+            warn!(
+                "Skipping Osiris row with a time that isn't a date: {}",
+                time
+            );
+            skipped += 1;
+            continue;
+        };
+        let period = aggregation.period(day);
+        if !periods.contains(&period) {
+            continue;
+        }
+        let i = match totals.iter().position(|(p, _)| *p == period) {
+            Some(i) => i,
+            None => {
+                totals.push((period, Data::default()));
+                totals.len() - 1
+            }
+        };
+        add_counts(&mut totals[i].1.investigations, data.investigations);
+        add_counts(&mut totals[i].1.incidents, data.incidents);
+    }
+    // Labels of one aggregation sort by time
+    totals.sort_by(|a, b| a.0.cmp(&b.0));
+    (totals, skipped)
+}
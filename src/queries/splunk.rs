@@ -1,26 +1,97 @@
 //! Splunk queries
 //!
 //! Holds the username and password for Splunk
-use super::ip::IpDB;
+use super::{
+    diagnostics::{Backend, Diagnostics},
+    ip::{IpDB, IpDbError},
+    QueryError, Timeouts,
+};
 use crate::user::vpnlog::VpnLog;
-use crate::user::{login::Login, User};
-use chrono::NaiveDateTime;
-use log::{debug, info};
+use crate::user::{
+    admin::AdminEvent,
+    canonical_name,
+    login::{Login, ResultMap},
+    zone::Zone,
+    ActivitySummary, Location, User,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{debug, error, info, warn};
 use rayon::prelude::*;
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::io::Read;
 use std::net::Ipv4Addr;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock, RwLock};
 use ureq;
 use url::Url;
 
 /// Date format for the Splunk API when specifying start and end times
 const DATE_FORMAT: &str = "%FT%T";
+/// Date format of `earliest` and `latest` inside a search, see [Search::spl]
+const SPL_DATE_FORMAT: &str = "%m/%d/%Y:%H:%M:%S";
 /// Buffer size of responses to queries for Sonar
 const BUF_SIZE: usize = 10_000;
+/// Results read per request when paging through a search job
+const JOB_PAGE: usize = 50_000;
+/// Longest a search job is waited on before it's given up on and deleted
+const JOB_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30 * 60);
+/// Most results Splunk hands back from a subsearch by default, anything past it is dropped
+/// without an error
+const SUBSEARCH_LIMIT: usize = 10_000;
+/// Duo events that make a user suspicious, see [LoginFilter::Suspicious]
+const SUSPICIOUS: &str = "(result=FAILURE OR result=FRAUD OR reason=\"restricted ofac location\")";
+
+/// Splunk index each kind of log is searched in, for sites that name theirs differently
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    /// Duo authentications
+    pub duo: String,
+    /// ISE accounting, for VPN sessions and MAC addresses
+    pub ise: String,
+    pub dhcp: String,
+    /// Cisco wireless, for which user had an IP
+    pub cisco: String,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            duo: "splunk_duo".to_owned(),
+            ise: "splunk_network_ise".to_owned(),
+            dhcp: "splunk_network_dhcp".to_owned(),
+            cisco: "splunk_network_cisco".to_owned(),
+        }
+    }
+}
+
+impl IndexConfig {
+    /// Checks every index is a single word, so it can't change the search it's put in
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, index) in [
+            ("Duo", &self.duo),
+            ("ISE", &self.ise),
+            ("DHCP", &self.dhcp),
+            ("Cisco", &self.cisco),
+        ] {
+            if index.is_empty() {
+                return Err(format!("{} index is empty", name));
+            }
+            if !index
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "_-*".contains(c))
+            {
+                return Err(format!("{} index is invalid: {}", name, index));
+            }
+        }
+        Ok(())
+    }
+}
 
 static GET_DUO_USER_RE: OnceLock<Regex> = OnceLock::new();
+static COUNT_RE: OnceLock<Regex> = OnceLock::new();
 static DHCP_IP_RE: OnceLock<Regex> = OnceLock::new();
 static DHCP_MAC_RE: OnceLock<Regex> = OnceLock::new();
 static CISCO_IP_RE: OnceLock<Regex> = OnceLock::new();
@@ -29,19 +100,41 @@ static ISE_USER_MAC_RE: OnceLock<Regex> = OnceLock::new();
 static ISE_MAC_MAC_RE: OnceLock<Regex> = OnceLock::new();
 
 pub struct Splunk {
+    agent: ureq::Agent,
     url: Url,
     auth: String,
     /// GeoIP db, it is held in Splunk as Splunk creates the logins and thus holds the IpDB to pass
     /// a reference to the login serialization function
     ipinfo: IpDB,
+    /// Why the IpDB is empty, if it failed to load
+    ipdb_error: Option<IpDbError>,
+    /// Indexes searched, see [IndexConfig]
+    indexes: IndexConfig,
+    /// Timezone Splunk's timestamps are in, held for the same reason as the IpDB
+    zone: RwLock<Zone>,
+    /// Login pulls longer than this go through a search job, see [Splunk::get_logins]
+    job_threshold: RwLock<chrono::Duration>,
+    /// Events each Sonar search reads, see [Splunk::sonar_search]
+    sonar_limit: RwLock<usize>,
+    /// Unknown Duo results counted as failures or fraud, see [Login::map_result]
+    result_map: RwLock<ResultMap>,
+    /// Replaced with the shared one by [Queries::new](super::Queries::new)
+    pub(super) diagnostics: Arc<Diagnostics>,
 }
 
 impl Splunk {
     /// Checks the user and password against Splunk and returns it's self if valid
-    pub fn new(username: &str, password: Option<&str>) -> Option<Self> {
-        let status = ureq::get("https://TOP_SNEAKY_URL")
+    pub fn new(
+        username: &str,
+        password: Option<&str>,
+        indexes: IndexConfig,
+        timeouts: Timeouts,
+    ) -> Result<Self, QueryError> {
+        let agent = timeouts.agent();
+        let status = agent
+            .get("https://TOP_SNEAKY_URL")
             .send_form(&[("username", username), ("password", password.unwrap_or(""))])
-            .ok()?
+            .map_err(|e| super::log_error("Splunk login", e))?
             .status();
 
         info!("Splnuk status was {}", status);
@@ -51,157 +144,585 @@ impl Splunk {
 
         let auth = super::basic_auth(username, password);
 
-        Some(Self {
+        // Logins can still be checked without geolocation, so a bad database isn't fatal
+        let (ipinfo, ipdb_error) = match IpDB::new() {
+            Ok(ipdb) => (ipdb, None),
+            Err(e) => {
+                error!("Could not load IP databases: {}", e);
+                (IpDB::empty(), Some(e))
+            }
+        };
+
+        Ok(Self {
+            agent,
             url,
             auth,
-            ipinfo: IpDB::new(),
+            ipinfo,
+            ipdb_error,
+            indexes,
+            zone: RwLock::new(Zone::default()),
+            job_threshold: RwLock::new(chrono::Duration::days(8)),
+            sonar_limit: RwLock::new(100),
+            result_map: RwLock::default(),
+            diagnostics: Arc::default(),
         })
     }
 
-    pub fn get_duo_users(
-        &self,
-        time_span: &TimeSpan,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+    /// Why logins have no geolocation, if the IP databases failed to load
+    pub fn ipdb_error(&self) -> Option<&IpDbError> {
+        self.ipdb_error.as_ref()
+    }
 
-        let search = "search index=splunk_duo host=duo_api user=* | dedup user";
+    /// Coordinates of a user's home, see [IpDB::geocode]
+    pub fn geocode(&self, location: &Location) -> Option<(f32, f32)> {
+        self.ipinfo.geocode(location)
+    }
 
-        info!("Querying splunk: {}", search);
+    /// Coordinates the IP database has for an IP
+    pub fn db_location(&self, ip: Ipv4Addr) -> Option<(f32, f32)> {
+        self.ipinfo.get_iploc(ip).map(|l| (l.lat, l.lon))
+    }
 
-        let resp = ureq::request_url("POST", &self.url)
+    /// Sets the timezone Splunk's timestamps are read in
+    pub fn set_zone(&self, zone: Zone) {
+        *self.zone.write().expect("Failed to get zone write lock") = zone;
+    }
+
+    fn zone(&self) -> Zone {
+        *self.zone.read().expect("Failed to get zone read lock")
+    }
+
+    /// Sets how long a login pull has to be before it goes through a search job
+    pub fn set_job_threshold(&self, threshold: chrono::Duration) {
+        *self
+            .job_threshold
+            .write()
+            .expect("Failed to get job threshold write lock") = threshold;
+    }
+
+    fn job_threshold(&self) -> chrono::Duration {
+        *self
+            .job_threshold
+            .read()
+            .expect("Failed to get job threshold read lock")
+    }
+
+    /// Sets how many events each Sonar search reads
+    pub fn set_sonar_limit(&self, limit: usize) {
+        *self
+            .sonar_limit
+            .write()
+            .expect("Failed to get Sonar limit write lock") = limit;
+    }
+
+    fn sonar_limit(&self) -> usize {
+        *self
+            .sonar_limit
+            .read()
+            .expect("Failed to get Sonar limit read lock")
+    }
+
+    /// Sets which unknown Duo results are counted as failures or fraud
+    pub fn set_result_map(&self, map: ResultMap) {
+        *self
+            .result_map
+            .write()
+            .expect("Failed to get result map write lock") = map;
+    }
+
+    fn result_map(&self) -> ResultMap {
+        self.result_map
+            .read()
+            .expect("Failed to get result map read lock")
+            .clone()
+    }
+
+    /// Pairs a search with a time span in Splunk's timezone
+    fn build_search(&self, search: String, time_span: &TimeSpan) -> Search {
+        let zone = self.zone();
+        Search {
+            search,
+            earliest: zone.to_zone(time_span.start),
+            latest: zone.to_zone(time_span.end),
+        }
+    }
+
+    /// POSTs a search to Splunk, logging why it failed if it does
+    fn post(&self, form: &[(&str, &str)]) -> Result<ureq::Response, QueryError> {
+        self.agent
+            .request_url("POST", &self.url)
             .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])?;
+            .send_form(form)
+            .map_err(|e| super::log_error("Splunk query", e))
+    }
 
-        let mut buf = String::with_capacity(1_000_000);
+    /// Reads a whole Splunk response, logging why it failed if it does
+    fn read(resp: ureq::Response, buf: &mut String) -> Result<(), QueryError> {
         resp.into_reader()
-            .read_to_string(&mut buf)
-            .map_err(ureq::Error::from)?;
+            .read_to_string(buf)
+            .map(|_| ())
+            .map_err(|e| super::log_error("Reading Splunk results", ureq::Error::from(e)))
+    }
+
+    /// Runs the cheapest search there is to check the session still works
+    pub fn health_check(&self) -> Result<(), QueryError> {
+        let timer = self.diagnostics.start(Backend::Splunk, "health_check");
+        let resp = self.post(&[("output_mode", "json"), ("search", "| makeresults")])?;
+        let mut buf = String::new();
+        Self::read(resp, &mut buf)?;
+        timer.finish(buf.len(), 1);
+        Ok(())
+    }
+
+    /// Counts the Duo events in a time span without pulling them, so a range that was picked by
+    /// accident can be narrowed before waiting on the real run
+    pub fn estimate_count(&self, time_span: &TimeSpan) -> Result<usize, QueryError> {
+        let timer = self.diagnostics.start(Backend::Splunk, "estimate_count");
+        let search = self.build_search(
+            format!(
+                "search index={} host=duo_api user=* | stats count",
+                self.indexes.duo
+            ),
+            time_span,
+        );
+        let (count, bytes) = self.count(&search)?;
+        timer.finish(bytes, 1);
+
+        info!("Estimated {} events", count);
+
+        Ok(count)
+    }
+
+    /// Runs a search ending in a `count` field, giving back the count and the bytes read
+    fn count(&self, search: &Search) -> Result<(usize, usize), QueryError> {
+        let (earliest_time, latest_time) = search.time_range();
+
+        info!("Querying splunk: {}", search);
+
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
+
+        let mut buf = String::new();
+        Self::read(resp, &mut buf)?;
+
+        let count = COUNT_RE
+            .get_or_init(|| Regex::new(r#""count":"(\d+)""#).unwrap())
+            .captures(&buf)
+            .and_then(|cap| cap[1].parse().ok())
+            .ok_or_else(|| {
+                error!("Couldn't find a count in {}", buf);
+                QueryError::Parse
+            })?;
+
+        Ok((count, buf.len()))
+    }
+
+    /// Search for every user with a Duo event in `time_span`
+    pub fn duo_users_search(&self, time_span: &TimeSpan) -> Search {
+        self.build_search(
+            format!(
+                "search index={} host=duo_api user=* | dedup user",
+                self.indexes.duo
+            ),
+            time_span,
+        )
+    }
+
+    pub fn get_duo_users(&self, time_span: &TimeSpan) -> Result<Vec<String>, QueryError> {
+        let timer = self.diagnostics.start(Backend::Splunk, "get_duo_users");
+        let search = self.duo_users_search(time_span);
+        let (earliest_time, latest_time) = search.time_range();
+
+        info!("Querying splunk: {}", search);
+
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
+
+        let mut buf = String::with_capacity(1_000_000);
+        Self::read(resp, &mut buf)?;
 
         info!("Got {} bytes", buf.len());
 
         let mut users: Vec<String> = GET_DUO_USER_RE
             .get_or_init(|| Regex::new(r#""user":"(\w+)""#).unwrap())
             .captures_iter(&buf)
-            .map(|cap| cap[1].to_owned())
+            .map(|cap| canonical_name(&cap[1]))
             .collect();
 
         users.par_sort();
         users.dedup();
 
+        timer.finish(buf.len(), users.len());
         info!("Retrieved {} users", users.len());
 
         Ok(users)
     }
 
+    /// Search for one user's logins in `time_span`
+    pub fn user_logins_search(&self, username: &str, time_span: &TimeSpan) -> Search {
+        self.build_search(
+            format!(
+                "search index={} host=duo_api result=* user={} | dedup _raw",
+                self.indexes.duo, username
+            ),
+            time_span,
+        )
+    }
+
+    /// Pulls one user's logins in `time_span`, keeping each login's raw event if `keep_raw`
     pub fn get_user_logins(
         &self,
         username: &str,
         time_span: &TimeSpan,
-    ) -> Result<Vec<Login>, Box<ureq::Error>> {
+        keep_raw: bool,
+    ) -> Result<Vec<Login>, QueryError> {
         let now = std::time::Instant::now();
         debug!("Starting! {:?}", now.elapsed());
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
-
-        let search = format!(
-            "search index=splunk_duo host=duo_api result=* user={} | dedup _time",
-            username
-        );
+        let timer = self.diagnostics.start(Backend::Splunk, "get_user_logins");
+        let search = self.user_logins_search(username, time_span);
+        let (earliest_time, latest_time) = search.time_range();
 
         info!("Querying splunk: {}", search);
 
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])?;
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
 
         debug!("Starting serialization {:?}", now.elapsed());
 
         let mut buf = String::with_capacity(5_000_000);
-        resp.into_reader()
-            .read_to_string(&mut buf)
-            .map_err(ureq::Error::from)?;
+        Self::read(resp, &mut buf)?;
 
         info!("Got {} bytes", buf.len());
 
+        let zone = self.zone();
+        let results = self.result_map();
         let mut logins: Vec<Login> = buf
             .par_lines()
-            .filter_map(|l| Login::new(l, &self.ipinfo))
+            .filter_map(|l| Self::parse_login(l, &self.ipinfo, zone, &results, keep_raw))
             .collect();
 
         logins.par_sort();
         logins.dedup();
 
+        timer.finish(buf.len(), logins.len());
+
         info!("Finished {:?}", now.elapsed());
         info!("Got {} logins", logins.len());
 
         Ok(logins)
     }
 
-    pub fn get_logins(&self, time_span: &TimeSpan) -> Result<Vec<Login>, Box<ureq::Error>> {
+    /// Parses one result line into a [Login] with its result mapped by `results`, keeping the line
+    /// too if `keep_raw`
+    fn parse_login(
+        line: &str,
+        ipdb: &IpDB,
+        zone: Zone,
+        results: &ResultMap,
+        keep_raw: bool,
+    ) -> Option<Login> {
+        let mut login = Login::new(line, ipdb, zone)?;
+        login.map_result(results);
+        if keep_raw {
+            login.raw = Some(line.to_owned());
+        }
+        Some(login)
+    }
+
+    /// Search for Duo administrator and self-service events about a user in `time_span`
+    pub fn admin_events_search(&self, username: &str, time_span: &TimeSpan) -> Search {
+        self.build_search(
+            format!(
+                "search index={} action=* object={} | dedup _raw",
+                self.indexes.duo, username
+            ),
+            time_span,
+        )
+    }
+
+    /// Pulls administrator and self-service events about a user, such as device activations and
+    /// bypass codes
+    pub fn get_admin_events(
+        &self,
+        username: &str,
+        time_span: &TimeSpan,
+    ) -> Result<Vec<AdminEvent>, QueryError> {
+        let timer = self.diagnostics.start(Backend::Splunk, "get_admin_events");
+        let search = self.admin_events_search(username, time_span);
+        let (earliest_time, latest_time) = search.time_range();
+
+        info!("Querying splunk: {}", search);
+
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
+
+        let mut buf = String::with_capacity(100_000);
+        Self::read(resp, &mut buf)?;
+
+        let zone = self.zone();
+        let mut events: Vec<AdminEvent> = buf
+            .lines()
+            .filter_map(|l| AdminEvent::new(l, zone))
+            .collect();
+        events.sort();
+        events.dedup();
+
+        timer.finish(buf.len(), events.len());
+        info!("Got {} admin events for {}", events.len(), username);
+
+        Ok(events)
+    }
+
+    /// Search for every login in `time_span` that `filter` lets through
+    pub fn logins_search(&self, time_span: &TimeSpan, filter: LoginFilter) -> Search {
+        self.build_search(filter.search(&self.indexes), time_span)
+    }
+
+    /// Pulls every login in `time_span`.  Spans longer than the job threshold are run as a search
+    /// job and paged through, since a plain search is cut off at Splunk's result limit, and
+    /// `progress` is told how far along the job is.  Each login's raw event is kept if
+    /// `keep_raw`.
+    pub fn get_logins(
+        &self,
+        time_span: &TimeSpan,
+        filter: LoginFilter,
+        keep_raw: bool,
+        progress: &dyn Fn(f32),
+    ) -> Result<Vec<Login>, QueryError> {
         let now = std::time::Instant::now();
         debug!("Starting! {:?}", now.elapsed());
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
-
-        let search = "search index=splunk_duo host=duo_api user=* result=* | dedup _time user";
+        let timer = self.diagnostics.start(Backend::Splunk, "get_logins");
+        let filter = match filter {
+            LoginFilter::Suspicious => {
+                let search =
+                    self.build_search(LoginFilter::suspicious_users(&self.indexes), time_span);
+                let (users, _) = self.count(&search)?;
+                info!("{} suspicious users", users);
+                let fitted = filter.fitted(users);
+                if fitted != filter {
+                    warn!(
+                        "{} suspicious users is past the subsearch limit {}, pulling every login",
+                        users, SUBSEARCH_LIMIT
+                    );
+                }
+                fitted
+            }
+            LoginFilter::All => filter,
+        };
+        let search = self.logins_search(time_span, filter);
+        let (earliest_time, latest_time) = search.time_range();
         info!("Querying splunk: {}", search);
 
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])?;
+        let form = [
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ];
 
-        debug!("Starting serialization {:?}", now.elapsed());
+        let buf = if time_span.end - time_span.start > self.job_threshold() {
+            info!("Running as a search job");
+            self.run_job(&form, progress)?
+        } else {
+            debug!("Sending query {:?}", now.elapsed());
+            let resp = self.post(&form)?;
 
-        let mut buf = String::with_capacity(150_000_000);
-        resp.into_reader()
-            .read_to_string(&mut buf)
-            .map_err(ureq::Error::from)?;
+            debug!("Starting serialization {:?}", now.elapsed());
 
-        info!("Got {} bytes", buf.len());
+            let mut buf = String::with_capacity(filter.buf_size());
+            Self::read(resp, &mut buf)?;
+            buf
+        };
+
+        info!("Got {} bytes for {:?} logins", buf.len(), filter);
 
+        let zone = self.zone();
+        let results = self.result_map();
         let mut logins: Vec<Login> = buf
             .par_lines()
-            .filter_map(|l| Login::new(l, &self.ipinfo))
+            .filter_map(|l| Self::parse_login(l, &self.ipinfo, zone, &results, keep_raw))
             .collect();
 
         logins.par_sort();
         logins.dedup();
 
+        timer.finish(buf.len(), logins.len());
+
         info!("Finished {:?}", now.elapsed());
         info!("Got {} logins", logins.len());
 
         Ok(logins)
     }
 
+    /// Runs a search as a job, waits for it to finish, and pages through its results.  Results
+    /// come back as the same lines a plain search gives, so they can be parsed the same way.
+    /// `progress` goes to a half while Splunk searches and the rest of the way while paging.  The
+    /// job is deleted once it's read or has failed, so it doesn't sit on Splunk's disk quota.
+    fn run_job(&self, form: &[(&str, &str)], progress: &dyn Fn(f32)) -> Result<String, QueryError> {
+        let jobs = self.job_url("")?;
+
+        // Without it Splunk keeps only the first 10,000 results of the job
+        let mut form = form.to_vec();
+        form.push(("max_count", "0"));
+
+        let resp: Value = self
+            .agent
+            .request_url("POST", &jobs)
+            .set("Authorization", &self.auth)
+            .send_form(&form)
+            .map_err(|e| super::log_error("Splunk job", e))?
+            .into_json()?;
+        let sid = resp["sid"].as_str().ok_or_else(|| {
+            error!("Splunk job has no sid: {}", resp);
+            QueryError::Parse
+        })?;
+        info!("Started Splunk job {}", sid);
+
+        let job = self.job_url(sid)?;
+        let buf = self.read_job(sid, &job, progress);
+        self.delete_job(sid, &job);
+        buf
+    }
+
+    /// Waits up to [JOB_TIMEOUT] for the job at `job` to finish and pages through its results
+    fn read_job(&self, sid: &str, job: &Url, progress: &dyn Fn(f32)) -> Result<String, QueryError> {
+        let deadline = std::time::Instant::now() + JOB_TIMEOUT;
+        let result_count = loop {
+            let status = self.get_json(job, &[("output_mode", "json")])?;
+            let content = &status["entry"][0]["content"];
+            match content["dispatchState"].as_str() {
+                Some("DONE") => break content["resultCount"].as_u64().unwrap_or(0) as usize,
+                Some("FAILED") => {
+                    error!("Splunk job {} failed: {}", sid, content["messages"]);
+                    return Err(QueryError::Parse);
+                }
+                state => debug!("Splunk job {} is {:?}", sid, state),
+            }
+            if std::time::Instant::now() >= deadline {
+                error!("Splunk job {} didn't finish in {:?}", sid, JOB_TIMEOUT);
+                return Err(QueryError::Timeout);
+            }
+            progress(content["doneProgress"].as_f64().unwrap_or(0.0) as f32 / 2.0);
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        };
+        info!("Splunk job {} found {} results", sid, result_count);
+
+        let results = self.job_url(&format!("{}/results", sid))?;
+        Self::page_results(
+            result_count,
+            |offset| {
+                self.get_json(
+                    &results,
+                    &[
+                        ("output_mode", "json"),
+                        ("offset", &offset.to_string()),
+                        ("count", &JOB_PAGE.to_string()),
+                    ],
+                )
+            },
+            progress,
+        )
+    }
+
+    /// Reads the `result_count` results of a finished job a page at a time from `fetch`, which
+    /// is given the offset to start at.  Splunk can hand back fewer than asked for per page, so
+    /// this keeps going until it has them all or a page comes back empty.  Each result is
+    /// wrapped as `{"result": ...}`, like the lines of a plain search.
+    pub(super) fn page_results(
+        result_count: usize,
+        mut fetch: impl FnMut(usize) -> Result<Value, QueryError>,
+        progress: &dyn Fn(f32),
+    ) -> Result<String, QueryError> {
+        let mut buf = String::new();
+        let mut offset = 0;
+        while offset < result_count {
+            let page = fetch(offset)?;
+            let Some(page) = page["results"].as_array() else {
+                error!("Splunk job results page has no results: {}", page);
+                return Err(QueryError::Parse);
+            };
+            if page.is_empty() {
+                warn!(
+                    "Splunk job ran out of results at {} of {}",
+                    offset, result_count
+                );
+                break;
+            }
+            for result in page {
+                buf.push_str(&serde_json::json!({ "result": result }).to_string());
+                buf.push('\n');
+            }
+            offset += page.len();
+            progress(0.5 + offset.min(result_count) as f32 / result_count as f32 / 2.0);
+        }
+
+        Ok(buf)
+    }
+
+    /// Deletes a finished or failed job, it's only logged if Splunk won't
+    fn delete_job(&self, sid: &str, job: &Url) {
+        match self
+            .agent
+            .request_url("DELETE", job)
+            .set("Authorization", &self.auth)
+            .call()
+        {
+            Ok(_) => debug!("Deleted Splunk job {}", sid),
+            Err(e) => warn!("Couldn't delete Splunk job {}: {}", sid, e),
+        }
+    }
+
+    /// URL of `path` under Splunk's search jobs endpoint
+    fn job_url(&self, path: &str) -> Result<Url, QueryError> {
+        self.url
+            .join(&format!("/services/search/jobs/{}", path))
+            .map_err(|e| {
+                error!("Bad Splunk jobs URL: {}", e);
+                QueryError::Parse
+            })
+    }
+
+    /// GETs a Splunk endpoint as JSON, logging why it failed if it does
+    fn get_json(&self, url: &Url, query: &[(&str, &str)]) -> Result<Value, QueryError> {
+        Ok(self
+            .agent
+            .request_url("GET", url)
+            .set("Authorization", &self.auth)
+            .query_pairs(query.iter().copied())
+            .call()
+            .map_err(|e| super::log_error("Splunk job", e))?
+            .into_json()?)
+    }
+
     pub fn match_users_and_logins(
         users: Vec<String>,
         logins: Vec<Login>,
-        earliest_time: &NaiveDateTime,
+        earliest_time: &DateTime<Utc>,
+        lookback: chrono::Duration,
     ) -> Vec<User> {
         let mut user_logins = HashMap::<String, Vec<Login>>::with_capacity(users.len());
         for user in users {
-            user_logins.insert(user.to_owned(), vec![]);
+            user_logins.insert(canonical_name(&user), vec![]);
         }
 
         for login in logins {
-            if let Some(user) = user_logins.get_mut(&login.user) {
+            if let Some(user) = user_logins.get_mut(&canonical_name(&login.user)) {
                 user.push(login);
             }
         }
@@ -210,7 +731,7 @@ impl Splunk {
             .into_iter()
             .map(|(user, mut logins)| {
                 logins.sort();
-                User::new(user, logins, earliest_time)
+                User::new(user, logins, earliest_time, lookback)
             })
             .collect();
 
@@ -223,52 +744,53 @@ impl Splunk {
         &self,
         username: &str,
         time_span: TimeSpan,
-    ) -> Result<Vec<VpnLog>, Box<ureq::Error>> {
+    ) -> Result<Vec<VpnLog>, QueryError> {
         let now = std::time::Instant::now();
         debug!("Starting! {:?}", now.elapsed());
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
-
-        let search = format!(
-            r#"search index=splunk_network_ise Firepower-9300-ASA Calling_Station_ID=* UserName={} Class=CUVPN Acct_Status_Type="Start" OR Acct_Status_Type="Stop" | dedup _time | sort -_time"#,
-            username
+        let timer = self.diagnostics.start(Backend::Splunk, "get_user_vpn");
+        let search = self.build_search(
+            format!(
+                r#"search index={} Firepower-9300-ASA Calling_Station_ID=* UserName={} Class=CUVPN Acct_Status_Type="Start" OR Acct_Status_Type="Stop" | dedup _time Acct_Status_Type Calling_Station_ID Framed_IP_Address | sort -_time"#,
+                self.indexes.ise, username
+            ),
+            &time_span,
         );
+        let (earliest_time, latest_time) = search.time_range();
         info!("Querying splunk: {}", search);
 
         debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])?;
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
 
         debug!("Starting serialization {:?}", now.elapsed());
 
         let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .read_to_string(&mut buf)
-            .map_err(ureq::Error::from)?;
+        Self::read(resp, &mut buf)?;
 
         info!("Got {} bytes", buf.len());
 
         let mut vpn_logs: Vec<VpnLog> = buf
             .par_lines()
-            .filter_map(|l| VpnLog::new(l, &self.ipinfo))
+            .filter_map(|l| VpnLog::new(l, &self.ipinfo, self.zone()))
             .collect();
 
         vpn_logs.par_sort();
         vpn_logs.dedup();
+        let vpn_logs = VpnLog::sessions(vpn_logs);
+
+        timer.finish(buf.len(), vpn_logs.len());
 
         info!("Finished {:?}", now.elapsed());
-        info!("Got {} logins", vpn_logs.len());
+        info!("Got {} sessions", vpn_logs.len());
 
         Ok(vpn_logs)
     }
 
-    pub fn correlate_vpn_logs(vpn_logs: &mut Vec<VpnLog>) {
+    pub fn correlate_vpn_logs(vpn_logs: &mut [VpnLog]) {
         for i in 1..vpn_logs.len() {
             if vpn_logs[i - 1].correlates(&vpn_logs[i]) {
                 vpn_logs[i - 1].correlate_prev = true;
@@ -278,310 +800,337 @@ impl Splunk {
 
     // -------------------- Sonar --------------------
 
-    pub fn get_ip_from_mac(&self, mac: &str) -> Option<Ipv4Addr> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
-        info!("Getting IP for {}", mac);
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
-
-        // It's faster to search Splunk without dest_mac={}
-        let search = format!("search index=splunk_network_dhcp {}", mac);
-        info!("Querying splunk: {}", search);
-
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
-
-        debug!("Starting serialization {:?}", now.elapsed());
-
-        let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
-
-        info!("Got {} bytes", buf.len());
-
-        DHCP_IP_RE
-            .get_or_init(|| Regex::new(r#"on ([0-9.]+) to"#).unwrap())
-            .captures(&buf)
-            .and_then(|cap| cap[1].parse().ok())
+    /// Sonar search for `term` in `index` over the past day, capped at the Sonar limit so busy IPs
+    /// and MACs don't balloon the response
+    fn sonar_search(&self, index: &str, term: &str) -> Search {
+        self.build_search(
+            format!(
+                "search index={} {} | head {}",
+                index,
+                term,
+                self.sonar_limit()
+            ),
+            &chrono::Duration::hours(24).into(),
+        )
     }
 
-    pub fn get_ip_from_user(&self, user: &str) -> Option<Ipv4Addr> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
-        info!("Getting IP for {}", user);
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+    /// Searches a Sonar lookup of `lookup` starts with.  Later rounds search for whatever these
+    /// find, so they can't be known up front.
+    pub fn sonar_searches(&self, lookup: &str) -> Vec<Search> {
+        if Self::is_mac(lookup) {
+            vec![
+                self.sonar_search(&self.indexes.dhcp, lookup),
+                self.sonar_search(&self.indexes.ise, lookup),
+            ]
+        } else if lookup.parse::<Ipv4Addr>().is_ok() {
+            vec![
+                self.sonar_search(&self.indexes.dhcp, lookup),
+                self.sonar_search(&self.indexes.cisco, lookup),
+            ]
+        } else if Self::is_user(lookup) {
+            vec![
+                self.sonar_search(&self.indexes.cisco, &format!("Username=* {}", lookup)),
+                self.sonar_search(&self.indexes.ise, lookup),
+            ]
+        } else {
+            vec![]
+        }
+    }
 
-        // It's faster to search Splunk without dest_mac={}
-        let search = format!("search index=splunk_network_cisco Username=* {}", user);
+    /// Runs a search from [Splunk::sonar_search]
+    fn sonar_run(&self, search: &Search) -> Result<String, QueryError> {
+        let (earliest_time, latest_time) = search.time_range();
         info!("Querying splunk: {}", search);
 
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
-
-        debug!("Starting serialization {:?}", now.elapsed());
+        let resp = self.post(&[
+            ("output_mode", "json"),
+            ("search", &search.search),
+            ("earliest_time", &earliest_time),
+            ("latest_time", &latest_time),
+        ])?;
 
         let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
+        Self::read(resp, &mut buf)?;
 
         info!("Got {} bytes", buf.len());
 
-        CISCO_IP_RE
-            .get_or_init(|| Regex::new(r#"IP (?:= |<)([0-9.]+)"#).unwrap())
-            .captures(&buf)
-            .and_then(|cap| cap[1].parse().ok())
+        Ok(buf)
     }
 
-    pub fn get_user_from_ip(&self, ip: Ipv4Addr) -> Option<String> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+    pub fn get_ip_from_mac(&self, mac: &str) -> Result<Ipv4Addr, QueryError> {
+        info!("Getting IP for {}", mac);
+        let timer = self.diagnostics.start(Backend::Splunk, "get_ip_from_mac");
 
         // It's faster to search Splunk without dest_mac={}
-        let search = format!("search index=splunk_network_cisco {}", ip);
-        info!("Querying splunk: {}", search);
+        let buf = self.sonar_run(&self.sonar_search(&self.indexes.dhcp, mac))?;
 
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
+        let ips = rank_matches(
+            DHCP_IP_RE
+                .get_or_init(|| Regex::new(r#"on ([0-9.]+) to"#).unwrap())
+                .captures_iter(&buf)
+                .filter_map(|cap| cap[1].parse::<Ipv4Addr>().ok()),
+        );
+        timer.finish(buf.len(), ips.len());
+        ips.into_iter().next().ok_or(QueryError::NotFound)
+    }
 
-        debug!("Starting serialization {:?}", now.elapsed());
+    pub fn get_ip_from_user(&self, user: &str) -> Result<Ipv4Addr, QueryError> {
+        info!("Getting IP for {}", user);
+        let timer = self.diagnostics.start(Backend::Splunk, "get_ip_from_user");
 
-        let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
+        // It's faster to search Splunk without dest_mac={}
+        let buf = self
+            .sonar_run(&self.sonar_search(&self.indexes.cisco, &format!("Username=* {}", user)))?;
+
+        let ips = rank_matches(
+            CISCO_IP_RE
+                .get_or_init(|| Regex::new(r#"IP (?:= |<)([0-9.]+)"#).unwrap())
+                .captures_iter(&buf)
+                .filter_map(|cap| cap[1].parse::<Ipv4Addr>().ok()),
+        );
+        timer.finish(buf.len(), ips.len());
+        ips.into_iter().next().ok_or(QueryError::NotFound)
+    }
 
-        info!("Got {} bytes", buf.len());
+    pub fn get_user_from_ip(&self, ip: Ipv4Addr) -> Result<String, QueryError> {
+        info!("Getting user for {}", ip);
+        let timer = self.diagnostics.start(Backend::Splunk, "get_user_from_ip");
 
-        CISCO_USER_RE
-            .get_or_init(|| Regex::new(r#"(?:user = |Username = |User <)(\w+)"#).unwrap())
-            .captures(&buf)
-            .and_then(|cap| {
-                let user = cap[1].to_string();
-                if Self::is_user(&user) {
-                    Some(user)
-                } else {
-                    None
-                }
-            })
+        // It's faster to search Splunk without dest_mac={}
+        let buf = self.sonar_run(&self.sonar_search(&self.indexes.cisco, &ip.to_string()))?;
+
+        let users = rank_matches(
+            CISCO_USER_RE
+                .get_or_init(|| Regex::new(r#"(?:user = |Username = |User <)(\w+)"#).unwrap())
+                .captures_iter(&buf)
+                .map(|cap| cap[1].to_string())
+                .filter(|user| Self::is_user(user)),
+        );
+        timer.finish(buf.len(), users.len());
+        users.into_iter().next().ok_or(QueryError::NotFound)
     }
 
-    pub fn get_mac_from_ip(&self, ip: Ipv4Addr) -> Option<Vec<String>> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
+    /// Every MAC given a lease on the IP, most frequent first
+    pub fn get_mac_from_ip(&self, ip: Ipv4Addr) -> Result<Vec<String>, QueryError> {
         info!("Getting MAC for {}", ip);
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+        let timer = self.diagnostics.start(Backend::Splunk, "get_mac_from_ip");
 
         // It's faster to search Splunk without dest_ip={}
-        let search = format!("search index=splunk_network_dhcp {}", ip);
-        info!("Querying splunk: {}", search);
-
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
-
-        debug!("Starting serialization {:?}", now.elapsed());
-
-        let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
-
-        info!("Got {} bytes", buf.len());
-
-        DHCP_MAC_RE
-            .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
-            .captures(&buf)
-            .map(|cap| {
-                cap.iter()
-                    .filter_map(|c| {
-                        if let Some(c) = c {
-                            if Self::is_mac(c.as_str()) {
-                                return Some(c.as_str().to_string());
-                            }
-                        }
-                        None
-                    })
-                    .collect::<Vec<String>>()
-            })
+        let buf = self.sonar_run(&self.sonar_search(&self.indexes.dhcp, &ip.to_string()))?;
+
+        let macs = rank_matches(
+            DHCP_MAC_RE
+                .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
+                .captures_iter(&buf)
+                .map(|cap| cap[1].to_string())
+                .filter(|mac| Self::is_mac(mac)),
+        );
+        timer.finish(buf.len(), macs.len());
+        if macs.is_empty() {
+            return Err(QueryError::NotFound);
+        }
+        Ok(macs)
     }
 
-    pub fn get_mac_from_user(&self, user: &str) -> Option<Vec<String>> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
+    /// Every MAC the user authenticated from, most frequent first
+    pub fn get_mac_from_user(&self, user: &str) -> Result<Vec<String>, QueryError> {
         info!("Getting MAC for {}", user);
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+        let timer = self.diagnostics.start(Backend::Splunk, "get_mac_from_user");
 
         // It's faster to search Splunk without dest_ip={}
-        let search = format!("search index=splunk_network_ise {}", user);
-        info!("Querying splunk: {}", search);
+        let buf = self.sonar_run(&self.sonar_search(&self.indexes.ise, user))?;
+
+        let macs = rank_matches(
+            ISE_USER_MAC_RE
+                .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
+                .captures_iter(&buf)
+                .map(|cap| cap[1].replace('-', ":"))
+                .filter(|mac| Self::is_mac(mac)),
+        );
+        timer.finish(buf.len(), macs.len());
+        if macs.is_empty() {
+            return Err(QueryError::NotFound);
+        }
+        Ok(macs)
+    }
 
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
+    pub fn get_user_from_mac(&self, mac: &str) -> Result<String, QueryError> {
+        info!("Getting MAC for {}", mac);
+        let timer = self.diagnostics.start(Backend::Splunk, "get_user_from_mac");
 
-        debug!("Starting serialization {:?}", now.elapsed());
+        // It's faster to search Splunk without dest_ip={}
+        let buf = self.sonar_run(&self.sonar_search(&self.indexes.ise, mac))?;
+
+        let macs = rank_matches(
+            ISE_MAC_MAC_RE
+                .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
+                .captures_iter(&buf)
+                .map(|cap| cap[1].to_string())
+                .filter(|mac| Self::is_mac(mac)),
+        );
+        timer.finish(buf.len(), macs.len());
+        macs.into_iter().next().ok_or(QueryError::NotFound)
+    }
 
-        let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
+    pub fn is_mac(mac: &str) -> bool {
+        mac.len() == 17
+            && mac
+                .split(':')
+                .all(|byte| byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit()))
+    }
 
-        info!("Got {} bytes", buf.len());
+    /// Splits a comma or newline separated list of usernames, lowercased and deduplicated in the
+    /// order given.  Returns the first name that isn't a valid username.
+    pub fn parse_users(list: &str) -> Result<Vec<String>, String> {
+        let mut users: Vec<String> = vec![];
+        for user in list
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+        {
+            if !Self::is_user(user) {
+                return Err(user.to_owned());
+            }
+            let user = canonical_name(user);
+            if !users.contains(&user) {
+                users.push(user);
+            }
+        }
+        Ok(users)
+    }
 
-        ISE_USER_MAC_RE
-            .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
-            .captures(&buf)
-            .map(|cap| {
-                cap.iter()
-                    .filter_map(|c| {
-                        if let Some(c) = c {
-                            let mac = c.as_str().replace('-', ":");
-                            if Self::is_mac(&mac) {
-                                return Some(mac);
-                            }
-                        }
-                        None
-                    })
-                    .collect::<Vec<String>>()
-            })
+    pub fn is_user(user: &str) -> bool {
+        user.len() >= 2 && user.len() < 20 && user.chars().all(|c| c.is_ascii_alphanumeric())
     }
+}
 
-    pub fn get_user_from_mac(&self, mac: &str) -> Option<String> {
-        let now = std::time::Instant::now();
-        debug!("Starting! {:?}", now.elapsed());
-        info!("Getting MAC for {}", mac);
-        let time_span: TimeSpan = chrono::Duration::hours(24).into();
-        let earliest_time = format!("{}", time_span.start.format(DATE_FORMAT));
-        let latest_time = format!("{}", time_span.end.format(DATE_FORMAT));
+/// Orders matches by how often they show up, then by how recent they are.  Splunk returns events
+/// newest first, so the first one seen is the most recent.
+pub(super) fn rank_matches<T: PartialEq>(matches: impl IntoIterator<Item = T>) -> Vec<T> {
+    ActivitySummary::tally(matches)
+        .into_iter()
+        .map(|(m, _)| m)
+        .collect()
+}
 
-        // It's faster to search Splunk without dest_ip={}
-        let search = format!("search index=splunk_network_ise {}", mac);
-        info!("Querying splunk: {}", search);
+/// Which logins [Splunk::get_logins] pulls
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoginFilter {
+    /// Every login in the range
+    #[default]
+    All,
+    /// Every login of users with a failure, fraud report, or OFAC deny in the range.  Users who
+    /// only succeeded are never pulled, so they can't be flagged for impossible travel or new
+    /// devices.  When there are more of those users than a subsearch hands back, every login is
+    /// pulled instead, see [fitted](Self::fitted).
+    Suspicious,
+}
 
-        debug!("Sending query {:?}", now.elapsed());
-        let resp = ureq::request_url("POST", &self.url)
-            .set("Authorization", &self.auth)
-            .send_form(&[
-                ("output_mode", "json"),
-                ("search", &search),
-                ("earliest_time", &earliest_time),
-                ("latest_time", &latest_time),
-            ])
-            .ok()?;
+impl LoginFilter {
+    /// Search for the logins, the suspicious one narrows users with a subsearch so failures still
+    /// see the successes that forgive them
+    fn search(self, indexes: &IndexConfig) -> String {
+        match self {
+            Self::All => format!(
+                "search index={} host=duo_api user=* result=* | dedup _raw",
+                indexes.duo
+            ),
+            Self::Suspicious => format!(
+                "search index={0} host=duo_api user=* result=* \
+                [search index={0} host=duo_api user=* {1} | dedup user | fields user] \
+                | dedup _raw",
+                indexes.duo, SUSPICIOUS
+            ),
+        }
+    }
 
-        debug!("Starting serialization {:?}", now.elapsed());
+    /// Counts the users the suspicious subsearch would hand back
+    fn suspicious_users(indexes: &IndexConfig) -> String {
+        format!(
+            "search index={} host=duo_api user=* {} | stats dc(user) as count",
+            indexes.duo, SUSPICIOUS
+        )
+    }
 
-        let mut buf = String::with_capacity(BUF_SIZE);
-        resp.into_reader()
-            .take(BUF_SIZE as u64)
-            .read_to_string(&mut buf)
-            .ok()?;
+    /// The filter to pull with once it's known how many users are suspicious.  Past
+    /// [SUBSEARCH_LIMIT] the subsearch would silently drop some, so every login is pulled instead.
+    pub(super) fn fitted(self, suspicious_users: usize) -> Self {
+        match self {
+            Self::Suspicious if suspicious_users >= SUBSEARCH_LIMIT => Self::All,
+            filter => filter,
+        }
+    }
 
-        info!("Got {} bytes", buf.len());
+    /// Bytes to reserve for the response
+    fn buf_size(self) -> usize {
+        match self {
+            Self::All => 150_000_000,
+            Self::Suspicious => 20_000_000,
+        }
+    }
+}
 
-        ISE_MAC_MAC_RE
-            .get_or_init(|| Regex::new(r#"to ([0-9a-f:]+)"#).unwrap())
-            .captures(&buf)
-            .and_then(|cap| {
-                let mac = cap[1].to_string();
-                if Self::is_mac(&mac) {
-                    Some(mac)
-                } else {
-                    None
-                }
-            })
+/// A search as it's sent to Splunk, with its time range in Splunk's timezone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Search {
+    pub search: String,
+    pub earliest: NaiveDateTime,
+    pub latest: NaiveDateTime,
+}
+
+impl Search {
+    /// Start and end for the Splunk API
+    fn time_range(&self) -> (String, String) {
+        (
+            self.earliest.format(DATE_FORMAT).to_string(),
+            self.latest.format(DATE_FORMAT).to_string(),
+        )
     }
 
-    pub fn is_mac(mac: &str) -> bool {
-        mac.len() == 17
-            && mac
-                .split(':')
-                .all(|byte| byte.len() == 2 && byte.chars().all(|c| c.is_ascii_hexdigit()))
+    /// The search with its time range written in, ready to paste into Splunk's search bar.
+    /// Subsearches don't take the range of the search around them, so each gets its own.
+    pub fn spl(&self) -> String {
+        let range = format!(
+            "earliest=\"{}\" latest=\"{}\"",
+            self.earliest.format(SPL_DATE_FORMAT),
+            self.latest.format(SPL_DATE_FORMAT)
+        );
+        self.search
+            .replacen("search ", &format!("search {} ", range), 1)
+            .replace("[search ", &format!("[search {} ", range))
     }
+}
 
-    pub fn is_user(user: &str) -> bool {
-        user.len() >= 2 && user.len() < 20 && user.chars().all(|c| c.is_ascii_alphanumeric())
+impl std::fmt::Display for Search {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.search)
     }
 }
 
 const TIME_FMT: &str = "%H:%M";
 
+#[derive(Clone, Copy)]
 pub struct TimeSpan {
-    pub start: NaiveDateTime,
-    pub end: NaiveDateTime,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
 }
 
 impl TimeSpan {
+    /// Span between two local dates and times
     pub fn from(dates: (chrono::NaiveDate, chrono::NaiveDate), times: &(String, String)) -> Self {
         let start_time: chrono::NaiveTime =
             chrono::NaiveTime::parse_from_str(&times.0, TIME_FMT).expect("Bad start time format");
         let end_time: chrono::NaiveTime =
             chrono::NaiveTime::parse_from_str(&times.1, TIME_FMT).expect("Bad end time format");
-        let start = NaiveDateTime::new(dates.0, start_time);
-        let end = NaiveDateTime::new(dates.1, end_time);
+        let start = Zone::Local.to_utc(NaiveDateTime::new(dates.0, start_time));
+        let end = Zone::Local.to_utc(NaiveDateTime::new(dates.1, end_time));
         TimeSpan { start, end }
     }
 }
 
 impl From<chrono::Duration> for TimeSpan {
     fn from(dur: chrono::Duration) -> Self {
-        let end = chrono::Local::now().naive_local();
+        let end = Utc::now();
         let start = end - dur;
         Self { start, end }
     }
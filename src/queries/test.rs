@@ -0,0 +1,849 @@
+#![cfg(test)]
+use super::diagnostics::{self, Backend, Diagnostics};
+use super::ip::{self, IpEnrichment, IpInfo};
+use super::{hdtools, splunk, QueryError, TimeoutConfig, Timeouts};
+use crate::user::{
+    login::{self, Login},
+    zone::Zone,
+    Location,
+};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// -------------------- Timeouts --------------------
+
+#[test]
+fn timeout_silent_server() {
+    let timeouts = Timeouts {
+        connect: Duration::from_secs(1),
+        read: Duration::from_secs(1),
+    };
+    // Connections queue up on the listener but nothing ever answers them, so the read hangs until
+    // the timeout kicks in
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let now = Instant::now();
+    let resp = timeouts.agent().get(&url).call();
+    assert!(resp.is_err());
+    assert!(now.elapsed() >= timeouts.read);
+    assert!(now.elapsed() < timeouts.connect + timeouts.read + Duration::from_secs(1));
+}
+
+#[test]
+fn timeouts_saved() {
+    let mut storage = crate::storage::Storage::in_memory();
+    storage.set_profile("tiger");
+    assert_eq!(storage.get_timeouts(), TimeoutConfig::default());
+    assert_eq!(storage.get_timeouts().splunk, Timeouts::SPLUNK);
+
+    let mut timeouts = TimeoutConfig::default();
+    timeouts.osiris.read = Duration::from_secs(90);
+    storage.set_timeouts(&timeouts);
+    assert_eq!(storage.get_timeouts(), timeouts);
+    // Shared by every profile, like the HDTools URLs
+    storage.set_profile("cat");
+    assert_eq!(storage.get_timeouts(), timeouts);
+
+    // Sources missing from what was saved keep their default
+    let timeouts: TimeoutConfig = serde_json::from_str(
+        r#"{"ip":{"connect":{"secs":3,"nanos":0},"read":{"secs":4,"nanos":0}}}"#,
+    )
+    .unwrap();
+    assert_eq!(timeouts.ip.read, Duration::from_secs(4));
+    assert_eq!(timeouts.hdtools, Timeouts::HDTOOLS);
+}
+
+// -------------------- Diagnostics --------------------
+
+#[test]
+fn diagnostics_ring_buffer() {
+    let diagnostics = Diagnostics::default();
+    for i in 0..diagnostics::RECENT + 5 {
+        let timer = diagnostics.start(Backend::Splunk, "get_logins");
+        // Dropping without finishing counts as a failure
+        if i % 2 == 0 {
+            timer.finish(100, 2);
+        }
+    }
+    diagnostics.start(Backend::HDTools, "lookup").finish(50, 1);
+
+    let recent = diagnostics.recent();
+    assert_eq!(recent.len(), diagnostics::RECENT);
+    assert_eq!(recent[0].backend, Backend::HDTools);
+    assert!(recent[0].ok);
+
+    let totals = diagnostics.totals();
+    assert_eq!(totals[0].0, Backend::Splunk);
+    let splunk = totals[0].1;
+    assert_eq!(splunk.calls, diagnostics::RECENT + 5);
+    assert_eq!(splunk.failures, (diagnostics::RECENT + 5) / 2);
+    assert_eq!(splunk.bytes, 100 * (splunk.calls - splunk.failures));
+    assert_eq!(totals[1].1.rows, 1);
+}
+
+// -------------------- HDTools --------------------
+
+const LOOKUP: &str = r#"{"results":[{"zid":"z1234567","name":"Tiger"}]}"#;
+const PERSON: &str = r#"{"zid":"z1234567","createDate":"2023-01-15T08:30:00-0500","primaryAffiliation":"student","accountStatus":"active"}"#;
+const STUDENT: &str = r#"{"primaryAddressCity":"Clemson","primaryAddressState":"SC","primaryAddressZip":"29634","primaryAddressCountry":"US"}"#;
+const STUDENT_EMPTY: &str = r#"{"termCode":"202308"}"#;
+const STUDENT_BOTH: &str = r#"{"termCode":"202308","primaryAddressCity":"Charleston","primaryAddressState":"SC","campusAddressCity":"Clemson","campusAddressState":"SC","campusAddressCountry":"US"}"#;
+const EMPLOYEE: &str = r#"{"hCity":"Greenville","hState":"SC","hZip":"29601"}"#;
+const EMPLOYEE_PHONE: &str =
+    r#"{"hCity":"Greenville","hState":"SC","wPhone":"","hPhone":"864-555-0123"}"#;
+
+fn location(city: &str, state: Option<&str>, country: Option<&str>) -> Location {
+    Location {
+        city: city.to_owned(),
+        state: state.map(str::to_owned),
+        country: country.map(str::to_owned),
+    }
+}
+
+#[test]
+fn hdtools_zid() {
+    assert_eq!(hdtools::parse_zid(LOOKUP), Some("z1234567".to_owned()));
+    assert_eq!(hdtools::parse_zid("{}"), None);
+}
+
+#[test]
+fn hdtools_lookup_status() {
+    assert_eq!(hdtools::lookup_zid(LOOKUP), Ok("z1234567".to_owned()));
+    // HDTools answered, just not with anyone
+    assert_eq!(hdtools::lookup_zid("{}"), Err(QueryError::NotFound));
+    // A login page isn't an answer
+    assert_eq!(
+        hdtools::lookup_zid("<html><body>Sign in</body></html>"),
+        Err(QueryError::Parse)
+    );
+
+    assert_eq!(
+        hdtools::HDToolsStatus::new(&Err(QueryError::NotFound)),
+        hdtools::HDToolsStatus::NoRecord
+    );
+    assert_eq!(
+        hdtools::HDToolsStatus::new(&Err(QueryError::Auth)),
+        hdtools::HDToolsStatus::Failed(QueryError::Auth)
+    );
+}
+
+#[test]
+fn hdtools_person() {
+    let info = hdtools::parse_person(PERSON).unwrap();
+    let creation_date = chrono::DateTime::parse_from_str("2023-01-15T08:30:00-0500", "%FT%T%z")
+        .unwrap()
+        .with_timezone(&chrono::Local)
+        .naive_local();
+    assert_eq!(info.creation_date, creation_date);
+    assert_eq!(info.location, None);
+    assert_eq!(info.affiliation.as_deref(), Some("student"));
+    assert_eq!(info.status.as_deref(), Some("active"));
+
+    assert_eq!(hdtools::parse_person(r#"{"createDate":"yesterday"}"#), None);
+}
+
+#[test]
+fn hdtools_student_address() {
+    assert_eq!(
+        hdtools::parse_student_addresses(STUDENT),
+        vec![(
+            hdtools::AddressRecord::StudentPrimary,
+            location("Clemson", Some("SC"), Some("US"))
+        )]
+    );
+    assert_eq!(hdtools::parse_student_addresses(STUDENT_EMPTY), vec![]);
+
+    // The campus country isn't taken for the primary address
+    assert_eq!(
+        hdtools::parse_student_addresses(STUDENT_BOTH),
+        vec![
+            (
+                hdtools::AddressRecord::StudentPrimary,
+                location("Charleston", Some("SC"), None)
+            ),
+            (
+                hdtools::AddressRecord::StudentCampus,
+                location("Clemson", Some("SC"), Some("US"))
+            ),
+        ]
+    );
+}
+
+#[test]
+fn hdtools_current_term() {
+    let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    assert!(hdtools::current_term(STUDENT_EMPTY, date(2023, 12, 1)));
+    // Upcoming terms count
+    assert!(hdtools::current_term(STUDENT_EMPTY, date(2023, 7, 1)));
+    assert!(!hdtools::current_term(STUDENT_EMPTY, date(2026, 1, 1)));
+    assert!(!hdtools::current_term(STUDENT, date(2023, 12, 1)));
+}
+
+#[test]
+fn hdtools_needs_employee() {
+    let date = |y, m, d| chrono::NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    // A current student address is enough
+    assert!(!hdtools::needs_employee(STUDENT_BOTH, date(2023, 10, 1)));
+    // The employee home might beat a stale one
+    assert!(hdtools::needs_employee(STUDENT_BOTH, date(2026, 10, 1)));
+    assert!(hdtools::needs_employee(STUDENT, date(2023, 10, 1)));
+    // Nothing to fall back on
+    assert!(hdtools::needs_employee(STUDENT_EMPTY, date(2023, 10, 1)));
+}
+
+#[test]
+fn hdtools_rank_addresses() {
+    let records = |today| {
+        hdtools::rank_addresses(STUDENT_BOTH, EMPLOYEE, today)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect::<Vec<_>>()
+    };
+    // Enrolled, so the student addresses win
+    assert_eq!(
+        records(chrono::NaiveDate::from_ymd_opt(2023, 10, 1).unwrap()),
+        vec![
+            hdtools::AddressRecord::StudentPrimary,
+            hdtools::AddressRecord::StudentCampus,
+            hdtools::AddressRecord::Employee,
+        ]
+    );
+    // Years later the employee home comes first
+    assert_eq!(
+        records(chrono::NaiveDate::from_ymd_opt(2026, 10, 1).unwrap()),
+        vec![
+            hdtools::AddressRecord::Employee,
+            hdtools::AddressRecord::StudentPrimary,
+            hdtools::AddressRecord::StudentCampus,
+        ]
+    );
+    assert_eq!(
+        hdtools::rank_addresses(STUDENT_EMPTY, "", chrono::NaiveDate::MIN),
+        vec![]
+    );
+}
+
+#[test]
+fn hdtools_employee_address() {
+    assert_eq!(
+        hdtools::parse_employee_address(EMPLOYEE),
+        Some(Location {
+            city: "Greenville".to_owned(),
+            state: Some("SC".to_owned()),
+            country: None,
+        })
+    );
+    assert_eq!(hdtools::parse_employee_address(STUDENT), None);
+}
+
+#[test]
+fn hdtools_phone() {
+    assert_eq!(
+        hdtools::parse_phone(EMPLOYEE_PHONE),
+        Some("864-555-0123".to_owned())
+    );
+    assert_eq!(hdtools::parse_phone(EMPLOYEE), None);
+    assert_eq!(hdtools::parse_phone(r#"{"phone":"N/A"}"#), None);
+}
+
+#[test]
+fn hdtools_address_record() {
+    for record in [
+        hdtools::AddressRecord::StudentPrimary,
+        hdtools::AddressRecord::StudentCampus,
+        hdtools::AddressRecord::Employee,
+    ] {
+        assert_eq!(record.to_string().parse(), Ok(record));
+    }
+    // Rows cached before the student addresses were told apart
+    assert_eq!(
+        "Student".parse(),
+        Ok(hdtools::AddressRecord::StudentPrimary)
+    );
+    // Rows cached before the record was kept are empty
+    assert_eq!("".parse::<hdtools::AddressRecord>(), Err(()));
+
+    // Kept through the cache and onto the user
+    let storage = crate::storage::Storage::in_memory();
+    let info = hdtools::HDToolsInfo {
+        creation_date: chrono::NaiveDate::from_ymd_opt(2020, 8, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+        location: None,
+        affiliation: None,
+        status: None,
+        address_record: Some(hdtools::AddressRecord::Employee),
+        addresses: vec![],
+        phone: None,
+        home: None,
+    };
+    storage.add_hdtools("tiger", info);
+    let cached = storage.get_hdtools("tiger").unwrap();
+    assert_eq!(
+        cached.address_record,
+        Some(hdtools::AddressRecord::Employee)
+    );
+    let mut user = crate::user::User::new(
+        "tiger".to_owned(),
+        vec![],
+        &chrono::Utc::now(),
+        chrono::Duration::days(1),
+    );
+    user.set_hdtools(cached);
+    assert_eq!(user.address_record, Some(hdtools::AddressRecord::Employee));
+}
+
+#[test]
+fn hdtools_config() {
+    let mut config = hdtools::HDToolsConfig {
+        base: "https://hdtools.example.edu".to_owned(),
+        lookup: "https://hdtools.example.edu/api/lookup/".to_owned(),
+        person: "https://hdtools.example.edu/api/person".to_owned(),
+        student: "https://hdtools.example.edu/api/student".to_owned(),
+        employee: "https://hdtools.example.edu/api/employee".to_owned(),
+    };
+    assert!(config.validate().is_ok());
+    assert_eq!(
+        hdtools::record_url(&config.lookup, "tiger"),
+        "https://hdtools.example.edu/api/lookup/tiger"
+    );
+    assert_eq!(
+        hdtools::record_url(&config.person, "Z123"),
+        "https://hdtools.example.edu/api/person/Z123"
+    );
+
+    config.student = "hdtools.example.edu/api/student".to_owned();
+    assert!(config.validate().unwrap_err().starts_with("Student"));
+    config.student = config.person.clone();
+    config.base = String::new();
+    assert!(config.validate().unwrap_err().starts_with("Base"));
+}
+
+// -------------------- Splunk --------------------
+
+#[test]
+fn index_config() {
+    let mut config = splunk::IndexConfig::default();
+    assert!(config.validate().is_ok());
+    config.ise = "network-ise".to_owned();
+    assert!(config.validate().is_ok());
+    config.dhcp = String::new();
+    assert!(config.validate().unwrap_err().starts_with("DHCP"));
+    config.dhcp = "dhcp | delete".to_owned();
+    assert!(config.validate().unwrap_err().starts_with("DHCP"));
+}
+
+#[test]
+fn rank_matches_frequency_then_recency() {
+    // Newest first, as Splunk returns them
+    let ranked = splunk::rank_matches(["10.0.0.3", "10.0.0.1", "10.0.0.2", "10.0.0.1", "10.0.0.2"]);
+    assert_eq!(ranked, ["10.0.0.1", "10.0.0.2", "10.0.0.3"]);
+
+    let ranked = splunk::rank_matches(["b", "a", "c"]);
+    assert_eq!(ranked, ["b", "a", "c"]);
+    assert!(splunk::rank_matches(Vec::<&str>::new()).is_empty());
+}
+
+#[test]
+fn parse_users_list() {
+    assert_eq!(
+        splunk::Splunk::parse_users("tiger, cat\n\nbengal,tiger\n"),
+        Ok(vec![
+            "tiger".to_owned(),
+            "cat".to_owned(),
+            "bengal".to_owned()
+        ])
+    );
+    assert_eq!(
+        splunk::Splunk::parse_users("Tiger\nTIGER,tiger"),
+        Ok(vec!["tiger".to_owned()])
+    );
+    assert_eq!(splunk::Splunk::parse_users("  "), Ok(vec![]));
+    assert_eq!(
+        splunk::Splunk::parse_users("tiger, bad user"),
+        Err("bad user".to_owned())
+    );
+}
+
+#[test]
+fn match_users_any_case() {
+    let log = |user: &str, integration: &str| {
+        format!(
+            r#"{{"preview":false,"result":{{"_raw":"{{\"factor\": \"Duo Push\", \"integration\": \"{}\", \"ip\": \"130.127.1.2\", \"result\": \"SUCCESS\", \"username\": \"{}\"}}","_time":"2023-09-06 07:33:19.000 EDT","user":"{}"}}}}"#,
+            integration, user, user
+        )
+    };
+    let logins: Vec<Login> = [
+        log("JSMITH", "Shibboleth"),
+        log("jsmith", "Clemson University VPN"),
+    ]
+    .iter()
+    .filter_map(|l| Login::new(l, &ip::IpDB::empty(), Zone::Utc))
+    .collect();
+    let earliest = Zone::Utc.parse("2023-09-06T00:00:00Z").unwrap();
+
+    let users = splunk::Splunk::match_users_and_logins(
+        vec!["JSmith".to_owned()],
+        logins,
+        &earliest,
+        chrono::Duration::zero(),
+    );
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].name, "jsmith");
+    assert_eq!(users[0].logins.len(), 2);
+}
+
+#[test]
+fn suspicious_filter_fits_subsearch() {
+    use splunk::LoginFilter;
+
+    assert_eq!(LoginFilter::Suspicious.fitted(0), LoginFilter::Suspicious);
+    assert_eq!(
+        LoginFilter::Suspicious.fitted(9_999),
+        LoginFilter::Suspicious
+    );
+    // The subsearch would drop users past its limit, so everyone is pulled
+    assert_eq!(LoginFilter::Suspicious.fitted(10_000), LoginFilter::All);
+    assert_eq!(LoginFilter::All.fitted(0), LoginFilter::All);
+}
+
+#[test]
+fn job_results_paged() {
+    let results: Vec<serde_json::Value> = (0..5)
+        .map(|i| serde_json::json!({ "user": format!("tiger{}", i) }))
+        .collect();
+    // Splunk caps pages below what was asked for, so every page has to be read until the count
+    let mut offsets = vec![];
+    let buf = splunk::Splunk::page_results(
+        results.len(),
+        |offset| {
+            offsets.push(offset);
+            let end = (offset + 2).min(results.len());
+            Ok(serde_json::json!({ "results": results[offset..end] }))
+        },
+        &|_| {},
+    )
+    .unwrap();
+    assert_eq!(offsets, [0, 2, 4]);
+    assert_eq!(buf.lines().count(), 5);
+    assert_eq!(buf.lines().last(), Some(r#"{"result":{"user":"tiger4"}}"#));
+
+    // A job that comes up short stops instead of asking forever
+    let buf =
+        splunk::Splunk::page_results(3, |_| Ok(serde_json::json!({ "results": [] })), &|_| {})
+            .unwrap();
+    assert!(buf.is_empty());
+    assert_eq!(
+        splunk::Splunk::page_results(1, |_| Ok(serde_json::json!({})), &|_| {}),
+        Err(QueryError::Parse)
+    );
+}
+
+#[test]
+fn job_results_parse_as_logins() {
+    let result = serde_json::json!({
+        "_raw": "{\"factor\": \"Duo Push\", \"integration\": \"Shibboleth\", \"ip\": \"130.127.1.2\", \"result\": \"SUCCESS\", \"username\": \"tiger\"}",
+        "_time": "2023-09-06 07:33:19.000 EDT",
+        "user": "tiger",
+    });
+    let buf = splunk::Splunk::page_results(
+        1,
+        |_| Ok(serde_json::json!({ "results": [result] })),
+        &|_| {},
+    )
+    .unwrap();
+    // Unwrapped the same as a line of a plain search
+    let login = Login::new(buf.trim_end(), &ip::IpDB::empty(), Zone::Utc).unwrap();
+    assert_eq!(login.user, "tiger");
+    assert_eq!(login.integration, login::Integration::Shibboleth);
+}
+
+#[test]
+fn search_spl_time_range() {
+    let time = |t| chrono::NaiveDateTime::parse_from_str(t, "%F %T").unwrap();
+    let search = splunk::Search {
+        search:
+            "search index=duo user=* [search index=duo result=FAILURE | fields user] | dedup _raw"
+                .to_owned(),
+        earliest: time("2023-09-06 07:00:00"),
+        latest: time("2023-09-06 08:30:00"),
+    };
+    // The subsearch gets the range too
+    assert_eq!(
+        search.spl(),
+        "search earliest=\"09/06/2023:07:00:00\" latest=\"09/06/2023:08:30:00\" index=duo user=* \
+        [search earliest=\"09/06/2023:07:00:00\" latest=\"09/06/2023:08:30:00\" index=duo result=FAILURE \
+        | fields user] | dedup _raw"
+    );
+}
+
+// -------------------- IP enrichment --------------------
+
+#[test]
+fn ip_enrichment_csv() {
+    let info = IpInfo {
+        ip: "130.127.1.2".to_owned(),
+        hostname: None,
+        city: "Clemson".to_owned(),
+        region: "South Carolina".to_owned(),
+        country: "US".to_owned(),
+        loc: ip::Location {
+            lat: 34.68,
+            lon: -82.84,
+        },
+        org: "AS12148 Clemson University, \"CU\"".to_owned(),
+        postal: "29634".to_owned(),
+        timezone: "America/New_York".to_owned(),
+    };
+    let enrichment = IpEnrichment {
+        ip: "130.127.1.2".parse().unwrap(),
+        info: Some(info),
+        threat: None,
+    };
+    assert_eq!(
+        enrichment.csv_row(),
+        "130.127.1.2,,Clemson,South Carolina,US,34.68,-82.84,\"AS12148 Clemson University, \"\"CU\"\"\",29634,America/New_York,,,,,,,,,"
+    );
+}
+
+#[test]
+fn ipinfo_cache_stats() {
+    use crate::storage::{Cache, Storage};
+
+    let storage = Storage::in_memory();
+    let ip = "130.127.1.2".parse().unwrap();
+    let info = IpInfo {
+        ip: "130.127.1.2".to_owned(),
+        hostname: None,
+        city: "Clemson".to_owned(),
+        region: "South Carolina".to_owned(),
+        country: "US".to_owned(),
+        loc: ip::Location {
+            lat: 34.68,
+            lon: -82.84,
+        },
+        org: "AS12148 Clemson University".to_owned(),
+        postal: "29634".to_owned(),
+        timezone: "America/New_York".to_owned(),
+    };
+    // Checking what's cached isn't a hit or a miss
+    assert!(storage.select_ipinfo(ip).is_none());
+    storage.add_ipinfo(ip, info);
+    assert!(storage.select_ipinfo(ip).is_some());
+    assert_eq!(storage.cache_stats().get(Cache::IpInfo), (0, 0));
+
+    assert!(storage.get_ipinfo(ip).is_some());
+    assert_eq!(storage.cache_stats().get(Cache::IpInfo), (1, 0));
+}
+
+// -------------------- IP databases --------------------
+
+const IPLOC_CSV: &str = "0,16777215,-,-,-,-,0.000000,0.000000
+16777216,16777471,US,United States of America,California,San Jose,37.339390,-121.894960";
+const PROXY_CSV: &str = "16777300,16777310";
+const ASN_CSV: &str = "16777216,16777471,AS13335 Cloudflare";
+
+#[test]
+fn ipdb_from_csv() {
+    let ipdb = ip::IpDB::from_csv(IPLOC_CSV, PROXY_CSV, ASN_CSV).unwrap();
+    let addr = std::net::Ipv4Addr::from(16777300u32);
+    let loc = ipdb.get_iploc(addr).unwrap();
+    assert_eq!(loc.city.as_deref(), Some("San Jose"));
+    assert_eq!(loc.country_code.as_deref(), Some("US"));
+    assert_eq!(
+        ipdb.get_iploc(std::net::Ipv4Addr::from(0u32)).unwrap().city,
+        None
+    );
+    assert!(ipdb.is_proxy(addr));
+    assert_eq!(
+        ipdb.get_asn(addr).map(String::as_str),
+        Some("AS13335 Cloudflare")
+    );
+}
+
+#[test]
+fn ipdb_malformed() {
+    let bad_iploc = "0,16777215,-,-,-,-,0.000000,0.000000\n16777216,oops,US";
+    assert_eq!(
+        ip::IpDB::from_csv(bad_iploc, PROXY_CSV, ASN_CSV).err(),
+        Some(ip::IpDbError {
+            file: "ip2location.csv",
+            line: 2,
+            content: "16777216,oops,US".to_owned(),
+        })
+    );
+
+    let err = ip::IpDB::from_csv(IPLOC_CSV, "16777300", ASN_CSV)
+        .err()
+        .unwrap();
+    assert_eq!((err.file, err.line), ("ip2proxy.csv", 1));
+
+    let err = ip::IpDB::from_csv(IPLOC_CSV, PROXY_CSV, "a,b,c")
+        .err()
+        .unwrap();
+    assert_eq!((err.file, err.line), ("ip2asn.csv", 1));
+}
+
+#[test]
+fn ipdb_geocode() {
+    let ipdb = ip::IpDB::from_csv(IPLOC_CSV, PROXY_CSV, ASN_CSV).unwrap();
+    let san_jose = Some((37.33939, -121.89496));
+
+    assert_eq!(ipdb.geocode(&location("San Jose", None, None)), san_jose);
+    assert_eq!(
+        ipdb.geocode(&location("SAN JOSE", Some("CA"), Some("USA"))),
+        san_jose
+    );
+    assert_eq!(
+        ipdb.geocode(&location("San Jose", Some("California"), Some("US"))),
+        san_jose
+    );
+    // Same city name elsewhere
+    assert_eq!(ipdb.geocode(&location("San Jose", Some("TX"), None)), None);
+    assert_eq!(ipdb.geocode(&location("San Jose", None, Some("CR"))), None);
+    assert_eq!(ipdb.geocode(&location("Clemson", Some("SC"), None)), None);
+    assert_eq!(
+        ip::IpDB::empty().geocode(&location("San Jose", None, None)),
+        None
+    );
+}
+
+#[test]
+fn hdtools_home_cached() {
+    let storage = crate::storage::Storage::in_memory();
+    let info = hdtools::HDToolsInfo {
+        creation_date: chrono::NaiveDate::from_ymd_opt(2020, 8, 1)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap(),
+        location: Some(Location {
+            city: "San Jose".to_owned(),
+            state: Some("CA".to_owned()),
+            country: None,
+        }),
+        affiliation: None,
+        status: None,
+        address_record: None,
+        addresses: vec![],
+        phone: None,
+        home: None,
+    };
+    storage.add_hdtools("tiger", info);
+    assert_eq!(storage.get_hdtools("tiger").unwrap().home, None);
+
+    // Rows saved before the home was looked up are filled in
+    storage.set_hdtools_home("Tiger", (37.33939, -121.89496));
+    let cached = storage.get_hdtools("tiger").unwrap();
+    assert_eq!(cached.home, Some((37.33939, -121.89496)));
+    assert_eq!(cached.location.unwrap().city, "San Jose");
+}
+
+#[test]
+fn ipdb_empty() {
+    let ipdb = ip::IpDB::empty();
+    let addr = std::net::Ipv4Addr::new(130, 127, 1, 2);
+    assert_eq!(ipdb.get_iploc(addr), None);
+    assert!(!ipdb.is_proxy(addr));
+    assert_eq!(ipdb.get_asn(addr), None);
+}
+
+// -------------------- Osiris --------------------
+
+#[test]
+fn osiris_aliases() {
+    use super::osiris::Data;
+
+    let storage = crate::storage::Storage::in_memory();
+    storage.rename_category("Phising", "Phishing");
+    // Renaming the new name takes the old one with it, "Phishing" was only ever an alias
+    storage.rename_category("Phishing", "Phishing email");
+    storage.rename_category("Malwar", "Malware");
+    // Renaming back to what Osiris has forgets the alias
+    storage.rename_category("Malware", "Malwar");
+    let aliases = storage.get_category_aliases();
+    assert!(!aliases.contains_key("Malwar"));
+    assert_eq!(aliases["Phising"], "Phishing email");
+    assert!(!aliases.contains_key("Phishing"));
+
+    let data = Data {
+        incidents: vec![("Malwar".to_owned(), 1)],
+        investigations: vec![
+            ("Phising".to_owned(), 2),
+            ("Compromised account".to_owned(), 4),
+            ("Phishing".to_owned(), 3),
+        ],
+    }
+    .alias(&aliases);
+    assert_eq!(data.incidents, vec![("Malwar".to_owned(), 1)]);
+    assert_eq!(
+        data.investigations,
+        vec![
+            ("Phishing email".to_owned(), 2),
+            ("Compromised account".to_owned(), 4),
+            ("Phishing".to_owned(), 3),
+        ]
+    );
+
+    storage.set_category_archived("Phishing email", true);
+    storage.set_category_archived("Malwar", true);
+    storage.set_category_archived("Malwar", false);
+    // Archived categories stay archived when renamed
+    storage.rename_category("Phishing email", "Phishing");
+    assert_eq!(
+        storage.get_archived_categories(),
+        vec!["Phishing".to_owned()]
+    );
+}
+
+#[test]
+fn osiris_chained_renames() {
+    let storage = crate::storage::Storage::in_memory();
+    storage.rename_category("A", "B");
+    storage.rename_category("B", "C");
+    assert_eq!(
+        storage.get_category_aliases(),
+        HashMap::from([("A".to_owned(), "C".to_owned())])
+    );
+
+    // Back to the start leaves nothing behind
+    storage.rename_category("C", "A");
+    assert!(storage.get_category_aliases().is_empty());
+
+    storage.rename_category("A", "B");
+    storage.rename_category("B", "A");
+    assert!(storage.get_category_aliases().is_empty());
+}
+
+#[test]
+fn osiris_aggregation() {
+    use super::osiris::{aggregate, Aggregation, Data};
+    use chrono::NaiveDate;
+
+    let day = |s: &str| NaiveDate::parse_from_str(s, "%F").unwrap();
+    assert_eq!(Aggregation::Weekly.period(day("2024-02-12")), "2024-W07");
+    // ISO weeks belong to the year their Thursday is in
+    assert_eq!(Aggregation::Weekly.period(day("2024-12-30")), "2025-W01");
+    assert_eq!(Aggregation::Monthly.period(day("2024-03-31")), "2024-03");
+
+    let rows = || {
+        [
+            ("2024-03-02", 8),
+            ("2024-02-11", 1),
+            ("2024-02-12", 2),
+            ("2024-02-20", 4),
+            ("2024-03-04", 16),
+            ("total", 32),
+        ]
+        .into_iter()
+        .map(|(time, count)| {
+            (
+                time.to_owned(),
+                Data {
+                    incidents: vec![("Phishing".to_owned(), count)],
+                    investigations: vec![("Duo".to_owned(), 1)],
+                },
+            )
+        })
+        .collect::<Vec<_>>()
+    };
+    // Tuesday of W07 through Thursday of W09
+    let range = (day("2024-02-13"), day("2024-02-29"));
+    let totals = |rows: Vec<(String, Data)>| {
+        rows.into_iter()
+            .map(|(time, data)| (time, data.incidents[0].1, data.investigations[0].1))
+            .collect::<Vec<_>>()
+    };
+
+    let (weekly, skipped) = aggregate(rows(), range, Aggregation::Weekly);
+    assert_eq!(
+        totals(weekly),
+        vec![
+            ("2024-W07".to_owned(), 2, 1),
+            ("2024-W08".to_owned(), 4, 1),
+            ("2024-W09".to_owned(), 8, 1),
+        ]
+    );
+    assert_eq!(skipped, 1);
+    // A backwards range is the same range
+    let (monthly, _) = aggregate(rows(), (range.1, range.0), Aggregation::Monthly);
+    assert_eq!(totals(monthly), vec![("2024-02".to_owned(), 7, 3)]);
+}
+
+#[test]
+fn osiris_daily_range() {
+    use super::osiris::{aggregate, Aggregation, Data};
+    use chrono::NaiveDate;
+
+    let day = |s: &str| NaiveDate::parse_from_str(s, "%F").unwrap();
+    let rows = [
+        "2024-03-01",
+        "2024-02-10",
+        "total",
+        "2024-02-29",
+        "2024-02-14",
+        "",
+        "2024-02-30",
+        " 2024-02-20 ",
+        "2024-02-09",
+    ]
+    .into_iter()
+    .map(|time| {
+        (
+            time.to_owned(),
+            Data {
+                incidents: vec![("Phishing".to_owned(), 1)],
+                investigations: vec![],
+            },
+        )
+    })
+    .collect();
+
+    // Both ends are kept, rows come back oldest first
+    let (daily, skipped) = aggregate(
+        rows,
+        (day("2024-02-29"), day("2024-02-10")),
+        Aggregation::Daily,
+    );
+    assert_eq!(
+        daily.into_iter().map(|(time, _)| time).collect::<Vec<_>>(),
+        ["2024-02-10", "2024-02-14", "2024-02-20", "2024-02-29"]
+    );
+    // total, the empty time, and February 30th
+    assert_eq!(skipped, 3);
+}
+
+#[test]
+fn osiris_diff_counts() {
+    use super::osiris::{diff_counts, CountDiff};
+
+    let counts = |counts: &[(&str, i64)]| {
+        counts
+            .iter()
+            .map(|(name, count)| (name.to_string(), *count))
+            .collect::<Vec<_>>()
+    };
+    let diffs = diff_counts(
+        &counts(&[("Phishing", 4), ("Duo", 2), ("Malware", 1)]),
+        &counts(&[("Duo", 5), ("Phishing", 4), ("Tor", 3)]),
+    );
+    let diff = |name: &str, before, after| CountDiff {
+        name: name.to_owned(),
+        before,
+        after,
+    };
+    assert_eq!(
+        diffs,
+        vec![
+            diff("Phishing", 4, 4),
+            diff("Duo", 2, 5),
+            diff("Malware", 1, 0),
+            diff("Tor", 0, 3),
+        ]
+    );
+    assert_eq!(
+        diffs.iter().map(CountDiff::change).collect::<Vec<_>>(),
+        [0, 3, -1, 3]
+    );
+    assert!(diff_counts(&[], &[]).is_empty());
+}
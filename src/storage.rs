@@ -2,59 +2,266 @@
 //!
 //! This stuct stores investigated users (ignored users), hdtools information, ip information
 //! from ipdata.co and ipinfo.io, along with the username and analyst name.  This data should be
-//! queried first before making a network query.
-use chrono::{Duration, Local, TimeZone};
+//! queried first before making a network query.  Settings, such as trusted ASNs and IPs, are kept here as
+//! well.
+//!
+//! Analysts sharing a machine each get a profile keyed by their Splunk username.  Thresholds,
+//! zones, shortcuts, the analyst name, and the window layout are kept per profile, while caches,
+//! ignored users, trusted ASNs and IPs, and the HDTools URLs are shared.
+use chrono::{DateTime, Duration, Local, TimeZone};
 use dirs::cache_dir;
-use log::{debug, error};
+use log::{debug, error, info};
 use rusqlite::Connection;
-use std::{fs::File, net::Ipv4Addr};
+use std::{
+    collections::HashMap,
+    fs::File,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
 use crate::{
+    prefs::{keymap::Keymap, redact::Redact, ThemeKind, UI_SCALE},
     queries::{
-        hdtools::HDToolsInfo,
+        hdtools::{AddressRecord, HDToolsConfig, HDToolsInfo},
         ip::{self, IpInfo, IpThreat},
+        splunk::IndexConfig,
+        TimeoutConfig,
+    },
+    user::{
+        canonical_name,
+        login::{FraudDisposition, ResultMap},
+        zone::Zone,
+        FailurePolicy, IpRange, Location, Scoring,
     },
-    user::Location,
 };
 
 /// Initializes the SQLite db tables
-const CREATE_DB: [&str; 5] = ["
-CREATE TABLE investigated_users (
-    name TEXT UNIQUE, time INTEGER
+const CREATE_DB: [&str; 15] = ["
+CREATE TABLE IF NOT EXISTS investigated_users (
+    name TEXT UNIQUE, time INTEGER, note TEXT, analyst TEXT
 );",
-"CREATE TABLE hdtools (
+"CREATE TABLE IF NOT EXISTS hdtools (
     name TEXT UNIQUE, time INTEGER, city TEXT,
-    state TEXT, country TEXT
+    state TEXT, country TEXT, affiliation TEXT, status TEXT, address_record TEXT, phone TEXT,
+    lat REAL, lon REAL
 );",
-"CREATE TABLE ipthreat (
+"CREATE TABLE IF NOT EXISTS ipthreat (
     ip INTEGER UNIQUE, is_tor INTEGER, is_icloud_relay INTEGER, is_proxy INTEGER,
     is_datacenter INTEGER, is_anonymous INTEGER, is_known_attacker INTEGER,
     is_known_abuser INTEGER, is_threat INTEGER, is_bogon INTEGER
 );",
-"CREATE TABLE ipinfo (
+"CREATE TABLE IF NOT EXISTS ipinfo (
     ip INTEGER UNIQUE, hostname TEXT, city TEXT, region TEXT, country TEXT,
     lat REAL, lon REAL, org TEXT, postal TEXT, timezone TEXT
 );",
-"CREATE TABLE misc (
+"CREATE TABLE IF NOT EXISTS misc (
     key INTEGER UNIQUE, value TEXT
+);",
+"CREATE TABLE IF NOT EXISTS trusted_asns (
+    asn TEXT UNIQUE
+);",
+"CREATE TABLE IF NOT EXISTS ip_allowlist (
+    ip INTEGER UNIQUE
+);",
+"CREATE TABLE IF NOT EXISTS panel_layout (
+    name TEXT, open INTEGER, x REAL, y REAL, w REAL, h REAL, profile TEXT,
+    UNIQUE(profile, name)
+);",
+"CREATE TABLE IF NOT EXISTS profile_misc (
+    profile TEXT, key INTEGER, value TEXT,
+    UNIQUE(profile, key)
+);",
+"CREATE TABLE IF NOT EXISTS investigated_history (
+    name TEXT, time INTEGER, analyst TEXT, note TEXT, ignored INTEGER
+);",
+"CREATE TABLE IF NOT EXISTS fraud_dispositions (
+    name TEXT, time INTEGER, disposition TEXT,
+    UNIQUE(name, time)
+);",
+"CREATE TABLE IF NOT EXISTS ip_range_allowlist (
+    cidr TEXT UNIQUE
+);",
+"CREATE TABLE IF NOT EXISTS location_overrides (
+    ip INTEGER UNIQUE, lat REAL, lon REAL
+);",
+"CREATE TABLE IF NOT EXISTS category_aliases (
+    name TEXT UNIQUE, alias TEXT
+);",
+"CREATE TABLE IF NOT EXISTS archived_categories (
+    name TEXT UNIQUE
 );"];
 
-const CHECK_DB: [(&str, &[(&str, &str)]); 5] = [
-    ("investigated_users", &[("name", "TEXT"), ("time", "INTEGER")]),
-    ("hdtools", &[("name", "TEXT"), ("time", "INTEGER"), ("city", "TEXT"), ("state", "TEXT"), ("country", "TEXT")]),
+const CHECK_DB: [(&str, &[(&str, &str)]); 15] = [
+    ("investigated_users", &[("name", "TEXT"), ("time", "INTEGER"), ("note", "TEXT"), ("analyst", "TEXT")]),
+    ("hdtools", &[("name", "TEXT"), ("time", "INTEGER"), ("city", "TEXT"), ("state", "TEXT"), ("country", "TEXT"), ("affiliation", "TEXT"), ("status", "TEXT"), ("address_record", "TEXT"), ("phone", "TEXT"), ("lat", "REAL"), ("lon", "REAL")]),
     ("ipthreat", &[("ip", "INTEGER"), ("is_tor", "INTEGER"), ("is_icloud_relay", "INTEGER"), ("is_proxy", "INTEGER"), ("is_datacenter", "INTEGER"), ("is_anonymous", "INTEGER"), ("is_known_attacker", "INTEGER"), ("is_known_abuser", "INTEGER"), ("is_threat", "INTEGER"), ("is_bogon", "INTEGER")]),
     ("ipinfo", &[("ip", "INTEGER"), ("hostname", "TEXT"), ("city", "TEXT"), ("region", "TEXT"), ("country", "TEXT"), ("lat", "REAL"), ("lon", "REAL"), ("org", "TEXT"), ("postal", "TEXT"), ("timezone", "TEXT")]),
-    ("misc", &[("key", "INTEGER"), ("value", "TEXT")])
+    ("misc", &[("key", "INTEGER"), ("value", "TEXT")]),
+    ("trusted_asns", &[("asn", "TEXT")]),
+    ("ip_allowlist", &[("ip", "INTEGER")]),
+    ("panel_layout", &[("name", "TEXT"), ("open", "INTEGER"), ("x", "REAL"), ("y", "REAL"), ("w", "REAL"), ("h", "REAL"), ("profile", "TEXT")]),
+    ("profile_misc", &[("profile", "TEXT"), ("key", "INTEGER"), ("value", "TEXT")]),
+    ("investigated_history", &[("name", "TEXT"), ("time", "INTEGER"), ("analyst", "TEXT"), ("note", "TEXT"), ("ignored", "INTEGER")]),
+    ("fraud_dispositions", &[("name", "TEXT"), ("time", "INTEGER"), ("disposition", "TEXT")]),
+    ("ip_range_allowlist", &[("cidr", "TEXT")]),
+    ("location_overrides", &[("ip", "INTEGER"), ("lat", "REAL"), ("lon", "REAL")]),
+    ("category_aliases", &[("name", "TEXT"), ("alias", "TEXT")]),
+    ("archived_categories", &[("name", "TEXT")])
 ];
 
-/// Key names for data stored in the misc table
+/// How long a user stays investigated, in seconds
+const INVESTIGATION_EXPIRATION: i64 = 86400; // 24hrs
+
+/// Most entries [Storage::investigated_history] returns
+const HISTORY_LIMIT: usize = 500;
+
+/// A user marked investigated that hasn't expired yet
+pub struct Investigated {
+    pub name: String,
+    /// When the user was marked
+    pub time: DateTime<Local>,
+    pub note: String,
+    /// Who marked the user, empty for users marked before this was kept
+    pub analyst: String,
+}
+
+impl Investigated {
+    /// Time until the user shows up again
+    pub fn remaining(&self) -> Duration {
+        self.time + Duration::seconds(INVESTIGATION_EXPIRATION) - Local::now()
+    }
+}
+
+/// One ignore or un-ignore.  These are never expired or cleared, so who ignored a user and why
+/// can be looked up later.
+pub struct IgnoreEvent {
+    pub name: String,
+    pub time: DateTime<Local>,
+    /// Analyst name of whoever did it, or their Splunk username if they haven't set one
+    pub analyst: String,
+    pub note: String,
+    /// False for an un-ignore
+    pub ignored: bool,
+}
+
+/// Disk caches that save a network lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cache {
+    HDTools,
+    IpInfo,
+    IpThreat,
+}
+
+impl Cache {
+    pub const ALL: [Self; 3] = [Self::HDTools, Self::IpInfo, Self::IpThreat];
+}
+
+impl std::fmt::Display for Cache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::HDTools => "HDTools",
+                Self::IpInfo => "IP info",
+                Self::IpThreat => "IP threat",
+            }
+        )
+    }
+}
+
+/// Hits and misses of each [Cache] since HORUS started.  Shared outside the storage lock so they
+/// can be read while Duplex holds it.
+#[derive(Default)]
+pub struct CacheStats {
+    hits: [AtomicUsize; 3],
+    misses: [AtomicUsize; 3],
+}
+
+impl CacheStats {
+    fn count(&self, cache: Cache, hit: bool) {
+        let counts = if hit { &self.hits } else { &self.misses };
+        counts[cache as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Hits and misses of a cache
+    pub fn get(&self, cache: Cache) -> (usize, usize) {
+        (
+            self.hits[cache as usize].load(Ordering::Relaxed),
+            self.misses[cache as usize].load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Where a panel was left when HORUS last ran
+pub struct PanelLayout {
+    pub name: String,
+    pub open: bool,
+    /// Window position and content size, [None] until the window is first moved or resized
+    pub rect: Option<egui::Rect>,
+}
+
+/// Key names for data stored in the misc and profile_misc tables
+#[derive(Clone, Copy, PartialEq)]
 enum MiscKeys {
     UserName = 0,
     AnalystName,
+    MinScore,
+    SplunkZone,
+    DisplayZone,
+    FatiguePushes,
+    FatigueMinutes,
+    FailureMinutes,
+    FailureMatch,
+    MaxKph,
+    Keymap,
+    HDToolsConfig,
+    UiScale,
+    Theme,
+    JobHours,
+    IndexConfig,
+    SonarLimit,
+    Redact,
+    Notify,
+    NotifyScore,
+    RawDuplex,
+    RawSimplex,
+    NewAccountMonths,
+    ResultMap,
+    NotifyRunDone,
+    DuplexOverview,
+    Schedule,
+    ScheduleMinutes,
+    LastRunEnd,
+    MinFailures,
+    NewAccountPass,
+    FlagRelays,
+    RelayWeight,
+    Timeouts,
+}
+
+impl MiscKeys {
+    /// Keys shared by every profile, kept in misc.  The rest are kept in profile_misc.
+    const GLOBAL: [Self; 4] = [
+        Self::UserName,
+        Self::HDToolsConfig,
+        Self::IndexConfig,
+        Self::Timeouts,
+    ];
+
+    fn is_global(self) -> bool {
+        Self::GLOBAL.contains(&self)
+    }
 }
 
 pub struct Storage {
     db: Connection,
+    /// Splunk username whose settings are read and written, see [Storage::set_profile]
+    profile: String,
+    cache_stats: Arc<CacheStats>,
 }
 
 impl Storage {
@@ -82,7 +289,13 @@ impl Storage {
                 }
 
                 if valid_schema {
-                    return Self { db };
+                    let storage = Self {
+                        db,
+                        profile: String::new(),
+                        cache_stats: Arc::default(),
+                    };
+                    storage.migrate();
+                    return storage;
                 }
                 std::fs::remove_file(&path).expect("Couldn't delete bad db");
             }
@@ -93,7 +306,107 @@ impl Storage {
             db.execute(table, ())
                 .expect("Couldn't initialize db tables");
         }
-        Storage { db }
+        Storage {
+            db,
+            profile: String::new(),
+            cache_stats: Arc::default(),
+        }
+    }
+
+    /// Empty db that isn't saved, for tests
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let db = Connection::open_in_memory().expect("Couldn't create database");
+        for table in CREATE_DB {
+            db.execute(table, ())
+                .expect("Couldn't initialize db tables");
+        }
+        Storage {
+            db,
+            profile: String::new(),
+            cache_stats: Arc::default(),
+        }
+    }
+
+    /// Brings an older db up to date by creating any missing tables and adding any missing
+    /// columns.  New columns are always appended, so they are left empty for existing rows.
+    fn migrate(&self) {
+        // Layouts saved before profiles had one row per panel, so they are dropped rather than
+        // given to whoever logs in next
+        let mut has_profile = false;
+        if let Err(e) = self.db.pragma(
+            Some(rusqlite::DatabaseName::Main),
+            "table_info",
+            "panel_layout",
+            |r| {
+                has_profile |= r.get::<_, String>("name")? == "profile";
+                Ok(())
+            },
+        ) {
+            error!("Could not get table info for panel_layout: {}", e);
+        } else if !has_profile {
+            if let Err(e) = self.db.execute("DROP TABLE IF EXISTS panel_layout", ()) {
+                error!("Could not drop panel_layout: {}", e);
+            }
+        }
+
+        for table in CREATE_DB {
+            if let Err(e) = self.db.execute(table, ()) {
+                error!("Could not create table: {}", e);
+            }
+        }
+
+        for (name, schema) in CHECK_DB {
+            let mut columns: Vec<String> = vec![];
+            if let Err(e) = self.db.pragma(
+                Some(rusqlite::DatabaseName::Main),
+                "table_info",
+                name,
+                |r| {
+                    columns.push(r.get::<_, String>("name")?);
+                    Ok(())
+                },
+            ) {
+                error!("Could not get table info for {}: {}", name, e);
+                continue;
+            }
+
+            for (col_name, col_type) in schema.iter().filter(|c| !columns.iter().any(|e| e == c.0))
+            {
+                info!("Adding column {} to {}", col_name, name);
+                let alter = format!("ALTER TABLE {} ADD COLUMN {} {}", name, col_name, col_type);
+                if let Err(e) = self.db.execute(&alter, ()) {
+                    error!("Could not add column {} to {}: {}", col_name, name, e);
+                }
+            }
+        }
+
+        // Names saved before usernames were lowercased.  A name that's already saved lowercased
+        // keeps that row.
+        for table in ["investigated_users", "hdtools"] {
+            if let Err(e) = self.db.execute(
+                &format!(
+                    "UPDATE OR IGNORE {table} SET name = lower(name) WHERE name != lower(name)"
+                ),
+                (),
+            ) {
+                error!("Could not lowercase names in {}: {}", table, e);
+            }
+            if let Err(e) = self.db.execute(
+                &format!("DELETE FROM {table} WHERE name != lower(name)"),
+                (),
+            ) {
+                error!("Could not drop mixed case names from {}: {}", table, e);
+            }
+        }
+
+        // Users ignored before notes existed
+        if let Err(e) = self.db.execute(
+            "UPDATE investigated_users SET note = '' WHERE note IS NULL",
+            (),
+        ) {
+            error!("Could not fill in notes for investigated_users: {}", e);
+        }
     }
 
     /// Checks if a users has been marked investigated and that it hasn't expired
@@ -108,7 +421,8 @@ impl Storage {
                 return false;
             }
         };
-        let time: i64 = match statement.query_row(&[(":name", user)], |r| r.get(0)) {
+        let user = canonical_name(user);
+        let time: i64 = match statement.query_row(&[(":name", &user)], |r| r.get(0)) {
             Ok(t) => t,
             Err(e) => {
                 if e != rusqlite::Error::QueryReturnedNoRows {
@@ -118,24 +432,114 @@ impl Storage {
             }
         };
 
-        let investigation_expiration = 86400; // 24hrs
-
         let time = Local::now()
             - chrono::offset::Local
                 .timestamp_opt(time, 0)
                 .single()
                 .unwrap_or_else(Local::now);
 
-        time < Duration::seconds(investigation_expiration)
+        time < Duration::seconds(INVESTIGATION_EXPIRATION)
+    }
+
+    /// Every user marked investigated that hasn't expired, most recent first
+    pub fn list_investigated(&self) -> Vec<Investigated> {
+        let mut statement = match self.db.prepare(
+            "SELECT name, time, note, analyst FROM investigated_users WHERE time > ?1
+            ORDER BY time DESC",
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for investigated_users: {e}");
+                return vec![];
+            }
+        };
+
+        let cutoff = Local::now().timestamp() - INVESTIGATION_EXPIRATION;
+        let rows = statement.query_map([cutoff], |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<String>>(3)?,
+            ))
+        });
+        match rows {
+            Ok(rows) => rows
+                .flatten()
+                .filter_map(|(name, time, note, analyst)| {
+                    Some(Investigated {
+                        name,
+                        time: Local.timestamp_opt(time, 0).single()?,
+                        note: note.unwrap_or_default(),
+                        analyst: analyst.unwrap_or_default(),
+                    })
+                })
+                .collect(),
+            Err(e) => {
+                error!("Could not query SELECT for investigated_users: {e}");
+                vec![]
+            }
+        }
+    }
+
+    /// Un-marks every user whose investigation hasn't expired.  Expired rows are kept for their
+    /// notes.
+    pub fn clear_investigated(&self) {
+        let cutoff = Local::now().timestamp() - INVESTIGATION_EXPIRATION;
+        if let Err(e) = self.db.execute(
+            "INSERT INTO investigated_history (name, time, analyst, note, ignored)
+            SELECT name, ?1, ?2, '', 0 FROM investigated_users WHERE time > ?3",
+            (Local::now().timestamp(), self.analyst(), cutoff),
+        ) {
+            error!("Could not execute INSERT for investigated_history: {}", e);
+        }
+        if let Err(e) = self.db.execute(
+            "UPDATE investigated_users SET time = 0 WHERE time > ?1",
+            [cutoff],
+        ) {
+            error!("Could not execute UPDATE for investigated_users: {}", e);
+        }
+    }
+
+    /// Why a user was last marked investigated, kept after the mark expires so it can be shown
+    /// when the user reappears.  Empty notes are [None].
+    pub fn investigated_note(&self, user: &str) -> Option<String> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT note FROM investigated_users WHERE name = :name")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for investigated_users: {e}");
+                return None;
+            }
+        };
+        let user = canonical_name(user);
+        match statement.query_row(&[(":name", &user)], |r| r.get::<_, Option<String>>(0)) {
+            Ok(note) => note.filter(|n| !n.is_empty()),
+            Err(e) => {
+                if e != rusqlite::Error::QueryReturnedNoRows {
+                    error!("Could not query SELECT for investigated_users: {e}");
+                }
+                None
+            }
+        }
     }
 
-    /// Adds or removed a user from the investigated_users table, depending on `mark`
-    pub fn mark_investigated(&self, user: String, mark: bool) {
+    /// Marks or un-marks a user as investigated, depending on `mark`, and records it in the
+    /// history.  `note` is only used when marking, without one the last note is kept.  Un-marking
+    /// expires the row so its note is still there when the user reappears.
+    pub fn mark_investigated(&self, user: String, mark: bool, note: Option<&str>) {
+        let user = canonical_name(&user);
+        let analyst = self.analyst();
+        self.record_investigated(&user, &analyst, mark, note.unwrap_or(""));
         if mark {
-            let mut statement = match self
-                .db
-                .prepare("INSERT INTO investigated_users VALUES (?1, ?2)")
-            {
+            let mut statement = match self.db.prepare(
+                "INSERT INTO investigated_users (name, time, note, analyst)
+                VALUES (?1, ?2, COALESCE(?3, ''), ?4)
+                ON CONFLICT(name) DO UPDATE SET time = excluded.time,
+                note = COALESCE(?3, note), analyst = excluded.analyst",
+            ) {
                 Ok(s) => s,
                 Err(e) => {
                     error!("Could not prepare INSERT for investigated users: {}", e);
@@ -146,17 +550,17 @@ impl Storage {
             debug!("Running {:?}", statement);
 
             let now = Local::now().timestamp();
-            if let Err(e) = statement.execute((user, now)) {
+            if let Err(e) = statement.execute((user, now, note, analyst)) {
                 error!("Could not execute INSERT for investigated_users: {}", e);
             }
         } else {
             let mut statement = match self
                 .db
-                .prepare("DELETE FROM investigated_users WHERE name = ?1")
+                .prepare("UPDATE investigated_users SET time = 0 WHERE name = ?1")
             {
                 Ok(s) => s,
                 Err(e) => {
-                    error!("Could not prepare DELETE for investigated users: {}", e);
+                    error!("Could not prepare UPDATE for investigated users: {}", e);
                     return;
                 }
             };
@@ -164,20 +568,321 @@ impl Storage {
             debug!("Running {:?}", statement);
 
             if let Err(e) = statement.execute([user]) {
-                error!("Could not execute DELETE for investigated_users: {}", e);
+                error!("Could not execute UPDATE for investigated_users: {}", e);
+            }
+        }
+    }
+
+    /// Marks a batch of users as investigated in a single transaction, keeping their notes
+    pub fn mark_many_investigated(&self, users: &[String]) {
+        let tx = match self.db.unchecked_transaction() {
+            Ok(tx) => tx,
+            Err(e) => {
+                error!("Could not start transaction for investigated_users: {}", e);
+                return;
+            }
+        };
+
+        {
+            let mut statement = match tx.prepare(
+                "INSERT INTO investigated_users (name, time, note, analyst)
+                VALUES (?1, ?2, '', ?3)
+                ON CONFLICT(name) DO UPDATE SET time = excluded.time, analyst = excluded.analyst",
+            ) {
+                Ok(s) => s,
+                Err(e) => {
+                    error!("Could not prepare INSERT for investigated users: {}", e);
+                    return;
+                }
+            };
+
+            debug!("Running {:?} for {} users", statement, users.len());
+
+            let now = Local::now().timestamp();
+            let analyst = self.analyst();
+            for user in users {
+                let user = canonical_name(user);
+                self.record_investigated(&user, &analyst, true, "");
+                if let Err(e) = statement.execute((user, now, &analyst)) {
+                    error!("Could not execute INSERT for investigated_users: {}", e);
+                }
+            }
+        }
+
+        if let Err(e) = tx.commit() {
+            error!("Could not commit investigated_users: {}", e);
+        }
+    }
+
+    /// Who's marking users, the analyst name if it's set
+    fn analyst(&self) -> String {
+        let name = self.get_analyst_name();
+        if name.trim().is_empty() {
+            self.get_username()
+        } else {
+            name
+        }
+    }
+
+    /// Adds an ignore or un-ignore to investigated_history
+    fn record_investigated(&self, user: &str, analyst: &str, ignored: bool, note: &str) {
+        if let Err(e) = self.db.execute(
+            "INSERT INTO investigated_history (name, time, analyst, note, ignored)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            (user, Local::now().timestamp(), analyst, note, ignored),
+        ) {
+            error!("Could not execute INSERT for investigated_history: {}", e);
+        }
+    }
+
+    /// Recent ignores and un-ignores of users whose name contains `user`, most recent first.  An
+    /// empty `user` matches everyone.
+    pub fn investigated_history(&self, user: &str) -> Vec<IgnoreEvent> {
+        let mut statement = match self.db.prepare(
+            "SELECT name, time, analyst, note, ignored FROM investigated_history
+            WHERE instr(name, ?1) > 0 ORDER BY time DESC, rowid DESC LIMIT ?2",
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for investigated_history: {e}");
+                return vec![];
+            }
+        };
+
+        let rows = statement.query_map((canonical_name(user), HISTORY_LIMIT), |r| {
+            Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, i64>(1)?,
+                r.get::<_, Option<String>>(2)?,
+                r.get::<_, Option<String>>(3)?,
+                r.get::<_, bool>(4)?,
+            ))
+        });
+        match rows {
+            Ok(rows) => rows
+                .flatten()
+                .filter_map(|(name, time, analyst, note, ignored)| {
+                    Some(IgnoreEvent {
+                        name,
+                        time: Local.timestamp_opt(time, 0).single()?,
+                        analyst: analyst.unwrap_or_default(),
+                        note: note.unwrap_or_default(),
+                        ignored,
+                    })
+                })
+                .collect(),
+            Err(e) => {
+                error!("Could not query SELECT for investigated_history: {e}");
+                vec![]
+            }
+        }
+    }
+
+    /// Dispositions of a user's fraud reports, keyed by the login's time in milliseconds
+    pub fn get_fraud_dispositions(&self, user: &str) -> HashMap<i64, FraudDisposition> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT time, disposition FROM fraud_dispositions WHERE name = ?1")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for fraud_dispositions: {e}");
+                return HashMap::new();
+            }
+        };
+
+        let rows = statement.query_map([canonical_name(user)], |r| {
+            Ok((r.get::<_, i64>(0)?, r.get::<_, String>(1)?))
+        });
+        match rows {
+            Ok(rows) => rows
+                .flatten()
+                .filter_map(|(time, disposition)| Some((time, disposition.parse().ok()?)))
+                .collect(),
+            Err(e) => {
+                error!("Could not query SELECT for fraud_dispositions: {e}");
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Saves the disposition of the fraud report at `time`, in milliseconds, or forgets it if
+    /// [None]
+    pub fn set_fraud_disposition(
+        &self,
+        user: &str,
+        time: i64,
+        disposition: Option<FraudDisposition>,
+    ) {
+        let user = canonical_name(user);
+        let result = match disposition {
+            Some(disposition) => self.db.execute(
+                "INSERT OR REPLACE INTO fraud_dispositions (name, time, disposition)
+                VALUES (?1, ?2, ?3)",
+                (user, time, disposition.to_string()),
+            ),
+            None => self.db.execute(
+                "DELETE FROM fraud_dispositions WHERE name = ?1 AND time = ?2",
+                (user, time),
+            ),
+        };
+        if let Err(e) = result {
+            error!("Could not save fraud disposition: {}", e);
+        }
+    }
+
+    /// Coordinates analysts set for IPs the databases misplace
+    pub fn get_location_overrides(&self) -> HashMap<Ipv4Addr, (f32, f32)> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT ip, lat, lon FROM location_overrides")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for location_overrides: {e}");
+                return HashMap::new();
+            }
+        };
+
+        let rows = statement.query_map((), |r| {
+            Ok((
+                Ipv4Addr::from(r.get::<_, u32>(0)?),
+                (r.get::<_, f32>(1)?, r.get::<_, f32>(2)?),
+            ))
+        });
+        match rows {
+            Ok(rows) => rows.flatten().collect(),
+            Err(e) => {
+                error!("Could not query SELECT for location_overrides: {e}");
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Saves the coordinates of an IP, or forgets them if [None]
+    pub fn set_location_override(&self, ip: Ipv4Addr, location: Option<(f32, f32)>) {
+        let ip = u32::from(ip);
+        let result = match location {
+            Some((lat, lon)) => self.db.execute(
+                "INSERT OR REPLACE INTO location_overrides (ip, lat, lon) VALUES (?1, ?2, ?3)",
+                (ip, lat, lon),
+            ),
+            None => self
+                .db
+                .execute("DELETE FROM location_overrides WHERE ip = ?1", [ip]),
+        };
+        if let Err(e) = result {
+            error!("Could not save location override: {}", e);
+        }
+    }
+
+    /// Names Osiris categories are shown and reported as, keyed by the name Osiris has
+    pub fn get_category_aliases(&self) -> HashMap<String, String> {
+        let mut statement = match self.db.prepare("SELECT name, alias FROM category_aliases") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for category_aliases: {e}");
+                return HashMap::new();
+            }
+        };
+
+        let rows = statement.query_map((), |r| Ok((r.get(0)?, r.get(1)?)));
+        match rows {
+            Ok(rows) => rows.flatten().collect(),
+            Err(e) => {
+                error!("Could not query SELECT for category_aliases: {e}");
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Shows `old`, and every category already shown as `old`, as `new` from now on.  Only names
+    /// Osiris has are aliased, so if `old` is itself an alias its categories move and `old` is
+    /// forgotten.  An archived `old` stays archived under its new name.
+    pub fn rename_category(&self, old: &str, new: &str) {
+        let result = self
+            .db
+            .execute(
+                "UPDATE category_aliases SET alias = ?2 WHERE alias = ?1",
+                (old, new),
+            )
+            .and_then(|renamed| {
+                if renamed > 0 {
+                    return Ok(0);
+                }
+                self.db.execute(
+                    "INSERT OR REPLACE INTO category_aliases (name, alias) VALUES (?1, ?2)",
+                    (old, new),
+                )
+            })
+            // Renaming back to what Osiris has
+            .and_then(|_| {
+                self.db
+                    .execute("DELETE FROM category_aliases WHERE name = alias", ())
+            })
+            .and_then(|_| {
+                self.db.execute(
+                    "UPDATE OR IGNORE archived_categories SET name = ?2 WHERE name = ?1",
+                    (old, new),
+                )
+            })
+            .and_then(|_| {
+                self.db
+                    .execute("DELETE FROM archived_categories WHERE name = ?1", [old])
+            });
+        if let Err(e) = result {
+            error!("Could not rename category: {}", e);
+        }
+    }
+
+    /// Categories Zeppelin hides unless asked, by the name they're shown as
+    pub fn get_archived_categories(&self) -> Vec<String> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT name FROM archived_categories ORDER BY name")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for archived_categories: {}", e);
+                return vec![];
+            }
+        };
+
+        let names = match statement.query_map((), |row| row.get(0)) {
+            Ok(names) => names,
+            Err(e) => {
+                error!("Could not query SELECT on archived_categories: {}", e);
+                return vec![];
             }
+        };
+
+        names.filter_map(|name| name.ok()).collect()
+    }
+
+    pub fn set_category_archived(&self, name: &str, archived: bool) {
+        let result = if archived {
+            self.db.execute(
+                "INSERT OR IGNORE INTO archived_categories VALUES (?1)",
+                [name],
+            )
+        } else {
+            self.db
+                .execute("DELETE FROM archived_categories WHERE name = ?1", [name])
+        };
+        if let Err(e) = result {
+            error!("Could not save archived category: {}", e);
         }
     }
 
     pub fn add_hdtools(&self, user: &str, info: HDToolsInfo) {
-        let loc = info.1.unwrap_or_else(|| crate::user::Location {
+        let loc = info.location.unwrap_or_else(|| crate::user::Location {
             city: "".to_owned(),
             state: None,
             country: None,
         });
         let mut statement = match self
             .db
-            .prepare("INSERT INTO hdtools VALUES (?1, ?2, ?3, ?4, ?5)")
+            .prepare("INSERT INTO hdtools VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")
         {
             Ok(s) => s,
             Err(e) => {
@@ -189,11 +894,19 @@ impl Storage {
         debug!("Running {:?}", statement);
 
         let params = (
-            user,
-            info.0.timestamp(),
+            canonical_name(user),
+            info.creation_date.timestamp(),
             loc.city,
             loc.state.unwrap_or_default(),
             loc.country.unwrap_or_default(),
+            info.affiliation.unwrap_or_default(),
+            info.status.unwrap_or_default(),
+            info.address_record
+                .map(|r| r.to_string())
+                .unwrap_or_default(),
+            info.phone.unwrap_or_default(),
+            info.home.map(|h| h.0),
+            info.home.map(|h| h.1),
         );
 
         if let Err(e) = statement.execute(params) {
@@ -202,10 +915,15 @@ impl Storage {
     }
 
     pub fn get_hdtools(&self, user: &str) -> Option<HDToolsInfo> {
-        let mut statement = match self
-            .db
-            .prepare("SELECT time,city,state,country FROM hdtools WHERE name = ?1")
-        {
+        let found = self.select_hdtools(user);
+        self.cache_stats.count(Cache::HDTools, found.is_some());
+        found
+    }
+
+    fn select_hdtools(&self, user: &str) -> Option<HDToolsInfo> {
+        let mut statement = match self.db.prepare(
+            "SELECT time,city,state,country,affiliation,status,address_record,phone,lat,lon FROM hdtools WHERE name = ?1",
+        ) {
             Ok(s) => s,
             Err(e) => {
                 error!("Could not prepare SELECT for hdtools: {e}");
@@ -213,7 +931,7 @@ impl Storage {
             }
         };
 
-        let mut rows = match statement.query([user]) {
+        let mut rows = match statement.query([canonical_name(user)]) {
             Ok(r) => r,
             Err(e) => {
                 error!("Could not query SELECT for hdtools: {}", e);
@@ -233,13 +951,49 @@ impl Storage {
                 country: row.get(3).ok().and_then(check_empty),
             };
 
-            return Some((date, Some(location)));
+            let address_record = row
+                .get::<_, String>(6)
+                .ok()
+                .and_then(|r| r.parse::<AddressRecord>().ok());
+
+            // Rows cached before these columns existed are NULL
+            return Some(HDToolsInfo {
+                creation_date: date,
+                addresses: address_record
+                    .map(|r| (r, location.clone()))
+                    .into_iter()
+                    .collect(),
+                location: Some(location),
+                affiliation: row.get(4).ok().and_then(check_empty),
+                status: row.get(5).ok().and_then(check_empty),
+                address_record,
+                phone: row.get(7).ok().and_then(check_empty),
+                home: row.get(8).ok().zip(row.get(9).ok()),
+            });
         }
 
         None
     }
 
+    /// Caches where a user's home is for rows saved before it was looked up
+    pub fn set_hdtools_home(&self, user: &str, home: (f32, f32)) {
+        if let Err(e) = self.db.execute(
+            "UPDATE hdtools SET lat = ?2, lon = ?3 WHERE name = ?1",
+            (canonical_name(user), home.0, home.1),
+        ) {
+            error!("Could not update home for hdtools: {}", e);
+        }
+    }
+
     pub fn get_threat(&self, ip: Ipv4Addr) -> Option<IpThreat> {
+        let found = self.select_threat(ip);
+        self.cache_stats.count(Cache::IpThreat, found.is_some());
+        found
+    }
+
+    /// Same as [get_threat](Self::get_threat) without counting toward the cache stats, for
+    /// checking every login against whatever happens to be cached
+    pub fn select_threat(&self, ip: Ipv4Addr) -> Option<IpThreat> {
         let mut statement = match self.db.prepare("SELECT * FROM ipthreat WHERE ip = ?1") {
             Ok(s) => s,
             Err(e) => {
@@ -336,6 +1090,14 @@ impl Storage {
     }
 
     pub fn get_ipinfo(&self, ip: Ipv4Addr) -> Option<IpInfo> {
+        let found = self.select_ipinfo(ip);
+        self.cache_stats.count(Cache::IpInfo, found.is_some());
+        found
+    }
+
+    /// Same as [get_ipinfo](Self::get_ipinfo) without counting toward the cache stats, for
+    /// checking what's cached before the lookup that's counted
+    pub fn select_ipinfo(&self, ip: Ipv4Addr) -> Option<IpInfo> {
         let mut statement = match self.db.prepare("SELECT * FROM ipinfo WHERE ip = :ip") {
             Ok(s) => s,
             Err(e) => {
@@ -374,14 +1136,38 @@ impl Storage {
         }
     }
 
-    pub fn add_ipinfo(&self, ip: Ipv4Addr, info: IpInfo) {
-        let ip: u32 = ip.into();
-        let IpInfo {
-            ip: _,
-            hostname,
-            city,
-            region,
-            country,
+    /// Every IP with cached ipinfo or ipthreat data
+    pub fn get_enriched_ips(&self) -> Vec<Ipv4Addr> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT ip FROM ipinfo UNION SELECT ip FROM ipthreat ORDER BY ip")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for enriched IPs: {}", e);
+                return vec![];
+            }
+        };
+
+        let ips = match statement.query_map((), |row| row.get::<_, u32>(0)) {
+            Ok(ips) => ips,
+            Err(e) => {
+                error!("Could not query SELECT for enriched IPs: {}", e);
+                return vec![];
+            }
+        };
+
+        ips.filter_map(|ip| ip.ok().map(Ipv4Addr::from)).collect()
+    }
+
+    pub fn add_ipinfo(&self, ip: Ipv4Addr, info: IpInfo) {
+        let ip: u32 = ip.into();
+        let IpInfo {
+            ip: _,
+            hostname,
+            city,
+            region,
+            country,
             loc,
             org,
             postal,
@@ -413,6 +1199,9 @@ impl Storage {
     }
 
     fn get_misc(&self, key: MiscKeys) -> String {
+        if !key.is_global() {
+            return self.get_profile_misc(key);
+        }
         let mut statement = match self.db.prepare("SELECT value FROM misc WHERE key = ?1") {
             Ok(s) => s,
             Err(e) => {
@@ -430,6 +1219,11 @@ impl Storage {
         }
     }
 
+    /// Hits and misses of the caches, see [CacheStats]
+    pub fn cache_stats(&self) -> Arc<CacheStats> {
+        Arc::clone(&self.cache_stats)
+    }
+
     pub fn get_username(&self) -> String {
         self.get_misc(MiscKeys::UserName)
     }
@@ -439,28 +1233,104 @@ impl Storage {
     }
 
     fn set_misc(&self, key: MiscKeys, value: String) {
-        let key = key as i64;
-        let mut statement = match self.db.prepare("UPDATE misc SET value = ?2 WHERE key = ?1") {
+        if !key.is_global() {
+            self.set_profile_misc(key, value);
+            return;
+        }
+        if let Err(e) = self.db.execute(
+            "INSERT OR REPLACE INTO misc VALUES (?1, ?2)",
+            (key as i64, value),
+        ) {
+            error!("Could not execute INSERT for misc: {}", e);
+        }
+    }
+
+    fn get_profile_misc(&self, key: MiscKeys) -> String {
+        let mut statement = match self
+            .db
+            .prepare("SELECT value FROM profile_misc WHERE profile = ?1 AND key = ?2")
+        {
             Ok(s) => s,
             Err(e) => {
-                error!("Could not prepare UPDATE for misc: {}", e);
-                return;
+                error!("Could not prepare SELECT for profile_misc: {}", e);
+                return String::default();
             }
         };
 
-        debug!("Running {:?}", statement);
+        match statement.query_row((&self.profile, key as i64), |row| row.get(0)) {
+            Ok(n) => n,
+            Err(rusqlite::Error::QueryReturnedNoRows) => String::default(),
+            Err(e) => {
+                error!("Could not bind SELECT for profile_misc: {}", e);
+                String::default()
+            }
+        }
+    }
 
-        if let Err(e) = statement.execute((key, value.to_owned())) {
-            log::warn!("Could not execute INSERT for misc: {}", e);
-            let mut statement = match self.db.prepare("INSERT INTO misc VALUES (?1, ?2)") {
-                Ok(s) => s,
-                Err(e) => {
-                    error!("Could not prepare INSERT for misc: {}", e);
-                    return;
-                }
-            };
-            if let Err(e) = statement.execute((key, value)) {
-                error!("Could not execute UPDATE for misc: {}", e);
+    fn set_profile_misc(&self, key: MiscKeys, value: String) {
+        if let Err(e) = self.db.execute(
+            "INSERT OR REPLACE INTO profile_misc VALUES (?1, ?2, ?3)",
+            (&self.profile, key as i64, value),
+        ) {
+            error!("Could not execute INSERT for profile_misc: {}", e);
+        }
+    }
+
+    /// Reads and writes settings for a Splunk username from now on.  The first profile made takes
+    /// over the settings saved before profiles existed.
+    pub fn set_profile(&mut self, profile: &str) {
+        info!("Using profile {}", profile);
+        self.profile = profile.to_owned();
+
+        let global: Vec<String> = MiscKeys::GLOBAL
+            .iter()
+            .map(|k| (*k as i64).to_string())
+            .collect();
+        let adopt = format!(
+            "INSERT INTO profile_misc SELECT ?1, key, value FROM misc
+            WHERE key NOT IN ({}) AND NOT EXISTS (SELECT 1 FROM profile_misc)",
+            global.join(", ")
+        );
+        if let Err(e) = self.db.execute(&adopt, [profile]) {
+            error!("Could not adopt old settings into profile_misc: {}", e);
+            return;
+        }
+        // Nothing else reads them, and they shouldn't come back if every profile is deleted
+        let forget = format!("DELETE FROM misc WHERE key NOT IN ({})", global.join(", "));
+        if let Err(e) = self.db.execute(&forget, ()) {
+            error!("Could not execute DELETE for misc: {}", e);
+        }
+    }
+
+    /// Every Splunk username with saved settings
+    pub fn list_profiles(&self) -> Vec<String> {
+        let mut statement = match self.db.prepare(
+            "SELECT profile FROM profile_misc UNION SELECT profile FROM panel_layout ORDER BY 1",
+        ) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for profile_misc: {}", e);
+                return vec![];
+            }
+        };
+
+        let profiles = match statement.query_map((), |row| row.get(0)) {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                error!("Could not query SELECT on profile_misc: {}", e);
+                return vec![];
+            }
+        };
+
+        profiles.filter_map(|p| p.ok()).collect()
+    }
+
+    /// Forgets a profile's settings and layout, it starts from the defaults if used again
+    pub fn delete_profile(&self, profile: &str) {
+        for table in ["profile_misc", "panel_layout"] {
+            let delete = format!("DELETE FROM {} WHERE profile = ?1", table);
+            if let Err(e) = self.db.execute(&delete, [profile]) {
+                error!("Could not execute DELETE for {}: {}", table, e);
             }
         }
     }
@@ -472,4 +1342,576 @@ impl Storage {
     pub fn set_analyst_name(&self, value: String) {
         self.set_misc(MiscKeys::AnalystName, value)
     }
+
+    /// Lowest score shown in Duplex, defaults to showing everyone
+    pub fn get_min_score(&self) -> usize {
+        self.get_misc(MiscKeys::MinScore).parse().unwrap_or(0)
+    }
+
+    pub fn set_min_score(&self, value: usize) {
+        self.set_misc(MiscKeys::MinScore, value.to_string())
+    }
+
+    /// Thresholds for the first vibe check, anything unset or invalid uses the default
+    pub fn get_scoring(&self) -> Scoring {
+        let default = Scoring::default();
+        Scoring {
+            fatigue_pushes: self
+                .get_misc(MiscKeys::FatiguePushes)
+                .parse()
+                .unwrap_or(default.fatigue_pushes),
+            fatigue_minutes: self
+                .get_misc(MiscKeys::FatigueMinutes)
+                .parse()
+                .unwrap_or(default.fatigue_minutes),
+            failure_policy: FailurePolicy {
+                window_minutes: self
+                    .get_misc(MiscKeys::FailureMinutes)
+                    .parse()
+                    .unwrap_or(default.failure_policy.window_minutes),
+                matching: self
+                    .get_misc(MiscKeys::FailureMatch)
+                    .parse()
+                    .unwrap_or(default.failure_policy.matching),
+                min_count: self
+                    .get_misc(MiscKeys::MinFailures)
+                    .parse()
+                    .unwrap_or(default.failure_policy.min_count),
+            },
+            max_kph: self
+                .get_misc(MiscKeys::MaxKph)
+                .parse()
+                .unwrap_or(default.max_kph),
+            new_account_months: self
+                .get_misc(MiscKeys::NewAccountMonths)
+                .parse()
+                .unwrap_or(default.new_account_months),
+            new_account_pass: self
+                .get_misc(MiscKeys::NewAccountPass)
+                .parse()
+                .unwrap_or(default.new_account_pass),
+            flag_relays: self.get_misc(MiscKeys::FlagRelays) == "true",
+            relay_weight: self
+                .get_misc(MiscKeys::RelayWeight)
+                .parse()
+                .unwrap_or(default.relay_weight),
+        }
+    }
+
+    pub fn set_scoring(&self, scoring: Scoring) {
+        self.set_misc(MiscKeys::FatiguePushes, scoring.fatigue_pushes.to_string());
+        self.set_misc(
+            MiscKeys::FatigueMinutes,
+            scoring.fatigue_minutes.to_string(),
+        );
+        self.set_misc(
+            MiscKeys::FailureMinutes,
+            scoring.failure_policy.window_minutes.to_string(),
+        );
+        self.set_misc(
+            MiscKeys::FailureMatch,
+            scoring.failure_policy.matching.key().to_string(),
+        );
+        self.set_misc(
+            MiscKeys::MinFailures,
+            scoring.failure_policy.min_count.to_string(),
+        );
+        self.set_misc(MiscKeys::MaxKph, scoring.max_kph.to_string());
+        self.set_misc(
+            MiscKeys::NewAccountMonths,
+            scoring.new_account_months.to_string(),
+        );
+        self.set_misc(
+            MiscKeys::NewAccountPass,
+            scoring.new_account_pass.to_string(),
+        );
+        self.set_misc(MiscKeys::FlagRelays, scoring.flag_relays.to_string());
+        self.set_misc(MiscKeys::RelayWeight, scoring.relay_weight.to_string());
+    }
+
+    /// Unknown Duo results counted as failures or fraud, defaults to none
+    pub fn get_result_map(&self) -> ResultMap {
+        serde_json::from_str(&self.get_misc(MiscKeys::ResultMap)).unwrap_or_default()
+    }
+
+    pub fn set_result_map(&self, map: &ResultMap) {
+        match serde_json::to_string(map) {
+            Ok(map) => self.set_misc(MiscKeys::ResultMap, map),
+            Err(e) => error!("Could not serialize result map: {}", e),
+        }
+    }
+
+    /// Timezone Splunk's timestamps are in, defaults to local
+    pub fn get_splunk_zone(&self) -> Zone {
+        self.get_misc(MiscKeys::SplunkZone)
+            .parse()
+            .unwrap_or_default()
+    }
+
+    pub fn set_splunk_zone(&self, zone: Zone) {
+        self.set_misc(MiscKeys::SplunkZone, zone.to_string())
+    }
+
+    /// Timezone times are shown in, defaults to local
+    pub fn get_display_zone(&self) -> Zone {
+        self.get_misc(MiscKeys::DisplayZone)
+            .parse()
+            .unwrap_or_default()
+    }
+
+    pub fn set_display_zone(&self, zone: Zone) {
+        self.set_misc(MiscKeys::DisplayZone, zone.to_string())
+    }
+
+    /// Falls back to the default URLs if none were saved
+    pub fn get_hdtools_config(&self) -> HDToolsConfig {
+        serde_json::from_str(&self.get_misc(MiscKeys::HDToolsConfig)).unwrap_or_default()
+    }
+
+    pub fn set_hdtools_config(&self, config: &HDToolsConfig) {
+        match serde_json::to_string(config) {
+            Ok(config) => self.set_misc(MiscKeys::HDToolsConfig, config),
+            Err(e) => error!("Could not serialize HDTools config: {}", e),
+        }
+    }
+
+    /// Falls back to the default index names if none were saved
+    pub fn get_index_config(&self) -> IndexConfig {
+        serde_json::from_str(&self.get_misc(MiscKeys::IndexConfig)).unwrap_or_default()
+    }
+
+    pub fn set_index_config(&self, config: &IndexConfig) {
+        match serde_json::to_string(config) {
+            Ok(config) => self.set_misc(MiscKeys::IndexConfig, config),
+            Err(e) => error!("Could not serialize Splunk index config: {}", e),
+        }
+    }
+
+    /// Falls back to the default timeouts if none were saved
+    pub fn get_timeouts(&self) -> TimeoutConfig {
+        serde_json::from_str(&self.get_misc(MiscKeys::Timeouts)).unwrap_or_default()
+    }
+
+    pub fn set_timeouts(&self, timeouts: &TimeoutConfig) {
+        match serde_json::to_string(timeouts) {
+            Ok(timeouts) => self.set_misc(MiscKeys::Timeouts, timeouts),
+            Err(e) => error!("Could not serialize timeouts: {}", e),
+        }
+    }
+
+    /// Scale on top of the OS's, defaults to none
+    pub fn get_ui_scale(&self) -> f32 {
+        self.get_misc(MiscKeys::UiScale)
+            .parse::<f32>()
+            .map_or(1.0, |s| s.clamp(*UI_SCALE.start(), *UI_SCALE.end()))
+    }
+
+    pub fn set_ui_scale(&self, scale: f32) {
+        self.set_misc(MiscKeys::UiScale, scale.to_string())
+    }
+
+    pub fn get_theme(&self) -> ThemeKind {
+        self.get_misc(MiscKeys::Theme).parse().unwrap_or_default()
+    }
+
+    pub fn set_theme(&self, theme: ThemeKind) {
+        self.set_misc(MiscKeys::Theme, theme.to_string())
+    }
+
+    /// Saves a theme where it was kept before profiles, for testing [set_profile](Self::set_profile)
+    #[cfg(test)]
+    pub fn set_theme_before_profiles(&self, theme: ThemeKind) {
+        self.db
+            .execute(
+                "INSERT INTO misc VALUES (?1, ?2)",
+                (MiscKeys::Theme as i64, theme.to_string()),
+            )
+            .expect("Couldn't save theme to misc");
+    }
+
+    /// Login pulls longer than this many hours run as a Splunk search job, defaults to 8 days so
+    /// Duplex's week of history is still a plain search
+    pub fn get_job_hours(&self) -> i64 {
+        self.get_misc(MiscKeys::JobHours)
+            .parse::<i64>()
+            .map_or(24 * 8, |h| h.max(1))
+    }
+
+    pub fn set_job_hours(&self, hours: i64) {
+        self.set_misc(MiscKeys::JobHours, hours.to_string())
+    }
+
+    /// Events each Sonar search reads, defaults to 100
+    pub fn get_sonar_limit(&self) -> usize {
+        self.get_misc(MiscKeys::SonarLimit)
+            .parse::<usize>()
+            .map_or(100, |l| l.max(1))
+    }
+
+    pub fn set_sonar_limit(&self, limit: usize) {
+        self.set_misc(MiscKeys::SonarLimit, limit.to_string())
+    }
+
+    /// Whether usernames and IPs are shown as pseudonyms, see [Redact]
+    pub fn get_redact(&self) -> Redact {
+        Redact(self.get_misc(MiscKeys::Redact) == "true")
+    }
+
+    pub fn set_redact(&self, redact: Redact) {
+        self.set_misc(MiscKeys::Redact, redact.0.to_string())
+    }
+
+    /// Whether auto-refresh sends a desktop notification for high scoring users, defaults to off
+    pub fn get_notify(&self) -> bool {
+        self.get_misc(MiscKeys::Notify) == "true"
+    }
+
+    pub fn set_notify(&self, notify: bool) {
+        self.set_misc(MiscKeys::Notify, notify.to_string())
+    }
+
+    /// Lowest score that sends a notification, defaults to 40
+    pub fn get_notify_score(&self) -> usize {
+        self.get_misc(MiscKeys::NotifyScore).parse().unwrap_or(40)
+    }
+
+    pub fn set_notify_score(&self, score: usize) {
+        self.set_misc(MiscKeys::NotifyScore, score.to_string())
+    }
+
+    /// Whether a finished Duplex run sends a desktop notification, defaults to off
+    pub fn get_notify_run_done(&self) -> bool {
+        self.get_misc(MiscKeys::NotifyRunDone) == "true"
+    }
+
+    pub fn set_notify_run_done(&self, notify: bool) {
+        self.set_misc(MiscKeys::NotifyRunDone, notify.to_string())
+    }
+
+    /// Whether Duplex keeps each login's raw event, defaults to off since it pulls every login
+    pub fn get_raw_duplex(&self) -> bool {
+        self.get_misc(MiscKeys::RawDuplex) == "true"
+    }
+
+    pub fn set_raw_duplex(&self, keep: bool) {
+        self.set_misc(MiscKeys::RawDuplex, keep.to_string())
+    }
+
+    /// Whether Duplex opens on the overview of every user, defaults to off
+    pub fn get_duplex_overview(&self) -> bool {
+        self.get_misc(MiscKeys::DuplexOverview) == "true"
+    }
+
+    pub fn set_duplex_overview(&self, overview: bool) {
+        self.set_misc(MiscKeys::DuplexOverview, overview.to_string())
+    }
+
+    /// Whether Duplex runs on its own while idle, defaults to off
+    pub fn get_schedule(&self) -> bool {
+        self.get_misc(MiscKeys::Schedule) == "true"
+    }
+
+    pub fn set_schedule(&self, schedule: bool) {
+        self.set_misc(MiscKeys::Schedule, schedule.to_string())
+    }
+
+    /// Minutes between scheduled Duplex runs, defaults to two hours
+    pub fn get_schedule_minutes(&self) -> i64 {
+        self.get_misc(MiscKeys::ScheduleMinutes)
+            .parse()
+            .unwrap_or(120)
+    }
+
+    pub fn set_schedule_minutes(&self, minutes: i64) {
+        self.set_misc(MiscKeys::ScheduleMinutes, minutes.to_string())
+    }
+
+    /// End of the range of the last Duplex run that finished, [None] if none have
+    pub fn get_last_run_end(&self) -> Option<DateTime<chrono::Utc>> {
+        let end = self.get_misc(MiscKeys::LastRunEnd).parse().ok()?;
+        chrono::Utc.timestamp_opt(end, 0).single()
+    }
+
+    pub fn set_last_run_end(&self, end: DateTime<chrono::Utc>) {
+        self.set_misc(MiscKeys::LastRunEnd, end.timestamp().to_string())
+    }
+
+    /// Whether Simplex keeps each login's raw event, defaults to on since it pulls one user
+    pub fn get_raw_simplex(&self) -> bool {
+        self.get_misc(MiscKeys::RawSimplex) != "false"
+    }
+
+    pub fn set_raw_simplex(&self, keep: bool) {
+        self.set_misc(MiscKeys::RawSimplex, keep.to_string())
+    }
+
+    pub fn get_keymap(&self) -> Keymap {
+        self.get_misc(MiscKeys::Keymap).parse().unwrap_or_default()
+    }
+
+    pub fn set_keymap(&self, keymap: &Keymap) {
+        self.set_misc(MiscKeys::Keymap, keymap.to_string())
+    }
+
+    /// ASNs whose logins are not scored for impossible travel
+    pub fn get_trusted_asns(&self) -> Vec<String> {
+        let mut statement = match self.db.prepare("SELECT asn FROM trusted_asns ORDER BY asn") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for trusted_asns: {}", e);
+                return vec![];
+            }
+        };
+
+        let asns = match statement.query_map((), |row| row.get(0)) {
+            Ok(asns) => asns,
+            Err(e) => {
+                error!("Could not query SELECT on trusted_asns: {}", e);
+                return vec![];
+            }
+        };
+
+        asns.filter_map(|asn| asn.ok()).collect()
+    }
+
+    pub fn add_trusted_asn(&self, asn: &str) {
+        let mut statement = match self
+            .db
+            .prepare("INSERT OR IGNORE INTO trusted_asns VALUES (?1)")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare INSERT for trusted_asns: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([asn]) {
+            error!("Could not execute INSERT for trusted_asns: {}", e);
+        }
+    }
+
+    pub fn remove_trusted_asn(&self, asn: &str) {
+        let mut statement = match self.db.prepare("DELETE FROM trusted_asns WHERE asn = ?1") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare DELETE for trusted_asns: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([asn]) {
+            error!("Could not execute DELETE for trusted_asns: {}", e);
+        }
+    }
+
+    /// IPs whose logins are not scored for failures or impossible travel
+    pub fn get_ip_allowlist(&self) -> Vec<Ipv4Addr> {
+        let mut statement = match self.db.prepare("SELECT ip FROM ip_allowlist ORDER BY ip") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for ip_allowlist: {}", e);
+                return vec![];
+            }
+        };
+
+        let ips = match statement.query_map((), |row| row.get::<_, u32>(0)) {
+            Ok(ips) => ips,
+            Err(e) => {
+                error!("Could not query SELECT on ip_allowlist: {}", e);
+                return vec![];
+            }
+        };
+
+        ips.filter_map(|ip| ip.ok().map(Ipv4Addr::from)).collect()
+    }
+
+    pub fn add_allowlisted_ip(&self, ip: Ipv4Addr) {
+        let ip: u32 = ip.into();
+        let mut statement = match self
+            .db
+            .prepare("INSERT OR IGNORE INTO ip_allowlist VALUES (?1)")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare INSERT for ip_allowlist: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([ip]) {
+            error!("Could not execute INSERT for ip_allowlist: {}", e);
+        }
+    }
+
+    pub fn remove_allowlisted_ip(&self, ip: Ipv4Addr) {
+        let ip: u32 = ip.into();
+        let mut statement = match self.db.prepare("DELETE FROM ip_allowlist WHERE ip = ?1") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare DELETE for ip_allowlist: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([ip]) {
+            error!("Could not execute DELETE for ip_allowlist: {}", e);
+        }
+    }
+
+    /// IP ranges whose logins are not scored for failures or impossible travel
+    pub fn get_ip_ranges(&self) -> Vec<IpRange> {
+        let mut statement = match self.db.prepare("SELECT cidr FROM ip_range_allowlist") {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for ip_range_allowlist: {}", e);
+                return vec![];
+            }
+        };
+
+        let ranges = match statement.query_map((), |row| row.get::<_, String>(0)) {
+            Ok(ranges) => ranges,
+            Err(e) => {
+                error!("Could not query SELECT on ip_range_allowlist: {}", e);
+                return vec![];
+            }
+        };
+
+        let mut ranges: Vec<IpRange> = ranges
+            .filter_map(|r| r.ok().and_then(|r| r.parse().ok()))
+            .collect();
+        ranges.sort();
+        ranges
+    }
+
+    pub fn add_ip_range(&self, range: IpRange) {
+        let mut statement = match self
+            .db
+            .prepare("INSERT OR IGNORE INTO ip_range_allowlist VALUES (?1)")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare INSERT for ip_range_allowlist: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([range.to_string()]) {
+            error!("Could not execute INSERT for ip_range_allowlist: {}", e);
+        }
+    }
+
+    pub fn remove_ip_range(&self, range: IpRange) {
+        let mut statement = match self
+            .db
+            .prepare("DELETE FROM ip_range_allowlist WHERE cidr = ?1")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare DELETE for ip_range_allowlist: {}", e);
+                return;
+            }
+        };
+
+        debug!("Running {:?}", statement);
+
+        if let Err(e) = statement.execute([range.to_string()]) {
+            error!("Could not execute DELETE for ip_range_allowlist: {}", e);
+        }
+    }
+
+    pub fn get_panel_layout(&self) -> Vec<PanelLayout> {
+        let mut statement = match self
+            .db
+            .prepare("SELECT name, open, x, y, w, h FROM panel_layout WHERE profile = ?1")
+        {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Could not prepare SELECT for panel_layout: {}", e);
+                return vec![];
+            }
+        };
+
+        let layout = match statement.query_map([&self.profile], |row| {
+            let rect = match (row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?) {
+                (Some(x), Some(y), Some(w), Some(h)) => Some(egui::Rect::from_min_size(
+                    egui::pos2(x, y),
+                    egui::vec2(w, h),
+                )),
+                _ => None,
+            };
+            Ok(PanelLayout {
+                name: row.get(0)?,
+                open: row.get(1)?,
+                rect,
+            })
+        }) {
+            Ok(layout) => layout,
+            Err(e) => {
+                error!("Could not query SELECT on panel_layout: {}", e);
+                return vec![];
+            }
+        };
+
+        layout.filter_map(|l| l.ok()).collect()
+    }
+
+    pub fn set_panel_open(&self, name: &str, open: bool) {
+        if let Err(e) = self.db.execute(
+            "INSERT OR IGNORE INTO panel_layout (profile, name) VALUES (?1, ?2)",
+            (&self.profile, name),
+        ) {
+            error!("Could not execute INSERT for panel_layout: {}", e);
+            return;
+        }
+        if let Err(e) = self.db.execute(
+            "UPDATE panel_layout SET open = ?3 WHERE profile = ?1 AND name = ?2",
+            (&self.profile, name, open),
+        ) {
+            error!("Could not execute UPDATE for panel_layout: {}", e);
+        }
+    }
+
+    pub fn set_panel_rect(&self, name: &str, rect: egui::Rect) {
+        if let Err(e) = self.db.execute(
+            "INSERT OR IGNORE INTO panel_layout (profile, name, open) VALUES (?1, ?2, 1)",
+            (&self.profile, name),
+        ) {
+            error!("Could not execute INSERT for panel_layout: {}", e);
+            return;
+        }
+        if let Err(e) = self.db.execute(
+            "UPDATE panel_layout SET x = ?3, y = ?4, w = ?5, h = ?6 WHERE profile = ?1 AND name = ?2",
+            (
+                &self.profile,
+                name,
+                rect.min.x,
+                rect.min.y,
+                rect.width(),
+                rect.height(),
+            ),
+        ) {
+            error!("Could not execute UPDATE for panel_layout: {}", e);
+        }
+    }
+
+    /// Forgets where every window was, keeping which panels are open
+    pub fn clear_panel_rects(&self) {
+        if let Err(e) = self.db.execute(
+            "UPDATE panel_layout SET x = NULL, y = NULL, w = NULL, h = NULL WHERE profile = ?1",
+            [&self.profile],
+        ) {
+            error!("Could not execute UPDATE for panel_layout: {}", e);
+        }
+    }
 }
@@ -3,35 +3,97 @@
 //! Hold all the weird bits that don't feel right staying in the UI but don't belong in any other
 //! module.  This is where the main logic lööps of the apps are.
 use crate::{
+    app::sonar::Details,
+    prefs::{keymap::Keymap, redact::Redact, ThemeKind},
     queries::{
-        hdtools::HDTools,
-        ip::IpThreat,
+        diagnostics::{Backend, QueryRecord, Totals},
+        hdtools::{HDTools, HDToolsInfo, HDToolsStatus},
+        ip::{Ip, IpEnrichment, IpThreat},
         osiris,
-        splunk::{Splunk, TimeSpan},
-        Queries,
+        splunk::{LoginFilter, Search, Splunk, TimeSpan},
+        Queries, QueryError, TimeoutConfig,
+    },
+    storage::{Cache, CacheStats, IgnoreEvent, Investigated, PanelLayout, Storage},
+    user::{
+        admin::AdminEvent,
+        canonical_name,
+        login::{FraudDisposition, LocationSource, Login, LoginRecord, LoginResult, ResultMap},
+        vpnlog::VpnLog,
+        zone::Zone,
+        Allowlist, IpRange, Scoring, User,
     },
-    storage::Storage,
-    user::{login::Login, vpnlog::VpnLog, User},
 };
 use chrono::{Duration, NaiveDate};
-use log::info;
+use log::{info, warn};
 use std::thread;
-use std::{net::Ipv4Addr, sync::Mutex};
+use std::{
+    collections::{HashMap, HashSet},
+    net::Ipv4Addr,
+    sync::Mutex,
+};
 use std::{
     sync::{Arc, RwLock},
     thread::JoinHandle,
 };
 
+/// Listed users at or below this many have their logins pulled one by one in
+/// [Store::run_duplex], above it every login is pulled at once
+const PER_USER_LOGINS: usize = 25;
+
+/// Where [Store::run_duplex] is at.  Each holds a range 0..=1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Progress {
+    /// Pulling logins from Splunk, only moves when the pull runs as a search job
+    Querying(f32),
+    /// How many users have been vibe checked
+    Checking(f32),
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::Querying(0.0)
+    }
+}
+
+/// Query timings and cache use this session, see [Store::diagnostics]
+pub struct Diagnostics {
+    /// Newest first
+    pub recent: Vec<QueryRecord>,
+    pub totals: Vec<(Backend, Totals)>,
+    /// Hits and misses of each cache
+    pub caches: Vec<(Cache, usize, usize)>,
+}
+
 pub struct Store {
     storage: Arc<Mutex<Storage>>,
     queries: Queries,
-    /// Range 0..=1 that keeps track of how many users have been processed for Duplex
-    progress: Arc<RwLock<f32>>,
+    /// How far along Duplex is, see [Progress]
+    progress: Arc<RwLock<Progress>>,
     analyst_name: String,
     /// Remembers failed IPs to avoid repeated network quering.  This is held in the store as putting
     /// inside ipq, where it should be, would mean wrapping it in a RwLock or Mutex, I'm lazy and
     /// didn't want to do this
     failed_ips: RwLock<Vec<Ipv4Addr>>,
+    /// IPs analysts have marked as known-good.  Kept in memory as the tables check every row
+    /// every frame.
+    ip_allowlist: RwLock<Vec<Ipv4Addr>>,
+    /// Trusted IP ranges, kept in memory for the same reason
+    ip_ranges: RwLock<Vec<IpRange>>,
+    /// Timezone times are shown in, kept in memory for the same reason
+    display_zone: RwLock<Zone>,
+    /// Keyboard shortcuts, kept in memory for the same reason
+    keymap: RwLock<Keymap>,
+    /// Scale on top of the OS's, read every frame
+    ui_scale: RwLock<f32>,
+    /// Read every frame as well
+    theme: RwLock<ThemeKind>,
+    /// Read for every username and IP shown
+    redact: RwLock<Redact>,
+    /// Scheduled runs are on, how many minutes apart they are, and where the last one ended, all
+    /// read every frame Duplex is idle
+    schedule: RwLock<(bool, i64, Option<chrono::DateTime<chrono::Utc>>)>,
+    /// Kept outside the storage lock so the Diagnostics panel doesn't wait on Duplex
+    cache_stats: Arc<CacheStats>,
 }
 
 impl Store {
@@ -41,19 +103,91 @@ impl Store {
         storage: Storage,
         analyst_name: String,
     ) -> Self {
+        let ip_allowlist = RwLock::new(storage.get_ip_allowlist());
+        let ip_ranges = RwLock::new(storage.get_ip_ranges());
+        let display_zone = RwLock::new(storage.get_display_zone());
+        let keymap = RwLock::new(storage.get_keymap());
+        let ui_scale = RwLock::new(storage.get_ui_scale());
+        let theme = RwLock::new(storage.get_theme());
+        let redact = RwLock::new(storage.get_redact());
+        let schedule = RwLock::new((
+            storage.get_schedule(),
+            storage.get_schedule_minutes(),
+            storage.get_last_run_end(),
+        ));
+        splunk.set_zone(storage.get_splunk_zone());
+        splunk.set_job_threshold(Duration::hours(storage.get_job_hours()));
+        splunk.set_sonar_limit(storage.get_sonar_limit());
+        splunk.set_result_map(storage.get_result_map());
+        let cache_stats = storage.cache_stats();
+        let queries = Queries::new(splunk, hdtools, &storage.get_timeouts());
         let storage = Arc::new(Mutex::new(storage));
-        let progress = Arc::new(RwLock::new(0.0));
+        let progress = Arc::new(RwLock::new(Progress::default()));
         Self {
             storage,
             progress,
-            queries: Queries::new(splunk, hdtools),
+            queries,
             analyst_name,
             failed_ips: RwLock::new(Vec::default()),
+            ip_allowlist,
+            ip_ranges,
+            display_zone,
+            keymap,
+            ui_scale,
+            theme,
+            redact,
+            schedule,
+            cache_stats,
+        }
+    }
+
+    // -------------------- Splunk --------------------
+
+    /// Checks that the Splunk session still works
+    pub fn check_splunk(&self) -> JoinHandle<Result<(), QueryError>> {
+        let splunk = Arc::clone(&self.queries.splunk);
+        thread::spawn(move || splunk.health_check())
+    }
+
+    /// Why logins have no geolocation, if the IP databases failed to load
+    pub fn ipdb_error(&self) -> Option<String> {
+        self.queries.splunk.ipdb_error().map(|e| e.to_string())
+    }
+
+    /// Recent query timings, totals for each backend, and cache hits and misses this session
+    pub fn diagnostics(&self) -> Diagnostics {
+        let diagnostics = &self.queries.diagnostics;
+        Diagnostics {
+            recent: diagnostics.recent(),
+            totals: diagnostics.totals(),
+            caches: Cache::ALL
+                .into_iter()
+                .map(|cache| {
+                    let (hits, misses) = self.cache_stats.get(cache);
+                    (cache, hits, misses)
+                })
+                .collect(),
         }
     }
 
     // -------------------- Duplex --------------------
 
+    /// Counts the Duo events Duplex would search, those in the range users are found in and those
+    /// in the history their logins are pulled from, see [Splunk::estimate_count]
+    pub fn estimate_duplex(
+        &self,
+        user_range: TimeSpan,
+        history_range: TimeSpan,
+    ) -> JoinHandle<Result<(usize, usize), QueryError>> {
+        let splunk = Arc::clone(&self.queries.splunk);
+        thread::spawn(move || {
+            Ok((
+                splunk.estimate_count(&user_range)?,
+                splunk.estimate_count(&history_range)?,
+            ))
+        })
+    }
+
     /// Main lööp of Duplex.  This pulls all users and logs from Splunk and performs three rounds
     /// of vibe checks.  The first only keeps users with fraud, failures, impossible travel, or
     /// device management portal access.  The second round removes all users created in the past 6
@@ -61,15 +195,21 @@ impl Store {
     /// every IP for alternate locations by polling other databases, determining which IP is closer
     /// to previous logs or the user's home, and then re-runs the first vibe check with the updated
     /// IP locations.
+    ///
+    /// When `users` isn't empty only they are checked instead of every Duo user in the range.
+    /// Short lists pull each user's logins on their own, longer ones pull every login and drop the
+    /// rest.
     pub fn run_duplex(
         &self,
         user_range: TimeSpan,
         history_range: TimeSpan,
-    ) -> JoinHandle<Vec<User>> {
+        filter: LoginFilter,
+        users: Vec<String>,
+    ) -> JoinHandle<Result<Vec<User>, QueryError>> {
         info!("Starting initial run");
         {
             if let Ok(mut prog) = self.progress.write() {
-                *prog = 0.0;
+                *prog = Progress::default();
             }
         }
         let hdtools = self.queries.hdtools.as_ref().map(Arc::clone);
@@ -77,29 +217,62 @@ impl Store {
         let splunk = Arc::clone(&self.queries.splunk);
         let storage = Arc::clone(&self.storage);
         let progress = Arc::clone(&self.progress);
-        thread::spawn::<_, Vec<User>>(move || {
-            let user_list = match splunk.get_duo_users(&user_range) {
-                Ok(users) => users,
-                Err(_) => return vec![],
+        let allowlist = self.allowlist();
+        let scoring = self.scoring();
+        let keep_raw = self.raw_duplex();
+        thread::spawn::<_, Result<Vec<User>, QueryError>>(move || {
+            let set_progress = |p: f32| {
+                if let Ok(mut prog) = progress.write() {
+                    *prog = Progress::Querying(p);
+                }
             };
-            let login_list = match splunk.get_logins(&history_range) {
-                Ok(logins) => logins,
-                Err(_) => return vec![],
+            let (user_list, login_list) = if users.is_empty() {
+                let user_list = splunk.get_duo_users(&user_range)?;
+                (
+                    user_list,
+                    splunk.get_logins(&history_range, filter, keep_raw, &set_progress)?,
+                )
+            } else if users.len() <= PER_USER_LOGINS {
+                info!("Pulling logins for {} listed users", users.len());
+                let mut login_list = vec![];
+                for (i, user) in users.iter().enumerate() {
+                    login_list.append(&mut splunk.get_user_logins(
+                        user,
+                        &history_range,
+                        keep_raw,
+                    )?);
+                    set_progress((i + 1) as f32 / users.len() as f32);
+                }
+                (users, login_list)
+            } else {
+                info!("Pulling every login for {} listed users", users.len());
+                let login_list =
+                    splunk.get_logins(&history_range, LoginFilter::All, keep_raw, &set_progress)?;
+                (users, login_list)
             };
             let mut users = crate::queries::splunk::Splunk::match_users_and_logins(
                 user_list,
                 login_list,
                 &user_range.start,
+                scoring.max_impossible_travel_time(),
             );
 
             info!("Performing first vibe check");
             {
                 // Brackets ensures storage is dropped
                 let storage = storage.lock().expect("Couldn't get storage lock");
+                let overrides = storage.get_location_overrides();
                 users = users
                     .into_iter()
                     .filter_map(|mut user| {
-                        if !user.first_vibe_check() && !storage.investigated(&user.name) {
+                        load_fraud_dispositions(&storage, &user.name, &mut user.logins);
+                        override_locations(&splunk, &overrides, &mut user.logins);
+                        if scoring.flag_relays {
+                            mark_threat_relays(&storage, &mut user.logins);
+                        }
+                        if !user.first_vibe_check(&allowlist, &scoring)
+                            && !storage.investigated(&user.name)
+                        {
                             Some(user)
                         } else {
                             None
@@ -119,22 +292,24 @@ impl Store {
                     .filter_map(|(i, mut user)| {
                         {
                             if let Ok(mut prog) = progress.write() {
-                                *prog = (i + 1) as f32 / count / 2.0;
+                                *prog = Progress::Checking((i + 1) as f32 / count / 2.0);
                             }
                         }
 
-                        if let Some((creation_date, location)) = storage.get_hdtools(&user.name) {
-                            user.location = location;
-                            user.creation_date = Some(creation_date);
-                        } else if let Some((creation_date, location)) = hdtools.get_info(&user.name)
-                        {
-                            user.location = location.to_owned();
-                            user.creation_date = Some(creation_date.to_owned());
-
-                            storage.add_hdtools(&user.name, (creation_date, location));
+                        if let Some(mut info) = storage.get_hdtools(&user.name) {
+                            locate_home(&splunk, &storage, &user.name, &mut info);
+                            user.set_hdtools(info);
+                        } else {
+                            let result = hdtools.get_info(&user.name);
+                            user.hdtools_status = HDToolsStatus::new(&result);
+                            if let Ok(mut info) = result {
+                                storage.add_hdtools(&user.name, info.clone());
+                                locate_home(&splunk, &storage, &user.name, &mut info);
+                                user.set_hdtools(info);
+                            }
                         }
 
-                        if !user.second_vibe_check() {
+                        if !user.second_vibe_check(&scoring) {
                             info!("{} failed second vibe check", user.name);
                             Some(user)
                         } else {
@@ -155,38 +330,18 @@ impl Store {
                         .filter_map(|(i, mut user)| {
                             {
                                 if let Ok(mut prog) = progress.write() {
-                                    *prog = (i + 1 + count as usize / 2) as f32 / count;
+                                    *prog = Progress::Checking(
+                                        (i + 1 + count as usize / 2) as f32 / count,
+                                    );
                                 }
                             }
 
-                            for i in 0..user.checked_login_count {
-                                let login = &user.logins[i];
-                                if login.is_priv_ip() || login.is_vpn_ip() {
-                                    continue;
-                                }
-                                if let Some(ip) = login.ip {
-                                    if let Some(ipinfo) = storage.get_ipinfo(ip).or_else(|| {
-                                        let ipinfo = ipq.get_info(ip);
-                                        if let Some(ipinfo) = &ipinfo {
-                                            storage.add_ipinfo(ip, ipinfo.clone());
-                                        }
-                                        ipinfo
-                                    }) {
-                                        // Updates login location if it correlates better with
-                                        // surrounding logs
-                                        if user.closer_to(&ipinfo, i) {
-                                            info!("Updating log with ip {} for {}", ip, user.name);
-                                            user.logins[i].location =
-                                                Some((ipinfo.loc.lat, ipinfo.loc.lon));
-                                            user.logins[i].country = Some(ipinfo.country);
-                                            user.logins[i].state = Some(ipinfo.region);
-                                            user.logins[i].city = Some(ipinfo.city);
-                                        }
-                                    }
-                                }
-                            }
+                            relocate_logins(&storage, &ipq, &mut user);
 
-                            if !user.first_vibe_check() && !storage.investigated(&user.name) {
+                            if !user.first_vibe_check(&allowlist, &scoring)
+                                && !storage.investigated(&user.name)
+                            {
+                                user.investigated_note = storage.investigated_note(&user.name);
                                 Some(user)
                             } else {
                                 info!("{} is no longer funky", user.name);
@@ -204,32 +359,595 @@ impl Store {
             users.sort();
 
             info!("Finished initial run with {} users", users.len());
+            Ok(users)
+        })
+    }
+
+    /// Reruns only the third vibe check over users that are already loaded, for when the IP
+    /// cache or databases have improved or analysts set coordinates for an IP.  Splunk isn't
+    /// queried again.  Users that now pass are dropped and the rest are sorted.
+    pub fn rerun_ip_pass(&self, users: Vec<User>) -> JoinHandle<Vec<User>> {
+        let ipq = Arc::clone(&self.queries.ipq);
+        let splunk = Arc::clone(&self.queries.splunk);
+        let storage = Arc::clone(&self.storage);
+        let allowlist = self.allowlist();
+        let scoring = self.scoring();
+        thread::spawn(move || {
+            info!("Rerunning third vibe check for {} users", users.len());
+            let (mut users, ips) = {
+                let storage = storage.lock().expect("Couldn't get storage lock");
+                let overrides = storage.get_location_overrides();
+                let mut ips = HashSet::new();
+                let users: Vec<User> = users
+                    .into_iter()
+                    .map(|mut user| {
+                        override_locations(&splunk, &overrides, &mut user.logins);
+                        ips.extend(uncached_ips(&storage, &user));
+                        user
+                    })
+                    .collect();
+                (users, ips)
+            };
+            // Looked up without the lock so ipinfo.io doesn't stall everything else using
+            // storage
+            let fetched: Vec<_> = ips
+                .into_iter()
+                .filter_map(|ip| ipq.get_info(ip).ok().map(|ipinfo| (ip, ipinfo)))
+                .collect();
+            let storage = storage.lock().expect("Couldn't get storage lock");
+            for (ip, ipinfo) in fetched {
+                storage.add_ipinfo(ip, ipinfo);
+            }
+            users = users
+                .into_iter()
+                .filter_map(|mut user| {
+                    relocate_cached(&storage, &mut user);
+                    if scoring.flag_relays {
+                        mark_threat_relays(&storage, &mut user.logins);
+                    }
+                    if !user.first_vibe_check(&allowlist, &scoring)
+                        && !storage.investigated(&user.name)
+                    {
+                        Some(user)
+                    } else {
+                        info!("{} is no longer funky", user.name);
+                        None
+                    }
+                })
+                .collect();
+            users.sort();
             users
         })
     }
 
+    /// Splunk searches [run_duplex](Self::run_duplex) runs with the same arguments, for running
+    /// them by hand
+    pub fn duplex_searches(
+        &self,
+        user_range: &TimeSpan,
+        history_range: &TimeSpan,
+        filter: LoginFilter,
+        users: &[String],
+    ) -> Vec<Search> {
+        let splunk = &self.queries.splunk;
+        if users.is_empty() {
+            vec![
+                splunk.duo_users_search(user_range),
+                splunk.logins_search(history_range, filter),
+            ]
+        } else if users.len() <= PER_USER_LOGINS {
+            users
+                .iter()
+                .map(|user| splunk.user_logins_search(user, history_range))
+                .collect()
+        } else {
+            vec![splunk.logins_search(history_range, LoginFilter::All)]
+        }
+    }
+
     /// Used by Duplex to query more logs for a specific user
-    pub fn more_info(&self, name: String, days: i64) -> JoinHandle<Option<Vec<Login>>> {
+    pub fn more_info(&self, name: String, days: i64) -> JoinHandle<Result<Vec<Login>, QueryError>> {
+        let splunk = Arc::clone(&self.queries.splunk);
+        let keep_raw = self.raw_duplex();
+        let storage = Arc::clone(&self.storage);
+        thread::spawn(move || {
+            let timespan = Duration::days(days).into();
+            let mut logins = splunk.get_user_logins(&name, &timespan, keep_raw)?;
+            let storage = storage.lock().expect("Failed to get storage lock");
+            load_fraud_dispositions(&storage, &name, &mut logins);
+            override_locations(&splunk, &storage.get_location_overrides(), &mut logins);
+            Ok(logins)
+        })
+    }
+
+    /// Used by Duplex to pull a user's Duo administrator events, such as device activations
+    pub fn admin_events(
+        &self,
+        name: String,
+        days: i64,
+    ) -> JoinHandle<Result<Vec<AdminEvent>, QueryError>> {
         let splunk = Arc::clone(&self.queries.splunk);
-        let days = days;
         thread::spawn(move || {
             let timespan = Duration::days(days).into();
-            splunk.get_user_logins(&name, &timespan).ok()
+            splunk.get_admin_events(&name, &timespan)
         })
     }
 
     /// Returns the progress of [run_duplex()](Self::run_duplex())
-    pub fn progress(&self) -> f32 {
-        let count = self
+    pub fn progress(&self) -> Progress {
+        *self
             .progress
             .read()
-            .expect("Failed to get storage read lock");
-        *count
+            .expect("Failed to get progress read lock")
+    }
+
+    /// Ignores or un-ignores a user, recording the current analyst in the ignore history
+    pub fn mark_investigated(&self, user: String, mark: bool, note: Option<&str>) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.mark_investigated(user, mark, note);
+    }
+
+    pub fn mark_many_investigated(&self, users: &[String]) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.mark_many_investigated(users);
+    }
+
+    pub fn list_investigated(&self) -> Vec<Investigated> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.list_investigated()
+    }
+
+    /// Recent ignores and un-ignores of users whose name contains `user`
+    pub fn investigated_history(&self, user: &str) -> Vec<IgnoreEvent> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.investigated_history(user)
+    }
+
+    pub fn clear_investigated(&self) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.clear_investigated();
+    }
+
+    /// Lowest score shown in Duplex
+    pub fn min_score(&self) -> usize {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_min_score()
+    }
+
+    pub fn set_min_score(&self, score: usize) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_min_score(score);
+    }
+
+    /// Whether auto-refresh sends a desktop notification for high scoring users
+    pub fn notify(&self) -> bool {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_notify()
+    }
+
+    pub fn set_notify(&self, notify: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_notify(notify);
+    }
+
+    /// Lowest score that sends a notification
+    pub fn notify_score(&self) -> usize {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_notify_score()
+    }
+
+    pub fn set_notify_score(&self, score: usize) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_notify_score(score);
+    }
+
+    /// Whether a finished Duplex run sends a desktop notification
+    pub fn notify_run_done(&self) -> bool {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_notify_run_done()
+    }
+
+    pub fn set_notify_run_done(&self, notify: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_notify_run_done(notify);
+    }
+
+    /// Whether Duplex keeps each login's raw event for viewing
+    pub fn raw_duplex(&self) -> bool {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_raw_duplex()
+    }
+
+    pub fn set_raw_duplex(&self, keep: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_raw_duplex(keep);
+    }
+
+    /// Whether Duplex opens on the overview of every user instead of the first one
+    pub fn duplex_overview(&self) -> bool {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_duplex_overview()
+    }
+
+    pub fn set_duplex_overview(&self, overview: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_duplex_overview(overview);
+    }
+
+    /// Whether Duplex runs every [schedule_minutes](Self::schedule_minutes) while idle
+    pub fn schedule(&self) -> bool {
+        self.schedule
+            .read()
+            .expect("Failed to get schedule read lock")
+            .0
+    }
+
+    pub fn set_schedule(&self, schedule: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_schedule(schedule);
+        self.schedule
+            .write()
+            .expect("Failed to get schedule write lock")
+            .0 = schedule;
+    }
+
+    pub fn schedule_minutes(&self) -> i64 {
+        self.schedule
+            .read()
+            .expect("Failed to get schedule read lock")
+            .1
+    }
+
+    pub fn set_schedule_minutes(&self, minutes: i64) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_schedule_minutes(minutes);
+        self.schedule
+            .write()
+            .expect("Failed to get schedule write lock")
+            .1 = minutes;
+    }
+
+    /// End of the latest range a full Duplex run covered, scheduled runs pick up from here
+    pub fn last_run_end(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.schedule
+            .read()
+            .expect("Failed to get schedule read lock")
+            .2
+    }
+
+    /// Records the end of a full run's range.  It only moves forward, so a run over a past range
+    /// doesn't send scheduled runs back to re-pull it.
+    pub fn set_last_run_end(&self, end: chrono::DateTime<chrono::Utc>) {
+        let mut schedule = self
+            .schedule
+            .write()
+            .expect("Failed to get schedule write lock");
+        if schedule.2.is_some_and(|last| last >= end) {
+            return;
+        }
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_last_run_end(end);
+        schedule.2 = Some(end);
+    }
+
+    /// Whether Simplex keeps each login's raw event for viewing
+    pub fn raw_simplex(&self) -> bool {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_raw_simplex()
+    }
+
+    pub fn set_raw_simplex(&self, keep: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_raw_simplex(keep);
+    }
+
+    /// ASNs whose logins aren't scored for impossible travel
+    pub fn trusted_asns(&self) -> Vec<String> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_trusted_asns()
+    }
+
+    pub fn add_trusted_asn(&self, asn: &str) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.add_trusted_asn(asn);
+    }
+
+    pub fn remove_trusted_asn(&self, asn: &str) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.remove_trusted_asn(asn);
+    }
+
+    /// IPs whose logins aren't scored for failures or impossible travel
+    pub fn ip_allowlist(&self) -> Vec<Ipv4Addr> {
+        self.ip_allowlist
+            .read()
+            .expect("Failed to get ip_allowlist read lock")
+            .clone()
+    }
+
+    pub fn is_ip_allowlisted(&self, ip: Ipv4Addr) -> bool {
+        self.ip_allowlist
+            .read()
+            .expect("Failed to get ip_allowlist read lock")
+            .contains(&ip)
+    }
+
+    pub fn add_allowlisted_ip(&self, ip: Ipv4Addr) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.add_allowlisted_ip(ip);
+        let mut ip_allowlist = self
+            .ip_allowlist
+            .write()
+            .expect("Failed to get ip_allowlist write lock");
+        if !ip_allowlist.contains(&ip) {
+            ip_allowlist.push(ip);
+        }
+    }
+
+    pub fn remove_allowlisted_ip(&self, ip: Ipv4Addr) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.remove_allowlisted_ip(ip);
+        self.ip_allowlist
+            .write()
+            .expect("Failed to get ip_allowlist write lock")
+            .retain(|i| *i != ip);
+    }
+
+    /// IP ranges whose logins aren't scored for failures or impossible travel
+    pub fn ip_ranges(&self) -> Vec<IpRange> {
+        self.ip_ranges
+            .read()
+            .expect("Failed to get ip_ranges read lock")
+            .clone()
+    }
+
+    /// Trusted range the IP falls in, if any
+    pub fn trusted_range(&self, ip: Ipv4Addr) -> Option<IpRange> {
+        self.ip_ranges
+            .read()
+            .expect("Failed to get ip_ranges read lock")
+            .iter()
+            .find(|r| r.contains(ip))
+            .copied()
+    }
+
+    pub fn add_ip_range(&self, range: IpRange) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.add_ip_range(range);
+        let mut ip_ranges = self
+            .ip_ranges
+            .write()
+            .expect("Failed to get ip_ranges write lock");
+        if !ip_ranges.contains(&range) {
+            ip_ranges.push(range);
+            ip_ranges.sort();
+        }
+    }
+
+    pub fn remove_ip_range(&self, range: IpRange) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.remove_ip_range(range);
+        self.ip_ranges
+            .write()
+            .expect("Failed to get ip_ranges write lock")
+            .retain(|r| *r != range);
+    }
+
+    /// Trusted ASNs, IPs, and IP ranges for the first vibe check
+    fn allowlist(&self) -> Allowlist {
+        Allowlist {
+            asns: self.trusted_asns(),
+            ips: self.ip_allowlist(),
+            ranges: self.ip_ranges(),
+        }
+    }
+
+    /// Reruns the first vibe check on a user's loaded logins with the current settings, returns
+    /// true if the user now passes
+    pub fn rescore_user(&self, user: &mut User) -> bool {
+        info!("Rescoring {}", user.name);
+        let scoring = self.scoring();
+        if scoring.flag_relays {
+            let storage = self.storage.lock().expect("Failed to get storage lock");
+            mark_threat_relays(&storage, &mut user.logins);
+        }
+        user.first_vibe_check(&self.allowlist(), &scoring)
+    }
+
+    /// Saves the disposition of one of a user's fraud reports, or forgets it if [None].  The user
+    /// still needs rescoring.
+    pub fn set_fraud_disposition(
+        &self,
+        user: &mut User,
+        login: usize,
+        disposition: Option<FraudDisposition>,
+    ) {
+        let login = &mut user.logins[login];
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_fraud_disposition(&user.name, login.time.timestamp_millis(), disposition);
+        login.fraud_disposition = disposition;
+    }
+
+    /// Saves the coordinates of an IP, or forgets them if [None], and moves the user's logins
+    /// from it.  Other loaded users pick it up on a location re-check.  The user still needs
+    /// rescoring.
+    pub fn set_location_override(
+        &self,
+        user: &mut User,
+        ip: Ipv4Addr,
+        location: Option<(f32, f32)>,
+    ) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_location_override(ip, location);
+        let overrides = storage.get_location_overrides();
+        override_locations(&self.queries.splunk, &overrides, &mut user.logins);
+    }
+
+    /// Thresholds for the first vibe check
+    pub fn scoring(&self) -> Scoring {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_scoring()
+    }
+
+    pub fn set_scoring(&self, scoring: Scoring) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_scoring(scoring);
+    }
+
+    /// Timezone Splunk's timestamps are read in
+    pub fn splunk_zone(&self) -> Zone {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_splunk_zone()
+    }
+
+    /// Only applies to logs pulled after it is set
+    pub fn set_splunk_zone(&self, zone: Zone) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_splunk_zone(zone);
+        self.queries.splunk.set_zone(zone);
+    }
+
+    /// Login pulls longer than this many hours run as a Splunk search job
+    pub fn job_hours(&self) -> i64 {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_job_hours()
+    }
+
+    pub fn set_job_hours(&self, hours: i64) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_job_hours(hours);
+        self.queries
+            .splunk
+            .set_job_threshold(Duration::hours(hours));
+    }
+
+    /// Connect and read timeouts for each query source, used from the next login
+    pub fn timeouts(&self) -> TimeoutConfig {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_timeouts()
+    }
+
+    pub fn set_timeouts(&self, timeouts: &TimeoutConfig) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_timeouts(timeouts);
+    }
+
+    /// Events each Sonar search reads
+    pub fn sonar_limit(&self) -> usize {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_sonar_limit()
+    }
+
+    pub fn set_sonar_limit(&self, limit: usize) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_sonar_limit(limit);
+        self.queries.splunk.set_sonar_limit(limit);
+    }
+
+    /// Unknown Duo results counted as failures or fraud
+    pub fn result_map(&self) -> ResultMap {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_result_map()
+    }
+
+    /// Only applies to logs pulled after it is set
+    pub fn set_result_map(&self, map: ResultMap) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_result_map(&map);
+        self.queries.splunk.set_result_map(map);
+    }
+
+    /// Timezone times are shown in
+    pub fn display_zone(&self) -> Zone {
+        *self
+            .display_zone
+            .read()
+            .expect("Failed to get display_zone read lock")
+    }
+
+    pub fn set_display_zone(&self, zone: Zone) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_display_zone(zone);
+        *self
+            .display_zone
+            .write()
+            .expect("Failed to get display_zone write lock") = zone;
+    }
+
+    pub fn keymap(&self) -> Keymap {
+        self.keymap
+            .read()
+            .expect("Failed to get keymap read lock")
+            .clone()
+    }
+
+    pub fn set_keymap(&self, keymap: Keymap) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_keymap(&keymap);
+        *self
+            .keymap
+            .write()
+            .expect("Failed to get keymap write lock") = keymap;
+    }
+
+    pub fn ui_scale(&self) -> f32 {
+        *self
+            .ui_scale
+            .read()
+            .expect("Failed to get ui_scale read lock")
+    }
+
+    pub fn set_ui_scale(&self, scale: f32) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_ui_scale(scale);
+        *self
+            .ui_scale
+            .write()
+            .expect("Failed to get ui_scale write lock") = scale;
+    }
+
+    pub fn theme(&self) -> ThemeKind {
+        *self.theme.read().expect("Failed to get theme read lock")
     }
 
-    pub fn mark_investigated(&self, user: String, mark: bool) {
+    pub fn set_theme(&self, theme: ThemeKind) {
         let storage = self.storage.lock().expect("Failed to get storage lock");
-        storage.mark_investigated(user, mark);
+        storage.set_theme(theme);
+        *self.theme.write().expect("Failed to get theme write lock") = theme;
+    }
+
+    /// Whether usernames and IPs are shown as pseudonyms for screenshots and demos
+    pub fn redact(&self) -> Redact {
+        *self.redact.read().expect("Failed to get redact read lock")
+    }
+
+    pub fn set_redact(&self, redact: Redact) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_redact(redact);
+        *self
+            .redact
+            .write()
+            .expect("Failed to get redact write lock") = redact;
+    }
+
+    pub fn panel_layout(&self) -> Vec<PanelLayout> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_panel_layout()
+    }
+
+    pub fn set_panel_open(&self, name: &str, open: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_panel_open(name, open);
+    }
+
+    pub fn set_panel_rect(&self, name: &str, rect: egui::Rect) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_panel_rect(name, rect);
+    }
+
+    pub fn clear_panel_rects(&self) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.clear_panel_rects();
     }
 
     pub fn analyst_name(&self) -> &str {
@@ -241,13 +959,15 @@ impl Store {
         self.queries.hdtools.is_some()
     }
 
-    pub fn get_ipthreat(&self, ip: Ipv4Addr) -> Option<IpThreat> {
+    /// Gets threat info for an IP from the cache or ipdata.co.  Only IPs that ipdata.co has
+    /// nothing on are remembered as failed, anything else is left to be retried.
+    pub fn get_ipthreat(&self, ip: Ipv4Addr) -> Result<IpThreat, QueryError> {
         let storage = self.storage.lock().expect("Failed to get storage lock");
         let ipthreat = storage.get_threat(ip);
         drop(storage);
 
-        if ipthreat.is_some() {
-            return ipthreat;
+        if let Some(ipthreat) = ipthreat {
+            return Ok(ipthreat);
         }
 
         if self
@@ -256,19 +976,24 @@ impl Store {
             .expect("Failed to get failed_ips read lock")
             .contains(&ip)
         {
-            return None;
+            return Err(QueryError::NotFound);
         }
 
-        if let Some(ipthreat) = self.queries.ipq.get_threat(ip) {
-            let storage = self.storage.lock().expect("Failed to get storage lock");
-            storage.add_threat(ip, ipthreat.clone());
-            Some(ipthreat)
-        } else {
-            self.failed_ips
-                .write()
-                .expect("Failed to get failed_ips write lock")
-                .push(ip);
-            None
+        match self.queries.ipq.get_threat(ip) {
+            Ok(ipthreat) => {
+                let storage = self.storage.lock().expect("Failed to get storage lock");
+                storage.add_threat(ip, ipthreat.clone());
+                Ok(ipthreat)
+            }
+            Err(e) => {
+                if e == QueryError::NotFound {
+                    self.failed_ips
+                        .write()
+                        .expect("Failed to get failed_ips write lock")
+                        .push(ip);
+                }
+                Err(e)
+            }
         }
     }
 
@@ -276,63 +1001,95 @@ impl Store {
 
     /// Main lööp of Simplex.  This will query the user's logs from Splunk and fetch their HDTools
     /// information, if available.
-    pub fn run_simplex(&self, user: String, days: i64) -> JoinHandle<Option<User>> {
+    pub fn run_simplex(
+        &self,
+        user: String,
+        timespan: TimeSpan,
+    ) -> JoinHandle<Result<User, QueryError>> {
         info!("Running Simplex");
         let splunk = Arc::clone(&self.queries.splunk);
         let hdtools = self.queries.hdtools.as_ref().map(Arc::clone);
         let storage = Arc::clone(&self.storage);
+        let lookback = self.scoring().max_impossible_travel_time();
+        let keep_raw = self.raw_simplex();
+        let user = canonical_name(&user);
         thread::spawn(move || {
-            let timespan: TimeSpan = Duration::days(days).into();
-            let logins = splunk.get_user_logins(user.as_str(), &timespan).ok()?;
-            let mut user = User::new(
-                user,
-                logins,
-                &(chrono::Local::now().naive_local() - Duration::days(days)),
-            );
+            let logins = splunk.get_user_logins(user.as_str(), &timespan, keep_raw)?;
+            let mut user = User::new(user, logins, &timespan.start, lookback);
+            // Logins are still worth showing without them
+            match splunk.get_admin_events(&user.name, &timespan) {
+                Ok(events) => user.admin_events = events,
+                Err(e) => warn!("Couldn't pull admin events for {}: {}", user.name, e),
+            }
 
             let storage = storage.lock().expect("Failed to get storage lock");
-            if let Some((creation_date, location)) = storage.get_hdtools(&user.name) {
-                user.creation_date = Some(creation_date);
-                user.location = location;
+            load_fraud_dispositions(&storage, &user.name, &mut user.logins);
+            override_locations(&splunk, &storage.get_location_overrides(), &mut user.logins);
+            if let Some(mut info) = storage.get_hdtools(&user.name) {
+                locate_home(&splunk, &storage, &user.name, &mut info);
+                user.set_hdtools(info);
             }
             if user.creation_date.is_none() || user.location.is_none() {
                 if let Some(hdtool) = hdtools {
-                    if let Some((creation_date, location)) = hdtool.get_info(&user.name) {
-                        storage.add_hdtools(&user.name, (creation_date, location.to_owned()));
+                    let result = hdtool.get_info(&user.name);
+                    user.hdtools_status = HDToolsStatus::new(&result);
+                    if let Ok(mut info) = result {
+                        storage.add_hdtools(&user.name, info.clone());
+                        locate_home(&splunk, &storage, &user.name, &mut info);
                         drop(storage);
 
-                        user.creation_date = Some(creation_date);
-                        user.location = location;
+                        user.set_hdtools(info);
                     }
                 }
             }
-            Some(user)
+            Ok(user)
         })
     }
 
+    /// Splunk searches [run_simplex](Self::run_simplex) runs with the same arguments, for running
+    /// them by hand
+    pub fn simplex_searches(&self, user: &str, timespan: &TimeSpan) -> Vec<Search> {
+        vec![
+            self.queries.splunk.user_logins_search(user, timespan),
+            self.queries.splunk.admin_events_search(user, timespan),
+        ]
+    }
+
     // -------------------- Visor --------------------
 
     /// Main lööp of Visor.  Will pull VPN logs from Splunk and try to correlate
-    pub fn run_visor(&self, user: String) -> JoinHandle<Option<Vec<VpnLog>>> {
+    pub fn run_visor(&self, user: String) -> JoinHandle<Result<Vec<VpnLog>, QueryError>> {
         info!("Running Visor");
         let splunk = Arc::clone(&self.queries.splunk);
         thread::spawn(move || {
             let timespan: TimeSpan = Duration::days(7).into();
-            let mut vpn_logs = splunk.get_user_vpn(user.as_str(), timespan).ok();
+            let mut vpn_logs = splunk.get_user_vpn(user.as_str(), timespan)?;
 
-            if let Some(ref mut vpn_logs) = vpn_logs {
-                Splunk::correlate_vpn_logs(vpn_logs);
-            }
+            Splunk::correlate_vpn_logs(&mut vpn_logs);
 
-            vpn_logs
+            Ok(vpn_logs)
         })
     }
 
     // -------------------- Sonar --------------------
 
+    /// Splunk searches a Sonar lookup of `lookup` starts with, for running them by hand
+    pub fn sonar_searches(&self, lookup: &str) -> Vec<Search> {
+        self.queries.splunk.sonar_searches(lookup)
+    }
+
     /// Main lööp of Sonar.  Runs two rounds of querying Splunk using IP/MAC/user to find more
     /// IPs/MACs/users.  Takes forever which is why I made the UI update as more things are found.
-    pub fn run_sonar(&self, lookup: String, details: &Arc<RwLock<crate::app::sonar::Details>>) {
+    pub fn run_sonar(&self, lookup: String, details: &Arc<RwLock<Details>>) {
+        /// Passes a query's result through, counting it as finished
+        fn counted<T>(details: &RwLock<Details>, result: T) -> T {
+            details
+                .write()
+                .expect("Failed to get details write lock")
+                .finish_query();
+            result
+        }
+
         info!("Running Sonar");
         let details = Arc::clone(details);
         let splunk = Arc::clone(&self.queries.splunk);
@@ -340,6 +1097,7 @@ impl Store {
             {
                 let mut details = details.write().expect("Failed to get details write lock");
                 details.running = true;
+                details.started = Some(std::time::Instant::now());
             }
 
             let mut ips: Vec<Ipv4Addr> = vec![];
@@ -365,11 +1123,18 @@ impl Store {
             }
 
             // Run twice to grab everything
-            for _ in 0..2 {
+            const ROUNDS: usize = 2;
+            for round in 0..ROUNDS {
+                {
+                    let queries = Details::round_queries(ips.len(), macs.len(), user.is_some());
+                    let mut details = details.write().expect("Failed to get details write lock");
+                    details.plan((ROUNDS - round) * queries);
+                }
+
                 // Find IPs
                 for mac in &macs {
                     info!("Looking up IP from MAC");
-                    if let Some(ip) = splunk.get_ip_from_mac(mac) {
+                    if let Ok(ip) = counted(&details, splunk.get_ip_from_mac(mac)) {
                         if ips.contains(&ip) {
                             continue;
                         }
@@ -381,7 +1146,7 @@ impl Store {
                 }
                 if let Some(user) = &user {
                     info!("Looking up IP from user");
-                    if let Some(ip) = splunk.get_ip_from_user(user) {
+                    if let Ok(ip) = counted(&details, splunk.get_ip_from_user(user)) {
                         if ips.contains(&ip) {
                             continue;
                         }
@@ -395,7 +1160,7 @@ impl Store {
                 // Find MACs
                 for ip in &ips {
                     info!("Looking up MAC from IP");
-                    if let Some(found_macs) = splunk.get_mac_from_ip(*ip) {
+                    if let Ok(found_macs) = counted(&details, splunk.get_mac_from_ip(*ip)) {
                         for mac in found_macs {
                             if macs.contains(&mac) {
                                 continue;
@@ -409,7 +1174,7 @@ impl Store {
                 }
                 if let Some(user) = &user {
                     info!("Looking up MAC from user");
-                    if let Some(found_macs) = splunk.get_mac_from_user(user) {
+                    if let Ok(found_macs) = counted(&details, splunk.get_mac_from_user(user)) {
                         for mac in found_macs {
                             if macs.contains(&mac) {
                                 continue;
@@ -426,7 +1191,7 @@ impl Store {
                 if user.is_none() {
                     for ip in &ips {
                         info!("Looking up user from IP");
-                        if let Some(user) = splunk.get_user_from_ip(*ip) {
+                        if let Ok(user) = counted(&details, splunk.get_user_from_ip(*ip)) {
                             let mut details =
                                 details.write().expect("Failed to get details write lock");
                             details.user = Some(user);
@@ -434,7 +1199,7 @@ impl Store {
                     }
                     for mac in &macs {
                         info!("Looking up user from MAC");
-                        if let Some(user) = splunk.get_user_from_mac(mac) {
+                        if let Ok(user) = counted(&details, splunk.get_user_from_mac(mac)) {
                             let mut details =
                                 details.write().expect("Failed to get details write lock");
                             details.user = Some(user);
@@ -452,30 +1217,164 @@ impl Store {
 
     // -------------------- Zeppelin --------------------
 
-    /// Pulls date's [Data](osiris::Data) from Osiris
-    pub fn run_zeppelin(&self, date: NaiveDate) -> JoinHandle<Option<osiris::Data>> {
+    /// Pulls date's [Data](osiris::Data) from Osiris as it has it, shown with
+    /// [category_aliases](Self::category_aliases) applied
+    pub fn run_zeppelin(&self, date: NaiveDate) -> JoinHandle<Result<osiris::Data, QueryError>> {
         let osiris = Arc::clone(&self.queries.osiris);
         thread::spawn(move || osiris.get_date(date))
     }
 
+    /// New names of renamed Osiris categories, keyed by the name Osiris has
+    pub fn category_aliases(&self) -> HashMap<String, String> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_category_aliases()
+    }
+
+    /// Shows and reports an Osiris category under a new name, see [osiris::Data::alias].  New
+    /// counts are sent under the new name, and old ones are added to it.
+    pub fn rename_category(&self, old: &str, new: &str) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.rename_category(old, new);
+    }
+
+    /// Osiris categories Zeppelin hides unless asked
+    pub fn archived_categories(&self) -> Vec<String> {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.get_archived_categories()
+    }
+
+    pub fn set_category_archived(&self, name: &str, archived: bool) {
+        let storage = self.storage.lock().expect("Failed to get storage lock");
+        storage.set_category_archived(name, archived);
+    }
+
     /// Sends data for a date to Osiris
-    pub fn post_osiris(&self, date: NaiveDate, data: osiris::Data) -> JoinHandle<Option<()>> {
+    pub fn post_osiris(
+        &self,
+        date: NaiveDate,
+        data: osiris::Data,
+    ) -> JoinHandle<Result<(), QueryError>> {
         let osiris = Arc::clone(&self.queries.osiris);
         thread::spawn(move || osiris.post_date(date, data))
     }
 
-    /// Pulls data for a date range and writes it to CSV file.  No, I do not apologize for using
-    /// `.join(", ")` instead of finding a better way to do it.
-    pub fn save_report(&self, file: String, range: (NaiveDate, NaiveDate)) -> JoinHandle<()> {
+    /// Writes everything cached about the given IPs, or every cached IP, to a file.  The file is
+    /// JSON if it ends in `.json`, otherwise it's CSV.  IPs with nothing cached are left out.
+    pub fn save_ip_enrichment(&self, file: String, ips: Option<Vec<Ipv4Addr>>) -> JoinHandle<()> {
+        let storage = Arc::clone(&self.storage);
+        thread::spawn(move || {
+            info!("Saving IP enrichment to {}", file);
+            let enrichment: Vec<IpEnrichment> = {
+                let storage = storage.lock().expect("Couldn't get storage lock");
+                ips.unwrap_or_else(|| storage.get_enriched_ips())
+                    .into_iter()
+                    .map(|ip| IpEnrichment {
+                        ip,
+                        info: storage.get_ipinfo(ip),
+                        threat: storage.get_threat(ip),
+                    })
+                    .filter(|e| e.info.is_some() || e.threat.is_some())
+                    .collect()
+            };
+
+            info!("Got {} enriched IPs", enrichment.len());
+
+            let output = if file.ends_with(".json") {
+                match serde_json::to_string_pretty(&enrichment) {
+                    Ok(output) => output,
+                    Err(e) => {
+                        log::error!("Couldn't serialize IP enrichment: {}", e);
+                        return;
+                    }
+                }
+            } else {
+                let mut output = vec![IpEnrichment::CSV_HEADER.join(",")];
+                output.extend(enrichment.iter().map(|e| e.csv_row()));
+                output.join("\n")
+            };
+
+            if std::fs::write(file, output).is_ok() {
+                info!("Wrote to file");
+            } else {
+                log::error!("Failed to write to file");
+            };
+        })
+    }
+
+    /// Writes the user's logins at `rows` to a file, as the tables show them.  The file is JSON if
+    /// it ends in `.json`, otherwise it's CSV.  Gives how many logins were written.
+    pub fn export_logins(
+        &self,
+        user: &User,
+        rows: &[usize],
+        file: String,
+    ) -> JoinHandle<Result<usize, String>> {
+        let logins: Vec<Login> = rows.iter().map(|&i| user.logins[i].clone()).collect();
+        let zone = self.display_zone();
+        let name = user.name.to_owned();
+        thread::spawn(move || {
+            info!("Exporting {} logins of {} to {}", logins.len(), name, file);
+            let records: Vec<LoginRecord> =
+                logins.iter().map(|l| LoginRecord::new(l, zone)).collect();
+
+            let output = if file.ends_with(".json") {
+                serde_json::to_string_pretty(&records).map_err(|e| {
+                    log::error!("Couldn't serialize logins: {}", e);
+                    e.to_string()
+                })?
+            } else {
+                let mut output = vec![LoginRecord::CSV_HEADER.join(",")];
+                output.extend(records.iter().map(|r| r.csv_row()));
+                output.join("\n")
+            };
+
+            std::fs::write(&file, output).map_err(|e| {
+                log::error!("Failed to write {}: {}", file, e);
+                e.to_string()
+            })?;
+            Ok(records.len())
+        })
+    }
+
+    /// Writes a run report from the Duplex done screen to a file
+    pub fn save_run_report(&self, file: String, report: String) -> JoinHandle<Result<(), String>> {
+        thread::spawn(move || {
+            info!("Saving run report to {}", file);
+            std::fs::write(&file, report).map_err(|e| {
+                log::error!("Failed to write {}: {}", file, e);
+                e.to_string()
+            })
+        })
+    }
+
+    /// Pulls data for a date range and writes it to CSV file, oldest first, with a row for each
+    /// day or rolled up by week or month.  Gives back how many rows Osiris sent without a date,
+    /// which aren't in the file.  No, I do not apologize for using `.join(", ")` instead of
+    /// finding a better way to do it.
+    pub fn save_report(
+        &self,
+        file: String,
+        range: (NaiveDate, NaiveDate),
+        aggregation: osiris::Aggregation,
+    ) -> JoinHandle<Result<usize, String>> {
         let osiris = Arc::clone(&self.queries.osiris);
+        let aliases = self.category_aliases();
         thread::spawn(move || {
             info!("Saving Osiris to {}", file);
-            let data = match osiris.get() {
-                Some(data) => data,
-                None => return,
+            // Renamed categories get one column instead of one for each name
+            let data: Vec<_> = match osiris.get() {
+                Ok(data) => data
+                    .into_iter()
+                    .map(|(time, data)| (time, data.alias(&aliases)))
+                    .collect(),
+                Err(e) => {
+                    log::error!("Couldn't get Osiris data: {}", e);
+                    return Err(e.to_string());
+                }
             };
 
             info!("Got {} lines of data", data.len());
+            let (data, skipped) = osiris::aggregate(data, range, aggregation);
 
             let mut types = vec!["time".to_owned()];
 
@@ -521,11 +1420,131 @@ impl Store {
 
             let output: Vec<String> = output.into_iter().map(|r| r.join(", ")).collect();
 
-            if std::fs::write(file, output.join("\n")).is_ok() {
-                info!("Wrote to file");
-            } else {
-                log::error!("Failed to write to file");
-            };
+            match std::fs::write(file, output.join("\n")) {
+                Ok(()) => {
+                    info!("Wrote to file");
+                    Ok(skipped)
+                }
+                Err(e) => {
+                    log::error!("Failed to write to file: {}", e);
+                    Err(e.to_string())
+                }
+            }
         })
     }
 }
+
+/// Sets the dispositions analysts saved on a user's fraud reports, so false positives aren't
+/// scored again
+fn load_fraud_dispositions(storage: &Storage, name: &str, logins: &mut [Login]) {
+    if !logins.iter().any(|l| l.result == LoginResult::Fraud) {
+        return;
+    }
+    let dispositions = storage.get_fraud_dispositions(name);
+    for login in logins {
+        login.fraud_disposition = dispositions.get(&login.time.timestamp_millis()).copied();
+    }
+}
+
+/// Moves logins to the coordinates analysts set for their IPs.  Logins whose IP no longer has
+/// any go back to the IP database's.
+fn override_locations(
+    splunk: &Splunk,
+    overrides: &HashMap<Ipv4Addr, (f32, f32)>,
+    logins: &mut [Login],
+) {
+    for login in logins {
+        let Some(ip) = login.ip else {
+            continue;
+        };
+        if let Some(location) = overrides.get(&ip) {
+            login.location = Some(*location);
+            login.location_source = Some(LocationSource::Analyst);
+        } else if login.location_source == Some(LocationSource::Analyst) {
+            login.location = splunk.db_location(ip);
+            // Without coordinates the place name came from Duo
+            login.location_source = Some(if login.location.is_some() {
+                LocationSource::Database
+            } else {
+                LocationSource::Duo
+            });
+        }
+    }
+}
+
+/// Marks logins as relays when the cached threat data for their IP says proxy, Tor, or iCloud
+/// Private Relay, which the IP databases can miss.  Nothing is looked up, so only IPs someone
+/// already checked are caught.
+fn mark_threat_relays(storage: &Storage, logins: &mut [Login]) {
+    for login in logins.iter_mut().filter(|l| !l.is_relay) {
+        if let Some(threat) = login.ip.and_then(|ip| storage.select_threat(ip)) {
+            login.is_relay = threat.is_proxy || threat.is_tor || threat.is_icloud_relay;
+        }
+    }
+}
+
+/// Finds the coordinates of a user's home from the IP databases and caches them, skipped if
+/// they're already cached
+fn locate_home(splunk: &Splunk, storage: &Storage, name: &str, info: &mut HDToolsInfo) {
+    if info.home.is_some() {
+        return;
+    }
+    info.home = info.location.as_ref().and_then(|l| splunk.geocode(l));
+    if let Some(home) = info.home {
+        storage.set_hdtools_home(name, home);
+    }
+}
+
+/// The third vibe check, moves each checked login to ipinfo.io's location for its IP when that
+/// correlates better with the surrounding logs.  ipinfo.io is only asked about IPs that aren't
+/// cached.
+fn relocate_logins(storage: &Storage, ipq: &Ip, user: &mut User) {
+    for ip in uncached_ips(storage, user) {
+        if let Ok(ipinfo) = ipq.get_info(ip) {
+            storage.add_ipinfo(ip, ipinfo);
+        }
+    }
+    relocate_cached(storage, user);
+}
+
+/// Checked logins' IPs the third vibe check would look up, which ipinfo.io hasn't been asked
+/// about yet
+fn uncached_ips(storage: &Storage, user: &User) -> HashSet<Ipv4Addr> {
+    user.logins[..user.checked_login_count]
+        .iter()
+        .filter(|l| relocatable(l))
+        .filter_map(|l| l.ip)
+        .filter(|ip| storage.select_ipinfo(*ip).is_none())
+        .collect()
+}
+
+/// Whether the third vibe check may move a login, an analyst's coordinates beat ipinfo.io's
+fn relocatable(login: &Login) -> bool {
+    !login.is_priv_ip()
+        && !login.is_vpn_ip()
+        && login.location_source != Some(LocationSource::Analyst)
+}
+
+/// [relocate_logins] with only what's already cached
+fn relocate_cached(storage: &Storage, user: &mut User) {
+    for i in 0..user.checked_login_count {
+        if !relocatable(&user.logins[i]) {
+            continue;
+        }
+        let Some(ip) = user.logins[i].ip else {
+            continue;
+        };
+        if let Some(ipinfo) = storage.get_ipinfo(ip) {
+            // Updates login location if it correlates better with
+            // surrounding logs
+            if user.closer_to(&ipinfo, i) {
+                info!("Updating log with ip {} for {}", ip, user.name);
+                user.logins[i].location = Some((ipinfo.loc.lat, ipinfo.loc.lon));
+                user.logins[i].country = Some(ipinfo.country);
+                user.logins[i].state = Some(ipinfo.region);
+                user.logins[i].city = Some(ipinfo.city);
+                user.logins[i].location_source = Some(LocationSource::IpInfo);
+            }
+        }
+    }
+}
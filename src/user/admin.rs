@@ -0,0 +1,100 @@
+//! One Duo administrator log
+//!
+//! Besides logins, the Duo index has administrator and self-service events, such as a user
+//! activating a new phone or an admin creating bypass codes.  Next to odd logins, these are a good
+//! sign someone else is setting up their own way in.  See [super::login] for why there's so much
+//! regex.
+use super::zone::Zone;
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use std::sync::OnceLock;
+
+static TIME_RE: OnceLock<Regex> = OnceLock::new();
+static ACTION_RE: OnceLock<Regex> = OnceLock::new();
+static ADMIN_RE: OnceLock<Regex> = OnceLock::new();
+static DESCRIPTION_RE: OnceLock<Regex> = OnceLock::new();
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AdminAction {
+    /// A phone or authenticator was activated, by the user or an admin
+    Activation,
+    /// Bypass codes were created
+    BypassCode,
+    Other(String),
+}
+
+impl std::fmt::Display for AdminAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Activation => "Device activated",
+                Self::BypassCode => "Bypass code created",
+                Self::Other(s) => s,
+            }
+        )
+    }
+}
+
+impl From<&str> for AdminAction {
+    fn from(action: &str) -> Self {
+        match action {
+            "activation_complete" => Self::Activation,
+            "admin_activate_duo_push" => Self::Activation,
+            "bypass_create" => Self::BypassCode,
+            _ => Self::Other(action.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AdminEvent {
+    pub time: DateTime<Utc>,
+    pub action: AdminAction,
+    /// Who did it, the user themselves for self-service
+    pub admin: Option<String>,
+    /// Duo's details, such as which phone was activated
+    pub description: Option<String>,
+}
+
+impl AdminEvent {
+    /// Parses one administrator log, reading its time with [Zone::parse]
+    pub fn new(log: &str, zone: Zone) -> Option<Self> {
+        let log = log.replace('\\', "");
+        let capture = |re: &OnceLock<Regex>, pattern: &str| {
+            re.get_or_init(|| Regex::new(pattern).unwrap())
+                .captures(&log)
+                .map(|c| c[1].to_owned())
+        };
+
+        let time = zone.parse(&capture(&TIME_RE, r#""_time": ?"([^"]+)""#)?)?;
+        let action = capture(&ACTION_RE, r#""action": ?"([^"]+)""#)?;
+        Some(Self {
+            time,
+            action: action.as_str().into(),
+            admin: capture(&ADMIN_RE, r#""username": ?"([^"]+)""#),
+            // Duo sends the description as JSON in a string
+            description: capture(&DESCRIPTION_RE, r#""description": ?"(\{[^}]*\}|[^"]*)""#)
+                .filter(|d| !d.is_empty()),
+        })
+    }
+}
+
+impl PartialOrd for AdminEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for AdminEvent {
+    /// Newest first, like [Login](super::login::Login)
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other
+            .time
+            .cmp(&self.time)
+            .then_with(|| self.action.cmp(&other.action))
+            .then_with(|| self.admin.cmp(&other.admin))
+            .then_with(|| self.description.cmp(&other.description))
+    }
+}
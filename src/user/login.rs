@@ -10,13 +10,21 @@
 //! necessary values.  This has been far more reliable than my original implementation, which did
 //! parse to [serde_json::value](https://docs.rs/serde_json/latest/serde_json/value/index.html). I
 //! love regex, real homies use regex, regex doesn't insult my code or question my decision making.
+//!
+//! That said, regex falls over on fields with nested JSON like `access_device`, and every new
+//! field is another regex.  Lines are now deserialized into a tolerant struct first, and the regex
+//! patterns are only used when that fails.  If regex can't find the user or time either, but the
+//! line is still valid JSON, the missing fields are looked up by path in a
+//! [serde_json::Value](https://docs.rs/serde_json/latest/serde_json/value/index.html) as a last
+//! resort.
+use super::zone::Zone;
 use crate::queries::ip::IpDB;
-use chrono::{Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Utc};
 use log::{debug, warn};
 use regex::Regex;
-use std::{net::Ipv4Addr, sync::OnceLock};
-
-const DATE_FORMAT: &str = "%F %T%.3f %Z";
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{net::Ipv4Addr, ops::Range, sync::OnceLock};
 
 const VPN_IPS: [Ipv4Addr; 3] = [
     Ipv4Addr::new(130, 127, 255, 220),
@@ -32,8 +40,13 @@ static INTEGRATION_RE: OnceLock<Regex> = OnceLock::new();
 static REASON_RE: OnceLock<Regex> = OnceLock::new();
 static RESULT_RE: OnceLock<Regex> = OnceLock::new();
 static IP_RE: OnceLock<Regex> = OnceLock::new();
+static ACCESS_IP_RE: OnceLock<Regex> = OnceLock::new();
+static ACCESS_LOCATION_RE: OnceLock<Regex> = OnceLock::new();
+static CITY_RE: OnceLock<Regex> = OnceLock::new();
+static STATE_RE: OnceLock<Regex> = OnceLock::new();
+static COUNTRY_RE: OnceLock<Regex> = OnceLock::new();
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Integration {
     Shibboleth,
     Citrix,
@@ -92,7 +105,7 @@ impl From<&str> for Integration {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum LoginResult {
     Success,
     Failure,
@@ -128,6 +141,33 @@ impl From<&str> for LoginResult {
     }
 }
 
+/// Results Duo has started logging that a site counts as failures or fraud, edited in Settings.
+/// Duo adds new results over time, and without this they'd never be scored.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ResultMap {
+    pub failure: Vec<String>,
+    pub fraud: Vec<String>,
+}
+
+impl ResultMap {
+    /// What an [Other](LoginResult::Other) result counts as, if it's listed.  Results are matched
+    /// case insensitively, and fraud wins if one is listed as both.
+    pub fn map(&self, result: &LoginResult) -> Option<LoginResult> {
+        let LoginResult::Other(result) = result else {
+            return None;
+        };
+        let listed = |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(result));
+        if listed(&self.fraud) {
+            Some(LoginResult::Fraud)
+        } else if listed(&self.failure) {
+            Some(LoginResult::Failure)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Factor {
     DuoPush,
@@ -247,61 +287,186 @@ impl From<&str> for Reason {
     }
 }
 
+/// Where a login's location came from, they differ a lot in accuracy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LocationSource {
+    /// The embedded IP database
+    Database,
+    /// Duo's own location for the access device, without coordinates
+    Duo,
+    /// ipinfo.io, which moved the login in the third vibe check
+    IpInfo,
+    /// Coordinates an analyst set for the IP
+    Analyst,
+}
+
+impl LocationSource {
+    /// How far the location can be trusted, for hover text
+    pub fn desc(&self) -> &'static str {
+        match self {
+            Self::Database => "From the IP database, often only right to the region",
+            Self::Duo => "From Duo, no coordinates so it isn't used for impossible travel",
+            Self::IpInfo => {
+                "From ipinfo.io, which placed it closer to the user's other logins than the IP database"
+            }
+            Self::Analyst => {
+                "Coordinates set by an analyst for this IP, the place name is still the IP database's"
+            }
+        }
+    }
+}
+
+/// What an analyst found out about a fraud report, saved per login so it outlasts the run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FraudDisposition {
+    /// Waiting to hear back from the user
+    Pending,
+    Confirmed,
+    /// The user hit fraud by accident, so the report isn't scored
+    FalsePositive,
+}
+
+impl FraudDisposition {
+    pub const ALL: [Self; 3] = [Self::Pending, Self::Confirmed, Self::FalsePositive];
+
+    /// Shown next to the result
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Pending => "⏳",
+            Self::Confirmed => "‼",
+            Self::FalsePositive => "✔",
+        }
+    }
+}
+
+impl std::fmt::Display for FraudDisposition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Pending => "Pending",
+                Self::Confirmed => "Confirmed",
+                Self::FalsePositive => "False positive",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for FraudDisposition {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL.into_iter().find(|d| d.to_string() == s).ok_or(())
+    }
+}
+
 /// Represents one duo log
 #[derive(Debug, Clone)]
 pub struct Login {
-    pub time: NaiveDateTime,
+    /// When the log was written, shown with [Zone::to_zone]
+    pub time: DateTime<Utc>,
     pub user: String,
     pub device: Option<String>,
     pub factor: Factor,
     pub integration: Integration,
     pub reason: Reason,
     pub result: LoginResult,
+    /// Result as Duo logged it, when a [ResultMap] turned it into [result](Self::result)
+    pub duo_result: Option<String>,
+    /// Set on fraud reports an analyst has looked into
+    pub fraud_disposition: Option<FraudDisposition>,
     pub ip: Option<Ipv4Addr>,
     pub city: Option<String>,
     pub country: Option<String>,
     pub state: Option<String>,
     pub location: Option<(f32, f32)>,
+    /// Where the location came from, [None] if there isn't one
+    pub location_source: Option<LocationSource>,
     /// True if the IP is an known relay
     pub is_relay: bool,
     /// Service Provider for the IP
     pub asn: Option<String>,
     /// Why the login was flagged
     pub flag_reasons: Vec<FlagReason>,
+    /// Browser of the access device, only found in structured logs
+    pub browser: Option<String>,
+    /// Version of the access device's browser, only found in structured logs
+    pub browser_version: Option<String>,
+    /// OS of the access device, only found in structured logs
+    pub os: Option<String>,
+    /// Version of the access device's OS, only found in structured logs
+    pub os_version: Option<String>,
+    /// Whether Duo considers the access device trusted, [None] if the log doesn't say
+    pub trusted_device: Option<bool>,
+    /// Duo key of the application logged into, only found in structured logs
+    pub application_key: Option<String>,
+    /// Splunk result the login was parsed from, only kept when asked for since it's most of a
+    /// login's size
+    pub raw: Option<String>,
 }
 
 impl PartialOrd for Login {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.time.partial_cmp(&self.time)
+        Some(self.cmp(other))
     }
 }
 
 impl Eq for Login {}
 
+/// Two events in the same second are only the same login when they also came through the same
+/// integration with the same result from the same IP, so a Shibboleth and a CUVPN login at once
+/// both survive a dedup
 impl PartialEq for Login {
     fn eq(&self, other: &Self) -> bool {
-        other.time == self.time && other.user == self.user
+        other.time == self.time
+            && other.user == self.user
+            && other.integration == self.integration
+            && other.result == self.result
+            && other.ip == self.ip
     }
 }
 
+/// Newest first, ties are broken on the fields [PartialEq] looks at so equal logins end up next
+/// to each other after a sort
 impl Ord for Login {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.time.cmp(&self.time)
+        other
+            .time
+            .cmp(&self.time)
+            .then_with(|| self.user.cmp(&other.user))
+            .then_with(|| self.integration.cmp(&other.integration))
+            .then_with(|| self.result.cmp(&other.result))
+            .then_with(|| self.ip.cmp(&other.ip))
     }
 }
 
 impl Login {
     /// Serializes one JSON line of duo logs to a Login.  Returns [None] if there is no username,
     /// or the username is euqal to `System` or has a space in it (gets rid of `API Vault User` and
-    /// such)
-    pub fn new(obj: &str, ipdb: &IpDB) -> Option<Self> {
-        let obj = obj.replace('\\', "");
+    /// such).  The log's time is read with [Zone::parse] and held in UTC.
+    pub fn new(obj: &str, ipdb: &IpDB, zone: Zone) -> Option<Self> {
+        let fields = match LogFields::from_json(obj) {
+            Some(fields) => {
+                debug!("Parsed log with serde");
+                fields
+            }
+            None => {
+                debug!("Couldn't parse log with serde, falling back to regex");
+                let fields = LogFields::from_regex(obj);
+                if fields.user.is_some() && fields.time.is_some() {
+                    fields
+                } else if let Some(value) = LogFields::from_value(obj) {
+                    debug!("Filling in fields regex missed from JSON paths");
+                    fields.or(value)
+                } else {
+                    fields
+                }
+            }
+        };
 
-        let user: String = match USERNAME_RE
-            .get_or_init(|| Regex::new(r#""user": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-        {
-            Some(user) => user[1].to_owned(),
+        let user = match fields.user {
+            Some(user) => user,
             None => {
                 warn!("Couldn't find user: {}", obj);
                 return None;
@@ -311,17 +476,15 @@ impl Login {
         if user.contains(' ') || user == "System" {
             return None;
         }
+        let user = super::canonical_name(&user);
 
         debug!("Parsing log for {}", user);
 
-        let time = match TIME_RE
-            .get_or_init(|| Regex::new(r#""_time": ?"([^"]*)""#).unwrap())
-            .captures(&obj)
-        {
-            Some(cap) => match Local.datetime_from_str(&cap[1], DATE_FORMAT) {
-                Ok(time) => time.with_timezone(&Local).naive_local(),
-                Err(_) => {
-                    warn!("Couldn't parse time of {} for user {}", &cap[1], user);
+        let time = match fields.time {
+            Some(time) => match zone.parse(&time) {
+                Some(time) => time,
+                None => {
+                    warn!("Couldn't parse time of {} for user {}", time, user);
                     return None;
                 }
             },
@@ -330,54 +493,39 @@ impl Login {
             }
         };
 
-        let device = DEVICE_RE
-            .get_or_init(|| Regex::new(r#""device": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .map(|c| c[1].to_owned());
-
-        let factor = FACTOR_RE
-            .get_or_init(|| Regex::new(r#""factor": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .map_or(Factor::None, |c| c[1].into());
-
-        let integration = INTEGRATION_RE
-            .get_or_init(|| Regex::new(r#""integration": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .map_or(Integration::None, |c| c[1].into());
-
-        let reason = REASON_RE
-            .get_or_init(|| Regex::new(r#""reason": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .map_or(Reason::None, |c| c[1].into());
-
-        let result = RESULT_RE
-            .get_or_init(|| Regex::new(r#""result": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .map_or(LoginResult::None, |c| c[1].into());
-
-        let ip = IP_RE
-            .get_or_init(|| Regex::new(r#""ip": ?"([^"]+)""#).unwrap())
-            .captures(&obj)
-            .and_then(|c| {
-                c[1].parse().ok().or_else(|| {
-                    let ip = c[1].to_string();
-                    if ip == "localhost" {
-                        Some(Ipv4Addr::LOCALHOST)
-                    } else {
-                        // Try to parse from hostname
-                        match ip.split('.').next() {
-                            Some(ip) => ip.replace('-', ".").parse().ok(),
-                            None => {
-                                warn!("Couldn't parse ip for user {}: {}", user, ip);
-                                None
-                            }
+        let device = fields.device;
+
+        let factor = fields.factor.map_or(Factor::None, |f| f.as_str().into());
+
+        let integration = fields
+            .integration
+            .map_or(Integration::None, |i| i.as_str().into());
+
+        let reason = fields.reason.map_or(Reason::None, |r| r.as_str().into());
+
+        let result = fields
+            .result
+            .map_or(LoginResult::None, |r| r.as_str().into());
+
+        let ip = fields.ip.and_then(|ip| {
+            ip.parse().ok().or_else(|| {
+                if ip == "localhost" {
+                    Some(Ipv4Addr::LOCALHOST)
+                } else {
+                    // Try to parse from hostname
+                    match ip.split('.').next() {
+                        Some(ip) => ip.replace('-', ".").parse().ok(),
+                        None => {
+                            warn!("Couldn't parse ip for user {}: {}", user, ip);
+                            None
                         }
                     }
-                })
-            });
+                }
+            })
+        });
 
-        let (mut country, mut state, mut city, mut location, mut asn) =
-            (None, None, None, None, None);
+        let (mut country, mut state, mut city, mut location, mut location_source, mut asn) =
+            (None, None, None, None, None, None);
         let mut is_relay = false;
         if let Some(ip) = ip {
             if let Some(iploc) = ipdb.get_iploc(ip) {
@@ -385,10 +533,27 @@ impl Login {
                 state = iploc.state.to_owned();
                 city = iploc.city.to_owned();
                 location = Some((iploc.lat, iploc.lon));
+                location_source = Some(LocationSource::Database);
             }
             is_relay = ipdb.is_proxy(ip);
             asn = ipdb.get_asn(ip).cloned();
         }
+        // Duo's own guess at where the access device was, when there's no IP or the IP databases
+        // don't know it.  It has no coordinates, so it can't be used for travel speeds.
+        if country.is_none() {
+            country = fields.country.map(|c| {
+                if super::US_NAMES.contains(&c.to_lowercase().as_str()) {
+                    "US".to_owned()
+                } else {
+                    c
+                }
+            });
+            state = fields.state;
+            city = fields.city;
+            if country.is_some() {
+                location_source = Some(LocationSource::Duo);
+            }
+        }
 
         Some(Login {
             city,
@@ -398,17 +563,49 @@ impl Login {
             integration,
             ip,
             location,
+            location_source,
             reason,
             result,
+            duo_result: None,
+            fraud_disposition: None,
             state,
             time,
             user,
             is_relay,
             asn,
             flag_reasons: vec![],
+            browser: fields.browser,
+            browser_version: fields.browser_version,
+            os: fields.os,
+            os_version: fields.os_version,
+            trusted_device: fields.trusted_device,
+            application_key: fields.application_key,
+            raw: None,
         })
     }
 
+    /// Counts an unknown result as a failure or fraud if `map` lists it, keeping what Duo logged
+    pub fn map_result(&mut self, map: &ResultMap) {
+        if let Some(result) = map.map(&self.result) {
+            self.duo_result = Some(self.result.to_string());
+            self.result = result;
+        }
+    }
+
+    /// Fraud reports that count against the user, every one not marked a false positive
+    pub fn is_scored_fraud(&self) -> bool {
+        self.result == LoginResult::Fraud
+            && self.fraud_disposition != Some(FraudDisposition::FalsePositive)
+    }
+
+    /// Result to show, what Duo logged if it was mapped
+    pub fn format_result(&self) -> String {
+        match &self.duo_result {
+            Some(result) => result.to_owned(),
+            None => self.result.to_string(),
+        }
+    }
+
     pub fn is_vpn_ip(&self) -> bool {
         if let Some(ip) = &self.ip {
             if VPN_IPS.contains(ip) {
@@ -432,6 +629,64 @@ impl Login {
         }
     }
 
+    /// True if the login's IP is on the allowlist or in a trusted range
+    pub fn is_allowlisted_ip(&self, allowlist: &super::Allowlist) -> bool {
+        self.ip.is_some_and(|ip| allowlist.contains(ip))
+    }
+
+    /// True if the login's ASN is on the trusted list
+    pub fn is_trusted_asn(&self, trusted_asns: &[String]) -> bool {
+        self.asn
+            .as_ref()
+            .is_some_and(|asn| trusted_asns.iter().any(|t| t.eq_ignore_ascii_case(asn)))
+    }
+
+    /// Browser and OS of the access device, such as `Chrome on Windows`
+    pub fn format_device(&self) -> Option<String> {
+        match (&self.browser, &self.os) {
+            (Some(browser), Some(os)) => Some(format!("{} on {}", browser, os)),
+            (Some(device), None) | (None, Some(device)) => Some(device.to_owned()),
+            (None, None) => None,
+        }
+    }
+
+    /// Location and access device for Cherwell templates
+    pub fn format_source(&self) -> String {
+        let location = self.format_location().unwrap_or_else(|| "CUVPN".to_owned());
+        match self.format_device() {
+            Some(device) => format!("{} using {}", location, device),
+            None => location,
+        }
+    }
+
+    /// Where the login came from, for grouping logins by place.  VPN and campus logins each get
+    /// their own group.
+    pub fn summary_location(&self) -> String {
+        if self.is_vpn_ip() {
+            "VPN".to_owned()
+        } else if self.is_priv_ip() {
+            "Campus".to_owned()
+        } else {
+            self.format_location()
+                .unwrap_or_else(|| "Unknown".to_owned())
+        }
+    }
+
+    /// The [raw](Self::raw) event pretty printed, with the Duo log inside it unpacked when it's
+    /// JSON.  Results that aren't JSON, like cut off lines, are left as they are.
+    pub fn raw_pretty(&self) -> Option<String> {
+        let raw = self.raw.as_ref()?;
+        let Ok(mut value) = serde_json::from_str::<Value>(raw) else {
+            return Some(raw.to_owned());
+        };
+        if let Some(log) = value.pointer_mut("/result/_raw") {
+            if let Some(parsed) = log.as_str().and_then(|l| serde_json::from_str(l).ok()) {
+                *log = parsed;
+            }
+        }
+        Some(serde_json::to_string_pretty(&value).unwrap_or_else(|_| raw.to_owned()))
+    }
+
     pub fn format_location(&self) -> Option<String> {
         if self.is_vpn_ip() {
             return Some("VPN".to_owned());
@@ -449,6 +704,372 @@ impl Login {
     }
 }
 
+/// Runs of consecutive logins that would look the same in a table, as ranges of `rows`, which
+/// index into `logins`.  A run shares its result, reason, factor, integration, and IP, like the
+/// unanswered pushes of an MFA fatigue attack.  Flagged logins never join a run so they always
+/// get their own row, and runs of one are left out.
+pub fn repeat_runs(logins: &[Login], rows: &[usize]) -> Vec<Range<usize>> {
+    let same = |a: &Login, b: &Login| {
+        a.result == b.result
+            && a.reason == b.reason
+            && a.factor == b.factor
+            && a.integration == b.integration
+            && a.ip == b.ip
+    };
+    let mut runs = vec![];
+    let mut start = 0;
+    for i in 1..=rows.len() {
+        let continues = i < rows.len() && {
+            let (prev, login) = (&logins[rows[i - 1]], &logins[rows[i]]);
+            prev.flag_reasons.is_empty() && login.flag_reasons.is_empty() && same(prev, login)
+        };
+        if !continues {
+            if i - start > 1 {
+                runs.push(start..i);
+            }
+            start = i;
+        }
+    }
+    runs
+}
+
+/// A login as the tables show it, for handing raw evidence to another team
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LoginRecord {
+    /// In the display zone, with its abbreviation
+    pub time: String,
+    pub result: String,
+    pub reason: String,
+    pub factor: String,
+    pub integration: String,
+    pub ip: Option<Ipv4Addr>,
+    pub asn: Option<String>,
+    pub location: Option<String>,
+    pub flag_reasons: Vec<String>,
+}
+
+impl LoginRecord {
+    pub const CSV_HEADER: [&'static str; 9] = [
+        "time",
+        "result",
+        "reason",
+        "factor",
+        "integration",
+        "ip",
+        "asn",
+        "location",
+        "flag_reasons",
+    ];
+
+    pub fn new(login: &Login, zone: Zone) -> Self {
+        Self {
+            time: format!(
+                "{} {}",
+                zone.to_zone(login.time).format("%F %T"),
+                zone.abbreviation(login.time)
+            ),
+            result: login.format_result(),
+            reason: login.reason.to_string(),
+            factor: login.factor.to_string(),
+            integration: login.integration.to_string(),
+            ip: login.ip,
+            asn: login.asn.to_owned(),
+            location: login.format_location(),
+            flag_reasons: login.flag_reasons.iter().map(|r| r.to_string()).collect(),
+        }
+    }
+
+    /// One CSV row in the order of [CSV_HEADER](Self::CSV_HEADER), missing data is left empty
+    pub fn csv_row(&self) -> String {
+        [
+            self.time.to_owned(),
+            self.result.to_owned(),
+            self.reason.to_owned(),
+            self.factor.to_owned(),
+            self.integration.to_owned(),
+            self.ip.map(|ip| ip.to_string()).unwrap_or_default(),
+            self.asn.to_owned().unwrap_or_default(),
+            self.location.to_owned().unwrap_or_default(),
+            self.flag_reasons.join("; "),
+        ]
+        .iter()
+        .map(|field| crate::queries::ip::csv_field(field))
+        .collect::<Vec<String>>()
+        .join(",")
+    }
+}
+
+// -------------------- Parsing --------------------
+
+/// One line of a Splunk JSON export
+#[derive(Deserialize)]
+struct SplunkLine {
+    result: SplunkResult,
+}
+
+/// A Splunk result, holding the raw Duo log along with the fields Splunk pulled out of it
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct SplunkResult {
+    #[serde(rename = "_time")]
+    time: Option<String>,
+    #[serde(rename = "_raw")]
+    raw: Option<String>,
+    #[serde(flatten)]
+    log: DuoLog,
+}
+
+/// A Duo auth log.  Older and newer logs disagree on most fields, so everything is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DuoLog {
+    user: Option<Named>,
+    username: Option<String>,
+    device: Option<String>,
+    factor: Option<String>,
+    integration: Option<String>,
+    reason: Option<String>,
+    result: Option<String>,
+    ip: Option<String>,
+    access_device: Option<AccessDevice>,
+    application: Option<Named>,
+    auth_device: Option<Named>,
+    /// `trusted`, `not trusted`, or `unknown` in newer logs
+    trusted_endpoint_status: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct AccessDevice {
+    browser: Option<String>,
+    browser_version: Option<String>,
+    os: Option<String>,
+    os_version: Option<String>,
+    ip: Option<String>,
+    location: Option<DuoLocation>,
+}
+
+/// Where Duo placed the access device
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct DuoLocation {
+    city: Option<String>,
+    state: Option<String>,
+    country: Option<String>,
+}
+
+/// Some fields are a plain name in older logs and an object in newer ones
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Named {
+    Name(String),
+    Object {
+        name: Option<String>,
+        key: Option<String>,
+    },
+}
+
+impl Named {
+    fn name(&self) -> Option<String> {
+        match self {
+            Self::Name(name) => Some(name.to_owned()),
+            Self::Object { name, .. } => name.to_owned(),
+        }
+    }
+
+    fn key(&self) -> Option<String> {
+        match self {
+            Self::Name(_) => None,
+            Self::Object { key, .. } => key.to_owned(),
+        }
+    }
+}
+
+impl DuoLog {
+    fn into_fields(self) -> LogFields {
+        let access_device = self.access_device.unwrap_or_default();
+        let location = access_device.location.unwrap_or_default();
+        LogFields {
+            user: self.user.as_ref().and_then(Named::name).or(self.username),
+            time: None,
+            // Newer logs only name the device used for 2FA under the auth device
+            device: self
+                .device
+                .or_else(|| self.auth_device.as_ref().and_then(Named::name)),
+            factor: self.factor,
+            integration: self
+                .integration
+                .or_else(|| self.application.as_ref().and_then(Named::name)),
+            reason: self.reason,
+            result: self.result,
+            ip: self.ip.or(access_device.ip),
+            browser: access_device.browser,
+            browser_version: access_device.browser_version,
+            os: access_device.os,
+            os_version: access_device.os_version,
+            trusted_device: match self.trusted_endpoint_status.as_deref() {
+                Some("trusted") => Some(true),
+                Some("not trusted") => Some(false),
+                _ => None,
+            },
+            application_key: self.application.as_ref().and_then(Named::key),
+            city: location.city,
+            state: location.state,
+            country: location.country,
+        }
+    }
+}
+
+/// The fields of a Duo log before they are turned into a [Login]
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct LogFields {
+    pub user: Option<String>,
+    pub time: Option<String>,
+    pub device: Option<String>,
+    pub factor: Option<String>,
+    pub integration: Option<String>,
+    pub reason: Option<String>,
+    pub result: Option<String>,
+    pub ip: Option<String>,
+    pub browser: Option<String>,
+    pub browser_version: Option<String>,
+    pub os: Option<String>,
+    pub os_version: Option<String>,
+    pub trusted_device: Option<bool>,
+    pub application_key: Option<String>,
+    /// Where Duo placed the access device, used when the IP databases can't place its IP
+    pub city: Option<String>,
+    pub state: Option<String>,
+    pub country: Option<String>,
+}
+
+impl LogFields {
+    /// Parses a line with serde.  Fields in the raw log win over the ones Splunk extracted.
+    /// Returns [None] if the line or the raw log isn't valid JSON.
+    pub(super) fn from_json(obj: &str) -> Option<Self> {
+        let line: SplunkLine = serde_json::from_str(obj).ok()?;
+        let extracted = line.result.log.into_fields();
+        let raw = match &line.result.raw {
+            Some(raw) => serde_json::from_str::<DuoLog>(raw).ok()?.into_fields(),
+            None => LogFields::default(),
+        };
+
+        Some(LogFields {
+            time: line.result.time,
+            ..raw.or(extracted)
+        })
+    }
+
+    /// Looks fields up by the paths they are known to live at, in the raw log first and then the
+    /// Splunk result.  Returns [None] if the line isn't valid JSON.
+    pub(super) fn from_value(obj: &str) -> Option<Self> {
+        let line: Value = serde_json::from_str(obj).ok()?;
+        let result = line.get("result").unwrap_or(&line);
+        let raw: Option<Value> = result
+            .get("_raw")
+            .and_then(Value::as_str)
+            .and_then(|raw| serde_json::from_str(raw).ok());
+
+        let find = |paths: &[&str]| {
+            [raw.as_ref(), Some(result)]
+                .into_iter()
+                .flatten()
+                .find_map(|v| paths.iter().find_map(|p| v.pointer(p).and_then(as_string)))
+        };
+
+        Some(LogFields {
+            user: find(&["/user/name", "/user", "/username"]),
+            time: result.get("_time").and_then(as_string),
+            device: find(&["/device", "/auth_device/name"]),
+            factor: find(&["/factor"]),
+            integration: find(&["/integration", "/application/name"]),
+            reason: find(&["/reason"]),
+            result: find(&["/result"]),
+            ip: find(&["/ip", "/access_device/ip"]),
+            browser: find(&["/access_device/browser"]),
+            browser_version: find(&["/access_device/browser_version"]),
+            os: find(&["/access_device/os"]),
+            os_version: find(&["/access_device/os_version"]),
+            trusted_device: None,
+            application_key: find(&["/application/key"]),
+            city: find(&["/access_device/location/city"]),
+            state: find(&["/access_device/location/state"]),
+            country: find(&["/access_device/location/country"]),
+        })
+    }
+
+    /// Fills in any missing fields from `other`
+    pub(super) fn or(self, other: Self) -> Self {
+        LogFields {
+            user: self.user.or(other.user),
+            time: self.time.or(other.time),
+            device: self.device.or(other.device),
+            factor: self.factor.or(other.factor),
+            integration: self.integration.or(other.integration),
+            reason: self.reason.or(other.reason),
+            result: self.result.or(other.result),
+            ip: self.ip.or(other.ip),
+            browser: self.browser.or(other.browser),
+            browser_version: self.browser_version.or(other.browser_version),
+            os: self.os.or(other.os),
+            os_version: self.os_version.or(other.os_version),
+            trusted_device: self.trusted_device.or(other.trusted_device),
+            application_key: self.application_key.or(other.application_key),
+            city: self.city.or(other.city),
+            state: self.state.or(other.state),
+            country: self.country.or(other.country),
+        }
+    }
+
+    /// Pulls fields out of a line with regex, for when it can't be deserialized
+    pub(super) fn from_regex(obj: &str) -> Self {
+        let obj = obj.replace('\\', "");
+
+        let capture_in = |text: &str, re: &OnceLock<Regex>, pattern: &str| {
+            re.get_or_init(|| Regex::new(pattern).unwrap())
+                .captures(text)
+                .map(|c| c[1].to_owned())
+        };
+        let capture = |re: &OnceLock<Regex>, pattern: &str| capture_in(&obj, re, pattern);
+
+        // The access device's location, which can follow other objects in the access device
+        let location = capture(
+            &ACCESS_LOCATION_RE,
+            r#""access_device": ?\{(?:[^{}]|\{[^{}]*\})*?"location": ?\{([^{}]*)\}"#,
+        )
+        .unwrap_or_default();
+
+        LogFields {
+            user: capture(&USERNAME_RE, r#""user": ?"([^"]+)""#),
+            time: capture(&TIME_RE, r#""_time": ?"([^"]*)""#),
+            device: capture(&DEVICE_RE, r#""device": ?"([^"]+)""#),
+            factor: capture(&FACTOR_RE, r#""factor": ?"([^"]+)""#),
+            integration: capture(&INTEGRATION_RE, r#""integration": ?"([^"]+)""#),
+            reason: capture(&REASON_RE, r#""reason": ?"([^"]+)""#),
+            result: capture(&RESULT_RE, r#""result": ?"([^"]+)""#),
+            // The first bare "ip" could be the auth device's, the phone rather than what logged in
+            ip: capture(
+                &ACCESS_IP_RE,
+                r#""access_device": ?\{(?:[^{}]|\{[^{}]*\})*?"ip": ?"([^"]+)""#,
+            )
+            .or_else(|| capture(&IP_RE, r#""ip": ?"([^"]+)""#)),
+            city: capture_in(&location, &CITY_RE, r#""city": ?"([^"]+)""#),
+            state: capture_in(&location, &STATE_RE, r#""state": ?"([^"]+)""#),
+            country: capture_in(&location, &COUNTRY_RE, r#""country": ?"([^"]+)""#),
+            ..Default::default()
+        }
+    }
+}
+
+/// Strings and numbers as a string, anything else is treated as missing
+fn as_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.to_owned()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
 /// Represents a reason why a login or user is flagged
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum FlagReason {
@@ -456,6 +1077,16 @@ pub enum FlagReason {
     Failure,
     Dmp,
     Travel,
+    /// Denied for coming from an OFAC sanctioned location
+    Restricted,
+    /// First success from a device not seen in older logs
+    NewDevice,
+    /// A run of unanswered pushes followed by an approval
+    Fatigue,
+    /// A device activated in the checked window alongside another flag
+    Enrollment,
+    /// Success through a known proxy or relay, only when turned on in Settings
+    Relay,
 }
 
 impl std::fmt::Display for FlagReason {
@@ -468,6 +1099,11 @@ impl std::fmt::Display for FlagReason {
                 FlagReason::Failure => "Failure",
                 FlagReason::Dmp => "DMP",
                 FlagReason::Travel => "Travel",
+                FlagReason::Restricted => "OFAC",
+                FlagReason::NewDevice => "New device",
+                FlagReason::Fatigue => "MFA fatigue",
+                FlagReason::Enrollment => "Enrollment",
+                FlagReason::Relay => "Proxy",
             }
         )
     }
@@ -1,22 +1,29 @@
 //! Structures and methods to represent a user
+pub mod admin;
 pub mod login;
-mod test;
+pub(crate) mod test;
 pub mod vpnlog;
-use crate::queries::ip::IpInfo;
+pub mod zone;
+use crate::queries::{
+    hdtools::{AddressRecord, HDToolsInfo, HDToolsStatus},
+    ip::IpInfo,
+};
 
-use self::login::{FlagReason, Integration, Reason};
+use self::admin::{AdminAction, AdminEvent};
+use self::login::{Factor, FlagReason, Integration, Reason};
 use self::login::{Login, LoginResult};
-use chrono::{Duration, NaiveDateTime};
+use chrono::{DateTime, Duration, Months, NaiveDateTime, Utc};
 use log::info;
 use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
 
 const MEAN_EARTH_RADIUS: f32 = 6_371_008.8;
 const EARTH_CIRCUMFERENCE: f32 = 40_030.23; // km
-/// The maximum time it could take to travel one side the earth to the other at 1000 kph which would still be
-/// considered impossible travel.  This is used to determine how far back to check user logs.
-const MAX_IMPOSSIBLE_TRAVEL_TIME: i64 = (EARTH_CIRCUMFERENCE / 2_f32 / 1_000_f32 * 60_f32) as i64; // min
+/// HDTools account statuses that mean the account can no longer be logged into
+const DISABLED_STATUSES: [&str; 4] = ["disabled", "terminated", "inactive", "locked"];
 
-const STATE_ABBREVIATIONS: [(&str, &str); 50] = [
+/// State, DC, and territory names with their postal codes.  Some have more than one name.
+const STATE_ABBREVIATIONS: [(&str, &str); 59] = [
     ("Alabama", "AL"),
     ("Alaska", "AK"),
     ("Arizona", "AZ"),
@@ -67,10 +74,598 @@ const STATE_ABBREVIATIONS: [(&str, &str); 50] = [
     ("West Virginia", "WV"),
     ("Wisconsin", "WI"),
     ("Wyoming", "WY"),
+    ("District of Columbia", "DC"),
+    ("Washington DC", "DC"),
+    ("Puerto Rico", "PR"),
+    ("Guam", "GU"),
+    ("Virgin Islands", "VI"),
+    ("US Virgin Islands", "VI"),
+    ("American Samoa", "AS"),
+    ("Northern Mariana Islands", "MP"),
+    ("United States Minor Outlying Islands", "UM"),
 ];
 
+/// Other ways data sources spell states, such as the AP style abbreviations some HDTools
+/// addresses use.  Periods don't matter here either, `S. Carolina` and `S Carolina` both match.
+const ALTERNATE_STATE_NAMES: [(&str, &str); 39] = [
+    ("N Carolina", "NC"),
+    ("S Carolina", "SC"),
+    ("N Dakota", "ND"),
+    ("S Dakota", "SD"),
+    ("W Virginia", "WV"),
+    ("N Hampshire", "NH"),
+    ("N Jersey", "NJ"),
+    ("N Mexico", "NM"),
+    ("N York", "NY"),
+    ("Ala", "AL"),
+    ("Ariz", "AZ"),
+    ("Ark", "AR"),
+    ("Calif", "CA"),
+    ("Colo", "CO"),
+    ("Conn", "CT"),
+    ("Del", "DE"),
+    ("Fla", "FL"),
+    ("Ill", "IL"),
+    ("Ind", "IN"),
+    ("Kan", "KS"),
+    ("Mass", "MA"),
+    ("Mich", "MI"),
+    ("Minn", "MN"),
+    ("Miss", "MS"),
+    ("Mont", "MT"),
+    ("Neb", "NE"),
+    ("Nev", "NV"),
+    ("Okla", "OK"),
+    ("Ore", "OR"),
+    ("Penn", "PA"),
+    ("Tenn", "TN"),
+    ("Tex", "TX"),
+    ("Wash", "WA"),
+    ("W Va", "WV"),
+    ("WVa", "WV"),
+    ("Wis", "WI"),
+    ("Wyo", "WY"),
+    ("Wash DC", "DC"),
+    ("Virgin Islands of the United States", "VI"),
+];
+
+/// Two letter postal code for a state, DC, or territory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateCode(pub &'static str);
+
+/// Reads a state name, code, or [alternate spelling](ALTERNATE_STATE_NAMES) in any case,
+/// ignoring periods, commas, and extra whitespace, so `Washington, D.C.`, `district of columbia`,
+/// and ` DC ` are all `StateCode("DC")`
+pub fn normalize_state(state: &str) -> Option<StateCode> {
+    let state = squash_state(state);
+    STATE_ABBREVIATIONS
+        .iter()
+        .chain(ALTERNATE_STATE_NAMES.iter())
+        .find(|&&(name, code)| state == squash_state(name) || state == code.to_lowercase())
+        .map(|&(_, code)| StateCode(code))
+}
+
+/// Lowercased with periods dropped, and commas and runs of whitespace made single spaces
+fn squash_state(state: &str) -> String {
+    state
+        .replace('.', "")
+        .replace(',', " ")
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Compares states by code when both are US states, foreign regions can still match by name.
+/// Names are compared the same forgiving way either way, so `Québec` matches ` QUÉBEC`.
+pub(crate) fn same_state(a: &str, b: &str) -> bool {
+    match (normalize_state(a), normalize_state(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => squash_state(a) == squash_state(b),
+    }
+}
+
+/// Known-good sources that the first vibe check goes easy on, edited in Settings
+#[derive(Debug, Default, Clone)]
+pub struct Allowlist {
+    /// ASNs whose logins aren't scored for impossible travel
+    pub asns: Vec<String>,
+    /// IPs whose logins aren't scored for failures or impossible travel
+    pub ips: Vec<Ipv4Addr>,
+    /// Blocks trusted the same as [ips](Self::ips), such as campus or corporate egress
+    pub ranges: Vec<IpRange>,
+}
+
+impl Allowlist {
+    /// True if the IP is trusted on its own or falls in a trusted range
+    pub fn contains(&self, ip: Ipv4Addr) -> bool {
+        self.ips.contains(&ip) || self.ranges.iter().any(|r| r.contains(ip))
+    }
+}
+
+/// Block of IPs written in CIDR notation, such as 130.127.0.0/16
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct IpRange {
+    /// First IP of the block stored as an unsigned 32 bit integer, like the IpDB ranges
+    lower: u32,
+    /// Leading bits every IP in the block shares
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Last IP of the block in the same format as [lower](Self::lower)
+    fn upper(self) -> u32 {
+        self.lower | !Self::mask(self.prefix)
+    }
+
+    fn mask(prefix: u8) -> u32 {
+        u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0)
+    }
+
+    pub fn contains(self, ip: Ipv4Addr) -> bool {
+        (self.lower..=self.upper()).contains(&u32::from(ip))
+    }
+}
+
+/// Takes a bare IP as a /32, and drops host bits so 130.127.1.2/16 is 130.127.0.0/16
+impl std::str::FromStr for IpRange {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ip, prefix) = match s.trim().split_once('/') {
+            Some((ip, prefix)) => (ip, prefix.trim().parse::<u8>().map_err(|_| ())?),
+            None => (s.trim(), 32),
+        };
+        if prefix > 32 {
+            return Err(());
+        }
+        let ip: Ipv4Addr = ip.trim().parse().map_err(|_| ())?;
+        Ok(Self {
+            lower: u32::from(ip) & Self::mask(prefix),
+            prefix,
+        })
+    }
+}
+
+impl std::fmt::Display for IpRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", Ipv4Addr::from(self.lower), self.prefix)
+    }
+}
+
+/// Tunable thresholds for the first vibe check, edited in Settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scoring {
+    /// Unanswered pushes needed before an approval counts as MFA fatigue
+    pub fatigue_pushes: usize,
+    /// How far back from the approval unanswered pushes are counted, in minutes
+    pub fatigue_minutes: i64,
+    /// Which failures are forgiven by a later success
+    pub failure_policy: FailurePolicy,
+    /// Travel at or above this speed is impossible
+    pub max_kph: u32,
+    /// Accounts created within this many calendar months of their latest login are new, and
+    /// their denies for not being enrolled are expected
+    pub new_account_months: u32,
+    /// What else a new account can be flagged for and still pass
+    pub new_account_pass: NewAccountPass,
+    /// Whether successes through a known proxy or relay are flagged
+    pub flag_relays: bool,
+    /// Points for each success through a proxy or relay when [flag_relays](Self::flag_relays) is
+    /// on
+    pub relay_weight: usize,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            fatigue_pushes: 3,
+            fatigue_minutes: 10,
+            failure_policy: FailurePolicy::default(),
+            max_kph: 1000,
+            new_account_months: 6,
+            new_account_pass: NewAccountPass::default(),
+            flag_relays: false,
+            relay_weight: 5,
+        }
+    }
+}
+
+impl Scoring {
+    /// The maximum time it could take to travel one side the earth to the other at
+    /// [max_kph](Self::max_kph) which would still be considered impossible travel.  This is used
+    /// to determine how far back to check user logs.
+    pub fn max_impossible_travel_time(&self) -> Duration {
+        let minutes = EARTH_CIRCUMFERENCE / 2_f32 / self.max_kph.max(1) as f32 * 60_f32;
+        Duration::minutes(minutes as i64)
+    }
+}
+
+/// A failure is forgiven if a matching success follows it within the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FailurePolicy {
+    /// How long after the failure a success can forgive it, in minutes
+    pub window_minutes: i64,
+    pub matching: FailureMatch,
+    /// Unforgiven failures needed before the user is flagged for them, so one mistyped passcode
+    /// doesn't surface anyone
+    pub min_count: usize,
+}
+
+impl Default for FailurePolicy {
+    fn default() -> Self {
+        Self {
+            window_minutes: 30,
+            matching: FailureMatch::IpIntegration,
+            min_count: 1,
+        }
+    }
+}
+
+impl FailurePolicy {
+    /// True if `success` forgives `failure`
+    pub fn forgives(&self, failure: &Login, success: &Login) -> bool {
+        if success.time - failure.time > Duration::minutes(self.window_minutes) {
+            return false;
+        }
+        match self.matching {
+            FailureMatch::Ip => failure.ip == success.ip,
+            FailureMatch::IpIntegration => {
+                failure.ip == success.ip && failure.integration == success.integration
+            }
+            FailureMatch::Subnet => match (failure.ip, success.ip) {
+                (Some(f), Some(s)) => f.octets()[..3] == s.octets()[..3],
+                (f, s) => f == s,
+            },
+        }
+    }
+}
+
+/// What a success has to share with a failure to forgive it
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FailureMatch {
+    /// Same IP, from any integration
+    Ip,
+    /// Same IP and integration
+    #[default]
+    IpIntegration,
+    /// Same /24, from any integration
+    Subnet,
+}
+
+impl FailureMatch {
+    pub fn choices() -> [Self; 3] {
+        [Self::Ip, Self::IpIntegration, Self::Subnet]
+    }
+
+    /// Name stored in settings, kept apart from the label so relabeling doesn't lose it
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Ip => "ip",
+            Self::IpIntegration => "ip_integration",
+            Self::Subnet => "subnet",
+        }
+    }
+}
+
+impl std::fmt::Display for FailureMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Ip => "Same IP",
+                Self::IpIntegration => "Same IP and integration",
+                Self::Subnet => "Same /24",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for FailureMatch {
+    type Err = ();
+
+    /// Takes the key, or the label older versions stored
+    fn from_str(matching: &str) -> Result<Self, Self::Err> {
+        Self::choices()
+            .into_iter()
+            .find(|m| m.key() == matching || m.to_string() == matching)
+            .ok_or(())
+    }
+}
+
+/// When a recently created account with a deny for not being enrolled passes the second vibe
+/// check
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NewAccountPass {
+    /// Only when failures are all it was flagged for, and every failure is a deny for not being
+    /// enrolled, which is what onboarding looks like
+    #[default]
+    Strict,
+    /// Whatever else it was flagged for, as long as there's no fraud or OFAC deny
+    Lenient,
+}
+
+impl NewAccountPass {
+    pub fn choices() -> [Self; 2] {
+        [Self::Strict, Self::Lenient]
+    }
+}
+
+impl std::fmt::Display for NewAccountPass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Strict => "Only onboarding",
+                Self::Lenient => "Anything but fraud",
+            }
+        )
+    }
+}
+
+impl std::str::FromStr for NewAccountPass {
+    type Err = ();
+
+    fn from_str(pass: &str) -> Result<Self, Self::Err> {
+        Self::choices()
+            .into_iter()
+            .find(|p| p.to_string() == pass)
+            .ok_or(())
+    }
+}
+
+/// How a user's score adds up, filled in by the first vibe check
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ScoreBreakdown {
+    pub components: Vec<ScoreComponent>,
+}
+
+impl ScoreBreakdown {
+    pub fn total(&self) -> usize {
+        self.components
+            .iter()
+            .fold(0, |total, c| total.saturating_add(c.contribution))
+    }
+}
+
+/// One line per component followed by the total, for tickets
+impl std::fmt::Display for ScoreBreakdown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for component in &self.components {
+            writeln!(f, "{}", component)?;
+        }
+        write!(f, "Total: {} pts", self.total())
+    }
+}
+
+/// What one [FlagReason] added to a user's score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreComponent {
+    pub reason: FlagReason,
+    /// Number of flagged events, such as failures or logins
+    pub count: usize,
+    /// Points per event, travel has none as each trip is scored by its speed
+    pub weight: Option<usize>,
+    pub contribution: usize,
+}
+
+impl ScoreComponent {
+    fn weighted(reason: FlagReason, count: usize, weight: usize) -> Self {
+        Self {
+            reason,
+            count,
+            weight: Some(weight),
+            contribution: count.saturating_mul(weight),
+        }
+    }
+}
+
+impl std::fmt::Display for ScoreComponent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.weight {
+            Some(weight) => write!(
+                f,
+                "{}: {} x {} = {} pts",
+                self.reason, self.count, weight, self.contribution
+            ),
+            None => write!(
+                f,
+                "{}: {} logins scored by speed = {} pts",
+                self.reason, self.count, self.contribution
+            ),
+        }
+    }
+}
+
+/// OpenStreetMap link with a marker on a `(lat, lon)`
+pub fn map_url(location: (f32, f32)) -> String {
+    let (lat, lon) = location;
+    format!(
+        "https://www.openstreetmap.org/?mlat={0}&mlon={1}#map=10/{0}/{1}",
+        lat, lon
+    )
+}
+
+/// A `(lat, lon)` typed as "34.68, -82.84", [None] if either is out of range
+pub fn parse_coordinates(text: &str) -> Option<(f32, f32)> {
+    let (lat, lon) = text.split_once(',')?;
+    let (lat, lon) = (
+        lat.trim().parse::<f32>().ok()?,
+        lon.trim().parse::<f32>().ok()?,
+    );
+    ((-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon)).then_some((lat, lon))
+}
+
+/// Checked logins from one [Login::summary_location]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocationCount {
+    pub location: String,
+    pub count: usize,
+    /// Logins from here with a [FlagReason]
+    pub flagged: usize,
+}
+
+/// Checked logins tallied by country, state, and ASN, each most logins first
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ActivitySummary {
+    pub countries: Vec<(String, usize)>,
+    pub states: Vec<(String, usize)>,
+    pub asns: Vec<(String, usize)>,
+}
+
+impl ActivitySummary {
+    /// Counts each value, most common first.  Ties stay in the order they were first seen, so
+    /// for logins that's the most recent.
+    pub(crate) fn tally<T: PartialEq>(values: impl IntoIterator<Item = T>) -> Vec<(T, usize)> {
+        let mut tally: Vec<(T, usize)> = vec![];
+        for value in values {
+            match tally.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, count)) => *count += 1,
+                None => tally.push((value, 1)),
+            }
+        }
+        tally.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        tally
+    }
+}
+
+/// `5 US, 2 RU | 4 South Carolina, 2 Moscow | AS12 Clemson, AS34 Rostelecom`
+impl std::fmt::Display for ActivitySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |tally: &[(String, usize)]| {
+            tally
+                .iter()
+                .map(|(value, count)| format!("{} {}", count, value))
+                .collect::<Vec<String>>()
+                .join(", ")
+        };
+        let asns = self
+            .asns
+            .iter()
+            .map(|(asn, _)| asn.as_str())
+            .collect::<Vec<&str>>()
+            .join(", ");
+        let parts: Vec<String> = [join(&self.countries), join(&self.states), asns]
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .collect();
+        write!(f, "{}", parts.join(" | "))
+    }
+}
+
+/// Names the US goes by across Duo, ipinfo.io, and HDTools
+const US_NAMES: [&str; 4] = ["us", "usa", "united states", "united states of america"];
+
+/// Compares countries by name or code, only the US has its names and code matched up
+pub(crate) fn same_country(a: &str, b: &str) -> bool {
+    let normalize = |c: &str| {
+        let c = c.trim().to_lowercase();
+        if US_NAMES.contains(&c.as_str()) {
+            "us".to_owned()
+        } else {
+            c
+        }
+    };
+    normalize(a) == normalize(b)
+}
+
+/// How a worked Duplex queue went, for the done screen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueueStats {
+    /// Users flagged for each reason, most common first
+    pub reasons: Vec<(FlagReason, usize)>,
+    /// Users marked investigated
+    pub investigated: usize,
+    /// Users looked at but not marked
+    pub skipped: usize,
+    /// Up to five countries outside users' home countries and how many users logged in from
+    /// each, most common first
+    pub countries: Vec<(String, usize)>,
+    pub highest_score: usize,
+    /// How long the queue took to work
+    pub elapsed: std::time::Duration,
+}
+
+impl QueueStats {
+    /// Users without a home country from HDTools are taken to live in the US
+    pub fn new(users: &[User], elapsed: std::time::Duration) -> Self {
+        let mut reasons: Vec<(FlagReason, usize)> = vec![];
+        let mut countries: Vec<(String, usize)> = vec![];
+        for user in users {
+            for &reason in &user.reasons {
+                match reasons.iter_mut().find(|(r, _)| *r == reason) {
+                    Some((_, count)) => *count += 1,
+                    None => reasons.push((reason, 1)),
+                }
+            }
+
+            let home = user
+                .location
+                .as_ref()
+                .and_then(|l| l.country.as_deref())
+                .unwrap_or("US");
+            let mut seen: Vec<&String> = vec![];
+            for login in user.logins.iter().take(user.checked_login_count) {
+                if login.is_vpn_ip() || login.is_priv_ip() {
+                    continue;
+                }
+                let Some(country) = &login.country else {
+                    continue;
+                };
+                if same_country(country, home) || seen.iter().any(|c| same_country(c, country)) {
+                    continue;
+                }
+                seen.push(country);
+                match countries.iter_mut().find(|(c, _)| same_country(c, country)) {
+                    Some((_, count)) => *count += 1,
+                    None => countries.push((country.to_owned(), 1)),
+                }
+            }
+        }
+        // Stable so ties stay in the order they were first seen
+        reasons.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        countries.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        countries.truncate(5);
+
+        let investigated = users.iter().filter(|u| u.investigated).count();
+        Self {
+            reasons,
+            investigated,
+            skipped: users.len() - investigated,
+            countries,
+            highest_score: users.iter().map(|u| u.score).max().unwrap_or(0),
+            elapsed,
+        }
+    }
+
+    /// Elapsed time as hours, minutes, and seconds
+    pub fn elapsed_text(&self) -> String {
+        let secs = self.elapsed.as_secs();
+        format!("{}h {:02}m {:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// One line per stat, for pasting into a handoff
+impl std::fmt::Display for QueueStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Users: {}", self.investigated + self.skipped)?;
+        writeln!(f, "Investigated: {}", self.investigated)?;
+        writeln!(f, "Skipped: {}", self.skipped)?;
+        writeln!(f, "Highest score: {}", self.highest_score)?;
+        for (reason, count) in &self.reasons {
+            writeln!(f, "{}: {}", reason, count)?;
+        }
+        for (country, count) in &self.countries {
+            writeln!(f, "{}: {}", country, count)?;
+        }
+        write!(f, "Elapsed: {}", self.elapsed_text())
+    }
+}
+
 /// Represents a person with dreams, ambition, *desires*, and shortcomings
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct User {
     pub name: String,
     pub logins: Vec<Login>,
@@ -78,36 +673,65 @@ pub struct User {
     pub checked_login_count: usize,
     /// Why the user failed the vibe checks
     pub reasons: Vec<FlagReason>,
+    /// How each reason added to the score
+    pub score_breakdown: ScoreBreakdown,
     pub score: usize,
     pub location: Option<Location>,
+    /// Coordinates of [location](Self::location) from the IP databases, cached with HDTools
+    pub home: Option<(f32, f32)>,
     pub creation_date: Option<NaiveDateTime>,
+    /// Primary affiliation from HDTools, such as student, employee, or alumni
+    pub affiliation: Option<String>,
+    /// Account status from HDTools
+    pub status: Option<String>,
+    /// Whether HDTools had the location from a student or employee record
+    pub address_record: Option<AddressRecord>,
+    /// Every address HDTools had, best first, see [HDToolsInfo::addresses]
+    pub addresses: Vec<(AddressRecord, Location)>,
+    /// Phone number from HDTools, for first contact
+    pub phone: Option<String>,
+    /// Duo administrator and self-service events, newest first.  Pulled by Simplex and on request
+    /// in Duplex, see [Splunk::get_admin_events](crate::queries::splunk::Splunk::get_admin_events)
+    pub admin_events: Vec<AdminEvent>,
     pub investigated: bool,
+    /// Why the user was last ignored, if an analyst said
+    pub investigated_note: Option<String>,
+    /// How the HDTools lookup went, to tell a user without records from HDTools failing
+    pub hdtools_status: HDToolsStatus,
+    /// Pairs of login indexes, older second, that scored as impossible travel
+    pub travel_hops: Vec<(usize, usize)>,
 }
 
 impl PartialOrd for User {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match other.fraud().partial_cmp(&self.fraud()) {
-            Some(std::cmp::Ordering::Less) => Some(std::cmp::Ordering::Less),
-            Some(std::cmp::Ordering::Equal) => other.score.partial_cmp(&self.score),
-            Some(std::cmp::Ordering::Greater) => Some(std::cmp::Ordering::Greater),
-            None => None,
-        }
+        Some(self.cmp(other))
     }
 }
 
 impl Eq for User {}
 
+/// Most fraud reports first, then highest score
 impl Ord for User {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.score.cmp(&self.score)
+        other
+            .fraud()
+            .cmp(&self.fraud())
+            .then_with(|| other.score.cmp(&self.score))
     }
 }
 
 impl User {
-    pub fn new(name: String, logins: Vec<Login>, earliest: &NaiveDateTime) -> Self {
+    /// Logins from `lookback` before `earliest` onward are checked, so travel into the window
+    /// is caught.  `lookback` comes from [Scoring::max_impossible_travel_time].
+    pub fn new(
+        name: String,
+        logins: Vec<Login>,
+        earliest: &DateTime<Utc>,
+        lookback: Duration,
+    ) -> Self {
         let checked_login_count = logins
             .iter()
-            .take_while(|l| l.time >= *earliest - Duration::minutes(MAX_IMPOSSIBLE_TRAVEL_TIME))
+            .take_while(|l| l.time >= *earliest - lookback)
             .count();
 
         User {
@@ -115,27 +739,141 @@ impl User {
             logins,
             checked_login_count,
             reasons: Vec::with_capacity(4),
+            score_breakdown: ScoreBreakdown::default(),
             score: 0,
             location: None,
+            home: None,
             creation_date: None,
+            affiliation: None,
+            status: None,
+            address_record: None,
+            addresses: vec![],
+            phone: None,
+            admin_events: vec![],
             investigated: false,
+            investigated_note: None,
+            hdtools_status: HDToolsStatus::Unchecked,
+            travel_hops: vec![],
+        }
+    }
+
+    /// Fills in the user's details from HDTools
+    pub fn set_hdtools(&mut self, info: HDToolsInfo) {
+        self.hdtools_status = HDToolsStatus::Found;
+        self.creation_date = Some(info.creation_date);
+        self.location = info.location;
+        self.home = info.home;
+        self.affiliation = info.affiliation;
+        self.status = info.status;
+        self.address_record = info.address_record;
+        self.addresses = info.addresses;
+        self.phone = info.phone;
+    }
+
+    /// How long before the first flagged login the account was created, or before the latest
+    /// login if none are flagged, and whether it was measured from a flagged login
+    pub fn account_age(&self) -> Option<(Duration, bool)> {
+        let created = self.creation_date?;
+        let (login, flagged) = match self
+            .logins
+            .iter()
+            .take(self.checked_login_count)
+            .rev()
+            .find(|l| !l.flag_reasons.is_empty())
+        {
+            Some(login) => (login, true),
+            None => (self.logins.first()?, false),
+        };
+        // HDTools dates are local
+        Some((zone::Zone::Local.to_zone(login.time) - created, flagged))
+    }
+
+    /// True if HDTools says the account can't be logged into
+    pub fn is_disabled(&self) -> bool {
+        self.status
+            .as_ref()
+            .is_some_and(|s| DISABLED_STATUSES.contains(&s.to_lowercase().as_str()))
+    }
+
+    /// Scores the user's logins.  Logins from allowlisted IPs aren't counted for failures or
+    /// travel, and travel between two logins that both come from a trusted ASN is not counted.
+    pub fn first_vibe_check(&mut self, allowlist: &Allowlist, scoring: &Scoring) -> bool {
+        // Reset on subsequent run
+        self.score = 0;
+        self.reasons.clear();
+        self.score_breakdown.components.clear();
+        self.travel_hops.clear();
+        for login in &mut self.logins {
+            login.flag_reasons.clear();
+        }
+
+        self.flag_logins(allowlist, scoring);
+
+        // A new device next to anything else funky is how a takeover keeps its access
+        let enrollments = self.enrollments();
+        if !self.reasons.is_empty() && enrollments > 0 {
+            self.flag(ScoreComponent::weighted(
+                FlagReason::Enrollment,
+                enrollments,
+                10,
+            ));
         }
+
+        self.reasons.is_empty()
     }
 
-    pub fn first_vibe_check(&mut self) -> bool {
+    /// Flags the checked logins, see [first_vibe_check](Self::first_vibe_check)
+    fn flag_logins(&mut self, allowlist: &Allowlist, scoring: &Scoring) {
         if self.checked_login_count == 0 || self.logins.is_empty() {
-            return true;
+            return;
         }
 
-        // Reset on subsequent run
-        if self.score != 0 {
-            self.score = 0;
-            self.reasons.clear();
-            for login in &mut self.logins {
-                login.flag_reasons.clear();
+        // OFAC denies always have to be reported, so they are flagged before anything can pass
+        let restricted = self.flag_restricted();
+        if restricted > 0 {
+            self.flag(ScoreComponent::weighted(
+                FlagReason::Restricted,
+                restricted,
+                25,
+            ));
+        }
+
+        // Push bombing ends in an approval, usually from wherever the user is, so it is checked
+        // before the in-state shortcut as well
+        let fatigue = self.flag_fatigue(scoring);
+        if fatigue > 0 {
+            self.flag(ScoreComponent::weighted(FlagReason::Fatigue, fatigue, 15));
+        }
+
+        // A proxy exit can be anywhere, including in state
+        if scoring.flag_relays {
+            let relays = self.flag_relays(allowlist);
+            if relays > 0 {
+                self.flag(ScoreComponent::weighted(
+                    FlagReason::Relay,
+                    relays,
+                    scoring.relay_weight,
+                ));
             }
         }
 
+        // Activity only from SC || NC passes
+        if self.in_state() {
+            info!("{} is in state - ignored", self.name);
+            return;
+        }
+
+        // A quiet takeover approves from a new device without a single failure, so devices are
+        // checked before history can pass the user
+        let new_devices = self.flag_new_device();
+        if new_devices > 0 {
+            self.flag(ScoreComponent::weighted(
+                FlagReason::NewDevice,
+                new_devices,
+                5,
+            ));
+        }
+
         // PERFECT history passes the vibe check
         if !self
             .logins
@@ -143,77 +881,113 @@ impl User {
             .take(self.checked_login_count)
             .any(|l| l.result != LoginResult::Success)
         {
-            return true;
+            return;
         }
 
-        // Activity only from SC || NC passes
-        if self.in_state() {
-            info!("{} is in state - ignored", self.name);
-            return true;
-        }
-
-        let failures = self.failures();
-        if failures > 0 {
-            self.reasons.push(FlagReason::Failure);
+        let failures = self.failures(allowlist, &scoring.failure_policy);
+        if failures > 0 && failures >= scoring.failure_policy.min_count {
+            self.flag(ScoreComponent::weighted(FlagReason::Failure, failures, 1));
         }
 
         let fraud = self.flag_fraud();
         if fraud > 0 {
-            self.reasons.push(FlagReason::Fraud);
+            self.flag(ScoreComponent::weighted(FlagReason::Fraud, fraud, 20));
         }
 
         if self.impossible_travel_precheck() {
-            let travel = self.impossible_travel();
+            let travel = self.impossible_travel(allowlist, scoring.max_kph);
             if travel > 0 {
-                self.score += travel;
-                self.reasons.push(FlagReason::Travel);
+                let legs = self
+                    .logins
+                    .iter()
+                    .filter(|l| l.flag_reasons.contains(&FlagReason::Travel))
+                    .count();
+                self.flag(ScoreComponent {
+                    reason: FlagReason::Travel,
+                    count: legs,
+                    weight: None,
+                    contribution: travel,
+                });
             }
         }
 
         let dmp = self.flag_dmp();
         if dmp > 0 {
-            self.reasons.push(FlagReason::Dmp);
+            self.flag(ScoreComponent::weighted(FlagReason::Dmp, dmp, 2));
         }
+    }
 
-        self.score = self
-            .score
-            .saturating_add(failures)
-            .saturating_add(fraud.saturating_mul(20))
-            .saturating_add(dmp.saturating_mul(2));
+    /// Records a reason the user failed the vibe check and what it added to the score
+    fn flag(&mut self, component: ScoreComponent) {
+        self.reasons.push(component.reason);
+        self.score = self.score.saturating_add(component.contribution);
+        self.score_breakdown.components.push(component);
+    }
 
-        self.reasons.is_empty()
+    /// Devices activated since the oldest checked login
+    pub fn enrollments(&self) -> usize {
+        let Some(oldest) = self
+            .checked_login_count
+            .checked_sub(1)
+            .and_then(|i| self.logins.get(i))
+        else {
+            return 0;
+        };
+        self.admin_events
+            .iter()
+            .filter(|e| e.action == AdminAction::Activation && e.time >= oldest.time)
+            .count()
     }
 
-    pub fn second_vibe_check(&self) -> bool {
-        if self.location.is_none() || self.creation_date.is_none() || self.fraud() != 0 {
+    /// Passes users HDTools can explain.  Each pass only needs the HDTools data it uses, so a
+    /// partial record can still pass the user.
+    pub fn second_vibe_check(&self, scoring: &Scoring) -> bool {
+        if self.logins.is_empty() || self.fraud() != 0 || self.restricted() != 0 {
             return false;
         }
 
-        let creation_date = self
-            .creation_date
-            .expect("Internal error - user has no creation date");
-
         let latest_log = &self.logins[0];
 
-        // If user has been created in the past 6 months
-        if latest_log.time - chrono::Duration::days(6 * 30) < creation_date
+        // If user was created recently, HDTools dates are local
+        let new_since = months_before(
+            zone::Zone::Local.to_zone(latest_log.time),
+            scoring.new_account_months,
+        );
+        if self.creation_date.is_some_and(|c| new_since < c)
             && self
                 .logins
                 .iter()
                 .take(self.checked_login_count)
                 .any(|l| l.reason == Reason::DenyUnenrolledUser)
+            && (scoring.new_account_pass == NewAccountPass::Lenient || self.onboarding())
+        {
+            info!(
+                "{} was created in the past {} months",
+                self.name, scoring.new_account_months
+            );
+            return true;
+        }
+
+        // A disabled account that never got in is not worth a look
+        if self.is_disabled()
+            && !self
+                .logins
+                .iter()
+                .take(self.checked_login_count)
+                .any(|l| l.result == LoginResult::Success)
         {
-            info!("{} was created in the past 6 months", self.name);
+            info!("{} is disabled with no successes", self.name);
             return true;
         }
 
         // Pass if activity is from home state
-        if self
-            .logins
-            .iter()
-            .take(self.checked_login_count)
-            .filter(|l| !l.is_vpn_ip() && l.state.is_some())
-            .all(|l| self.same_state(l.state.as_ref().expect("Failed to get state from login")))
+        if self.location.is_some()
+            && self
+                .logins
+                .iter()
+                .take(self.checked_login_count)
+                .filter(|l| !l.is_vpn_ip() && l.state.is_some())
+                .all(|l| self.same_state(l.state.as_ref().expect("Failed to get state from login")))
         {
             info!("{}'s activity is from home state", self.name);
             return true;
@@ -222,11 +996,28 @@ impl User {
         false
     }
 
-    pub fn failures(&self) -> usize {
+    /// True if the user was flagged for nothing but failures, and every failure is a deny for not
+    /// being enrolled.  The device activation that ends onboarding is flagged next to those
+    /// failures, so it's let through too.  Anything else on a new account, like travel or passcodes
+    /// guessed from overseas, is worth a look.
+    fn onboarding(&self) -> bool {
+        self.reasons
+            .iter()
+            .all(|r| matches!(r, FlagReason::Failure | FlagReason::Enrollment))
+            && self
+                .logins
+                .iter()
+                .take(self.checked_login_count)
+                .filter(|l| l.result == LoginResult::Failure)
+                .all(|l| l.reason == Reason::DenyUnenrolledUser)
+    }
+
+    /// Counts failures that weren't forgiven by a later success, skipping allowlisted IPs
+    pub fn failures(&self, allowlist: &Allowlist, policy: &FailurePolicy) -> usize {
         let mut failures = 0;
         'f: for i in (0..self.checked_login_count).rev() {
             let login = &self.logins[i];
-            if login.result != LoginResult::Failure {
+            if login.result != LoginResult::Failure || login.is_allowlisted_ip(allowlist) {
                 continue;
             }
 
@@ -236,11 +1027,7 @@ impl User {
                     continue;
                 }
 
-                let time_diff = later_login.time - login.time;
-                if time_diff <= Duration::minutes(30)
-                    && login.integration == later_login.integration
-                    && login.ip == later_login.ip
-                {
+                if policy.forgives(login, later_login) {
                     continue 'f;
                 }
             }
@@ -249,10 +1036,11 @@ impl User {
         failures
     }
 
+    /// Flags fraud reports, skipping ones marked false positives
     pub fn flag_fraud(&mut self) -> usize {
         let mut count = 0;
         for login in &mut self.logins.iter_mut().take(self.checked_login_count) {
-            if login.result == LoginResult::Fraud {
+            if login.is_scored_fraud() {
                 login.flag_reasons.push(FlagReason::Fraud);
                 count += 1;
             }
@@ -260,14 +1048,85 @@ impl User {
         count
     }
 
+    /// Counts fraud reports, skipping ones marked false positives
     pub fn fraud(&self) -> usize {
         self.logins
             .iter()
             .take(self.checked_login_count)
-            .filter(|l| l.result == LoginResult::Fraud)
+            .filter(|l| l.is_scored_fraud())
+            .count()
+    }
+
+    pub fn flag_restricted(&mut self) -> usize {
+        let mut count = 0;
+        for login in &mut self.logins.iter_mut().take(self.checked_login_count) {
+            if login.reason == Reason::RestrictedOFAC {
+                login.flag_reasons.push(FlagReason::Restricted);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    pub fn restricted(&self) -> usize {
+        self.logins
+            .iter()
+            .take(self.checked_login_count)
+            .filter(|l| l.reason == Reason::RestrictedOFAC)
             .count()
     }
 
+    /// Flags runs of unanswered pushes that end in an approval.  Unlike [failures()](Self::failures),
+    /// the IP of the approval doesn't matter, as matching it is what the attacker is after.
+    pub fn flag_fatigue(&mut self, scoring: &Scoring) -> usize {
+        let mut count = 0;
+        // Indices of unanswered pushes since the last success
+        let mut pushes: Vec<usize> = vec![];
+
+        // Oldest first so the pushes come before the approval
+        for i in (0..self.checked_login_count).rev() {
+            let login = &self.logins[i];
+            if login.reason == Reason::NoResponse && login.factor == Factor::DuoPush {
+                pushes.push(i);
+                continue;
+            }
+            if login.result != LoginResult::Success {
+                continue;
+            }
+
+            let time = login.time;
+            let recent: Vec<usize> = pushes
+                .drain(..)
+                .filter(|p| {
+                    time - self.logins[*p].time <= Duration::minutes(scoring.fatigue_minutes)
+                })
+                .collect();
+
+            if login.reason == Reason::UserApproved && recent.len() >= scoring.fatigue_pushes {
+                for p in recent.into_iter().chain([i]) {
+                    self.logins[p].flag_reasons.push(FlagReason::Fatigue);
+                }
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Flags successes through a known proxy or relay, skipping trusted IPs
+    pub fn flag_relays(&mut self, allowlist: &Allowlist) -> usize {
+        let mut count = 0;
+        for login in self.logins.iter_mut().take(self.checked_login_count) {
+            if login.is_relay
+                && login.result == LoginResult::Success
+                && !login.ip.is_some_and(|ip| allowlist.contains(ip))
+            {
+                login.flag_reasons.push(FlagReason::Relay);
+                count += 1;
+            }
+        }
+        count
+    }
+
     pub fn flag_dmp(&mut self) -> usize {
         let mut count = 0;
         for login in &mut self.logins.iter_mut().take(self.checked_login_count) {
@@ -279,37 +1138,198 @@ impl User {
         count
     }
 
+    /// Flags the first success from each device that isn't in the logs older than the checked
+    /// window.  Nothing is flagged if there are no older logs to compare against, so this works
+    /// best with a longer history range or after pulling more logs.
+    pub fn flag_new_device(&mut self) -> usize {
+        let mut seen: Vec<String> = self
+            .logins
+            .iter()
+            .skip(self.checked_login_count)
+            .filter_map(|l| l.device.as_deref().map(device_key))
+            .collect();
+
+        if seen.is_empty() {
+            return 0;
+        }
+
+        let mut count = 0;
+        // Oldest first so only the first success from a device is flagged
+        for login in self
+            .logins
+            .iter_mut()
+            .take(self.checked_login_count)
+            .rev()
+            .filter(|l| l.result == LoginResult::Success)
+        {
+            let Some(device) = login.device.as_deref().map(device_key) else {
+                continue;
+            };
+            if !seen.contains(&device) {
+                login.flag_reasons.push(FlagReason::NewDevice);
+                seen.push(device);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Reason that added the most to the score, the first of any ties, or the first reason found
+    /// if none were scored
+    pub fn top_reason(&self) -> Option<FlagReason> {
+        self.score_breakdown
+            .components
+            .iter()
+            .rev()
+            .max_by_key(|c| c.contribution)
+            .map(|c| c.reason)
+            .or_else(|| self.reasons.first().copied())
+    }
+
+    /// Checked logins grouped by location, most logins first
+    pub fn location_summary(&self) -> Vec<LocationCount> {
+        let mut summary: Vec<LocationCount> = vec![];
+        for login in self.logins.iter().take(self.checked_login_count) {
+            let location = login.summary_location();
+            let i = match summary.iter().position(|s| s.location == location) {
+                Some(i) => i,
+                None => {
+                    summary.push(LocationCount {
+                        location,
+                        count: 0,
+                        flagged: 0,
+                    });
+                    summary.len() - 1
+                }
+            };
+            summary[i].count += 1;
+            if !login.flag_reasons.is_empty() {
+                summary[i].flagged += 1;
+            }
+        }
+        // Stable so ties stay in order of the most recent login
+        summary.sort_by_key(|s| std::cmp::Reverse(s.count));
+        summary
+    }
+
+    /// Distinct countries, states, and ASNs across the checked logins
+    pub fn activity_summary(&self) -> ActivitySummary {
+        let checked = || self.logins.iter().take(self.checked_login_count);
+        ActivitySummary {
+            countries: ActivitySummary::tally(checked().filter_map(|l| l.country.clone())),
+            states: ActivitySummary::tally(checked().filter_map(|l| l.state.clone())),
+            asns: ActivitySummary::tally(checked().filter_map(|l| l.asn.clone())),
+        }
+    }
+
+    /// Incident summary for a ticket body: who the user is, why they were flagged, and each
+    /// flagged login with its time in `zone`.  Signed by the analyst if they gave a name.
+    pub fn incident_summary(&self, analyst_name: &str, zone: zone::Zone) -> String {
+        let mut lines = vec![format!("Duo incident summary for {}", self.name)];
+        let reasons: Vec<String> = self.reasons.iter().map(|r| r.to_string()).collect();
+        if reasons.is_empty() {
+            lines.push(format!("Score: {}", self.score));
+        } else {
+            lines.push(format!("Score: {} ({})", self.score, reasons.join(", ")));
+        }
+        if let Some(date) = self.creation_date {
+            lines.push(format!("Account created: {}", date.format("%F")));
+        }
+        if let Some(location) = &self.location {
+            lines.push(format!("Home: {}", location));
+        }
+        let activity = self.activity_summary().to_string();
+        if !activity.is_empty() {
+            lines.push(format!("Activity: {}", activity));
+        }
+
+        let flagged: Vec<&Login> = self
+            .logins
+            .iter()
+            .take(self.checked_login_count)
+            .filter(|l| !l.flag_reasons.is_empty())
+            .collect();
+        if flagged.is_empty() {
+            lines.push("No flagged logins".to_owned());
+        } else {
+            lines.push("Flagged logins:".to_owned());
+        }
+        for login in flagged {
+            let reasons: Vec<String> = login.flag_reasons.iter().map(|r| r.to_string()).collect();
+            let ip = login.ip.map(|ip| format!(" ({})", ip)).unwrap_or_default();
+            lines.push(format!(
+                "- {} {}: {} to {} from {}{} [{}]",
+                zone.to_zone(login.time).format("%m/%d %I:%M %p"),
+                zone.abbreviation(login.time),
+                login.result,
+                login.integration,
+                login.summary_location(),
+                ip,
+                reasons.join(", ")
+            ));
+        }
+
+        if !analyst_name.is_empty() {
+            lines.push(format!("Prepared by {}", analyst_name));
+        }
+        lines.join("\n")
+    }
+
+    /// Lowercased name, IPs, ASNs, and locations, one per line, for searching across users
+    pub fn search_text(&self) -> String {
+        let mut text = vec![self.name.to_lowercase()];
+        if let Some(location) = &self.location {
+            text.push(location.to_string().to_lowercase());
+        }
+        for login in &self.logins {
+            if let Some(ip) = login.ip {
+                text.push(ip.to_string());
+            }
+            for field in [&login.asn, &login.city, &login.state, &login.country]
+                .into_iter()
+                .flatten()
+            {
+                text.push(field.to_lowercase());
+            }
+        }
+        text.sort();
+        text.dedup();
+        text.join("\n")
+    }
+
     pub fn in_state(&self) -> bool {
-        let mut states: Vec<&String> = vec![];
+        // Anything that isn't a US state is None, which never passes
+        let mut states: Vec<Option<StateCode>> = vec![];
 
         self.logins
             .iter()
             .take(self.checked_login_count)
             .filter_map(|l| {
                 if !l.is_vpn_ip() {
-                    l.state.as_ref()
+                    l.state.as_deref()
                 } else {
                     None
                 }
             })
+            .map(normalize_state)
             .for_each(|s| {
                 if !states.contains(&s) {
                     states.push(s)
                 }
             });
 
-        let sc = "South Carolina".to_owned();
-        let nc = "North Carolina".to_owned();
-        let ga = "Georgia".to_owned();
+        let sc = Some(StateCode("SC"));
+        let nc = Some(StateCode("NC"));
+        let ga = Some(StateCode("GA"));
 
-        if states.len() == 1 && (*states[0] == sc || *states[0] == nc) {
+        if states.len() == 1 && (states[0] == sc || states[0] == nc) {
             return true;
         }
         if states.len() == 2 {
-            if states.contains(&&sc) && states.contains(&&nc) {
+            if states.contains(&sc) && states.contains(&nc) {
                 return true;
             }
-            if states.contains(&&sc) && states.contains(&&ga) {
+            if states.contains(&sc) && states.contains(&ga) {
                 return true;
             }
         }
@@ -345,27 +1365,36 @@ impl User {
         true
     }
 
-    pub fn impossible_travel(&mut self) -> usize {
+    /// Scores travel between consecutive logins.  Logins from allowlisted IPs are ignored and pairs
+    /// where both logins come from a trusted ASN are skipped, this only affects travel and not
+    /// fraud or failures.
+    pub fn impossible_travel(&mut self, allowlist: &Allowlist, max_kph: u32) -> usize {
         let mut travel = 0.0;
         let mut logins = self
             .logins
             .iter_mut()
             .take(self.checked_login_count)
-            .filter(|login| {
+            .enumerate()
+            .filter(|(_, login)| {
                 login.location.is_some()
                     && !login.is_vpn_ip()
                     && !login.is_priv_ip()
                     && !login.is_relay
+                    && !login.is_allowlisted_ip(allowlist)
                     && login.integration != Integration::Linux
             })
-            .collect::<Vec<&mut Login>>();
+            .collect::<Vec<(usize, &mut Login)>>();
 
         if logins.len() < 2 {
             return 0;
         }
 
         for i in 0..logins.len() - 1 {
-            let (prev, next) = (&logins[i], &logins[i + 1]);
+            let ((prev_index, prev), (next_index, next)) = (&logins[i], &logins[i + 1]);
+
+            if prev.is_trusted_asn(&allowlist.asns) && next.is_trusted_asn(&allowlist.asns) {
+                continue;
+            }
 
             let distance = Self::haversine_distance(
                 &prev
@@ -388,14 +1417,15 @@ impl User {
             // Minutes / 60 is used to get decimal, as .num_hours() returns i64
             let kph = distance / (time.num_minutes().abs() as f32 / 60_f32);
 
-            // The limit for impossible travel is 1000 kph to filter out the noise of
+            // The limit for impossible travel defaults to 1000 kph to filter out the noise of
             // geoIP.  Additionally it is not too high to miss inter-country travel.
-            if kph >= 1000_f32 {
+            if kph >= max_kph as f32 {
                 // Score is weighted such that from Clemson to Bejing in a minute is ~15 points
                 // and Clemson to NY is 10 points
                 travel += kph.log2().min(15_f32);
-                logins[i].flag_reasons.push(FlagReason::Travel);
-                logins[i + 1].flag_reasons.push(FlagReason::Travel);
+                self.travel_hops.push((*prev_index, *next_index));
+                logins[i].1.flag_reasons.push(FlagReason::Travel);
+                logins[i + 1].1.flag_reasons.push(FlagReason::Travel);
             }
         }
 
@@ -423,6 +1453,14 @@ impl User {
                     return true;
                 }
             }
+            if let Some(home) = self.home {
+                let ip_loc = (ip.loc.lat, ip.loc.lon);
+                if Self::haversine_distance(&home, &ip_loc)
+                    < Self::haversine_distance(&home, &log_loc)
+                {
+                    return true;
+                }
+            }
         }
         false
     }
@@ -439,20 +1477,40 @@ impl User {
     }
 
     fn same_state(&self, login_state: &str) -> bool {
-        if let Some(location) = &self.location {
-            if let Some(user_state) = &location.state {
-                if user_state == login_state {
-                    return true;
-                }
-                for (state, code) in STATE_ABBREVIATIONS {
-                    if user_state == code && login_state == state {
-                        return true;
-                    }
-                }
-            }
-        }
+        let Some(user_state) = self.location.as_ref().and_then(|l| l.state.as_deref()) else {
+            return false;
+        };
+        same_state(user_state, login_state)
+    }
+}
 
-        false
+/// Calendar months rather than 30 day blocks, clamped to the end of shorter months (6 months
+/// before Aug 31 gives Feb 28)
+pub(crate) fn months_before(time: NaiveDateTime, months: u32) -> NaiveDateTime {
+    time.checked_sub_months(Months::new(months))
+        .unwrap_or(NaiveDateTime::MIN)
+}
+
+/// Username as it's kept and compared.  Duo logs the same person as `JSMITH` and `jsmith`
+/// depending on the integration, so names are lowercased before they're matched to logins or
+/// used as storage keys.
+pub fn canonical_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// Normalizes a Duo device name for comparison.  Phone numbers are masked differently between
+/// logs (`864-555-0100` vs `XXX-XXX-0100`), so only their last four digits are compared.
+pub(crate) fn device_key(device: &str) -> String {
+    let device = device
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>()
+        .to_lowercase();
+
+    if device.len() >= 4 && device.chars().all(|c| c.is_ascii_digit() || c == 'x') {
+        device[device.len() - 4..].to_owned()
+    } else {
+        device
     }
 }
 
@@ -0,0 +1,1705 @@
+#![cfg(test)]
+use super::admin::{AdminAction, AdminEvent};
+use super::login::{
+    repeat_runs, Factor, FlagReason, FraudDisposition, Integration, LocationSource, LogFields,
+    Login, LoginRecord, LoginResult, Reason, ResultMap,
+};
+use super::vpnlog::VpnLog;
+use super::{
+    canonical_name, map_url, months_before, normalize_state, parse_coordinates, same_state,
+    zone::Zone, ActivitySummary, Allowlist, FailureMatch, FailurePolicy, IpRange, Location,
+    LocationCount, NewAccountPass, QueueStats, ScoreComponent, Scoring, StateCode, User,
+};
+use crate::queries::ip::IpDB;
+use crate::storage::Storage;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+
+// -------------------- Login parsing --------------------
+
+/// Older Duo log, every field is a plain string
+const LOG_V1: &str = r#"{"preview":false,"result":{"_raw":"{\"access_device\": {\"browser\": \"Chrome\", \"browser_version\": \"116.0.0.0\", \"os\": \"Windows\", \"os_version\": \"10\"}, \"device\": \"864-555-0100\", \"factor\": \"Duo Push\", \"integration\": \"Shibboleth\", \"ip\": \"130.127.1.2\", \"reason\": \"User approved\", \"result\": \"SUCCESS\", \"timestamp\": 1693999999, \"username\": \"tiger\"}","_time":"2023-09-06 07:33:19.000 EDT","user":"tiger"}}"#;
+
+/// Newer Duo log, the user, application, and devices are objects
+const LOG_V2: &str = r#"{"preview":false,"result":{"_raw":"{\"access_device\": {\"browser\": \"Safari\", \"browser_version\": \"16.6\", \"ip\": \"8.8.4.4\", \"location\": {\"city\": \"Clemson\", \"country\": \"United States\", \"state\": \"South Carolina\"}, \"os\": \"Mac OS X\", \"os_version\": \"13.5\"}, \"application\": {\"key\": \"DIABC123\", \"name\": \"Shibboleth\"}, \"auth_device\": {\"ip\": \"8.8.8.8\", \"name\": \"864-555-0100\"}, \"factor\": \"Duo Push\", \"reason\": \"User approved\", \"result\": \"SUCCESS\", \"trusted_endpoint_status\": \"not trusted\", \"user\": {\"key\": \"DU123\", \"name\": \"tiger\"}}","_time":"2023-09-06 07:33:20.000 EDT","user":"tiger"}}"#;
+
+/// Cut off mid line, so only regex can make sense of it
+const LOG_TRUNCATED: &str = r#"{"preview":false,"result":{"_raw":"{\"factor\": \"Duo Push\", \"ip\": \"130.127.1.2\", \"reason\": \"User approved\", \"result\": \"FAILURE\"}","_time":"2023-09-06 07:33:21.000 EDT","user":"tiger""#;
+
+/// No top level IP, the auth device comes first and the access device's location is before its IP
+const LOG_ACCESS_DEVICE: &str = r#"{"preview":false,"result":{"_raw":"{\"auth_device\": {\"ip\": \"8.8.8.8\", \"name\": \"864-555-0100\"}, \"access_device\": {\"browser\": \"Firefox\", \"location\": {\"city\": \"Toronto\", \"country\": \"Canada\", \"state\": \"Ontario\"}, \"ip\": \"8.8.4.4\"}, \"factor\": \"Duo Push\", \"reason\": \"User approved\", \"result\": \"SUCCESS\", \"user\": {\"key\": \"DU123\", \"name\": \"tiger\"}}","_time":"2023-09-06 07:33:23.000 EDT","user":"tiger"}}"#;
+
+/// The raw log isn't JSON and the user is an object, so only JSON paths can find the user
+const LOG_NESTED: &str = r#"{"preview":false,"result":{"_raw":"tiger approved a push","_time":"2023-09-06 07:33:22.000 EDT","factor":"Duo Push","result":"SUCCESS","user":{"key":"DU123","name":"tiger"}}}"#;
+
+#[test]
+fn login_fields_v1() {
+    let json = LogFields::from_json(LOG_V1).unwrap();
+    let regex = LogFields::from_regex(LOG_V1);
+
+    assert_eq!(json.user.as_deref(), Some("tiger"));
+    assert_eq!(json.time.as_deref(), Some("2023-09-06 07:33:19.000 EDT"));
+    assert_eq!(json.ip.as_deref(), Some("130.127.1.2"));
+
+    // Both paths agree on everything regex can find, serde also gets the access device
+    assert_eq!(
+        json,
+        LogFields {
+            browser: Some("Chrome".to_owned()),
+            browser_version: Some("116.0.0.0".to_owned()),
+            os: Some("Windows".to_owned()),
+            os_version: Some("10".to_owned()),
+            ..regex
+        }
+    );
+}
+
+#[test]
+fn login_fields_v2() {
+    let json = LogFields::from_json(LOG_V2).unwrap();
+    let regex = LogFields::from_regex(LOG_V2);
+
+    for fields in [&json, &regex] {
+        assert_eq!(fields.user.as_deref(), Some("tiger"));
+        assert_eq!(fields.time.as_deref(), Some("2023-09-06 07:33:20.000 EDT"));
+        assert_eq!(fields.factor.as_deref(), Some("Duo Push"));
+        assert_eq!(fields.reason.as_deref(), Some("User approved"));
+        assert_eq!(fields.result.as_deref(), Some("SUCCESS"));
+        assert_eq!(fields.ip.as_deref(), Some("8.8.4.4"));
+        assert_eq!(fields.city.as_deref(), Some("Clemson"));
+        assert_eq!(fields.state.as_deref(), Some("South Carolina"));
+        assert_eq!(fields.country.as_deref(), Some("United States"));
+    }
+
+    // Only serde can see into the nested objects
+    assert_eq!(json.integration.as_deref(), Some("Shibboleth"));
+    assert_eq!(json.device.as_deref(), Some("864-555-0100"));
+    assert_eq!(json.browser.as_deref(), Some("Safari"));
+    assert_eq!(json.os.as_deref(), Some("Mac OS X"));
+    assert_eq!(json.browser_version.as_deref(), Some("16.6"));
+    assert_eq!(json.os_version.as_deref(), Some("13.5"));
+    assert_eq!(json.trusted_device, Some(false));
+    assert_eq!(json.application_key.as_deref(), Some("DIABC123"));
+
+    assert_eq!(regex.integration, None);
+    assert_eq!(regex.browser, None);
+    assert_eq!(regex.trusted_device, None);
+}
+
+#[test]
+fn login_fields_fallback() {
+    assert_eq!(LogFields::from_json(LOG_TRUNCATED), None);
+
+    let regex = LogFields::from_regex(LOG_TRUNCATED);
+    assert_eq!(regex.browser, None);
+    assert_eq!(regex.os, None);
+    assert_eq!(regex.user.as_deref(), Some("tiger"));
+    assert_eq!(regex.result.as_deref(), Some("FAILURE"));
+    assert_eq!(regex.ip.as_deref(), Some("130.127.1.2"));
+}
+
+#[test]
+fn login_fields_access_device() {
+    let json = LogFields::from_json(LOG_ACCESS_DEVICE).unwrap();
+    let regex = LogFields::from_regex(LOG_ACCESS_DEVICE);
+    // Cut off after the access device, so only regex can read it
+    let truncated =
+        LogFields::from_regex(&LOG_ACCESS_DEVICE[..LOG_ACCESS_DEVICE.find("factor").unwrap()]);
+    assert_eq!(
+        LogFields::from_json(&LOG_ACCESS_DEVICE[..LOG_ACCESS_DEVICE.find("factor").unwrap()]),
+        None
+    );
+
+    for fields in [&json, &regex, &truncated] {
+        assert_eq!(fields.ip.as_deref(), Some("8.8.4.4"));
+        assert_eq!(fields.city.as_deref(), Some("Toronto"));
+        assert_eq!(fields.state.as_deref(), Some("Ontario"));
+        assert_eq!(fields.country.as_deref(), Some("Canada"));
+    }
+}
+
+#[test]
+fn login_fields_value() {
+    assert_eq!(LogFields::from_json(LOG_NESTED), None);
+
+    let regex = LogFields::from_regex(LOG_NESTED);
+    assert_eq!(regex.user, None);
+    assert_eq!(regex.time.as_deref(), Some("2023-09-06 07:33:22.000 EDT"));
+
+    let fields = regex.or(LogFields::from_value(LOG_NESTED).unwrap());
+    assert_eq!(fields.user.as_deref(), Some("tiger"));
+    assert_eq!(fields.factor.as_deref(), Some("Duo Push"));
+    assert_eq!(fields.result.as_deref(), Some("SUCCESS"));
+
+    // Truncated lines aren't JSON at all
+    assert_eq!(LogFields::from_value(LOG_TRUNCATED), None);
+}
+
+/// Shibboleth approval in the same second as [LOG_CUVPN]
+const LOG_SHIB: &str = r#"{"preview":false,"result":{"_raw":"{\"factor\": \"Duo Push\", \"integration\": \"Shibboleth\", \"ip\": \"130.127.1.2\", \"reason\": \"User approved\", \"result\": \"SUCCESS\", \"username\": \"tiger\"}","_time":"2023-09-06 07:33:19.000 EDT","user":"tiger"}}"#;
+
+/// CUVPN denial in the same second as [LOG_SHIB]
+const LOG_CUVPN: &str = r#"{"preview":false,"result":{"_raw":"{\"factor\": \"Duo Push\", \"integration\": \"Clemson University VPN\", \"ip\": \"8.8.8.8\", \"reason\": \"User mistake\", \"result\": \"FAILURE\", \"username\": \"tiger\"}","_time":"2023-09-06 07:33:19.000 EDT","user":"tiger"}}"#;
+
+fn parse(line: &str) -> Login {
+    Login::new(line, &IpDB::empty(), Zone::Utc).unwrap()
+}
+
+#[test]
+fn duo_location_fallback() {
+    // Neither IP is in an empty IpDB, so Duo's location is all there is
+    let login = parse(LOG_V2);
+    assert_eq!(login.ip, Some("8.8.4.4".parse().unwrap()));
+    assert_eq!(login.country.as_deref(), Some("US"));
+    assert_eq!(login.state.as_deref(), Some("South Carolina"));
+    assert_eq!(login.city.as_deref(), Some("Clemson"));
+    assert_eq!(login.location, None);
+    assert_eq!(login.location_source, Some(LocationSource::Duo));
+
+    let login = parse(LOG_ACCESS_DEVICE);
+    assert_eq!(login.country.as_deref(), Some("Canada"));
+    assert_eq!(login.city.as_deref(), Some("Toronto"));
+
+    // Nothing to go on at all
+    let login = parse(LOG_SHIB);
+    assert_eq!(login.country, None);
+    assert_eq!(login.location_source, None);
+}
+
+#[test]
+fn raw_event_pretty() {
+    let mut login = parse(LOG_V2);
+    assert_eq!(login.raw_pretty(), None);
+
+    // The Duo log is unpacked from its string
+    login.raw = Some(LOG_V2.to_owned());
+    let pretty = login.raw_pretty().unwrap();
+    assert!(pretty.contains("\"_raw\": {"));
+    assert!(pretty.contains("\"city\": \"Clemson\""));
+
+    login.raw = Some(LOG_TRUNCATED.to_owned());
+    assert_eq!(login.raw_pretty().as_deref(), Some(LOG_TRUNCATED));
+}
+
+#[test]
+fn result_map() {
+    let map = ResultMap {
+        failure: vec!["DENIED_BY_POLICY".to_owned()],
+        fraud: vec!["phished".to_owned(), "Denied_By_Policy".to_owned()],
+    };
+    assert_eq!(map.map(&"PHISHED".into()), Some(LoginResult::Fraud));
+    // Fraud wins when listed as both
+    assert_eq!(
+        map.map(&"DENIED_BY_POLICY".into()),
+        Some(LoginResult::Fraud)
+    );
+    assert_eq!(map.map(&"ERROR".into()), None);
+    // Known results are never remapped
+    assert_eq!(map.map(&LoginResult::Success), None);
+
+    let map = ResultMap {
+        failure: vec!["error".to_owned()],
+        ..ResultMap::default()
+    };
+    let mut login = parse(LOG_V2);
+    login.result = "ERROR".into();
+    login.map_result(&map);
+    assert_eq!(login.result, LoginResult::Failure);
+    assert_eq!(login.format_result(), "ERROR");
+    assert_eq!(LoginRecord::new(&login, Zone::Utc).result, "ERROR");
+
+    let mut login = parse(LOG_V2);
+    login.map_result(&map);
+    assert_eq!(login.duo_result, None);
+    assert_eq!(login.format_result(), "Success");
+}
+
+#[test]
+fn dedup_same_second() {
+    let shib = parse(LOG_SHIB);
+    let cuvpn = parse(LOG_CUVPN);
+    assert_eq!(shib.time, cuvpn.time);
+    assert_ne!(shib, cuvpn);
+
+    // Duplicates are split up by the other event, sorting has to bring them back together
+    let mut logins = vec![shib.clone(), cuvpn.clone(), shib.clone(), cuvpn.clone()];
+    logins.sort();
+    logins.dedup();
+    assert_eq!(logins.len(), 2);
+    assert!(logins.contains(&shib));
+    assert!(logins.contains(&cuvpn));
+}
+
+#[test]
+fn repeat_runs_collapse() {
+    let push = |minutes| login(minutes, LoginResult::Failure, Reason::NoResponse);
+    let mut logins = vec![
+        login(1, LoginResult::Success, Reason::UserApproved),
+        push(2),
+        push(3),
+        push(4),
+        login(5, LoginResult::Success, Reason::UserApproved),
+        push(6),
+        push(7),
+    ];
+    let all: Vec<usize> = (0..logins.len()).collect();
+    assert_eq!(repeat_runs(&logins, &all), vec![1..4, 5..7]);
+
+    // Flagged logins always get their own row
+    logins[2].flag_reasons.push(FlagReason::Fatigue);
+    assert_eq!(repeat_runs(&logins, &all), vec![5..7]);
+    logins[2].flag_reasons.clear();
+
+    // Another IP breaks the run
+    logins[3].ip = Some("8.8.8.8".parse().unwrap());
+    assert_eq!(repeat_runs(&logins, &all), vec![1..3, 5..7]);
+
+    // Runs follow the rows shown, filtering out the approval joins the pushes around it
+    assert_eq!(repeat_runs(&logins, &[1, 2, 5, 6]), vec![0..4]);
+    assert!(repeat_runs(&logins, &[]).is_empty());
+    assert!(repeat_runs(&logins, &[0]).is_empty());
+}
+
+#[test]
+fn dedup_same_second_fields() {
+    let shib = parse(LOG_SHIB);
+
+    let mut result = shib.clone();
+    result.result = LoginResult::Failure;
+    let mut ip = shib.clone();
+    ip.ip = Some("8.8.4.4".parse().unwrap());
+    let mut integration = shib.clone();
+    integration.integration = Integration::CuVpn;
+    for login in [&result, &ip, &integration] {
+        assert_ne!(&shib, login);
+    }
+
+    // Other fields don't make it a different event
+    let mut device = shib.clone();
+    device.device = Some("864-555-0100".to_owned());
+    assert_eq!(shib, device);
+}
+
+#[test]
+fn login_sort_order() {
+    let shib = parse(LOG_SHIB);
+    let cuvpn = parse(LOG_CUVPN);
+    let mut older = shib.clone();
+    older.time -= Duration::seconds(1);
+    let mut newer = cuvpn.clone();
+    newer.time += Duration::seconds(1);
+
+    // Newest first no matter where ties land
+    let mut logins = vec![older.clone(), cuvpn.clone(), newer.clone(), shib.clone()];
+    logins.sort();
+    assert_eq!(logins.first(), Some(&newer));
+    assert_eq!(logins.last(), Some(&older));
+    assert!(logins.windows(2).all(|w| w[0].time >= w[1].time));
+
+    // Ties always land the same way
+    let mut reversed = vec![shib, cuvpn, older, newer];
+    reversed.sort();
+    assert_eq!(logins, reversed);
+}
+
+#[test]
+fn login_record_csv() {
+    let mut login = Login::new(LOG_V1, &IpDB::empty(), Zone::Utc).unwrap();
+    login.city = Some("Clemson".to_owned());
+    login.state = Some("South Carolina".to_owned());
+    login.country = Some("US".to_owned());
+    login.asn = Some("AS12 \"Clemson\" University".to_owned());
+    login.flag_reasons = vec![FlagReason::Travel, FlagReason::NewDevice];
+
+    let record = LoginRecord::new(&login, Zone::Utc);
+    assert_eq!(record.time, "2023-09-06 11:33:19 UTC");
+    // Commas and quotes are quoted, the rest is left alone
+    assert_eq!(
+        record.csv_row(),
+        "2023-09-06 11:33:19 UTC,Success,User approved,Duo push,Shibboleth,130.127.1.2,\
+        \"AS12 \"\"Clemson\"\" University\",\"Clemson, South Carolina, US\",Travel; New device"
+    );
+    assert_eq!(
+        record.csv_row().split(',').count(),
+        LoginRecord::CSV_HEADER.len() + 2
+    );
+}
+
+// -------------------- Usernames --------------------
+
+#[test]
+fn username_case() {
+    assert_eq!(canonical_name(" JSmith "), "jsmith");
+
+    // Same login under either casing, so they dedup
+    let upper = parse(&LOG_SHIB.replace("tiger", "TIGER"));
+    assert_eq!(upper.user, "tiger");
+    assert_eq!(upper, parse(LOG_SHIB));
+}
+
+#[test]
+fn investigated_any_case() {
+    let storage = Storage::in_memory();
+    storage.mark_investigated("TIGER".to_owned(), true, Some("Called them"));
+    assert!(storage.investigated("tiger"));
+    assert!(storage.investigated("Tiger"));
+    assert_eq!(
+        storage.investigated_note("tiger").as_deref(),
+        Some("Called them")
+    );
+
+    storage.mark_investigated("tiger".to_owned(), false, None);
+    assert!(!storage.investigated("TIGER"));
+
+    storage.mark_many_investigated(&["Cat".to_owned()]);
+    assert!(storage.investigated("cat"));
+}
+
+#[test]
+fn investigated_note_kept() {
+    let storage = Storage::in_memory();
+    storage.mark_investigated("tiger".to_owned(), true, Some("Called them"));
+
+    // Bulk ignoring doesn't wipe the note
+    storage.mark_many_investigated(&["tiger".to_owned()]);
+    assert!(storage.investigated("tiger"));
+    assert_eq!(
+        storage.investigated_note("tiger").as_deref(),
+        Some("Called them")
+    );
+
+    // Nor does un-ignoring, so it's there when they reappear
+    storage.mark_investigated("tiger".to_owned(), false, None);
+    assert!(!storage.investigated("tiger"));
+    assert!(storage.list_investigated().is_empty());
+    assert_eq!(
+        storage.investigated_note("tiger").as_deref(),
+        Some("Called them")
+    );
+
+    // Ignoring again without a note keeps it, a new note replaces it
+    storage.mark_investigated("tiger".to_owned(), true, None);
+    assert_eq!(
+        storage.investigated_note("tiger").as_deref(),
+        Some("Called them")
+    );
+    storage.mark_investigated("tiger".to_owned(), true, Some("Traveling"));
+    storage.clear_investigated();
+    assert!(!storage.investigated("tiger"));
+    assert_eq!(
+        storage.investigated_note("tiger").as_deref(),
+        Some("Traveling")
+    );
+}
+
+#[test]
+fn investigated_history() {
+    let storage = Storage::in_memory();
+    storage.set_analyst_name("Sam".to_owned());
+    storage.mark_investigated("tiger".to_owned(), true, Some("Traveling"));
+    storage.mark_investigated("Tiger".to_owned(), false, None);
+    storage.mark_many_investigated(&["cat".to_owned(), "tigress".to_owned()]);
+    storage.clear_investigated();
+    assert!(storage.list_investigated().is_empty());
+
+    // Un-ignores are kept alongside ignores, newest first, for any name containing the search
+    let history = storage.investigated_history("TIG");
+    let events: Vec<(&str, bool)> = history
+        .iter()
+        .map(|e| (e.name.as_str(), e.ignored))
+        .collect();
+    assert_eq!(
+        events,
+        [
+            ("tigress", false),
+            ("tigress", true),
+            ("tiger", false),
+            ("tiger", true)
+        ]
+    );
+    assert!(history.iter().all(|e| e.analyst == "Sam"));
+    assert_eq!(history[3].note, "Traveling");
+    assert_eq!(storage.investigated_history("TIGER").len(), 2);
+
+    assert_eq!(storage.investigated_history("").len(), 6);
+}
+
+// -------------------- Vibe checks --------------------
+
+fn now() -> DateTime<Utc> {
+    Utc::now()
+}
+
+/// HDTools creation dates are local
+fn created(days_ago: i64) -> Option<NaiveDateTime> {
+    Some(chrono::Local::now().naive_local() - Duration::days(days_ago))
+}
+
+/// A login from Clemson, `minutes` ago
+pub(crate) fn login(minutes: i64, result: LoginResult, reason: Reason) -> Login {
+    Login {
+        time: now() - Duration::minutes(minutes),
+        user: "tiger".to_owned(),
+        device: Some("864-555-0100".to_owned()),
+        factor: Factor::DuoPush,
+        integration: Integration::Shibboleth,
+        reason,
+        result,
+        duo_result: None,
+        fraud_disposition: None,
+        ip: Some("130.127.1.2".parse().unwrap()),
+        city: Some("Clemson".to_owned()),
+        country: Some("US".to_owned()),
+        state: Some("South Carolina".to_owned()),
+        location: Some((34.68, -82.84)),
+        location_source: Some(LocationSource::Database),
+        is_relay: false,
+        asn: None,
+        flag_reasons: vec![],
+        browser: None,
+        browser_version: None,
+        os: None,
+        os_version: None,
+        trusted_device: None,
+        application_key: None,
+        raw: None,
+    }
+}
+
+/// Checks logins from a day back, plus the default travel lookback
+pub(crate) fn new_user(logins: Vec<Login>) -> User {
+    let lookback = Scoring::default().max_impossible_travel_time();
+    User::new(
+        "tiger".to_owned(),
+        logins,
+        &(now() - Duration::days(1)),
+        lookback,
+    )
+}
+
+#[test]
+fn lookback_window() {
+    let scoring = Scoring::default();
+    // Half the earth at 1000 kph
+    assert_eq!(
+        scoring.max_impossible_travel_time(),
+        Duration::minutes(1200)
+    );
+
+    let earliest = now() - Duration::days(1);
+    let logins = || {
+        vec![
+            login(24 * 60 + 1199, LoginResult::Success, Reason::UserApproved),
+            login(24 * 60 + 1201, LoginResult::Success, Reason::UserApproved),
+        ]
+    };
+    let user = User::new(
+        "tiger".to_owned(),
+        logins(),
+        &earliest,
+        scoring.max_impossible_travel_time(),
+    );
+    assert_eq!(user.checked_login_count, 1);
+
+    // Slower travel takes longer to cross the earth, so more logins are checked
+    let scoring = Scoring {
+        max_kph: 500,
+        ..Scoring::default()
+    };
+    assert_eq!(
+        scoring.max_impossible_travel_time(),
+        Duration::minutes(2401)
+    );
+    let user = User::new(
+        "tiger".to_owned(),
+        logins(),
+        &earliest,
+        scoring.max_impossible_travel_time(),
+    );
+    assert_eq!(user.checked_login_count, 2);
+}
+
+/// In state, no travel, and nothing else funky, only the OFAC deny
+fn ofac_user() -> User {
+    let logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Failure, Reason::RestrictedOFAC),
+        login(30, LoginResult::Success, Reason::UserApproved),
+    ];
+    new_user(logins)
+}
+
+#[test]
+fn ofac_deny_flagged() {
+    let mut user = ofac_user();
+
+    assert!(user.in_state());
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(user.reasons, vec![FlagReason::Restricted]);
+    assert_eq!(user.score, 25);
+    assert_eq!(user.logins[1].flag_reasons, vec![FlagReason::Restricted]);
+
+    // Re-running doesn't double count
+    let score = user.score;
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(user.score, score);
+}
+
+#[test]
+fn ofac_deny_survives_second_check() {
+    let mut user = ofac_user();
+    user.first_vibe_check(&Allowlist::default(), &Scoring::default());
+    user.location = Some(Location {
+        city: "Clemson".to_owned(),
+        state: Some("SC".to_owned()),
+        country: None,
+    });
+    user.creation_date = created(30);
+
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+#[test]
+fn months_calendar() {
+    let time = |s| NaiveDateTime::parse_from_str(s, "%F %T").unwrap();
+    assert_eq!(
+        months_before(time("2023-08-31 12:00:00"), 6),
+        time("2023-02-28 12:00:00")
+    );
+    assert_eq!(
+        months_before(time("2024-08-31 12:00:00"), 6),
+        time("2024-02-29 12:00:00")
+    );
+    assert_eq!(
+        months_before(time("2024-03-15 00:00:00"), 6),
+        time("2023-09-15 00:00:00")
+    );
+}
+
+/// Out of state with a deny for not being enrolled, and no HDTools data yet
+fn hdtools_user(state: &str) -> User {
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Failure, Reason::DenyUnenrolledUser),
+    ];
+    for login in &mut logins {
+        login.state = Some(state.to_owned());
+    }
+    new_user(logins)
+}
+
+fn home(state: &str) -> Option<Location> {
+    Some(Location {
+        city: "Clemson".to_owned(),
+        state: Some(state.to_owned()),
+        country: None,
+    })
+}
+
+#[test]
+fn second_check_partial_data() {
+    // Nothing from HDTools can't pass anyone
+    assert!(!hdtools_user("Ohio").second_vibe_check(&Scoring::default()));
+
+    // Location alone passes activity from home
+    let mut user = hdtools_user("South Carolina");
+    user.location = home("SC");
+    assert!(user.second_vibe_check(&Scoring::default()));
+
+    let mut user = hdtools_user("Ohio");
+    user.location = home("SC");
+    assert!(!user.second_vibe_check(&Scoring::default()));
+
+    // Creation date alone passes a new account
+    let mut user = hdtools_user("Ohio");
+    user.creation_date = created(30);
+    assert!(user.second_vibe_check(&Scoring::default()));
+
+    let mut user = hdtools_user("Ohio");
+    user.creation_date = created(365);
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+#[test]
+fn second_check_month_boundary() {
+    let time = |s| NaiveDateTime::parse_from_str(s, "%F %T").unwrap();
+    let mut user = hdtools_user("Ohio");
+    user.logins[0].time = Zone::Local.to_utc(time("2023-08-31 12:00:00"));
+
+    // Older than 180 days, but inside six calendar months
+    user.creation_date = Some(time("2023-03-01 12:00:00"));
+    assert!(user.second_vibe_check(&Scoring::default()));
+
+    user.creation_date = Some(time("2023-02-27 12:00:00"));
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+#[test]
+fn second_check_new_account_window() {
+    let mut user = hdtools_user("Ohio");
+    user.creation_date = created(120);
+    assert!(user.second_vibe_check(&Scoring::default()));
+
+    let scoring = Scoring {
+        new_account_months: 3,
+        ..Scoring::default()
+    };
+    assert!(!user.second_vibe_check(&scoring));
+
+    // New accounts only pass when Duo denied them for not being enrolled
+    let mut user = new_user(vec![login(10, LoginResult::Failure, Reason::UserMistake)]);
+    user.creation_date = created(30);
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+/// A new account denied for not being enrolled, with passcodes guessed from overseas since
+fn compromised_new_user() -> User {
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Failure, Reason::DenyUnenrolledUser),
+    ];
+    for minutes in [30, 31, 32] {
+        let mut guess = login(minutes, LoginResult::Failure, Reason::InvalidPasscode);
+        guess.ip = Some("41.58.1.2".parse().unwrap());
+        guess.city = Some("Lagos".to_owned());
+        guess.state = Some("Lagos".to_owned());
+        guess.country = Some("NG".to_owned());
+        guess.location = Some((6.45, 3.39));
+        logins.push(guess);
+    }
+    for login in &mut logins[..2] {
+        login.state = Some("Ohio".to_owned());
+    }
+    let mut user = new_user(logins);
+    user.creation_date = created(30);
+    user
+}
+
+#[test]
+fn second_check_compromised_new_account() {
+    let mut user = compromised_new_user();
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert!(user.reasons.contains(&FlagReason::Failure));
+    assert!(user.reasons.contains(&FlagReason::Travel));
+    // Only the hop to Lagos, the guesses after it are all from there
+    assert_eq!(user.travel_hops, vec![(1, 2)]);
+    assert!(!user.second_vibe_check(&Scoring::default()));
+
+    let lenient = Scoring {
+        new_account_pass: NewAccountPass::Lenient,
+        ..Scoring::default()
+    };
+    assert!(user.second_vibe_check(&lenient));
+
+    // Failures alone still aren't onboarding unless they're all unenrolled denies
+    user.reasons = vec![FlagReason::Failure];
+    assert!(!user.second_vibe_check(&Scoring::default()));
+
+    for pass in NewAccountPass::choices() {
+        assert_eq!(pass.to_string().parse(), Ok(pass));
+    }
+}
+
+#[test]
+fn second_check_onboarding() {
+    // Denied until the first device was enrolled, then approved
+    let mut user = hdtools_user("Ohio");
+    user.first_vibe_check(&Allowlist::default(), &Scoring::default());
+    user.creation_date = created(30);
+    assert!(user.second_vibe_check(&Scoring::default()));
+}
+
+#[test]
+fn second_check_onboarding_enrollment() {
+    // Enrolling the first device is what onboarding is, so the activation flagged next to the
+    // unenrolled deny doesn't stop the pass
+    let mut logins = vec![
+        login(10, LoginResult::Failure, Reason::DenyUnenrolledUser),
+        login(20, LoginResult::Failure, Reason::DenyUnenrolledUser),
+    ];
+    for login in &mut logins {
+        login.state = Some("Ohio".to_owned());
+    }
+    let mut user = new_user(logins);
+    user.creation_date = created(30);
+    user.admin_events = vec![activation(5)];
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(
+        user.reasons,
+        vec![FlagReason::Failure, FlagReason::Enrollment]
+    );
+    assert!(user.second_vibe_check(&Scoring::default()));
+
+    // It still doesn't cover the guesses from overseas
+    let mut user = compromised_new_user();
+    user.admin_events = vec![activation(5)];
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert!(user.reasons.contains(&FlagReason::Enrollment));
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+#[test]
+fn account_age() {
+    let mut user = new_user(vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(30 * 60, LoginResult::Failure, Reason::InvalidPasscode),
+    ]);
+    assert_eq!(user.account_age(), None);
+
+    // Measured from the latest login until something is flagged
+    user.creation_date = created(4);
+    let (age, flagged) = user.account_age().unwrap();
+    assert_eq!((age.num_days(), flagged), (3, false));
+
+    user.logins[1].flag_reasons.push(FlagReason::Failure);
+    let (age, flagged) = user.account_age().unwrap();
+    assert_eq!((age.num_days(), flagged), (2, true));
+}
+
+#[test]
+fn second_check_no_logins() {
+    // Merging can leave a user with nothing, which shouldn't pass or panic
+    let mut user = new_user(vec![]);
+    user.creation_date = created(30);
+    user.location = home("SC");
+    assert!(!user.second_vibe_check(&Scoring::default()));
+}
+
+/// One success from `device` in the checked window, on top of older history from `history`
+fn device_user(device: Option<&str>, history: &[Option<&str>]) -> User {
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Success, Reason::UserApproved),
+    ];
+    logins[0].device = device.map(str::to_owned);
+    logins[1].device = device.map(str::to_owned);
+    for (i, old_device) in history.iter().enumerate() {
+        let mut old = login(
+            (3 + i as i64) * 24 * 60,
+            LoginResult::Success,
+            Reason::UserApproved,
+        );
+        old.device = old_device.map(str::to_owned);
+        logins.push(old);
+    }
+    new_user(logins)
+}
+
+#[test]
+fn new_device_flagged() {
+    let mut user = device_user(Some("864-555-0199"), &[Some("864-555-0100")]);
+
+    assert_eq!(user.checked_login_count, 2);
+    assert_eq!(user.flag_new_device(), 1);
+    // Only the first success from the device is flagged
+    assert_eq!(user.logins[1].flag_reasons, vec![FlagReason::NewDevice]);
+    assert!(user.logins[0].flag_reasons.is_empty());
+}
+
+#[test]
+fn new_device_masked_phone() {
+    let mut user = device_user(Some("XXX-XXX-0100"), &[Some("864-555-0100")]);
+    assert_eq!(user.flag_new_device(), 0);
+
+    let mut user = device_user(Some("iPhone 14"), &[Some("iphone 14")]);
+    assert_eq!(user.flag_new_device(), 0);
+}
+
+#[test]
+fn new_device_missing() {
+    // No device on the new logins
+    let mut user = device_user(None, &[Some("864-555-0100")]);
+    assert_eq!(user.flag_new_device(), 0);
+
+    // No history to compare against
+    let mut user = device_user(Some("864-555-0199"), &[]);
+    assert_eq!(user.flag_new_device(), 0);
+
+    // History without devices is no history at all
+    let mut user = device_user(Some("864-555-0199"), &[None, None]);
+    assert_eq!(user.flag_new_device(), 0);
+}
+
+#[test]
+fn new_device_perfect_history() {
+    let mut user = device_user(Some("864-555-0199"), &[Some("864-555-0100")]);
+    for login in &mut user.logins {
+        login.state = Some("Ohio".to_owned());
+    }
+
+    // No failures, but the approvals came from a phone the user has never used
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(user.reasons, vec![FlagReason::NewDevice]);
+    assert_eq!(user.score, 5);
+}
+
+/// A success followed by failures that each share less with it, newest first
+fn failure_user() -> User {
+    let success = login(0, LoginResult::Success, Reason::UserApproved);
+    // Same IP and integration
+    let same = login(5, LoginResult::Failure, Reason::UserMistake);
+    // Same IP, different integration
+    let mut integration = login(10, LoginResult::Failure, Reason::UserMistake);
+    integration.integration = Integration::Citrix;
+    // Same /24, different IP and integration
+    let mut subnet = login(15, LoginResult::Failure, Reason::UserMistake);
+    subnet.integration = Integration::Citrix;
+    subnet.ip = Some("130.127.1.99".parse().unwrap());
+    // Same IP and integration, but long before the success
+    let old = login(45, LoginResult::Failure, Reason::UserMistake);
+    new_user(vec![success, same, integration, subnet, old])
+}
+
+#[test]
+fn failure_policy_default() {
+    let user = failure_user();
+    assert_eq!(
+        FailurePolicy::default(),
+        FailurePolicy {
+            window_minutes: 30,
+            matching: FailureMatch::IpIntegration,
+            min_count: 1,
+        }
+    );
+    assert_eq!(
+        user.failures(&Allowlist::default(), &FailurePolicy::default()),
+        3
+    );
+}
+
+#[test]
+fn failure_policy_matching() {
+    let user = failure_user();
+    let policy = |matching| FailurePolicy {
+        matching,
+        ..FailurePolicy::default()
+    };
+    assert_eq!(
+        user.failures(&Allowlist::default(), &policy(FailureMatch::Ip)),
+        2
+    );
+    assert_eq!(
+        user.failures(&Allowlist::default(), &policy(FailureMatch::IpIntegration)),
+        3
+    );
+    assert_eq!(
+        user.failures(&Allowlist::default(), &policy(FailureMatch::Subnet)),
+        1
+    );
+
+    for matching in FailureMatch::choices() {
+        assert_eq!(matching.key().parse(), Ok(matching));
+        assert_eq!(matching.to_string().parse(), Ok(matching));
+    }
+    assert_eq!("subnet".parse(), Ok(FailureMatch::Subnet));
+    assert_eq!("Same /24".parse(), Ok(FailureMatch::Subnet));
+}
+
+#[test]
+fn failure_policy_window() {
+    let user = failure_user();
+    let policy = |window_minutes| FailurePolicy {
+        window_minutes,
+        ..FailurePolicy::default()
+    };
+    assert_eq!(user.failures(&Allowlist::default(), &policy(0)), 4);
+    assert_eq!(user.failures(&Allowlist::default(), &policy(60)), 2);
+}
+
+#[test]
+fn failure_policy_min_count() {
+    let mut user = failure_user();
+    // Out of state so nothing gets shortcut
+    for login in &mut user.logins {
+        login.state = Some("Ohio".to_owned());
+    }
+    let scoring = |min_count| Scoring {
+        failure_policy: FailurePolicy {
+            min_count,
+            ..FailurePolicy::default()
+        },
+        ..Scoring::default()
+    };
+
+    user.first_vibe_check(&Allowlist::default(), &scoring(3));
+    assert!(user.reasons.contains(&FlagReason::Failure));
+    user.first_vibe_check(&Allowlist::default(), &scoring(4));
+    assert!(!user.reasons.contains(&FlagReason::Failure));
+}
+
+#[test]
+fn trusted_ranges() {
+    let range: IpRange = "130.127.1.2/16".parse().unwrap();
+    assert_eq!(range.to_string(), "130.127.0.0/16");
+    assert!(range.contains("130.127.255.255".parse().unwrap()));
+    assert!(!range.contains("130.128.0.0".parse().unwrap()));
+    assert_eq!(
+        "10.1.2.3".parse::<IpRange>().map(|r| r.to_string()),
+        Ok("10.1.2.3/32".to_owned())
+    );
+    let everything: IpRange = "0.0.0.0/0".parse().unwrap();
+    assert!(everything.contains(std::net::Ipv4Addr::BROADCAST));
+    for bad in ["130.127.0.0/33", "130.127/16", "campus", "130.127.0.0/"] {
+        assert_eq!(bad.parse::<IpRange>(), Err(()), "{}", bad);
+    }
+
+    // Every failure comes from inside the range
+    let allowlist = Allowlist {
+        ranges: vec!["130.127.1.0/24".parse().unwrap()],
+        ..Allowlist::default()
+    };
+    let user = failure_user();
+    assert_eq!(user.failures(&allowlist, &FailurePolicy::default()), 0);
+}
+
+#[test]
+fn location_overrides() {
+    assert_eq!(parse_coordinates("34.68, -82.84"), Some((34.68, -82.84)));
+    assert_eq!(parse_coordinates(" 6.45 ,3.39 "), Some((6.45, 3.39)));
+    for bad in ["34.68", "91, 0", "0, 181", "north, west", ""] {
+        assert_eq!(parse_coordinates(bad), None, "{}", bad);
+    }
+
+    let storage = Storage::in_memory();
+    let ip = "41.58.1.2".parse().unwrap();
+    storage.set_location_override(ip, Some((6.45, 3.39)));
+    // Setting it again replaces it
+    storage.set_location_override(ip, Some((9.06, 7.49)));
+    assert_eq!(
+        storage.get_location_overrides().get(&ip),
+        Some(&(9.06, 7.49))
+    );
+    storage.set_location_override(ip, None);
+    assert!(storage.get_location_overrides().is_empty());
+}
+
+/// `pushes` unanswered pushes a minute apart, then an approval from the same IP
+fn fatigue_user(pushes: i64) -> User {
+    let mut logins = vec![login(10, LoginResult::Success, Reason::UserApproved)];
+    for i in 0..pushes {
+        logins.push(login(11 + i, LoginResult::Failure, Reason::NoResponse));
+    }
+    // Out of state so nothing gets shortcut
+    for login in &mut logins {
+        login.state = Some("Ohio".to_owned());
+    }
+    new_user(logins)
+}
+
+#[test]
+fn fatigue_flagged() {
+    let mut user = fatigue_user(4);
+
+    // The approval forgives every failure, fatigue has to catch it anyway
+    assert_eq!(
+        user.failures(&Allowlist::default(), &FailurePolicy::default()),
+        0
+    );
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(user.reasons, vec![FlagReason::Fatigue]);
+    assert_eq!(user.score, 15);
+    assert!(user
+        .logins
+        .iter()
+        .all(|l| l.flag_reasons == vec![FlagReason::Fatigue]));
+}
+
+#[test]
+fn fatigue_thresholds() {
+    let scoring = Scoring::default();
+
+    let mut user = fatigue_user(scoring.fatigue_pushes as i64 - 1);
+    assert_eq!(user.flag_fatigue(&scoring), 0);
+
+    // Pushes older than the window don't count, only three of the four are within 3 minutes
+    let mut user = fatigue_user(4);
+    let scoring = Scoring {
+        fatigue_pushes: 4,
+        fatigue_minutes: 3,
+        ..Scoring::default()
+    };
+    assert_eq!(user.flag_fatigue(&scoring), 0);
+
+    let scoring = Scoring {
+        fatigue_pushes: 3,
+        fatigue_minutes: 3,
+        ..Scoring::default()
+    };
+    assert_eq!(user.flag_fatigue(&scoring), 1);
+    assert!(user.logins[4].flag_reasons.is_empty());
+}
+
+#[test]
+fn relay_flagged() {
+    // In state, so only relays and fatigue are checked
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Success, Reason::UserApproved),
+        login(30, LoginResult::Failure, Reason::DenyUnenrolledUser),
+    ];
+    for login in &mut logins {
+        login.is_relay = true;
+    }
+    let mut user = new_user(logins);
+
+    // Off by default
+    assert!(user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+
+    let scoring = Scoring {
+        flag_relays: true,
+        relay_weight: 7,
+        ..Scoring::default()
+    };
+    assert!(!user.first_vibe_check(&Allowlist::default(), &scoring));
+    assert_eq!(user.reasons, vec![FlagReason::Relay]);
+    // Only the successes count
+    assert_eq!(user.score, 14);
+    assert!(user.logins[2].flag_reasons.is_empty());
+
+    // Trusted IPs aren't flagged
+    let allowlist = Allowlist {
+        ips: vec![std::net::Ipv4Addr::new(130, 127, 1, 2)],
+        ..Allowlist::default()
+    };
+    assert!(user.first_vibe_check(&allowlist, &scoring));
+}
+
+#[test]
+fn score_breakdown_adds_up() {
+    let mut user = fatigue_user(4);
+    for _ in 0..2 {
+        assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+        assert_eq!(
+            user.score_breakdown.components,
+            vec![ScoreComponent {
+                reason: FlagReason::Fatigue,
+                count: 1,
+                weight: Some(15),
+                contribution: 15,
+            }]
+        );
+        assert_eq!(user.score_breakdown.total(), user.score);
+    }
+    assert_eq!(
+        user.score_breakdown.to_string(),
+        "MFA fatigue: 1 x 15 = 15 pts\nTotal: 15 pts"
+    );
+}
+
+#[test]
+fn top_reason() {
+    let mut user = new_user(vec![]);
+    assert_eq!(user.top_reason(), None);
+
+    // Unscored reasons fall back to the first one found
+    user.reasons = vec![FlagReason::Failure, FlagReason::Travel];
+    assert_eq!(user.top_reason(), Some(FlagReason::Failure));
+
+    let component = |reason, contribution| ScoreComponent {
+        reason,
+        count: 1,
+        weight: None,
+        contribution,
+    };
+    user.score_breakdown.components = vec![
+        component(FlagReason::Failure, 10),
+        component(FlagReason::Travel, 12),
+        component(FlagReason::Fatigue, 12),
+    ];
+    assert_eq!(user.top_reason(), Some(FlagReason::Travel));
+}
+
+#[test]
+fn rescore_clears_old_flags() {
+    let mut user = fatigue_user(4);
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+
+    // Raising the threshold past the push count clears the user
+    let scoring = Scoring {
+        fatigue_pushes: 5,
+        ..Scoring::default()
+    };
+    assert!(user.first_vibe_check(&Allowlist::default(), &scoring));
+    assert_eq!(user.score, 0);
+    assert!(user.reasons.is_empty());
+    assert!(user.score_breakdown.components.is_empty());
+    assert!(user.logins.iter().all(|l| l.flag_reasons.is_empty()));
+}
+
+/// A device activated by the user themselves, with Duo's description as JSON in a string
+const ADMIN_LOG: &str = r#"{"preview":false,"result":{"_raw":"{\"action\": \"activation_complete\", \"description\": \"{\\\"phone\\\": \\\"+1 864-555-0199\\\"}\", \"object\": \"tiger\", \"timestamp\": 1693999999, \"username\": \"tiger\"}","_time":"2023-09-06 07:33:19.000 EDT","action":"activation_complete","object":"tiger"}}"#;
+
+#[test]
+fn admin_event_parsing() {
+    let event = AdminEvent::new(ADMIN_LOG, Zone::Utc).unwrap();
+    assert_eq!(event.action, AdminAction::Activation);
+    assert_eq!(event.admin.as_deref(), Some("tiger"));
+    assert_eq!(
+        event.description.as_deref(),
+        Some(r#"{"phone": "+1 864-555-0199"}"#)
+    );
+    // The zone name in the time wins over the Splunk zone
+    assert_eq!(
+        event.time,
+        "2023-09-06T11:33:19Z".parse::<DateTime<Utc>>().unwrap()
+    );
+
+    assert_eq!(AdminAction::from("bypass_create"), AdminAction::BypassCode);
+    assert!(AdminEvent::new(
+        r#"{"result":{"_time":"2023-09-06 07:33:19.000 EDT"}}"#,
+        Zone::Utc
+    )
+    .is_none());
+}
+
+fn activation(minutes: i64) -> AdminEvent {
+    AdminEvent {
+        time: now() - Duration::minutes(minutes),
+        action: AdminAction::Activation,
+        admin: Some("tiger".to_owned()),
+        description: None,
+    }
+}
+
+#[test]
+fn enrollment_flagged_with_other_flags() {
+    let mut user = fatigue_user(4);
+    user.admin_events = vec![activation(5)];
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(
+        user.reasons,
+        vec![FlagReason::Fatigue, FlagReason::Enrollment]
+    );
+    assert_eq!(user.score, 25);
+
+    // Activations from before the oldest checked login don't count
+    user.admin_events = vec![activation(60 * 24 * 30)];
+    assert!(!user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert_eq!(user.reasons, vec![FlagReason::Fatigue]);
+}
+
+#[test]
+fn enrollment_alone_passes() {
+    // A new phone with nothing else funky is just someone getting a new phone
+    let mut user = new_user(vec![login(10, LoginResult::Success, Reason::UserApproved)]);
+    user.admin_events = vec![activation(5)];
+    assert!(user.first_vibe_check(&Allowlist::default(), &Scoring::default()));
+    assert!(user.reasons.is_empty());
+    assert_eq!(user.enrollments(), 1);
+}
+
+#[test]
+fn search_text() {
+    let mut user = fatigue_user(1);
+    user.logins[0].asn = Some("AS12345 Clemson University".to_owned());
+    user.logins[1].ip = Some("203.0.113.7".parse().unwrap());
+    let text = user.search_text();
+    for needle in [
+        "tiger",
+        "203.0.113.",
+        "130.127.1.2",
+        "clemson university",
+        "ohio",
+    ] {
+        assert!(text.contains(needle), "{} not in {}", needle, text);
+    }
+    assert_eq!(text.matches("130.127.1.2").count(), 1);
+}
+
+#[test]
+fn location_summary_counts() {
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Success, Reason::UserApproved),
+        login(30, LoginResult::Failure, Reason::UserMistake),
+        login(40, LoginResult::Success, Reason::UserApproved),
+        login(50, LoginResult::Success, Reason::UserApproved),
+    ];
+    logins[2].city = Some("Lagos".to_owned());
+    logins[2].state = Some("Lagos".to_owned());
+    logins[2].country = Some("NG".to_owned());
+    logins[2].flag_reasons.push(FlagReason::Failure);
+    logins[3].ip = Some("10.0.0.5".parse().unwrap());
+    logins[4].ip = Some("10.0.0.6".parse().unwrap());
+    let user = new_user(logins);
+
+    assert_eq!(
+        user.location_summary(),
+        vec![
+            LocationCount {
+                location: "Clemson, South Carolina, US".to_owned(),
+                count: 2,
+                flagged: 0,
+            },
+            LocationCount {
+                location: "Campus".to_owned(),
+                count: 2,
+                flagged: 0,
+            },
+            LocationCount {
+                location: "Lagos, Lagos, NG".to_owned(),
+                count: 1,
+                flagged: 1,
+            },
+        ]
+    );
+}
+
+#[test]
+fn activity_summary_counts() {
+    let mut logins = vec![
+        login(10, LoginResult::Success, Reason::UserApproved),
+        login(20, LoginResult::Success, Reason::UserApproved),
+        login(30, LoginResult::Failure, Reason::UserMistake),
+        login(40, LoginResult::Success, Reason::UserApproved),
+    ];
+    logins[0].asn = Some("Clemson University".to_owned());
+    logins[1].asn = Some("Clemson University".to_owned());
+    logins[2].state = Some("Lagos".to_owned());
+    logins[2].country = Some("NG".to_owned());
+    logins[2].asn = Some("MTN Nigeria".to_owned());
+    // Only the checked logins count
+    logins.push(login(
+        60 * 24 * 30,
+        LoginResult::Success,
+        Reason::UserApproved,
+    ));
+    logins[4].country = Some("RU".to_owned());
+    let user = new_user(logins);
+
+    let summary = user.activity_summary();
+    assert_eq!(
+        summary,
+        ActivitySummary {
+            countries: vec![("US".to_owned(), 3), ("NG".to_owned(), 1)],
+            states: vec![("South Carolina".to_owned(), 3), ("Lagos".to_owned(), 1)],
+            asns: vec![
+                ("Clemson University".to_owned(), 2),
+                ("MTN Nigeria".to_owned(), 1)
+            ],
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "3 US, 1 NG | 3 South Carolina, 1 Lagos | Clemson University, MTN Nigeria"
+    );
+    assert_eq!(ActivitySummary::default().to_string(), "");
+}
+
+#[test]
+fn incident_summary_text() {
+    let logins = vec![
+        login(10, LoginResult::Fraud, Reason::None),
+        login(20, LoginResult::Success, Reason::UserApproved),
+    ];
+    let mut user = new_user(logins);
+    user.score = 12;
+    user.reasons = vec![FlagReason::Fraud];
+    user.creation_date = NaiveDateTime::parse_from_str("2021-08-01 09:00", "%F %H:%M").ok();
+    user.location = Some(Location {
+        city: "Clemson".to_owned(),
+        state: Some("South Carolina".to_owned()),
+        country: Some("US".to_owned()),
+    });
+    user.logins[0].time = "2023-09-06T11:33:19Z".parse().unwrap();
+    user.logins[0].flag_reasons = vec![FlagReason::Fraud];
+
+    assert_eq!(
+        user.incident_summary("Analyst", Zone::Utc),
+        "Duo incident summary for tiger\n\
+        Score: 12 (Fraud)\n\
+        Account created: 2021-08-01\n\
+        Home: Clemson, South Carolina, US\n\
+        Activity: 2 US | 2 South Carolina\n\
+        Flagged logins:\n\
+        - 09/06 11:33 AM UTC: Fraud to Shibboleth from Clemson, South Carolina, US (130.127.1.2) [Fraud]\n\
+        Prepared by Analyst"
+    );
+
+    // Unsigned without a name, and says so when nothing was flagged
+    user.logins[0].flag_reasons.clear();
+    let summary = user.incident_summary("", Zone::Utc);
+    assert!(summary.ends_with("No flagged logins"));
+}
+
+#[test]
+fn queue_stats() {
+    let abroad = |country: &str| {
+        let mut l = login(10, LoginResult::Success, Reason::UserApproved);
+        l.country = Some(country.to_owned());
+        l
+    };
+    let mut traveler = new_user(vec![
+        abroad("NG"),
+        abroad("NG"),
+        abroad("United States"),
+        abroad("GB"),
+    ]);
+    traveler.reasons = vec![FlagReason::Travel, FlagReason::Failure];
+    traveler.score = 40;
+    traveler.investigated = true;
+    let mut homebody = new_user(vec![abroad("NG"), abroad("CA")]);
+    homebody.reasons = vec![FlagReason::Failure];
+    homebody.score = 3;
+    homebody.location = Some(Location {
+        city: "Toronto".to_owned(),
+        state: None,
+        country: Some("CA".to_owned()),
+    });
+
+    let stats = QueueStats::new(&[traveler, homebody], std::time::Duration::from_secs(3725));
+    assert_eq!(
+        stats.reasons,
+        vec![(FlagReason::Failure, 2), (FlagReason::Travel, 1)]
+    );
+    assert_eq!((stats.investigated, stats.skipped), (1, 1));
+    assert_eq!(
+        stats.countries,
+        vec![("NG".to_owned(), 2), ("GB".to_owned(), 1)]
+    );
+    assert_eq!(stats.highest_score, 40);
+    assert_eq!(stats.elapsed_text(), "1h 02m 05s");
+}
+
+#[test]
+fn fraud_false_positive() {
+    let mut user = new_user(vec![
+        login(5, LoginResult::Fraud, Reason::None),
+        login(10, LoginResult::Fraud, Reason::None),
+    ]);
+    // Out of state, or the in-state pass would clear the user before fraud is looked at
+    for login in &mut user.logins {
+        login.state = Some("Ohio".to_owned());
+    }
+    user.logins[1].fraud_disposition = Some(FraudDisposition::FalsePositive);
+    user.logins[0].fraud_disposition = Some(FraudDisposition::Pending);
+    user.first_vibe_check(&Allowlist::default(), &Scoring::default());
+    assert_eq!(user.fraud(), 1);
+    assert_eq!(user.logins[0].flag_reasons, [FlagReason::Fraud]);
+    assert!(user.logins[1].flag_reasons.is_empty());
+
+    // Once the other is cleared up too, nothing stops the second vibe check
+    user.logins[0].fraud_disposition = Some(FraudDisposition::FalsePositive);
+    user.first_vibe_check(&Allowlist::default(), &Scoring::default());
+    assert_eq!(user.fraud(), 0);
+    assert!(!user.reasons.contains(&FlagReason::Fraud));
+}
+
+#[test]
+fn fraud_dispositions_saved() {
+    let storage = Storage::in_memory();
+    let time = login(5, LoginResult::Fraud, Reason::None)
+        .time
+        .timestamp_millis();
+    storage.set_fraud_disposition("Tiger", time, Some(FraudDisposition::Confirmed));
+    storage.set_fraud_disposition("tiger", time, Some(FraudDisposition::FalsePositive));
+    assert_eq!(
+        storage.get_fraud_dispositions("tiger").get(&time),
+        Some(&FraudDisposition::FalsePositive)
+    );
+
+    storage.set_fraud_disposition("tiger", time, None);
+    assert!(storage.get_fraud_dispositions("tiger").is_empty());
+}
+
+// -------------------- VPN sessions --------------------
+
+/// An ISE accounting log for a session from `source` given `vpn`, `minute` minutes past 07:00 EDT
+fn vpn_log(minute: u32, status: &str, source: &str, vpn: &str) -> VpnLog {
+    let log = format!(
+        r#"{{"result":{{"_time":"2023-09-06 07:{:02}:00.000 EDT","_raw":"Acct-Status-Type={}, Framed-IP-Address={}, Calling-Station-ID={}, cisco-av-pair=mdm-tlv=device-platform=win, cisco-av-pair=mdm-tlv=device-mac=00-11-22-33-44-55, cisco-av-pair=mdm-tlv=device-platform-version=10, cisco-av-pair=mdm-tlv=user-agent=AnyConnect Windows 4.10, "}}}}"#,
+        minute, status, vpn, source
+    );
+    VpnLog::new(&log, &IpDB::empty(), Zone::Utc).unwrap()
+}
+
+#[test]
+fn vpn_sessions() {
+    let mut logs = vec![
+        vpn_log(0, "Start", "8.8.8.8", "10.0.0.1"),
+        // A second device connecting at the same time isn't a duplicate
+        vpn_log(0, "Start", "8.8.4.4", "10.0.0.2"),
+        vpn_log(0, "Start", "8.8.4.4", "10.0.0.2"),
+        vpn_log(30, "Stop", "8.8.4.4", "10.0.0.2"),
+        vpn_log(45, "Stop", "8.8.8.8", "10.0.0.1"),
+        vpn_log(50, "Start", "8.8.8.8", "10.0.0.3"),
+    ];
+    logs.sort();
+    logs.dedup();
+    assert_eq!(logs.len(), 5);
+
+    let sessions: Vec<(String, String)> = VpnLog::sessions(logs)
+        .iter()
+        .map(|s| (s.vpn_ip.to_string(), s.format_duration()))
+        .collect();
+    assert_eq!(
+        sessions,
+        [
+            ("10.0.0.3".to_owned(), "Ongoing".to_owned()),
+            ("10.0.0.2".to_owned(), "0h 30m".to_owned()),
+            ("10.0.0.1".to_owned(), "0h 45m".to_owned()),
+        ]
+    );
+
+    // A stop whose start came before the search window is still shown
+    let sessions = VpnLog::sessions(vec![vpn_log(10, "Stop", "8.8.8.8", "10.0.0.1")]);
+    assert_eq!(sessions[0].format_duration(), "Start not found");
+}
+
+// -------------------- States --------------------
+
+#[test]
+fn normalize_state_names() {
+    for (name, code) in [
+        ("South Carolina", "SC"),
+        ("south carolina", "SC"),
+        ("SOUTH  CAROLINA", "SC"),
+        ("District of Columbia", "DC"),
+        ("Washington, D.C.", "DC"),
+        ("Washington", "WA"),
+        ("Puerto Rico", "PR"),
+        ("U.S. Virgin Islands", "VI"),
+        ("Guam", "GU"),
+    ] {
+        assert_eq!(normalize_state(name), Some(StateCode(code)), "{}", name);
+        assert_eq!(normalize_state(code), Some(StateCode(code)), "{}", code);
+        assert_eq!(
+            normalize_state(&code.to_lowercase()),
+            Some(StateCode(code)),
+            "{}",
+            code
+        );
+    }
+
+    for garbage in ["", "  ", "Ontario", "S.C.D.", "Carolina"] {
+        assert_eq!(normalize_state(garbage), None, "{}", garbage);
+    }
+}
+
+#[test]
+fn same_state_both_ways() {
+    let mut user = new_user(vec![]);
+    for (home, login) in [
+        ("SC", "South Carolina"),
+        ("South Carolina", "SC"),
+        ("south carolina", "South Carolina"),
+        ("DC", "Washington, D.C."),
+        ("District of Columbia", "DC"),
+        ("Ontario", "ontario"),
+    ] {
+        user.location = Some(Location {
+            city: "Somewhere".to_owned(),
+            state: Some(home.to_owned()),
+            country: None,
+        });
+        assert!(user.same_state(login), "{} vs {}", home, login);
+    }
+    assert!(!user.same_state("Quebec"));
+    assert!(!user.same_state("SC"));
+}
+
+#[test]
+fn same_state_every_abbreviation() {
+    for (name, code) in super::STATE_ABBREVIATIONS
+        .iter()
+        .chain(super::ALTERNATE_STATE_NAMES.iter())
+    {
+        assert!(same_state(name, code), "{} vs {}", name, code);
+        assert!(same_state(code, name), "{} vs {}", code, name);
+        assert!(
+            same_state(&name.to_uppercase(), &code.to_lowercase()),
+            "{} vs {}",
+            name,
+            code
+        );
+    }
+}
+
+#[test]
+fn same_state_messy() {
+    for (a, b) in [
+        ("S. Carolina", "South Carolina"),
+        ("SC", "s. carolina"),
+        ("South Carolina ", " SC"),
+        ("  south   carolina\t", "SOUTH CAROLINA"),
+        ("S.C.", "sc"),
+        ("N. Carolina", "NC"),
+        ("W. Va.", "West Virginia"),
+        ("W.Va.", "WV"),
+        ("Calif.", "California"),
+        ("Mass.", "MA"),
+        ("Penn", "Pennsylvania"),
+        ("Wash. D.C.", "DC"),
+        ("Ontario ", "ONTARIO"),
+        ("Québec", "QUÉBEC"),
+        ("Nouvelle  Aquitaine", "nouvelle aquitaine"),
+    ] {
+        assert!(same_state(a, b), "{:?} vs {:?}", a, b);
+        assert!(same_state(b, a), "{:?} vs {:?}", b, a);
+    }
+
+    for (a, b) in [
+        ("S. Carolina", "N. Carolina"),
+        ("SC", "NC"),
+        ("Wash.", "DC"),
+        ("West Virginia", "Virginia"),
+        ("Ontario", "Quebec"),
+        ("SC", "Ontario"),
+        ("", "SC"),
+    ] {
+        assert!(!same_state(a, b), "{:?} vs {:?}", a, b);
+        assert!(!same_state(b, a), "{:?} vs {:?}", b, a);
+    }
+}
+
+#[test]
+fn in_state_normalized() {
+    let mut user = hdtools_user("SC");
+    assert!(user.in_state());
+
+    user.logins[1].state = Some("south carolina".to_owned());
+    assert!(user.in_state());
+
+    user.logins[1].state = Some("GA".to_owned());
+    assert!(user.in_state());
+
+    user.logins[1].state = Some("Ontario".to_owned());
+    assert!(!user.in_state());
+}
+
+// -------------------- Timezones --------------------
+
+#[test]
+fn zone_strings() {
+    for zone in Zone::choices() {
+        assert_eq!(zone.to_string().parse(), Ok(zone));
+    }
+    assert_eq!("UTC-04:30".parse(), Ok(Zone::Offset(-270)));
+    assert_eq!("UTC-00:30".parse(), Ok(Zone::Offset(-30)));
+    assert_eq!(Zone::Offset(-30).to_string(), "UTC-00:30");
+    assert_eq!("".parse::<Zone>(), Err(()));
+    assert_eq!("EST".parse::<Zone>(), Err(()));
+}
+
+#[test]
+fn zone_round_trip() {
+    let time = NaiveDateTime::parse_from_str("2023-09-06 07:33:19", "%F %T").unwrap();
+    for zone in Zone::choices() {
+        assert_eq!(zone.to_zone(zone.to_utc(time)), time);
+    }
+
+    // Both ends of a travel pair move together, so the gap between them doesn't change
+    let later = time + Duration::minutes(42);
+    for zone in Zone::choices() {
+        assert_eq!(
+            zone.to_utc(later) - zone.to_utc(time),
+            Duration::minutes(42)
+        );
+    }
+}
+
+#[test]
+fn zone_named() {
+    let chicago = Zone::Named(chrono_tz::Tz::America__Chicago);
+    assert_eq!("America/Chicago".parse(), Ok(chicago));
+    assert_eq!(chicago.to_string(), "America/Chicago");
+    assert_eq!("America/Nowhere".parse::<Zone>(), Err(()));
+
+    // The abbreviation follows DST, a fixed offset doesn't
+    let summer = Zone::Utc.parse("2023-07-04 17:00:00.000").unwrap();
+    let winter = Zone::Utc.parse("2023-12-25 17:00:00.000").unwrap();
+    assert_eq!(chicago.abbreviation(summer), "CDT");
+    assert_eq!(chicago.abbreviation(winter), "CST");
+    assert_eq!(Zone::Offset(-360).abbreviation(summer), "UTC-06:00");
+    assert_eq!(chicago.to_zone(summer).format("%T").to_string(), "12:00:00");
+    assert_eq!(chicago.to_zone(winter).format("%T").to_string(), "11:00:00");
+}
+
+#[test]
+fn zone_parse_offsets() {
+    let utc =
+        Zone::Utc.to_utc(NaiveDateTime::parse_from_str("2023-09-06 11:33:19", "%F %T").unwrap());
+
+    // The timestamp's own offset wins over the Splunk zone setting
+    for zone in Zone::choices() {
+        for time in [
+            "2023-09-06 07:33:19.000 EDT",
+            "2023-09-06 06:33:19.000 CDT",
+            "2023-09-06 11:33:19.000 UTC",
+            "2023-09-06 07:33:19.000 -0400",
+            "2023-09-06T07:33:19.000-04:00",
+        ] {
+            assert_eq!(zone.parse(time), Some(utc), "{} in {}", time, zone);
+        }
+    }
+
+    // Without one, or with a name that isn't known, the setting is used
+    for time in ["2023-09-06 07:33:19.000", "2023-09-06 07:33:19.000 XYZ"] {
+        assert_eq!(Zone::Offset(-240).parse(time), Some(utc));
+        assert_eq!(Zone::Utc.parse(time), Some(utc - Duration::hours(4)));
+    }
+    assert_eq!(Zone::Utc.parse("yesterday"), None);
+}
+
+#[test]
+fn zone_parse_dst() {
+    let gap = |a, b| Zone::Local.parse(b).unwrap() - Zone::Local.parse(a).unwrap();
+
+    // Spring forward, 1:59:59 EST is followed by 3:00:00 EDT
+    assert_eq!(
+        gap("2024-03-10 01:59:59.000 EST", "2024-03-10 03:00:00.000 EDT"),
+        Duration::seconds(1)
+    );
+
+    // Fall back, 1:15 EST comes 45 minutes after 1:30 EDT rather than before it
+    assert_eq!(
+        gap("2024-11-03 01:30:00.000 EDT", "2024-11-03 01:15:00.000 EST"),
+        Duration::minutes(45)
+    );
+}
+
+#[test]
+fn zone_mixed_offsets_travel() {
+    let clemson = |time: &str| {
+        let mut login = parse(&LOG_SHIB.replace("2023-09-06 07:33:19.000 EDT", time));
+        login.location = Some((34.68, -82.84));
+        login
+    };
+    let seattle = |time: &str| {
+        let mut login = parse(&LOG_CUVPN.replace("2023-09-06 07:33:19.000 EDT", time));
+        login.location = Some((47.61, -122.33));
+        login
+    };
+    let travel = |logins: Vec<Login>| {
+        let earliest = logins.last().unwrap().time;
+        let mut user = User::new(
+            "tiger".to_owned(),
+            logins,
+            &earliest,
+            Scoring::default().max_impossible_travel_time(),
+        );
+        user.impossible_travel(&Allowlist::default(), Scoring::default().max_kph)
+    };
+
+    // Four and a half hours apart, slow enough to fly
+    let logins = vec![
+        seattle("2023-09-06 09:00:00.000 PDT"),
+        clemson("2023-09-06 07:30:00.000 EDT"),
+    ];
+    assert_eq!(logins[0].time - logins[1].time, Duration::minutes(270));
+    assert_eq!(travel(logins), 0);
+
+    // Read as the same zone they're only an hour and a half apart
+    let logins = vec![
+        seattle("2023-09-06 09:00:00.000 EDT"),
+        clemson("2023-09-06 07:30:00.000 EDT"),
+    ];
+    assert!(travel(logins) > 0);
+}
+
+#[test]
+fn map_link() {
+    assert_eq!(
+        map_url((34.68, -82.84)),
+        "https://www.openstreetmap.org/?mlat=34.68&mlon=-82.84#map=10/34.68/-82.84"
+    );
+}
@@ -1,22 +1,37 @@
 //! One log from `splunk_network_cisco`
 //!
 //! See [super::login] for why there's so much regex
+//!
+//! ISE logs a Start when a VPN session connects and a Stop when it disconnects.  They're paired
+//! into one row per session by [VpnLog::sessions].
+use super::zone::Zone;
 use crate::queries::ip::IpDB;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Duration, Utc};
 use regex::Regex;
 use std::{net::Ipv4Addr, sync::OnceLock};
 
-const DATE_FORMAT: &str = "%F %T%.3f %Z";
-
 static TIME_RE: OnceLock<Regex> = OnceLock::new();
 static VPN_IP_RE: OnceLock<Regex> = OnceLock::new();
 static SOURCE_IP_RE: OnceLock<Regex> = OnceLock::new();
 static PLATFORM_RE: OnceLock<Regex> = OnceLock::new();
 static MAC_RE: OnceLock<Regex> = OnceLock::new();
 static USER_AGENT_RE: OnceLock<Regex> = OnceLock::new();
+static STATUS_RE: OnceLock<Regex> = OnceLock::new();
+
+/// Whether an accounting log opened or closed a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AcctStatus {
+    Start,
+    Stop,
+}
 
+#[derive(Debug)]
 pub struct VpnLog {
-    pub time: NaiveDateTime,
+    /// When the session started, or when it stopped if its start wasn't found
+    pub time: DateTime<Utc>,
+    pub status: AcctStatus,
+    /// When a started session stopped, [None] if it's still going or the stop wasn't found
+    pub end: Option<DateTime<Utc>>,
     pub vpn_ip: Ipv4Addr,
     pub source_ip: Ipv4Addr,
     pub dev_platform: String,
@@ -27,17 +42,20 @@ pub struct VpnLog {
     pub city: Option<String>,
     pub state: Option<String>,
     pub country: Option<String>,
+    /// Latitude and longitude of the source IP
+    pub location: Option<(f32, f32)>,
     /// True if the IP is an identified relay
     pub is_relay: bool,
 }
 
 impl VpnLog {
-    pub fn new(log: &str, ipdb: &IpDB) -> Option<Self> {
+    /// Parses one VPN log, reading its time with [Zone::parse]
+    pub fn new(log: &str, ipdb: &IpDB, zone: Zone) -> Option<Self> {
         let time = TIME_RE
             .get_or_init(|| Regex::new(r#""_time": ?"([^"]+)""#).unwrap())
             .captures(log)?[1]
             .to_string();
-        let time = NaiveDateTime::parse_from_str(&time, DATE_FORMAT).ok()?;
+        let time = zone.parse(&time)?;
         let vpn_ip: Ipv4Addr = VPN_IP_RE
             .get_or_init(|| Regex::new(r#"Framed-IP-Address=([^,]+)"#).unwrap())
             .captures(log)?[1]
@@ -60,17 +78,28 @@ impl VpnLog {
             .get_or_init(|| Regex::new(r#"user-agent=([^,]+)"#).unwrap())
             .captures(log)?[1]
             .to_string();
+        let status = match &STATUS_RE
+            .get_or_init(|| Regex::new(r#"Acct-Status-Type=(\w+)"#).unwrap())
+            .captures(log)?[1]
+        {
+            "Start" => AcctStatus::Start,
+            "Stop" => AcctStatus::Stop,
+            _ => return None,
+        };
 
-        let (mut city, mut state, mut country) = (None, None, None);
+        let (mut city, mut state, mut country, mut location) = (None, None, None, None);
         if let Some(loc) = ipdb.get_iploc(source_ip) {
             city = loc.city.to_owned();
             state = loc.state.to_owned();
             country = loc.country_code.to_owned();
+            location = Some((loc.lat, loc.lon));
         }
         let is_relay = ipdb.is_proxy(source_ip);
 
         Some(Self {
             time,
+            status,
+            end: None,
             vpn_ip,
             source_ip,
             dev_platform,
@@ -80,10 +109,55 @@ impl VpnLog {
             city,
             state,
             country,
+            location,
             is_relay,
         })
     }
 
+    /// Pairs each Start with the next Stop of the same session, newest session first.  `logs`
+    /// must be sorted newest first.  A Stop with no Start before it is kept as its own row, since
+    /// its session started before the search window.
+    pub fn sessions(logs: Vec<Self>) -> Vec<Self> {
+        let mut sessions: Vec<Self> = Vec::with_capacity(logs.len());
+        for log in logs.into_iter().rev() {
+            if log.status == AcctStatus::Stop {
+                let start = sessions.iter_mut().rev().find(|s| {
+                    s.status == AcctStatus::Start && s.end.is_none() && s.same_session(&log)
+                });
+                if let Some(start) = start {
+                    start.end = Some(log.time);
+                    continue;
+                }
+            }
+            sessions.push(log);
+        }
+        sessions.reverse();
+        sessions
+    }
+
+    /// Logs of one session share both IPs
+    fn same_session(&self, other: &Self) -> bool {
+        self.source_ip == other.source_ip && self.vpn_ip == other.vpn_ip
+    }
+
+    /// How long the session lasted, if both ends were found
+    pub fn duration(&self) -> Option<Duration> {
+        self.end.map(|end| end - self.time)
+    }
+
+    /// Session length for the table
+    pub fn format_duration(&self) -> String {
+        match (self.status, self.duration()) {
+            (_, Some(duration)) => format!(
+                "{}h {:02}m",
+                duration.num_hours(),
+                duration.num_minutes() % 60
+            ),
+            (AcctStatus::Start, None) => "Ongoing".to_owned(),
+            (AcctStatus::Stop, None) => "Start not found".to_owned(),
+        }
+    }
+
     pub fn correlates(&self, other: &Self) -> bool {
         self.source_ip == other.source_ip
             || (self.dev_mac.is_some() && self.dev_mac == other.dev_mac)
@@ -103,9 +177,14 @@ impl VpnLog {
     }
 }
 
+/// Logs are the same event if they share a time, status, and both IPs, so simultaneous sessions
+/// are kept apart
 impl PartialEq for VpnLog {
     fn eq(&self, other: &Self) -> bool {
         self.time == other.time
+            && self.status == other.status
+            && self.source_ip == other.source_ip
+            && self.vpn_ip == other.vpn_ip
     }
 }
 
@@ -113,12 +192,19 @@ impl Eq for VpnLog {}
 
 impl PartialOrd for VpnLog {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(other.time.cmp(&self.time))
+        Some(self.cmp(other))
     }
 }
 
+/// Newest first, and a Stop before the Start at the same time so pairing oldest first sees the
+/// Start first
 impl Ord for VpnLog {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.time.cmp(&self.time)
+        other
+            .time
+            .cmp(&self.time)
+            .then_with(|| other.status.cmp(&self.status))
+            .then_with(|| self.source_ip.cmp(&other.source_ip))
+            .then_with(|| self.vpn_ip.cmp(&other.vpn_ip))
     }
 }
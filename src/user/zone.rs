@@ -0,0 +1,185 @@
+//! Timezones for reading and showing log times
+//!
+//! Log times are held in UTC so they compare against [chrono::Utc::now()] and each other without
+//! any conversion, and a DST change between two logins can't move one of them.  A [Zone] is only
+//! used at the edges, when a Splunk timestamp without its own offset is read and when a time is
+//! shown in a table or template.
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, LocalResult, NaiveDateTime, Offset, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use std::sync::OnceLock;
+
+static LOCAL_TZ: OnceLock<Option<Tz>> = OnceLock::new();
+
+/// IANA zones offered in settings ahead of the fixed offsets, any other can be typed in
+const NAMED: [Tz; 4] = [
+    Tz::America__New_York,
+    Tz::America__Chicago,
+    Tz::America__Denver,
+    Tz::America__Los_Angeles,
+];
+
+/// Zone names Splunk writes after its timestamps.  chrono can't look names up on its own, and
+/// these are the only ones the SOC sees.
+const ZONE_NAMES: [(&str, i32); 10] = [
+    ("UTC", 0),
+    ("GMT", 0),
+    ("EST", -5 * 60),
+    ("EDT", -4 * 60),
+    ("CST", -6 * 60),
+    ("CDT", -5 * 60),
+    ("MST", -7 * 60),
+    ("MDT", -6 * 60),
+    ("PST", -8 * 60),
+    ("PDT", -7 * 60),
+];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Zone {
+    #[default]
+    Local,
+    Utc,
+    /// Fixed offset east of UTC in minutes
+    Offset(i32),
+    /// IANA zone such as `America/Chicago`, which follows its own DST changes
+    Named(Tz),
+}
+
+impl Zone {
+    /// Zones offered in settings, whole hour offsets are enough for the SOC
+    pub fn choices() -> Vec<Self> {
+        let mut choices = vec![Self::Local, Self::Utc];
+        choices.extend(NAMED.into_iter().map(Self::Named));
+        choices.extend((-12..=14).filter(|h| *h != 0).map(|h| Self::Offset(h * 60)));
+        choices
+    }
+
+    /// Reads a Splunk timestamp such as `2023-09-06 07:33:19.000 EDT`.  An offset or known zone
+    /// name on the end wins, this zone is only used when the timestamp doesn't say.
+    pub fn parse(self, time: &str) -> Option<DateTime<Utc>> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(time) {
+            return Some(time.with_timezone(&Utc));
+        }
+        if let Ok(time) = DateTime::parse_from_str(time, "%F %T%.f %z") {
+            return Some(time.with_timezone(&Utc));
+        }
+
+        let (naive, name) = match time.rsplit_once(' ') {
+            Some((naive, name)) if name.chars().all(|c| c.is_ascii_alphabetic()) => {
+                (naive, Some(name))
+            }
+            _ => (time, None),
+        };
+        let naive = NaiveDateTime::parse_from_str(naive, "%F %T%.f").ok()?;
+        let offset = name.and_then(|name| {
+            ZONE_NAMES
+                .iter()
+                .find(|(zone, _)| zone.eq_ignore_ascii_case(name))
+                .and_then(|(_, minutes)| FixedOffset::east_opt(minutes * 60))
+        });
+        Some(match offset {
+            Some(offset) => from_local(&offset, naive),
+            None => self.to_utc(naive),
+        })
+    }
+
+    /// Reads a time in this zone
+    pub fn to_utc(self, time: NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Self::Local => from_local(&Local, time),
+            Self::Utc => Utc.from_utc_datetime(&time),
+            Self::Offset(minutes) => match FixedOffset::east_opt(minutes * 60) {
+                Some(offset) => from_local(&offset, time),
+                None => Utc.from_utc_datetime(&time),
+            },
+            Self::Named(tz) => from_local(&tz, time),
+        }
+    }
+
+    /// Converts a time to this zone for showing
+    pub fn to_zone(self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Self::Local => time.with_timezone(&Local).naive_local(),
+            Self::Utc => time.naive_utc(),
+            Self::Offset(minutes) => match FixedOffset::east_opt(minutes * 60) {
+                Some(offset) => time.with_timezone(&offset).naive_local(),
+                None => time.naive_utc(),
+            },
+            Self::Named(tz) => time.with_timezone(&tz).naive_local(),
+        }
+    }
+
+    /// Short name of the zone at `time`, such as `EDT` or `CST`.  Local time is named after the
+    /// system's IANA zone when it can be found.
+    pub fn abbreviation(self, time: DateTime<Utc>) -> String {
+        match self {
+            Self::Local => match local_tz() {
+                Some(tz) => Self::Named(tz).abbreviation(time),
+                None => time.with_timezone(&Local).format("UTC%:z").to_string(),
+            },
+            Self::Utc | Self::Offset(_) => self.to_string(),
+            Self::Named(tz) => time.with_timezone(&tz).format("%Z").to_string(),
+        }
+    }
+}
+
+/// The system's IANA zone, looked up once
+fn local_tz() -> Option<Tz> {
+    *LOCAL_TZ.get_or_init(|| iana_time_zone::get_timezone().ok()?.parse().ok())
+}
+
+/// Reads a wall clock time in `tz`.  A time repeated by a fall-back is read as the first of the
+/// two, and a time skipped by a spring-forward with the offset from before the jump.
+fn from_local<Tz: TimeZone>(tz: &Tz, time: NaiveDateTime) -> DateTime<Utc> {
+    match tz.from_local_datetime(&time) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.with_timezone(&Utc),
+        LocalResult::None => {
+            // A day back is always before the jump
+            let before = tz
+                .offset_from_local_datetime(&(time - Duration::days(1)))
+                .earliest()
+                .map_or(Utc.fix(), |offset| offset.fix());
+            Utc.from_utc_datetime(&(time - before))
+        }
+    }
+}
+
+impl std::fmt::Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Local => write!(f, "Local"),
+            Self::Utc => write!(f, "UTC"),
+            Self::Offset(minutes) => {
+                let sign = if *minutes < 0 { '-' } else { '+' };
+                let minutes = minutes.abs();
+                write!(f, "UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+            }
+            Self::Named(tz) => write!(f, "{}", tz.name()),
+        }
+    }
+}
+
+impl std::str::FromStr for Zone {
+    type Err = ();
+
+    fn from_str(zone: &str) -> Result<Self, Self::Err> {
+        match zone {
+            "Local" => Ok(Self::Local),
+            "UTC" => Ok(Self::Utc),
+            // Abbreviations like EST are zones to chrono-tz too, but only full names are taken
+            _ if zone.contains('/') => zone.parse().map(Self::Named).map_err(|_| ()),
+            _ => {
+                let offset = zone.strip_prefix("UTC").ok_or(())?;
+                let (sign, offset) = match offset.strip_prefix('-') {
+                    Some(offset) => (-1, offset),
+                    None => (1, offset.strip_prefix('+').ok_or(())?),
+                };
+                let (hours, minutes) = offset.split_once(':').ok_or(())?;
+                let hours: i32 = hours.parse().map_err(|_| ())?;
+                let minutes: i32 = minutes.parse().map_err(|_| ())?;
+                Ok(Self::Offset(sign * (hours * 60 + minutes)))
+            }
+        }
+    }
+}